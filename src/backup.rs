@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::Duration;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use serde::{Deserialize, Serialize};
+use crate::constant::BACKUP_MANIFEST;
+use crate::messages;
+use crate::utils::parse_duration;
+
+/// A backup job from the `backups` section of `rot.json`, run by
+/// `rot backup run`:
+///
+/// ```json
+/// "backups": {
+///   "photos": {
+///     "sources": ["/home/me/Pictures"],
+///     "destination": "backup/photos",
+///     "key": "backup",
+///     "schedule": "daily",
+///     "keep": 7
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BackupJob {
+    /// Local files and directories to back up, each stored in the snapshot
+    /// under its own name.
+    pub sources: Vec<String>,
+    /// Prefix the snapshots are written below, or `oss://bucket/prefix`.
+    pub destination: String,
+    /// Patterns left out, as `--exclude` takes them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Name of a key saved with `rot key add`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    /// Stored in `rot.json` in the clear; a named `key` is better.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The least time between snapshots, such as `daily` or `6h`. Until it
+    /// has passed `rot backup run` skips the job, so a timer may call it
+    /// more often than that.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+    /// How many snapshots to keep; older ones are deleted after a successful
+    /// run. All are kept when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+}
+
+impl BackupJob {
+    /// Checks the settings that can be checked without touching any files.
+    pub fn validate(&self, name: &str) -> Result<(), String> {
+        if self.sources.is_empty() {
+            return Err(messages::backup_without_sources(name));
+        }
+        if self.destination.trim_matches('/').is_empty() {
+            return Err(messages::backup_without_destination(name));
+        }
+        let secrets = [self.key.is_some(), self.key_file.is_some(), !self.recipients.is_empty(), self.password.is_some()];
+        if secrets.iter().filter(|given| **given).count() > 1 {
+            return Err(messages::backup_keys_conflict(name));
+        }
+        if self.keep == Some(0) {
+            return Err(messages::backup_keep_zero(name));
+        }
+        self.interval()?;
+        let mut names = BTreeSet::new();
+        for source in &self.sources {
+            let source_name = source_name(source).ok_or_else(|| messages::backup_source_invalid(name, source))?;
+            if source_name == BACKUP_MANIFEST {
+                return Err(messages::backup_source_reserved(name, BACKUP_MANIFEST));
+            }
+            if !names.insert(source_name) {
+                return Err(messages::backup_sources_collide(name, source_name));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `schedule` as a duration: `hourly`, `daily`, `weekly`, or
+    /// anything `parse_duration` reads.
+    pub fn interval(&self) -> Result<Option<Duration>, String> {
+        self.schedule.as_deref().map(|schedule| match schedule {
+            "hourly" => Ok(Duration::from_secs(60 * 60)),
+            "daily" => Ok(Duration::from_secs(24 * 60 * 60)),
+            "weekly" => Ok(Duration::from_secs(7 * 24 * 60 * 60)),
+            other => parse_duration(other).map_err(|_| messages::backup_schedule_invalid(other)),
+        }).transpose()
+    }
+
+    /// Whether a snapshot is due at `now`, given when the last one was
+    /// taken. Both are seconds since the Unix epoch.
+    pub fn is_due(&self, last: Option<i64>, now: i64) -> Result<bool, String> {
+        Ok(match (self.interval()?, last) {
+            (Some(interval), Some(last)) => now - last >= interval.as_secs() as i64,
+            _ => true,
+        })
+    }
+
+    /// The snapshots to delete so that `keep` remain, oldest first, from
+    /// `snapshots` sorted oldest first.
+    pub fn expired<'a>(&self, snapshots: &'a [String]) -> &'a [String] {
+        match self.keep {
+            Some(keep) if snapshots.len() > keep => &snapshots[..snapshots.len() - keep],
+            _ => &[],
+        }
+    }
+}
+
+/// What a snapshot holds, stored in it as `manifest.json`. A file that
+/// hasn't changed since the previous snapshot isn't uploaded again: its
+/// entry points at the object that already holds it, which may lie in an
+/// older snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SnapshotManifest {
+    /// Keyed by path within the snapshot, such as `Pictures/2024/a.jpg`.
+    pub files: BTreeMap<String, SnapshotFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SnapshotFile {
+    pub size: u64,
+    /// SHA-256 of the plaintext.
+    pub sha256: String,
+    /// When the file was last modified, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<i64>,
+    /// The object holding the file's contents.
+    pub object: String,
+}
+
+impl SnapshotManifest {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(data).map_err(messages::snapshot_manifest_invalid)
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("manifest is serializable")
+    }
+
+    /// The object already holding `path` with these contents, if any.
+    pub fn unchanged(&self, path: &str, size: u64, sha256: &str) -> Option<&str> {
+        self.files.get(path)
+            .filter(|file| file.size == size && file.sha256 == sha256)
+            .map(|file| file.object.as_str())
+    }
+
+    /// Every object the snapshot's files are read from.
+    pub fn objects(&self) -> BTreeSet<&str> {
+        self.files.values().map(|file| file.object.as_str()).collect()
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|file| file.size).sum()
+    }
+}
+
+/// The name a source gets in the snapshot: its last path component.
+pub(crate) fn source_name(source: &str) -> Option<&str> {
+    Path::new(source.trim_end_matches(['/', '\\'])).file_name().and_then(|name| name.to_str())
+}
+
+/// The name of a snapshot taken at `secs`, such as `20240601T020000Z`,
+/// which sorts in time order.
+pub(crate) fn snapshot_name(secs: i64) -> String {
+    DateTime::from_secs(secs).fmt(DateTimeFormat::DateTime).unwrap_or_default().replace(['-', ':'], "")
+}
+
+/// When the snapshot `name` was taken, `None` if it isn't a snapshot name.
+pub(crate) fn snapshot_time(name: &str) -> Option<i64> {
+    let name = name.trim_end_matches('/');
+    if name.len() != 16 || !name.is_ascii() {
+        return None;
+    }
+    let text = format!("{}-{}-{}T{}:{}:{}Z", &name[..4], &name[4..6], &name[6..8], &name[9..11], &name[11..13], &name[13..15]);
+    DateTime::from_str(&text, DateTimeFormat::DateTime).ok()
+        .filter(|time| snapshot_name(time.secs()) == name)
+        .map(|time| time.secs())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::backup::{BackupJob, SnapshotFile, SnapshotManifest, snapshot_name, snapshot_time, source_name};
+
+    fn job() -> BackupJob {
+        BackupJob { sources: vec!["/home/me/Pictures".into()], destination: "backup/photos".into(), ..BackupJob::default() }
+    }
+
+    #[test]
+    fn test_snapshot_name() {
+        assert_eq!(snapshot_name(1_717_207_200), "20240601T020000Z");
+        assert_eq!(snapshot_time("20240601T020000Z/"), Some(1_717_207_200));
+        assert_eq!(snapshot_time("20241301T020000Z"), None);
+        assert_eq!(snapshot_time("latest"), None);
+        assert_eq!(source_name("/home/me/Pictures/"), Some("Pictures"));
+        assert_eq!(source_name("/"), None);
+    }
+
+    #[test]
+    fn test_backup_job() {
+        let mut job = job();
+        job.validate("photos").unwrap();
+        assert!(job.is_due(Some(0), 1).unwrap());
+
+        job.schedule = Some("daily".into());
+        assert_eq!(job.interval().unwrap(), Some(Duration::from_secs(86_400)));
+        assert!(job.is_due(None, 1000).unwrap());
+        assert!(!job.is_due(Some(1000), 1000 + 86_399).unwrap());
+        assert!(job.is_due(Some(1000), 1000 + 86_400).unwrap());
+        job.schedule = Some("6h".into());
+        assert_eq!(job.interval().unwrap(), Some(Duration::from_secs(21_600)));
+        job.schedule = Some("often".into());
+        assert!(job.validate("photos").is_err());
+        job.schedule = None;
+
+        let snapshots: Vec<String> = ["a", "b", "c"].iter().map(|name| name.to_string()).collect();
+        assert!(job.expired(&snapshots).is_empty());
+        job.keep = Some(1);
+        assert_eq!(job.expired(&snapshots), ["a", "b"]);
+        job.keep = Some(0);
+        assert!(job.validate("photos").is_err());
+        job.keep = None;
+
+        job.key = Some("backup".into());
+        job.password = Some("secret".into());
+        assert!(job.validate("photos").is_err());
+        job.password = None;
+        job.sources.push("/mnt/old/Pictures".into());
+        assert!(job.validate("photos").is_err());
+        job.sources = vec!["/home/me/manifest.json".into()];
+        assert!(job.validate("photos").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_manifest() {
+        let file = |size, sha256: &str, object: &str| SnapshotFile { size, sha256: sha256.into(), modified: Some(1_717_207_200), object: object.into() };
+        let mut manifest = SnapshotManifest::default();
+        manifest.files.insert("Pictures/a.jpg".into(), file(3, "aa", "backup/photos/20240601T020000Z/Pictures/a.jpg"));
+        manifest.files.insert("Pictures/b.jpg".into(), file(4, "bb", "backup/photos/20240530T020000Z/Pictures/b.jpg"));
+
+        let parsed = SnapshotManifest::parse(&manifest.to_json()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert_eq!(parsed.unchanged("Pictures/b.jpg", 4, "bb"), Some("backup/photos/20240530T020000Z/Pictures/b.jpg"));
+        assert_eq!(parsed.unchanged("Pictures/b.jpg", 4, "cc"), None);
+        assert_eq!(parsed.unchanged("Pictures/c.jpg", 4, "bb"), None);
+        assert_eq!(parsed.objects().len(), 2);
+        assert_eq!(parsed.total_size(), 7);
+        assert!(SnapshotManifest::parse(b"[]").is_err());
+    }
+}
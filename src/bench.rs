@@ -0,0 +1,143 @@
+use std::env;
+use std::path::Path;
+use std::time::Instant;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use crate::client::{AliyunClient, Concurrency};
+use crate::constant::{BENCH_PREFIX, TEMP_FOLDER};
+use crate::messages;
+use crate::output::Reporter;
+use crate::utils::{create_dir, DeleteFolder, format_size};
+
+pub(crate) struct BenchPlan {
+    pub object_sizes: Vec<u64>,
+    pub part_sizes: Vec<u64>,
+    pub concurrencies: Vec<Concurrency>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BenchResult {
+    pub object_size: u64,
+    pub part_size: u64,
+    pub concurrency: String,
+    pub upload_seconds: f64,
+    pub upload_mib_per_second: f64,
+    pub download_seconds: f64,
+    pub download_mib_per_second: f64,
+}
+
+/// Uploads and downloads a random object for every combination in `plan`,
+/// removing the remote objects and local scratch files afterwards, also
+/// when a run fails.
+pub(crate) async fn run(client: &AliyunClient, plan: &BenchPlan) -> Result<Vec<BenchResult>, String> {
+    let work_dir = env::temp_dir().join(TEMP_FOLDER).join("bench");
+    create_dir(&work_dir).await;
+    let result = measure(client, plan, &work_dir).await;
+    clean_up(client, plan).await;
+    work_dir.delete().await;
+    result
+}
+
+async fn measure(client: &AliyunClient, plan: &BenchPlan, work_dir: &Path) -> Result<Vec<BenchResult>, String> {
+    let download_path = work_dir.join("download.bin");
+
+    let mut results = Vec::new();
+    for &object_size in &plan.object_sizes {
+        let filename = scratch_name(object_size);
+        let source = work_dir.join(&filename);
+        write_random_file(&source, object_size).await?;
+        let key = format!("{}/{}", BENCH_PREFIX, filename);
+
+        for &part_size in &plan.part_sizes {
+            for &concurrency in &plan.concurrencies {
+                // Every run writes the same scratch object and file.
+                let client = client.clone()
+                    .with_overwrite(true)
+                    .with_part_size(part_size as usize)
+                    .with_concurrency(concurrency);
+
+                let started = Instant::now();
+                client.upload_file(BENCH_PREFIX, source.clone(), None::<String>, None).await?;
+                let upload_seconds = started.elapsed().as_secs_f64();
+
+                let started = Instant::now();
+                client.download_file(&key, &download_path, None::<String>).await?;
+                let download_seconds = started.elapsed().as_secs_f64();
+
+                results.push(BenchResult {
+                    object_size,
+                    part_size,
+                    concurrency: concurrency.to_string(),
+                    upload_seconds,
+                    upload_mib_per_second: mib_per_second(object_size, upload_seconds),
+                    download_seconds,
+                    download_mib_per_second: mib_per_second(object_size, download_seconds),
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn scratch_name(object_size: u64) -> String {
+    format!("bench-{}.bin", object_size)
+}
+
+/// Deletes the scratch objects and any multipart upload a failed run left
+/// under `BENCH_PREFIX`. Errors are ignored: there may be nothing to delete.
+async fn clean_up(client: &AliyunClient, plan: &BenchPlan) {
+    for &object_size in &plan.object_sizes {
+        let _ = client.delete_obj(format!("{}/{}", BENCH_PREFIX, scratch_name(object_size))).await;
+    }
+    let uploads = client.list_multipart_uploads(Some(format!("{}/", BENCH_PREFIX))).await.unwrap_or_default();
+    for upload in uploads {
+        if let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) {
+            let _ = client.abort_multipart_upload(key, upload_id).await;
+        }
+    }
+}
+
+pub(crate) fn print_table(reporter: Reporter, results: &[BenchResult]) {
+    reporter.message(format!("{:>10} {:>10} {:>6} {:>10} {:>12} {:>10} {:>12}",
+                             "size", "part", "conc", "up (s)", "up (MiB/s)", "down (s)", "down (MiB/s)"));
+    for result in results {
+        reporter.record(result, format!("{:>10} {:>10} {:>6} {:>10.2} {:>12.2} {:>10.2} {:>12.2}",
+                                        format_size(result.object_size),
+                                        format_size(result.part_size),
+                                        result.concurrency,
+                                        result.upload_seconds,
+                                        result.upload_mib_per_second,
+                                        result.download_seconds,
+                                        result.download_mib_per_second));
+    }
+}
+
+fn mib_per_second(bytes: u64, seconds: f64) -> f64 {
+    bytes as f64 / (1 << 20) as f64 / seconds.max(f64::EPSILON)
+}
+
+async fn write_random_file(path: &Path, size: u64) -> Result<(), String> {
+    let rng = SystemRandom::new();
+    let mut file = File::create(path).await.map_err(|e| e.to_string())?;
+    let mut block = vec![0u8; 1 << 20];
+    let mut remaining = size;
+    while remaining > 0 {
+        let len = remaining.min(block.len() as u64) as usize;
+        rng.fill(&mut block[..len]).map_err(|_| messages::random_failed())?;
+        file.write_all(&block[..len]).await.map_err(|e| e.to_string())?;
+        remaining -= len as u64;
+    }
+    file.flush().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bench::mib_per_second;
+
+    #[test]
+    fn test_mib_per_second() {
+        assert_eq!(mib_per_second(8 << 20, 2.0), 4.0);
+    }
+}
@@ -1,15 +1,144 @@
 use std::env;
+use tokio_util::sync::CancellationToken;
 use raven_oss_tools::client::AliyunOssCommandExecutor;
+use raven_oss_tools::{config, keyfile, locale, messages, sts};
+use raven_oss_tools::error::RotError;
+use raven_oss_tools::output::OutputFormat;
 
 #[tokio::main]
-async fn main() -> Result<(), String>{
-    let args: Vec<String> = env::args().collect();
-    let mut client = match AliyunOssCommandExecutor::new().await {
+async fn main() {
+    // The library only returns errors; deciding to exit is up to us.
+    if let Err(e) = run().await {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    }
+}
+
+async fn run() -> Result<(), RotError> {
+    // Messages are in Chinese until a language is chosen; a bad choice
+    // leaves them so.
+    if let Err(e) = locale::init().await {
+        eprintln!("{}", e);
+    }
+    let mut args: Vec<String> = env::args().collect();
+    let offline = args.iter().any(|arg| arg == "--offline");
+    let read_only = args.iter().any(|arg| arg == "--read-only");
+    // `--dry-run` is also accepted after a command, but taken out here so
+    // that `rot --dry-run rm x` does not read `x` as its value.
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    args.retain(|arg| arg != "--offline" && arg != "--read-only" && arg != "--dry-run");
+    let globals = match Globals::take(&mut args) {
+        Ok(globals) => globals,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2)
+        }
+    };
+    let profile = globals.profile;
+
+    // These work on the configuration itself, before there is a client.
+    match args.get(1).map(String::as_str) {
+        Some("config") => return config::execute(args, profile.as_deref()).await,
+        Some("assume-role") => return sts::execute(args, profile.as_deref()).await,
+        Some("keygen") => return keyfile::execute(args).await,
+        Some("key") => return keyfile::execute_named(args).await,
+        _ => {}
+    }
+
+    // Local files need no account, so the offline store stands in for one.
+    let local = matches!(args.get(1).map(String::as_str), Some("encrypt" | "decrypt"));
+    let executor = if offline || local {
+        AliyunOssCommandExecutor::new_offline()
+    } else {
+        match AliyunOssCommandExecutor::new(profile.as_deref()).await {
+            Ok(executor) => executor,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1)
+            }
+        }
+    };
+    let mut client = match executor {
         Some(value) => value,
+        None if offline => {
+            eprintln!("{}", messages::offline_cache_missing());
+            std::process::exit(1)
+        }
         None => {
-            println!("已在~/.config/rot/内初始化配置文件，请填写rot.json。");
-            std::process::exit(0)
+            eprintln!("{}", messages::credentials_missing());
+            std::process::exit(1)
         }
     };
+    if read_only {
+        client.set_read_only();
+    }
+    if dry_run {
+        client.set_dry_run();
+    }
+    if let Some(output) = globals.output {
+        client.set_output(output);
+    }
+    if let Err(e) = client.set_network(globals.connect_timeout.as_deref(), globals.read_timeout.as_deref(), globals.max_attempts.as_deref()) {
+        eprintln!("{}", e);
+        std::process::exit(2)
+    }
+    if let Some(rate) = globals.bwlimit {
+        if let Err(e) = client.set_bwlimit(&rate) {
+            eprintln!("{}", e);
+            std::process::exit(2)
+        }
+    }
+    // Ctrl+C stops transfers cleanly: uploads are aborted and partial
+    // downloads removed. A second one exits at once.
+    let cancellation = CancellationToken::new();
+    client.set_cancellation(cancellation.clone());
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("{}", messages::cancelling());
+            cancellation.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
     client.execute(args).await
 }
+
+/// Options taking a value that apply to every command.
+struct Globals {
+    output: Option<OutputFormat>,
+    profile: Option<String>,
+    connect_timeout: Option<String>,
+    read_timeout: Option<String>,
+    max_attempts: Option<String>,
+    bwlimit: Option<String>,
+}
+
+impl Globals {
+    fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        Ok(Self {
+            output: take_value(args, "--output", messages::missing_output_format)?
+                .map(|value| value.parse()).transpose()?,
+            profile: take_value(args, "--profile", messages::missing_profile)?,
+            connect_timeout: take_value(args, "--connect-timeout", || messages::missing_duration("--connect-timeout", "10s"))?,
+            read_timeout: take_value(args, "--read-timeout", || messages::missing_duration("--read-timeout", "1m"))?,
+            max_attempts: take_value(args, "--max-attempts", messages::missing_attempts)?,
+            bwlimit: take_value(args, "--bwlimit", messages::missing_bwlimit)?,
+        })
+    }
+}
+
+/// Removes `<name> <value>` or `<name>=<value>` from `args`, failing with
+/// `missing()` when no value follows.
+fn take_value(args: &mut Vec<String>, name: &str, missing: impl FnOnce() -> String) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", name);
+    let Some(index) = args.iter().position(|arg| arg == name || arg.starts_with(&prefix)) else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_string())),
+        None if index < args.len() => Ok(Some(args.remove(index))),
+        None => Err(missing()),
+    }
+}
@@ -0,0 +1,720 @@
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use crate::messages;
+use crate::store::{ObjectInfo, StoreClient};
+use crate::sync::join_key;
+use crate::utils::{create_dir, format_size, key_to_relative_path, relative_key_prefix, walk_dir};
+
+/// Runs the two-pane browser of `rot browse` until `q`: the local directory
+/// `local_dir` on the left, the objects below `prefix` on the right. Every
+/// operation goes through `ObjectStore`, so it works the same on each
+/// backend; uploads, downloads, deletions and renames run in the
+/// background, one at a time, while the panes stay usable.
+pub(crate) async fn run(client: StoreClient, local_dir: PathBuf, prefix: String) -> Result<(), String> {
+    if !std::io::stdout().is_terminal() {
+        return Err(messages::browse_needs_terminal());
+    }
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let mut browser = Browser::new(client, local_dir, prefix, sender.clone());
+    browser.refresh().await;
+    std::thread::spawn(move || read_keys(sender));
+
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal, &mut messages).await;
+    ratatui::restore();
+    result
+}
+
+enum Message {
+    Key(KeyEvent),
+    /// The terminal was resized.
+    Redraw,
+    /// How far the running job has got.
+    Progress(String),
+    /// The running job finished, with a summary or an error.
+    Done(Result<String, String>),
+}
+
+/// Forwards key presses from the terminal, which only blocking reads offer.
+fn read_keys(sender: UnboundedSender<Message>) {
+    loop {
+        let message = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => Message::Key(key),
+            Ok(Event::Resize(..)) => Message::Redraw,
+            Ok(_) => continue,
+            Err(_) => break,
+        };
+        if sender.send(message).is_err() {
+            break;
+        }
+    }
+}
+
+/// A file or folder shown in a pane.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    name: String,
+    dir: bool,
+    /// `None` for folders.
+    size: Option<u64>,
+}
+
+impl Entry {
+    fn folder(name: impl Into<String>) -> Self {
+        Self { name: name.into(), dir: true, size: None }
+    }
+
+    fn file(name: impl Into<String>, size: u64) -> Self {
+        Self { name: name.into(), dir: false, size: Some(size) }
+    }
+
+    fn is_parent(&self) -> bool {
+        self.name == ".."
+    }
+}
+
+/// One level below `prefix` of the objects `ObjectStore::list` returned for
+/// it: folders first, then files, each in name order. Folder placeholder
+/// keys are left out.
+fn remote_entries(prefix: &str, objects: &[ObjectInfo]) -> Vec<Entry> {
+    let mut folders = BTreeSet::new();
+    let mut files = Vec::new();
+    for object in objects {
+        let Some(relative) = object.key.strip_prefix(prefix) else { continue };
+        match relative.split_once('/') {
+            Some((folder, _)) if !folder.is_empty() => {
+                folders.insert(folder.to_string());
+            }
+            None if !relative.is_empty() => files.push(Entry::file(relative, object.size)),
+            _ => {}
+        }
+    }
+    folders.into_iter().map(Entry::folder).chain(files).collect()
+}
+
+/// The entries of the local directory `dir`, ordered like `remote_entries`.
+/// Names that aren't valid UTF-8 can't become keys and are left out.
+async fn local_entries(dir: &Path) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut read = tokio::fs::read_dir(dir).await.map_err(|e| messages::cannot_read(dir.display(), e))?;
+    while let Some(entry) = read.next_entry().await.map_err(|e| messages::cannot_read(dir.display(), e))? {
+        let Ok(name) = entry.file_name().into_string() else { continue };
+        // Follows symlinks, so that linked folders can be opened.
+        let Ok(metadata) = tokio::fs::metadata(entry.path()).await else { continue };
+        entries.push(if metadata.is_dir() { Entry::folder(name) } else { Entry::file(name, metadata.len()) });
+    }
+    entries.sort_by(|a, b| b.dir.cmp(&a.dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// The prefix above `prefix`, which is empty or ends with `/`.
+fn parent_prefix(prefix: &str) -> String {
+    match prefix.trim_end_matches('/').rsplit_once('/') {
+        Some((parent, _)) => format!("{}/", parent),
+        None => String::new(),
+    }
+}
+
+/// Whether `name` can name an entry of the current folder.
+fn valid_name(name: &str) -> bool {
+    !(name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']))
+}
+
+#[derive(Debug, Default)]
+struct Pane {
+    entries: Vec<Entry>,
+    selected: usize,
+}
+
+impl Pane {
+    /// Shows `entries`, led by `..` unless at the top, keeping the
+    /// selection on the entry named `keep` if there is one.
+    fn set(&mut self, entries: Vec<Entry>, top: bool, keep: Option<&str>) {
+        self.entries = (!top).then(|| Entry::folder("..")).into_iter().chain(entries).collect();
+        self.selected = keep.and_then(|name| self.entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(self.selected)
+            .min(self.entries.len().saturating_sub(1));
+    }
+
+    /// The name of the entry under the cursor, `..` included.
+    fn current(&self) -> Option<String> {
+        self.entries.get(self.selected).map(|entry| entry.name.clone())
+    }
+
+    /// The selected entry, never `..`.
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected).filter(|entry| !entry.is_parent())
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        self.selected = self.selected.saturating_add_signed(delta).min(self.entries.len().saturating_sub(1));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Local,
+    Remote,
+}
+
+enum Mode {
+    Normal,
+    /// Waiting for `y` to run the job.
+    Confirm(String, Job),
+    /// Typing a new name for the selected entry.
+    Rename(String),
+}
+
+/// Work that runs in the background. Folders are handled file by file;
+/// a failed file doesn't stop the others.
+#[derive(Debug, Clone)]
+enum Job {
+    /// The local file or folder `path` into `prefix`, a folder keeping its
+    /// name as `rot upload -r` does.
+    Upload { path: PathBuf, dir: bool, prefix: String },
+    /// The object `key`, or every object below the folder `key`, to `target`.
+    Download { key: String, dir: bool, target: PathBuf },
+    DeleteLocal { path: PathBuf, dir: bool },
+    DeleteRemote { key: String, dir: bool },
+    RenameLocal { from: PathBuf, to: PathBuf },
+    /// The object or folder `from` becomes `to`, by copying and deleting.
+    RenameRemote { from: String, to: String, dir: bool },
+}
+
+struct Browser {
+    client: StoreClient,
+    local_dir: PathBuf,
+    prefix: String,
+    local: Pane,
+    remote: Pane,
+    focus: Side,
+    mode: Mode,
+    status: String,
+    failed: bool,
+    busy: bool,
+    /// `q` was pressed while a job was running; another one quits anyway.
+    quitting: bool,
+    sender: UnboundedSender<Message>,
+}
+
+impl Browser {
+    fn new(client: StoreClient, local_dir: PathBuf, prefix: String, sender: UnboundedSender<Message>) -> Self {
+        Self {
+            client,
+            local_dir,
+            prefix,
+            local: Pane::default(),
+            remote: Pane::default(),
+            focus: Side::Local,
+            mode: Mode::Normal,
+            status: String::new(),
+            failed: false,
+            busy: false,
+            quitting: false,
+            sender,
+        }
+    }
+
+    async fn run(&mut self, terminal: &mut DefaultTerminal, messages: &mut UnboundedReceiver<Message>) -> Result<(), String> {
+        loop {
+            terminal.draw(|frame| self.draw(frame)).map_err(messages::cannot_draw)?;
+            let Some(message) = messages.recv().await else { return Ok(()) };
+            match message {
+                Message::Key(key) => {
+                    if self.on_key(key).await {
+                        return Ok(());
+                    }
+                }
+                Message::Redraw => {}
+                Message::Progress(text) => self.report(Ok(text)),
+                Message::Done(result) => {
+                    self.busy = false;
+                    self.report(result);
+                    self.refresh().await;
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, result: Result<String, String>) {
+        (self.status, self.failed) = match result {
+            Ok(text) => (text, false),
+            Err(text) => (text, true),
+        };
+    }
+
+    /// Lists both panes again, keeping their selections.
+    async fn refresh(&mut self) {
+        self.show_local(self.local_dir.clone(), self.local.current()).await;
+        self.show_remote(self.prefix.clone(), self.remote.current()).await;
+    }
+
+    /// Shows `dir` in the local pane, selecting `keep`. On failure the pane
+    /// stays where it was.
+    async fn show_local(&mut self, dir: PathBuf, keep: Option<String>) {
+        match local_entries(&dir).await {
+            Ok(entries) => {
+                if dir != self.local_dir {
+                    self.local.selected = 0;
+                }
+                self.local.set(entries, dir.parent().is_none(), keep.as_deref());
+                self.local_dir = dir;
+            }
+            Err(e) => self.report(Err(e)),
+        }
+    }
+
+    /// Shows the folder `prefix` in the remote pane, like `show_local`.
+    async fn show_remote(&mut self, prefix: String, keep: Option<String>) {
+        match self.client.store.list(&prefix).await {
+            Ok(objects) => {
+                if prefix != self.prefix {
+                    self.remote.selected = 0;
+                }
+                self.remote.set(remote_entries(&prefix, &objects), prefix.is_empty(), keep.as_deref());
+                self.prefix = prefix;
+            }
+            Err(e) => self.report(Err(messages::cannot_list(prefix, e))),
+        }
+    }
+
+    fn pane(&mut self) -> &mut Pane {
+        match self.focus {
+            Side::Local => &mut self.local,
+            Side::Remote => &mut self.remote,
+        }
+    }
+
+    /// Handles a key press, returning whether to quit.
+    async fn on_key(&mut self, key: KeyEvent) -> bool {
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => {}
+            Mode::Confirm(_, job) => {
+                match key.code {
+                    KeyCode::Char('y' | 'Y') => self.start(job),
+                    _ => self.report(Ok(messages::cancelled())),
+                }
+                return false;
+            }
+            Mode::Rename(mut name) => {
+                match key.code {
+                    KeyCode::Enter => self.rename(name),
+                    KeyCode::Esc => self.report(Ok(messages::cancelled())),
+                    KeyCode::Backspace => {
+                        name.pop();
+                        self.mode = Mode::Rename(name);
+                    }
+                    KeyCode::Char(c) => {
+                        name.push(c);
+                        self.mode = Mode::Rename(name);
+                    }
+                    _ => self.mode = Mode::Rename(name),
+                }
+                return false;
+            }
+        }
+
+        let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL));
+        if quit {
+            if self.busy && !self.quitting {
+                self.quitting = true;
+                self.report(Err(messages::browse_busy_quit()));
+                return false;
+            }
+            return true;
+        }
+        self.quitting = false;
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = if self.focus == Side::Local { Side::Remote } else { Side::Local };
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.pane().move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.pane().move_by(1),
+            KeyCode::PageUp => self.pane().move_by(-10),
+            KeyCode::PageDown => self.pane().move_by(10),
+            KeyCode::Home => self.pane().selected = 0,
+            KeyCode::End => self.pane().move_by(isize::MAX),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open().await,
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => self.up().await,
+            KeyCode::Char('u') => self.upload(),
+            KeyCode::Char('d') => self.download(),
+            KeyCode::Char('x') | KeyCode::Delete => self.delete(),
+            KeyCode::Char('r') | KeyCode::F(2) => match self.pane().selected() {
+                Some(entry) => self.mode = Mode::Rename(entry.name.clone()),
+                None => self.report(Err(messages::browse_nothing_to_rename())),
+            },
+            KeyCode::Char('g') => self.refresh().await,
+            _ => {}
+        }
+        false
+    }
+
+    /// Enters the selected folder of the focused pane.
+    async fn open(&mut self) {
+        let pane = self.pane();
+        let Some(entry) = pane.entries.get(pane.selected).cloned() else { return };
+        if entry.is_parent() {
+            return self.up().await;
+        }
+        if !entry.dir {
+            return;
+        }
+        match self.focus {
+            Side::Local => self.show_local(self.local_dir.join(&entry.name), None).await,
+            Side::Remote => self.show_remote(format!("{}{}/", self.prefix, entry.name), None).await,
+        }
+    }
+
+    /// Goes up a level in the focused pane, selecting the folder left.
+    async fn up(&mut self) {
+        match self.focus {
+            Side::Local => {
+                let Some(parent) = self.local_dir.parent().map(Path::to_path_buf) else { return };
+                let left = self.local_dir.file_name().map(|name| name.to_string_lossy().to_string());
+                self.show_local(parent, left).await;
+            }
+            Side::Remote => {
+                if self.prefix.is_empty() {
+                    return;
+                }
+                let left = self.prefix.trim_end_matches('/').rsplit('/').next().map(String::from);
+                self.show_remote(parent_prefix(&self.prefix), left).await;
+            }
+        }
+    }
+
+    /// Uploads the selected local entry into the remote folder shown.
+    fn upload(&mut self) {
+        let Some(entry) = self.local.selected().cloned() else {
+            return self.report(Err(messages::browse_nothing_to_upload()));
+        };
+        if let Err(e) = self.client.ensure_writable(&messages::action_upload()) {
+            return self.report(Err(e.to_string()));
+        }
+        let job = Job::Upload { path: self.local_dir.join(&entry.name), dir: entry.dir, prefix: self.prefix.clone() };
+        if self.remote.entries.iter().any(|existing| existing.name == entry.name) {
+            self.confirm(messages::browse_overwrite(format!("/{}{}", self.prefix, entry.name)), job);
+        } else {
+            self.start(job);
+        }
+    }
+
+    /// Downloads the selected remote entry into the local directory shown.
+    fn download(&mut self) {
+        let Some(entry) = self.remote.selected().cloned() else {
+            return self.report(Err(messages::browse_nothing_to_download()));
+        };
+        let target = self.local_dir.join(&entry.name);
+        let key = format!("{}{}{}", self.prefix, entry.name, if entry.dir { "/" } else { "" });
+        let exists = target.exists();
+        let job = Job::Download { key, dir: entry.dir, target };
+        if exists {
+            self.confirm(messages::browse_overwrite(self.local_dir.join(&entry.name).display()), job);
+        } else {
+            self.start(job);
+        }
+    }
+
+    fn delete(&mut self) {
+        let Some(entry) = self.pane().selected().cloned() else { return };
+        let (job, prompt) = match self.focus {
+            Side::Local => {
+                let path = self.local_dir.join(&entry.name);
+                let prompt = match entry.dir {
+                    true => messages::browse_delete_local_dir(path.display()),
+                    false => messages::browse_delete_local_file(path.display()),
+                };
+                (Job::DeleteLocal { path, dir: entry.dir }, prompt)
+            }
+            Side::Remote => {
+                if let Err(e) = self.client.ensure_writable(&messages::action_delete()) {
+                    return self.report(Err(e.to_string()));
+                }
+                let key = format!("{}{}{}", self.prefix, entry.name, if entry.dir { "/" } else { "" });
+                let prompt = match entry.dir {
+                    true => messages::browse_delete_remote_dir(&key),
+                    false => messages::browse_delete_remote_object(&key),
+                };
+                (Job::DeleteRemote { key, dir: entry.dir }, prompt)
+            }
+        };
+        self.confirm(prompt, job);
+    }
+
+    /// Renames the selected entry of the focused pane to `name`.
+    fn rename(&mut self, name: String) {
+        let Some(entry) = self.pane().selected().cloned() else { return };
+        if name == entry.name {
+            return;
+        }
+        if !valid_name(&name) {
+            return self.report(Err(messages::browse_name_invalid(name)));
+        }
+        if self.pane().entries.iter().any(|existing| existing.name == name) {
+            return self.report(Err(messages::browse_name_taken(name)));
+        }
+        let job = match self.focus {
+            Side::Local => Job::RenameLocal { from: self.local_dir.join(&entry.name), to: self.local_dir.join(&name) },
+            Side::Remote => {
+                if let Err(e) = self.client.ensure_writable(&messages::action_rename()) {
+                    return self.report(Err(e.to_string()));
+                }
+                let suffix = if entry.dir { "/" } else { "" };
+                Job::RenameRemote {
+                    from: format!("{}{}{}", self.prefix, entry.name, suffix),
+                    to: format!("{}{}{}", self.prefix, name, suffix),
+                    dir: entry.dir,
+                }
+            }
+        };
+        self.start(job);
+    }
+
+    fn confirm(&mut self, prompt: String, job: Job) {
+        if self.busy {
+            return self.report(Err(messages::browse_busy()));
+        }
+        self.mode = Mode::Confirm(prompt, job);
+    }
+
+    fn start(&mut self, job: Job) {
+        if self.busy {
+            return self.report(Err(messages::browse_busy()));
+        }
+        self.busy = true;
+        self.report(Ok(messages::browse_working()));
+        let client = self.client.clone();
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let result = run_job(&client, job, &sender).await;
+            let _ = sender.send(Message::Done(result));
+        });
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [panes, status, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(panes);
+        self.draw_pane(frame, left, Side::Local, messages::browse_local_title(self.local_dir.display()));
+        self.draw_pane(frame, right, Side::Remote, messages::browse_remote_title(&self.prefix));
+
+        let status_line = match &self.mode {
+            Mode::Confirm(prompt, _) => Line::from(prompt.as_str()).yellow(),
+            Mode::Rename(name) => Line::from(messages::browse_rename_prompt(name)).yellow(),
+            Mode::Normal if self.failed => Line::from(self.status.as_str()).red(),
+            Mode::Normal => Line::from(self.status.as_str()),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+        frame.render_widget(Paragraph::new(Line::from(messages::browse_help()).dim()), help);
+    }
+
+    fn draw_pane(&self, frame: &mut Frame, area: Rect, side: Side, title: String) {
+        let pane = if side == Side::Local { &self.local } else { &self.remote };
+        let items: Vec<ListItem> = pane.entries.iter().map(|entry| match entry.size {
+            Some(size) => ListItem::new(Line::from(vec![entry.name.as_str().into(), format!("  {}", format_size(size)).dim()])),
+            None => ListItem::new(Line::from(format!("{}/", entry.name).bold())),
+        }).collect();
+        let border = if side == self.focus { Style::new().yellow() } else { Style::new() };
+        let list = List::new(items)
+            .block(Block::bordered().title(title).border_style(border))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        let mut state = ListState::default().with_selected((!pane.entries.is_empty()).then_some(pane.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// Runs `job`, reporting progress through `sender`, and sums it up.
+async fn run_job(client: &StoreClient, job: Job, sender: &UnboundedSender<Message>) -> Result<String, String> {
+    let progress = |action: Action, done: usize, total: usize, item: &str| {
+        let _ = sender.send(Message::Progress(messages::browse_progress(action.ongoing(), done + 1, total, item)));
+    };
+    match job {
+        Job::Upload { path, dir: false, prefix } => {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let key = join_key(&prefix, &name);
+            progress(Action::Upload, 0, 1, &key);
+            client.store.put(&key, &path).await.map_err(|e| messages::upload_failed_for(&key, e))?;
+            Ok(messages::browse_uploaded(key))
+        }
+        Job::Upload { path, dir: true, prefix } => {
+            let files = walk_dir(&path).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+            let mut jobs = Vec::new();
+            for file in files {
+                let Some(name) = file.file_name().and_then(|name| name.to_str()) else { continue };
+                jobs.push((format!("{}{}", relative_key_prefix(&prefix, &path, &file), name), file.clone()));
+            }
+            let total = jobs.len();
+            let mut outcome = Outcome::default();
+            for (i, (key, file)) in jobs.into_iter().enumerate() {
+                progress(Action::Upload, i, total, &key);
+                outcome.add(&key, client.store.put(&key, &file).await);
+            }
+            outcome.finish(Action::Upload)
+        }
+        Job::Download { key, dir: false, target } => {
+            progress(Action::Download, 0, 1, &key);
+            let size = client.store.get(&key, &target).await.map_err(|e| messages::download_failed_for(&key, e))?;
+            Ok(messages::browse_downloaded(target.display(), format_size(size)))
+        }
+        Job::Download { key, dir: true, target } => {
+            let objects = client.store.list(&key).await.map_err(|e| messages::cannot_list(&key, e))?;
+            let objects: Vec<ObjectInfo> = objects.into_iter().filter(|object| !object.key.ends_with('/')).collect();
+            let total = objects.len();
+            let mut outcome = Outcome::default();
+            for (i, object) in objects.iter().enumerate() {
+                progress(Action::Download, i, total, &object.key);
+                let path = target.join(key_to_relative_path(&object.key, &key));
+                if let Some(parent) = path.parent() {
+                    create_dir(parent).await;
+                }
+                outcome.add(&object.key, client.store.get(&object.key, &path).await.map(|_| ()));
+            }
+            outcome.finish(Action::Download)
+        }
+        Job::DeleteLocal { path, dir } => {
+            let result = if dir { tokio::fs::remove_dir_all(&path).await } else { tokio::fs::remove_file(&path).await };
+            result.map_err(|e| messages::cannot_delete(path.display(), e))?;
+            Ok(messages::browse_deleted(path.display()))
+        }
+        Job::DeleteRemote { key, dir: false } => {
+            client.store.delete(&key).await.map_err(|e| messages::cannot_delete(format!("/{}", key), e))?;
+            Ok(messages::browse_deleted(format!("/{}", key)))
+        }
+        Job::DeleteRemote { key, dir: true } => {
+            let objects = client.store.list(&key).await.map_err(|e| messages::cannot_list(&key, e))?;
+            let total = objects.len();
+            let mut outcome = Outcome::default();
+            for (i, object) in objects.iter().enumerate() {
+                progress(Action::Delete, i, total, &object.key);
+                outcome.add(&object.key, client.store.delete(&object.key).await);
+            }
+            outcome.finish(Action::Delete)
+        }
+        Job::RenameLocal { from, to } => {
+            tokio::fs::rename(&from, &to).await.map_err(|e| messages::cannot_rename(from.display(), to.display(), e))?;
+            Ok(messages::browse_renamed(to.display()))
+        }
+        Job::RenameRemote { from, to, dir } => {
+            let keys = match dir {
+                true => client.store.list(&from).await.map_err(|e| messages::cannot_list(&from, e))?
+                    .into_iter().map(|object| object.key).collect(),
+                false => vec![from.clone()],
+            };
+            let total = keys.len();
+            let mut outcome = Outcome::default();
+            for (i, key) in keys.iter().enumerate() {
+                let target = format!("{}{}", to, &key[from.len()..]);
+                progress(Action::Rename, i, total, key);
+                // The original is only removed once the copy is in place.
+                let result = match client.store.copy(key, &target).await {
+                    Ok(()) => client.store.delete(key).await,
+                    Err(e) => Err(e),
+                };
+                outcome.add(key, result);
+            }
+            outcome.finish(Action::Rename)
+        }
+    }
+}
+
+/// Tallies the files of a job on a folder.
+#[derive(Default)]
+struct Outcome {
+    done: usize,
+    failed: usize,
+    first_error: Option<String>,
+}
+
+impl Outcome {
+    fn add<E: std::fmt::Display>(&mut self, item: &str, result: Result<(), E>) {
+        match result {
+            Ok(()) => self.done += 1,
+            Err(e) => {
+                self.failed += 1;
+                self.first_error.get_or_insert_with(|| messages::file_error(item, e));
+            }
+        }
+    }
+
+    fn finish(self, action: Action) -> Result<String, String> {
+        match self.first_error {
+            None => Ok(messages::browse_job_done(action.done(), self.done)),
+            Some(e) => Err(messages::browse_job_failed(action.done(), self.done, self.failed, e)),
+        }
+    }
+}
+
+/// What a job does to each of its files, as its progress and summary say.
+#[derive(Clone, Copy)]
+enum Action {
+    Upload,
+    Download,
+    Delete,
+    Rename,
+}
+
+impl Action {
+    fn ongoing(self) -> String {
+        match self {
+            Action::Upload => messages::browse_uploading(),
+            Action::Download => messages::browse_downloading(),
+            Action::Delete => messages::browse_deleting(),
+            Action::Rename => messages::browse_renaming(),
+        }
+    }
+
+    fn done(self) -> String {
+        match self {
+            Action::Upload => messages::browse_upload_done(),
+            Action::Download => messages::browse_download_done(),
+            Action::Delete => messages::browse_delete_done(),
+            Action::Rename => messages::browse_rename_done(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::browse::{Entry, Pane, parent_prefix, remote_entries, valid_name};
+    use crate::store::ObjectInfo;
+
+    #[test]
+    fn test_remote_entries() {
+        let objects: Vec<ObjectInfo> = [("docs/", 0), ("docs/a.txt", 3), ("docs/a/x.txt", 1), ("docs/a/y/z.txt", 1), ("docs/b.txt", 2), ("docs/c/", 0)]
+            .iter()
+            .map(|(key, size)| ObjectInfo { key: key.to_string(), size: *size, ..ObjectInfo::default() })
+            .collect();
+        assert_eq!(remote_entries("docs/", &objects), [Entry::folder("a"), Entry::folder("c"), Entry::file("a.txt", 3), Entry::file("b.txt", 2)]);
+        assert_eq!(remote_entries("", &objects), [Entry::folder("docs")]);
+
+        assert_eq!(parent_prefix("docs/a/"), "docs/");
+        assert_eq!(parent_prefix("docs/"), "");
+        assert!(valid_name("notes.txt"));
+        assert!(!valid_name("a/b") && !valid_name("..") && !valid_name(""));
+    }
+
+    #[test]
+    fn test_pane() {
+        let mut pane = Pane::default();
+        pane.set(vec![Entry::folder("a"), Entry::file("b", 1), Entry::file("c", 1)], false, None);
+        assert_eq!(pane.entries[0].name, "..");
+        assert_eq!(pane.selected(), None);
+        pane.move_by(10);
+        assert_eq!(pane.selected().map(|entry| entry.name.as_str()), Some("c"));
+        pane.set(vec![Entry::file("b", 1)], true, Some("b"));
+        assert_eq!(pane.selected, 0);
+        pane.move_by(-1);
+        assert_eq!(pane.selected().map(|entry| entry.name.as_str()), Some("b"));
+        pane.set(Vec::new(), true, None);
+        assert_eq!(pane.selected(), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::interceptors::context::BeforeDeserializationInterceptorContextRef;
+use aws_smithy_runtime_api::client::interceptors::Intercept;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_types::config_bag::ConfigBag;
+use tokio::io::AsyncReadExt;
+use crate::constant::{CRC64_HEADER, READ_BUFFER_SIZE};
+use crate::messages;
+
+/// The reflected ECMA-182 polynomial, as used by CRC-64/XZ.
+const POLY: u64 = 0xC96C_5795_D787_0F42;
+
+const TABLE: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Running CRC-64/ECMA of a byte stream, the checksum OSS reports for every
+/// object and part in `x-oss-hash-crc64ecma`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Crc64 {
+    state: u64,
+}
+
+impl Crc64 {
+    pub fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    /// Continues from `value`, the checksum of data that came before.
+    pub fn resume(value: u64) -> Self {
+        Self { state: !value }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.state = TABLE[((self.state ^ byte as u64) & 0xff) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        !self.state
+    }
+}
+
+pub(crate) fn crc64(data: &[u8]) -> u64 {
+    let mut crc = Crc64::new();
+    crc.update(data);
+    crc.value()
+}
+
+/// The checksum of the first `len` bytes of the file at `path`.
+pub(crate) async fn crc64_file_prefix(path: &Path, len: u64) -> Result<Crc64, String> {
+    let file = tokio::fs::File::open(path).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+    let mut reader = file.take(len);
+    let mut crc = Crc64::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buffer).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Ok(crc);
+        }
+        crc.update(&buffer[..n]);
+    }
+}
+
+/// Fails when the server reported a checksum that differs from `local`. A
+/// server that reports none, like plain S3, is trusted.
+pub(crate) fn compare(what: &str, reported: Option<u64>, local: u64) -> Result<(), String> {
+    match reported {
+        Some(reported) if reported != local => Err(messages::crc64_mismatch(what, reported, local)),
+        _ => Ok(()),
+    }
+}
+
+/// Records the CRC64 header of the response to the request it is attached to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Crc64Capture {
+    value: Arc<Mutex<Option<u64>>>,
+}
+
+impl Crc64Capture {
+    pub fn value(&self) -> Option<u64> {
+        *self.value.lock().unwrap()
+    }
+}
+
+impl Intercept for Crc64Capture {
+    fn name(&self) -> &'static str {
+        "Crc64Capture"
+    }
+
+    fn read_before_deserialization(&self,
+                                   context: &BeforeDeserializationInterceptorContextRef<'_>,
+                                   _runtime_components: &RuntimeComponents,
+                                   _cfg: &mut ConfigBag) -> Result<(), BoxError> {
+        let value = context.response().headers().get(CRC64_HEADER).and_then(|value| value.parse().ok());
+        *self.value.lock().unwrap() = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checksum::{compare, crc64, Crc64};
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(b""), 0);
+        assert_eq!(crc64(b"123456789"), 0x995D_C9BB_DF19_39FA);
+        let mut crc = Crc64::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.value(), crc64(b"123456789"));
+        let mut resumed = Crc64::resume(crc64(b"1234"));
+        resumed.update(b"56789");
+        assert_eq!(resumed.value(), crc64(b"123456789"));
+        assert!(compare("a", Some(1), 1).is_ok());
+        assert!(compare("a", None, 1).is_ok());
+        assert!(compare("a", Some(2), 1).is_err());
+    }
+}
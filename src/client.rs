@@ -1,126 +1,357 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::SeekFrom;
 use std::option::Option;
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use aws_config::{BehaviorVersion, Region, SdkConfig};
 use aws_sdk_s3::{Client, config};
-use aws_sdk_s3::config::{Credentials, SharedCredentialsProvider};
+use aws_sdk_s3::config::{Credentials, RequestChecksumCalculation, ResponseChecksumValidation, SharedCredentialsProvider};
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use aws_sdk_s3::operation::get_object::GetObjectOutput;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
-use aws_sdk_s3::operation::put_object::PutObjectOutput;
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadOutput;
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::{ByteStream, DateTime};
-use serde::{Deserialize, Serialize};
-use tokio::fs::{DirBuilder, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::types::{Bucket, BucketLifecycleConfiguration, BucketLocationConstraint, CreateBucketConfiguration, CompletedMultipartUpload, CompletedPart, Delete, GlacierJobParameters, Grant, LifecycleRule, MultipartUpload, ObjectCannedAcl, Permission, Object, ObjectIdentifier, RestoreRequest, Tag, Tagging, Tier};
+use aws_smithy_types::retry::RetryConfig;
+use aws_smithy_types::timeout::TimeoutConfig;
+use aws_smithy_runtime_api::client::http::{http_client_fn, SharedHttpClient, SharedHttpConnector};
+use bytes::Bytes;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::task::JoinSet;
+use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 use crate::command::{CommandRegistry};
-use crate::constant::TEMP_FOLDER;
-use crate::crypt::encrypt_file;
-use crate::handler;
+use crate::constant::{ALL_USERS_URI, CHUNK_SIZE, COPY_PART_SIZE, DEFAULT_COMPRESSION_LEVEL, DEFAULT_CONCURRENCY, MULTIPART_COPY_THRESHOLD, PART_RETRIES, PART_SIZE, SIDECAR_METADATA, CHECKSUM_METADATA, EXPIRY_TAG, HEADER_LEN, MAX_TAGS, MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN, MAX_DELETE_BATCH, CONNECT_TIMEOUT, READ_TIMEOUT, MAX_ATTEMPTS, MAX_BACKOFF, OFFLINE_BUCKET, OFFLINE_ENDPOINT, SYMLINK_TARGET_HEADER};
+use crate::config::{parse_attempts, resolve, Config, Provider};
+use crate::store::StoreClient;
+use crate::crypt::{BufferSizes, Cipher, FileCipher, header_len, is_compressed, is_hidden_name, StreamDecryptor};
+use crate::error::RotError;
+use crate::event::TransferEvent;
+use crate::{checksum, handler, messages, proxy, store, transfer};
+use crate::checksum::{crc64, Crc64, Crc64Capture};
+use crate::symlink::SymlinkCapture;
+use crate::transfer::{ConcurrencyController, MemoryBudget, Part, RateLimiter};
+use crate::manifest::{HashingReader, sha256_file, to_hex};
 use crate::parser::{CommandParser};
-use crate::utils::{create_dir, DeleteFolder, get_parent_path, open_file};
+use crate::local::LocalStore;
+use crate::output::{OutputFormat, Reporter, VersionRecord};
+use crate::resume::{DownloadState, first_missing_part, part_path, UploadState};
+use crate::utils::{cache_dir, file_url, format_size, parse_duration, parse_rate, long_path, normalize_key, percent_encode_component, percent_encode_key};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AliyunClient {
     client: Client,
     bucket: String,
+    concurrency: Concurrency,
+    part_size: usize,
+    /// Objects larger than this are copied with UploadPartCopy.
+    multipart_copy_threshold: u64,
+    buffers: BufferSizes,
+    memory_budget: Option<MemoryBudget>,
+    rate_limiter: Option<RateLimiter>,
+    read_only: bool,
+    resume_downloads: bool,
+    overwrite: bool,
+    verify: bool,
+    /// Tags set on every object this client uploads.
+    upload_tags: BTreeMap<String, String>,
+    /// ACL set on every object this client uploads.
+    upload_acl: Option<ObjectCannedAcl>,
+    /// Store encrypted uploads under a hash of their file name.
+    hide_names: bool,
+    /// zstd level encrypted uploads are compressed with.
+    compression: Option<i32>,
+    /// Where progress of uploads and downloads is reported.
+    events: Option<Sender<TransferEvent>>,
+    /// Stops uploads and downloads once cancelled.
+    cancellation: Option<CancellationToken>,
+    /// Version that downloads read instead of the latest one.
+    version_id: Option<String>,
+    dry_run: bool,
+    output: OutputFormat,
+    /// Proxy from the configuration, also used for requests made outside
+    /// the SDK.
+    proxy: Option<String>,
+    endpoint: Option<String>,
+    /// The directory of the offline store, which can't sign URLs.
+    offline_root: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
-pub struct Config {
-    access_key_id: String,
-    secret_access_key: String,
-    region: String,
-    endpoint_url: String,
-    bucket: String,
+/// How many parts of a multipart upload are sent at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    Fixed(usize),
+    /// Starts small and adapts to the observed throughput and error rate.
+    Auto,
+}
+
+/// A started multipart upload and the parts it already holds.
+struct OpenUpload<'a> {
+    key: &'a str,
+    upload_id: &'a str,
+    part_size: usize,
+    stored_parts: Vec<CompletedPart>,
+}
+
+/// HTTP method a presigned URL is valid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareMethod {
+    Get,
+    Put,
+}
+
+impl FromStr for ShareMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "GET" => Ok(ShareMethod::Get),
+            "PUT" => Ok(ShareMethod::Put),
+            _ => Err(messages::unsupported_share_method(s)),
+        }
+    }
+}
+
+/// Where an archived object stands in being restored, from the
+/// `x-amz-restore` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStatus {
+    /// Never restored, or the restored copy has expired.
+    NotRestored,
+    InProgress,
+    /// Readable until the given HTTP date, if the server said.
+    Restored(Option<String>),
+}
+
+impl RestoreStatus {
+    fn parse(header: Option<&str>) -> Self {
+        match header {
+            None => RestoreStatus::NotRestored,
+            Some(value) if value.contains("ongoing-request=\"true\"") => RestoreStatus::InProgress,
+            Some(value) => RestoreStatus::Restored(value.split_once("expiry-date=")
+                .map(|(_, date)| date.trim().trim_matches('"').to_string())),
+        }
+    }
+}
+
+/// Keys removed by a batch delete, with the delete marker's version id on
+/// versioned buckets, and the keys that could not be removed with the reason.
+#[derive(Debug, Default)]
+pub(crate) struct DeleteReport {
+    pub deleted: Vec<(String, Option<String>)>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Result of an upload that may be skipped because the object is unchanged.
+#[derive(Debug)]
+pub(crate) enum UploadOutcome {
+    Uploaded(Box<CompleteMultipartUploadOutput>),
+    Unchanged(String),
+}
+
+/// Integrity data attached to an upload.
+#[derive(Debug, Default)]
+pub(crate) struct UploadChecks {
+    /// Recorded as object metadata so `upload_if_changed` can compare it.
+    pub checksum: Option<String>,
+    /// SHA-256 the source must hash to; the upload is aborted otherwise.
+    pub expected_sha256: Option<String>,
 }
 
+/// A part handed to an upload task, kept together with its memory
+/// reservation so that a retry doesn't have to reserve again.
+struct PendingPart {
+    number: i32,
+    data: Bytes,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+type PartResult = Result<(i32, usize, Option<String>), (PendingPart, String)>;
+
 pub struct AliyunOssCommandExecutor {
-    client: Arc<Mutex<AliyunClient>>,
+    /// What the commands every provider supports work on.
+    store: Arc<Mutex<StoreClient>>,
+    /// The client behind `store` when the provider has an S3 API, for the
+    /// commands that need one.
+    client: Option<Arc<Mutex<AliyunClient>>>,
     registry: CommandRegistry,
 }
 
-impl Config {
-    pub fn new_empty() -> Self {
-        Config {
-            access_key_id: "".into(),
-            secret_access_key: "".into(),
-            region: "".into(),
-            endpoint_url: "".into(),
-            bucket: "".into(),
+impl FromStr for Concurrency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Concurrency::Auto);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Concurrency::Fixed(n)),
+            _ => Err(messages::invalid_concurrency(s)),
         }
     }
+}
 
-    pub fn is_valid(&self) -> bool {
-        !(self.access_key_id.is_empty()
-            || self.secret_access_key.is_empty()
-            || self.region.is_empty()
-            || self.endpoint_url.is_empty()
-            || self.bucket.is_empty())
+impl Concurrency {
+    /// Number of whole files transferred at once when a command works on many
+    /// objects. The adaptive mode only applies to parts within one file.
+    pub fn pool_size(&self) -> usize {
+        match self {
+            Concurrency::Fixed(n) => (*n).max(1),
+            Concurrency::Auto => DEFAULT_CONCURRENCY,
+        }
     }
 }
 
-impl AliyunClient {
-    pub async fn load_from_env() -> Option<Self> {
-        let home_path = match home::home_dir() {
-            Some(path) => path,
-            None => {
-                eprintln!("Impossible to get your home dir!");
-                return None;
-            }
-        };
+impl Display for Concurrency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Concurrency::Fixed(n) => write!(f, "{}", n),
+            Concurrency::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// Describes a failed request with the whole chain of causes, since the SDK
+/// error on its own only names the kind of failure, such as "service error".
+fn request_error(operation: &str, error: impl std::error::Error) -> String {
+    messages::request_failed(operation, DisplayErrorContext(error))
+}
 
-        let path_str = home_path.to_str().unwrap();
+/// Turns an expiry given in seconds from now into a Unix timestamp.
+fn expires_at(expiry_seconds: Option<i64>) -> Option<i64> {
+    expiry_seconds.map(|seconds| DateTime::from(SystemTime::now()).secs() + seconds)
+}
 
-        let file_prefix_path = format!("{}/.config/rot/", path_str);
-        let filename = "rot.json";
-        DirBuilder::new()
-            .recursive(true)
-            .create(&file_prefix_path).await.expect("Couldn't create or open dir.");
+/// Tags an object with its expiry so that `rot expire` (or a bucket
+/// lifecycle rule filtering on the tag) can remove it later. The `Expires`
+/// header alone is only a caching hint and deletes nothing.
+fn expiry_tagging(expires_at: i64) -> String {
+    format!("{}={}", EXPIRY_TAG, expires_at)
+}
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(format!("{}{}", &file_prefix_path, filename))
-            .await
-            .expect("Couldn't create or open file.");
-
-        let mut text = String::new();
-        file.read_to_string(&mut text).await.expect("Couldn't read file.");
-
-        if text.is_empty() {
-            let config_text = serde_json::to_string(&Config::new_empty()).expect("Couldn't serialize.");
-            println!("is empty: {}", &config_text);
-            file.write_all(config_text.as_bytes()).await.expect("TODO: panic message");
-            return None;
-        } else {
-            let result = serde_json::from_str::<Config>(&text);
-            let config: Option<Config> = match result {
-                Ok(value) => {
-                    Some(value)
-                }
-                Err(_) => {
-                    let config_text = serde_json::to_string(&Config::new_empty()).expect("Couldn't serialize.");
-                    file.write_all(config_text.as_bytes()).await.expect("TODO: panic message");
-                    None
-                }
-            };
+/// The tagging header value for `tags` plus the expiry tag.
+fn object_tagging(tags: &BTreeMap<String, String>, expires_at: Option<i64>) -> Option<String> {
+    let mut pairs: Vec<String> = tags.iter()
+        .map(|(key, value)| format!("{}={}", percent_encode_key(key), percent_encode_key(value)))
+        .collect();
+    pairs.extend(expires_at.map(expiry_tagging));
+    (!pairs.is_empty()).then(|| pairs.join("&"))
+}
 
-            if config.is_none() {
-                println!("Configuration is missing.");
-                return None;
-            } else if let Some(value) = config {
-                if value.is_valid() {
-                    return Some(Self::new(
-                        value.access_key_id,
-                        value.secret_access_key,
-                        value.endpoint_url,
-                        value.region,
-                        value.bucket,
-                    ));
-                }
+/// Parses `key=value` tags given on the command line, each argument holding
+/// one or more comma-separated pairs.
+pub(crate) fn parse_tags<'a>(args: impl IntoIterator<Item=&'a str>) -> Result<BTreeMap<String, String>, String> {
+    let mut tags = BTreeMap::new();
+    for pair in args.into_iter().flat_map(|arg| arg.split(',')).filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').ok_or_else(|| messages::invalid_tag(pair))?;
+        if key.is_empty() || key.chars().count() > MAX_TAG_KEY_LEN || value.chars().count() > MAX_TAG_VALUE_LEN {
+            return Err(messages::invalid_tag_length(pair, MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN));
+        }
+        tags.insert(key.to_string(), value.to_string());
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(messages::too_many_tags(MAX_TAGS));
+    }
+    Ok(tags)
+}
+
+/// Parses the ACL given to `rot acl set` or `rot upload --acl`.
+pub(crate) fn parse_acl(value: &str) -> Result<ObjectCannedAcl, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "private" => Ok(ObjectCannedAcl::Private),
+        "public-read" => Ok(ObjectCannedAcl::PublicRead),
+        _ => Err(messages::unknown_acl(value)),
+    }
+}
+
+/// Names the canned ACL that `grants` amount to, judged by what everyone
+/// may do with the object.
+fn canned_acl(grants: &[Grant]) -> &'static str {
+    let public: Vec<&Permission> = grants.iter()
+        .filter(|grant| grant.grantee().and_then(|grantee| grantee.uri()) == Some(ALL_USERS_URI))
+        .filter_map(|grant| grant.permission())
+        .collect();
+    if public.iter().any(|permission| matches!(permission, Permission::Write | Permission::FullControl)) {
+        "public-read-write"
+    } else if public.contains(&&Permission::Read) {
+        "public-read"
+    } else {
+        "private"
+    }
+}
+
+fn find_expiry(tags: &[Tag]) -> Option<i64> {
+    tags.iter()
+        .find(|tag| tag.key() == EXPIRY_TAG)
+        .and_then(|tag| tag.value().parse().ok())
+}
+
+impl AliyunClient {
+    /// Builds the client for `profile`, or the default one, as
+    /// `resolve` finds it. `Ok(None)` means the credentials still
+    /// have to be configured.
+    pub async fn load_from_env(profile: Option<&str>) -> Result<Option<Self>, RotError> {
+        resolve(profile).await
+            .map_err(RotError::Config)?
+            .map(|value| Self::from_config(&value))
+            .transpose()
+    }
+
+    /// A client for the credentials and settings of one profile.
+    pub fn from_config(value: &Config) -> Result<Self, RotError> {
+        let credentials = Credentials::new(
+            value.access_key_id.clone(),
+            value.secret_access_key.clone(),
+            value.security_token.clone(),
+            None,
+            "static",
+        );
+        let mut client = match (value.provider, &value.root) {
+            (Provider::Local, Some(root)) => Self::offline(root).with_bucket(value.bucket.clone()),
+            (Provider::Local, None) => return Err(RotError::Config(messages::local_needs_root())),
+            (Provider::Sftp | Provider::WebDav, _) => return Err(RotError::Config(messages::provider_without_s3(value.provider))),
+            _ => {
+                let http_client = value.proxy.as_deref().map(proxy::sdk_http_client).transpose().map_err(RotError::Config)?;
+                let client = AliyunClient::build_aws_client(credentials,
+                                                            value.service_endpoint(),
+                                                            value.signing_region().to_string(),
+                                                            value.uses_path_style(),
+                                                            value.supports_checksum_headers(),
+                                                            http_client);
+                let mut client = Self::with_client(client, value.bucket.clone());
+                client.endpoint = value.service_endpoint();
+                client
             }
+        };
+        client.proxy = value.proxy.clone();
+        client = client
+            .with_timeouts(Some(value.connect_timeout.map_or(CONNECT_TIMEOUT, Duration::from_secs)),
+                           Some(value.read_timeout.map_or(READ_TIMEOUT, Duration::from_secs)))
+            .with_max_attempts(value.max_attempts.unwrap_or(MAX_ATTEMPTS));
+        if let Some(size) = value.read_buffer_size {
+            client = client.with_read_buffer_size(size);
+        }
+        if let Some(size) = value.write_buffer_size {
+            client = client.with_write_buffer_size(size);
+        }
+        if let Some(size) = value.max_memory {
+            client = client.with_max_memory(size);
+        }
+        if let Some(rate) = value.bwlimit {
+            client = client.with_bwlimit(rate);
         }
-        None
+        Ok(client.with_read_only(value.read_only))
     }
 
     pub fn new(access_key_id: impl Into<String>,
@@ -129,19 +360,407 @@ impl AliyunClient {
                region: impl Into<Cow<'static, str>>,
                bucket: impl Into<String>,
     ) -> Self {
-        let client = AliyunClient::build_aws_client(access_key_id, secret_access_key, endpoint_url, region);
+        let credentials = Credentials::new(access_key_id, secret_access_key, None, None, "static");
+        let endpoint_url = endpoint_url.into();
+        let client = AliyunClient::build_aws_client(credentials, Some(endpoint_url.clone()), region, false, true, None);
+        let mut client = Self::with_client(client, bucket);
+        client.endpoint = Some(endpoint_url);
+        client
+    }
+
+    fn with_client(client: Client, bucket: impl Into<String>) -> Self {
         Self {
             client,
             bucket: bucket.into(),
+            concurrency: Concurrency::Fixed(DEFAULT_CONCURRENCY),
+            part_size: PART_SIZE,
+            multipart_copy_threshold: MULTIPART_COPY_THRESHOLD,
+            buffers: BufferSizes::default(),
+            memory_budget: None,
+            rate_limiter: None,
+            read_only: false,
+            resume_downloads: true,
+            overwrite: false,
+            verify: true,
+            upload_tags: BTreeMap::new(),
+            upload_acl: None,
+            hide_names: false,
+            compression: None,
+            events: None,
+            cancellation: None,
+            version_id: None,
+            dry_run: false,
+            output: OutputFormat::Text,
+            proxy: None,
+            endpoint: None,
+            offline_root: None,
+        }
+    }
+
+    /// A client backed by the local directory `root` instead of a real
+    /// endpoint, for trying rot without credentials and for end-to-end tests.
+    /// Each bucket is a subdirectory of `root`; the default bucket is `offline`.
+    pub fn offline(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let endpoint = format!("local://{}", root.display());
+        let store = LocalStore::new(root.clone());
+        let s3_config = config::Builder::new()
+            .credentials_provider(Credentials::new("offline", "offline", None, None, "offline"))
+            .endpoint_url(OFFLINE_ENDPOINT)
+            .region(Region::new("local"))
+            .force_path_style(true)
+            .http_client(http_client_fn(move |_, _| SharedHttpConnector::new(store.clone())))
+            .behavior_version(BehaviorVersion::latest())
+            .build();
+        let mut client = Self::with_client(Client::from_conf(s3_config), OFFLINE_BUCKET);
+        client.endpoint = Some(endpoint);
+        client.offline_root = Some(root);
+        client
+    }
+
+    pub fn with_bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.bucket = bucket.into();
+        self
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// The endpoint requests go to, `None` when the SDK derives it from the
+    /// region. For the offline store it is the `local://` URL of its root.
+    pub(crate) fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
+    }
+
+    pub fn with_concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Sets the multipart part size, rounded up to a whole number of
+    /// encryption chunks so that encrypted parts can be sealed independently.
+    pub fn with_part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size.max(1).div_ceil(CHUNK_SIZE) * CHUNK_SIZE;
+        self
+    }
+
+    /// Sets the size above which `copy_from` copies in parts rather than
+    /// with a single CopyObject request.
+    pub fn with_multipart_copy_threshold(mut self, threshold: u64) -> Self {
+        self.multipart_copy_threshold = threshold;
+        self
+    }
+
+    pub fn with_read_buffer_size(mut self, read_buffer_size: usize) -> Self {
+        self.buffers.read = read_buffer_size.max(1);
+        self
+    }
+
+    pub fn with_write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.buffers.write = write_buffer_size.max(1);
+        self
+    }
+
+    /// Caps the memory held by transfer buffers. The budget is shared by every
+    /// clone of this client, so parallel files draw from the same pool.
+    pub fn with_max_memory(mut self, max_memory: u64) -> Self {
+        self.memory_budget = Some(MemoryBudget::new(max_memory));
+        self
+    }
+
+    /// Caps transfer bandwidth at `bytes_per_second`. Like the memory budget,
+    /// the limit is shared by every clone of this client.
+    pub fn with_bwlimit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(bytes_per_second));
+        self
+    }
+
+    /// Rejects every operation that would modify the bucket, whatever the
+    /// credentials allow.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether uploads may replace existing objects and downloads existing
+    /// files. Off by default, so that nothing is lost without `--force`.
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn overwrites(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Whether the CRC64 the server reports for uploaded parts and downloaded
+    /// objects is compared with the data sent or received. On by default.
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Tags every uploaded object with `tags` at creation time, next to the
+    /// expiry tag rot manages itself.
+    pub fn with_upload_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.upload_tags = tags;
+        self
+    }
+
+    /// Gives every uploaded object `acl` instead of inheriting the bucket's.
+    pub fn with_upload_acl(mut self, acl: Option<ObjectCannedAcl>) -> Self {
+        self.upload_acl = acl;
+        self
+    }
+
+    /// Stores encrypted uploads under a keyed hash of their file name, with
+    /// the real name sealed in the header for downloads to restore.
+    /// Unencrypted uploads keep their names.
+    pub fn with_hidden_names(mut self, hide_names: bool) -> Self {
+        self.hide_names = hide_names;
+        self
+    }
+
+    /// Compresses encrypted uploads with zstd at `level` before sealing each
+    /// chunk. Unencrypted uploads are stored as they are. Compressed uploads
+    /// can't be resumed, since their parts no longer line up with the file.
+    pub fn with_compression(mut self, level: Option<i32>) -> Self {
+        self.compression = level;
+        self
+    }
+
+    /// Sends a [`TransferEvent`] to `events` for every step of each upload
+    /// and download. A full channel holds the transfer up until the receiver
+    /// catches up; a closed one is ignored.
+    pub fn with_events(mut self, events: Sender<TransferEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Stops uploads and downloads once `token` is cancelled. A cancelled
+    /// upload is aborted rather than kept for resuming, and a cancelled
+    /// download removes its partial file.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Reads version `version_id` of objects instead of the latest one, for
+    /// `rot download --version-id`.
+    pub fn with_version_id(mut self, version_id: impl Into<String>) -> Self {
+        self.version_id = Some(version_id.into());
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Resolves once the transfers are cancelled; never without a token.
+    pub(crate) async fn cancelled(&self) {
+        match &self.cancellation {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn emit(&self, event: TransferEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event).await;
         }
     }
 
+    /// Passes `result` on after reporting how the transfer of `key` ended.
+    async fn report<T>(&self, key: &str, result: Result<T, String>) -> Result<T, String> {
+        let key = key.to_string();
+        self.emit(match &result {
+            Ok(_) => TransferEvent::Completed { key },
+            Err(e) => TransferEvent::Failed { key, error: e.clone() },
+        }).await;
+        result
+    }
+
+    /// The header and keys for a new encrypted upload, compressed if this
+    /// client compresses.
+    fn new_sealer(&self, cipher: &Cipher) -> Result<FileCipher, String> {
+        let sealer = cipher.new_file(CHUNK_SIZE)?;
+        match self.compression {
+            Some(level) => sealer.with_compression(level),
+            None => Ok(sealer),
+        }
+    }
+
+    /// Only reports what mutating commands would do. Handlers check
+    /// [`Self::is_dry_run`] before changing anything; the client itself
+    /// refuses writes as a safety net.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// Limits how long a request waits for a connection and, once
+    /// connected, for more data. `None` keeps the current timeout.
+    pub fn with_timeouts(mut self, connect: Option<Duration>, read: Option<Duration>) -> Self {
+        let config = self.client.config();
+        let mut timeouts = config.timeout_config().map_or_else(TimeoutConfig::builder, TimeoutConfig::to_builder);
+        if let Some(connect) = connect {
+            timeouts = timeouts.connect_timeout(connect);
+        }
+        if let Some(read) = read {
+            timeouts = timeouts.read_timeout(read);
+        }
+        self.client = Client::from_conf(config.to_builder().timeout_config(timeouts.build()).build());
+        self
+    }
+
+    /// Tries each request up to `attempts` times, waiting an exponentially
+    /// growing, jittered delay between attempts.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        let retry = RetryConfig::standard()
+            .with_max_attempts(attempts.max(1))
+            .with_max_backoff(MAX_BACKOFF);
+        self.client = Client::from_conf(self.client.config().to_builder().retry_config(retry).build());
+        self
+    }
+
+    /// Whether an interrupted download is continued from its `.part` file
+    /// rather than started over. On by default.
+    pub fn with_download_resume(mut self, resume_downloads: bool) -> Self {
+        self.resume_downloads = resume_downloads;
+        self
+    }
+
+    /// Prints command results as text or as JSON lines.
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub(crate) fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    pub(crate) fn reporter(&self) -> Reporter {
+        Reporter::new(self.output)
+    }
+
+    pub(crate) fn ensure_writable(&self, action: &str) -> Result<(), RotError> {
+        if self.read_only {
+            return Err(RotError::Config(messages::read_only_bucket(action)));
+        }
+        if self.dry_run {
+            return Err(RotError::Config(messages::dry_run_refused(action)));
+        }
+        Ok(())
+    }
+
+    pub fn concurrency(&self) -> Concurrency {
+        self.concurrency
+    }
+
+    /// Lists every object under `prefix`, following continuation tokens.
+    pub async fn list_all(&self, prefix_path: Option<String>) -> Result<Vec<Object>, RotError> {
+        let mut objects = Vec::new();
+        let mut token = None;
+        loop {
+            let resp = self.list_obj(None, prefix_path.clone(), token).await?;
+            objects.extend(resp.contents.unwrap_or_default());
+            match resp.next_continuation_token {
+                Some(next) if resp.is_truncated == Some(true) => token = Some(next),
+                _ => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    /// The "folders" directly below `prefix`, following continuation tokens.
+    pub(crate) async fn list_folders(&self, prefix_path: Option<String>) -> Result<Vec<String>, RotError> {
+        let mut folders = Vec::new();
+        let mut token = None;
+        loop {
+            let resp = self.list_level(None, prefix_path.clone(), token).await?;
+            folders.extend(resp.common_prefixes().iter().filter_map(|common| common.prefix.clone()));
+            match resp.next_continuation_token {
+                Some(next) if resp.is_truncated == Some(true) => token = Some(next),
+                _ => break,
+            }
+        }
+        Ok(folders)
+    }
+
+    /// Every version of `key` and its delete markers, newest first.
+    pub(crate) async fn list_versions(&self, key: &str) -> Result<Vec<VersionRecord>, RotError> {
+        let mut records = Vec::new();
+        let mut markers = (None, None);
+        loop {
+            let resp = self.client.list_object_versions()
+                .bucket(&self.bucket)
+                .prefix(key)
+                .set_key_marker(markers.0)
+                .set_version_id_marker(markers.1)
+                .send()
+                .await
+                .map_err(|e| RotError::Sdk(request_error("ListObjectVersions", e)))?;
+            records.extend(resp.versions().iter().map(VersionRecord::from));
+            records.extend(resp.delete_markers().iter().map(VersionRecord::from));
+            if resp.is_truncated != Some(true) {
+                break;
+            }
+            markers = (resp.next_key_marker, resp.next_version_id_marker);
+        }
+        records.retain(|record| record.key == key);
+        // Timestamps are RFC 3339 in UTC, so they sort as text.
+        records.sort_by(|a, b| b.is_latest.cmp(&a.is_latest).then_with(|| b.last_modified.cmp(&a.last_modified)));
+        Ok(records)
+    }
+
     pub async fn list_obj(&self,
                           max_keys: Option<i32>,
                           prefix_path: Option<String>,
-                          token: Option<String>) -> ListObjectsV2Output {
+                          token: Option<String>) -> Result<ListObjectsV2Output, RotError> {
+        self.list_page(max_keys, prefix_path, token, None).await.map(|(page, _)| page)
+    }
+
+    /// One level of the listing below `prefix_path`: the objects directly
+    /// in it, and the "folders" below it as common prefixes.
+    pub async fn list_level(&self,
+                            max_keys: Option<i32>,
+                            prefix_path: Option<String>,
+                            token: Option<String>) -> Result<ListObjectsV2Output, RotError> {
+        self.list_page(max_keys, prefix_path, token, Some("/")).await.map(|(page, _)| page)
+    }
+
+    /// `list_obj`, or `list_level` when not `recursive`, together with the
+    /// keys on the page that are symlinks.
+    pub(crate) async fn list_with_links(&self,
+                                        max_keys: Option<i32>,
+                                        prefix_path: Option<String>,
+                                        token: Option<String>,
+                                        recursive: bool) -> Result<(ListObjectsV2Output, BTreeSet<String>), RotError> {
+        self.list_page(max_keys, prefix_path, token, (!recursive).then_some("/")).await
+    }
+
+    async fn list_page(&self,
+                       max_keys: Option<i32>,
+                       prefix_path: Option<String>,
+                       token: Option<String>,
+                       delimiter: Option<&str>) -> Result<(ListObjectsV2Output, BTreeSet<String>), RotError> {
         let mut res = self.client.list_objects_v2()
-            .bucket(&self.bucket);
+            .bucket(&self.bucket)
+            .set_delimiter(delimiter.map(str::to_string));
 
 
         if let Some(value) = max_keys {
@@ -156,167 +775,2199 @@ impl AliyunClient {
             res = res.continuation_token(value)
         }
 
-        let resp = res.send().await.expect("Request Error by list object.");
-
-        resp
+        let capture = SymlinkCapture::default();
+        let page = res.customize()
+            .interceptor(capture.clone())
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("ListObjectsV2", e)))?;
+        Ok((page, capture.links()))
     }
 
     pub async fn upload_file(&self,
                              key: impl Into<String>,
                              input_path: PathBuf,
                              password: Option<impl Into<String>>,
-                             expiry_seconds: Option<i64>) -> Result<PutObjectOutput, String> {
-        let mut delete_path: Option<PathBuf> = None;
+                             expiry_seconds: Option<i64>) -> Result<CompleteMultipartUploadOutput, RotError> {
+        let cipher = password.map(|pwd| Arc::new(Cipher::new(pwd)));
+        self.upload_file_with_key(key, input_path, cipher, expiry_seconds).await.map_err(RotError::Transfer)
+    }
 
-        let filename = match input_path.file_name() {
-            Some(file_name) => file_name.to_string_lossy(),
-            None => {
-                return Err("couldn't get filename！".into());
-            }
-        };
+    /// Same as `upload_file`, but with a shared `Cipher` so that batch
+    /// uploads run the password KDF once instead of once per file.
+    pub(crate) async fn upload_file_with_key(&self,
+                                             key: impl Into<String>,
+                                             input_path: PathBuf,
+                                             cipher: Option<Arc<Cipher>>,
+                                             expiry_seconds: Option<i64>) -> Result<CompleteMultipartUploadOutput, String> {
+        let object_key = self.upload_key(key.into(), &input_path, cipher.as_deref())?;
+        self.upload_to_key(&object_key, input_path, cipher, expiry_seconds, None).await
+    }
+
+    /// Like `upload_file_with_key`, but first compares the file's SHA-256
+    /// with the checksum recorded on the existing object and skips the
+    /// transfer when they match. The checksum is stored as object metadata,
+    /// so only objects uploaded this way can be recognised as unchanged.
+    pub(crate) async fn upload_if_changed(&self,
+                                          key: impl Into<String>,
+                                          input_path: PathBuf,
+                                          cipher: Option<Arc<Cipher>>,
+                                          expiry_seconds: Option<i64>) -> Result<UploadOutcome, String> {
+        let object_key = self.upload_key(key.into(), &input_path, cipher.as_deref())?;
+        let checksum = Self::upload_checksum(&input_path, cipher.as_deref()).await?;
+        if self.has_checksum(&object_key, &checksum).await {
+            return Ok(UploadOutcome::Unchanged(object_key));
+        }
+
+        let resp = self.upload_to_key(&object_key, input_path, cipher, expiry_seconds, Some(checksum)).await?;
+        Ok(UploadOutcome::Uploaded(Box::new(resp)))
+    }
 
-        let content =
-            if let Some(pwd) = password {
+    /// The object key an upload of `input_path` below `key` would write,
+    /// and whether it would be transferred at all: with `skip_unchanged` an
+    /// object already holding the same content is left alone. Used by
+    /// `--dry-run`.
+    pub(crate) async fn preview_upload(&self,
+                                       key: impl Into<String>,
+                                       input_path: &Path,
+                                       cipher: Option<&Cipher>,
+                                       skip_unchanged: bool) -> Result<(String, bool), String> {
+        let object_key = self.upload_key(key.into(), input_path, cipher)?;
+        if skip_unchanged {
+            let checksum = Self::upload_checksum(input_path, cipher).await?;
+            if self.has_checksum(&object_key, &checksum).await {
+                return Ok((object_key, false));
+            }
+        }
+        self.ensure_overwritable(&object_key).await?;
+        Ok((object_key, true))
+    }
 
-                let mut output_path = match get_parent_path(&input_path).await {
-                    Ok(value) => value,
-                    Err(e) => { return Err(e); }
-                };
+    /// Refuses to replace an existing object unless overwriting is allowed.
+    async fn ensure_overwritable(&self, object_key: &str) -> Result<(), String> {
+        if !self.overwrite && self.head_obj(object_key).await.is_ok() {
+            return Err(messages::object_exists(object_key));
+        }
+        Ok(())
+    }
 
-                output_path.push(TEMP_FOLDER);
-                create_dir(&output_path).await;
-                output_path.push(filename.to_string());
+    /// The checksum `upload_if_changed` records on the object, keyed by the
+    /// cipher's secret when the upload is encrypted.
+    async fn upload_checksum(input_path: &Path, cipher: Option<&Cipher>) -> Result<String, String> {
+        let (sha256, _) = sha256_file(input_path).await?;
+        match cipher {
+            Some(cipher) => cipher.content_checksum(&sha256),
+            None => Ok(sha256),
+        }
+    }
 
-                encrypt_file(&input_path, &output_path, pwd).await.expect("failed to encrypt file.");
-                let bs = ByteStream::from_path(&output_path).await.expect("not found file");
-                output_path.pop();
-                delete_path = Some(output_path);
-                bs
-            } else {
-                ByteStream::from_path(&input_path).await.expect("not found file")
-            };
+    async fn has_checksum(&self, object_key: &str, checksum: &str) -> bool {
+        match self.head_obj(object_key).await {
+            Ok(existing) => existing.metadata()
+                .and_then(|metadata| metadata.get(CHECKSUM_METADATA))
+                .is_some_and(|stored| stored == checksum),
+            Err(_) => false,
+        }
+    }
 
-        let mut prefix_key = key.into();
+    /// Joins the key prefix and the file name into the object key.
+    fn object_key(mut prefix_key: String, input_path: &Path) -> Result<String, String> {
+        let filename = match input_path.file_name() {
+            Some(file_name) => file_name.to_str()
+                .ok_or_else(|| messages::file_name_not_utf8(input_path.display()))?,
+            None => {
+                return Err(messages::no_file_name(input_path.display()));
+            }
+        };
 
-        if !(prefix_key.ends_with('/') || prefix_key.ends_with("\\")) {
+        if !(prefix_key.ends_with('/') || prefix_key.ends_with('\\')) {
             if prefix_key.len() > 1 {
                 prefix_key.push('/');
             } else if prefix_key.len() == 1 {
                 prefix_key.clear()
             }
         }
+        Ok(normalize_key(&format!("{}{}", prefix_key, filename)))
+    }
 
+    /// `object_key`, with the file name hidden if this client hides the
+    /// names of encrypted uploads.
+    fn upload_key(&self, prefix_key: String, input_path: &Path, cipher: Option<&Cipher>) -> Result<String, String> {
+        let object_key = Self::object_key(prefix_key, input_path)?;
+        match cipher.filter(|_| self.hide_names) {
+            Some(cipher) => {
+                let (dir, name) = object_key.rsplit_once('/').map_or(("", object_key.as_str()), |(dir, name)| (dir, name));
+                let hidden = cipher.hidden_name(name)?;
+                Ok(if dir.is_empty() { hidden } else { format!("{}/{}", dir, hidden) })
+            }
+            None => Ok(object_key),
+        }
+    }
 
-        let mut upload = self.client.put_object()
-            .bucket(&self.bucket)
-            .key(format!("{}{}", prefix_key, filename))
-            .body(content);
+    /// The original file name of an object uploaded with hidden names, read
+    /// from its header; `None` if it has none.
+    pub(crate) async fn hidden_file_name(&self, key: &str, cipher: &Cipher) -> Result<Option<String>, String> {
+        cipher.file_name(&self.get_header(key).await?)
+    }
 
-        if let Some(value) = expiry_seconds {
-            let expiry_time = DateTime::from_secs(value);
-            upload = upload.expires(expiry_time);
+    /// The encryption header of `key`, with the wrapped keys and file name
+    /// that may follow it.
+    async fn get_header(&self, key: &str) -> Result<Vec<u8>, String> {
+        let head = self.get_range(key, 0, HEADER_LEN as u64).await?;
+        match header_len(&head) {
+            len if len > HEADER_LEN => self.get_range(key, 0, len as u64).await,
+            _ => Ok(head),
         }
+    }
 
-        let resp = match upload.send().await {
-            Ok(value) => {
-                delete_path.delete().await;
-                value
-            }
-            Err(_) => {
-                delete_path.delete().await;
-                return Err("request error by put object".into());
+    /// Uploads a local file. The upload id is kept in a state file until the
+    /// upload completes, so that if it fails a later run for the same file,
+    /// key and password continues after the parts already stored.
+    pub(crate) async fn upload_to_key(&self,
+                                      object_key: &str,
+                                      input_path: PathBuf,
+                                      cipher: Option<Arc<Cipher>>,
+                                      expiry_seconds: Option<i64>,
+                                      checksum: Option<String>) -> Result<CompleteMultipartUploadOutput, String> {
+        let result = self.upload_path(object_key, input_path, cipher, expiry_seconds, checksum).await;
+        self.report(object_key, result).await
+    }
+
+    async fn upload_path(&self,
+                         object_key: &str,
+                         input_path: PathBuf,
+                         cipher: Option<Arc<Cipher>>,
+                         expiry_seconds: Option<i64>,
+                         checksum: Option<String>) -> Result<CompleteMultipartUploadOutput, String> {
+        self.ensure_writable(&messages::action_upload())?;
+        self.ensure_overwritable(object_key).await?;
+        let mut file = match File::open(&input_path).await {
+            Ok(file) => file,
+            Err(e) => return Err(messages::cannot_open(input_path.display(), e)),
+        };
+        let metadata = file.metadata().await.map_err(|e| e.to_string())?;
+        let file_len = metadata.len();
+        self.emit(TransferEvent::Started { key: object_key.to_string(), total: Some(file_len) }).await;
+        let mut sealer = match cipher.as_deref() {
+            Some(cipher) if self.hide_names => {
+                let name = input_path.file_name().and_then(|name| name.to_str()).map(normalize_key).unwrap_or_default();
+                Some(self.new_sealer(cipher)?.with_name(&name)?)
             }
+            Some(cipher) => Some(self.new_sealer(cipher)?),
+            None => None,
         };
+        let mut state = UploadState {
+            bucket: self.bucket.clone(),
+            key: object_key.to_string(),
+            path: input_path.clone(),
+            upload_id: String::new(),
+            part_size: transfer::part_size_for(file_len, self.part_size),
+            file_len,
+            modified: metadata.modified().map(|time| DateTime::from(time).secs()).unwrap_or(0),
+            key_check: sealer.as_ref().map(|sealer| sealer.key_check().to_string()),
+            header: sealer.as_ref().map(|sealer| to_hex(sealer.header())),
+        };
+        let state_path = UploadState::path_for(&self.bucket, object_key, &input_path)
+            .filter(|_| !sealer.as_ref().is_some_and(FileCipher::is_compressed));
 
+        let mut stored_parts = Vec::new();
+        let previous = match &state_path {
+            Some(path) => UploadState::load(path).await.filter(|previous| previous.resumes(&state)),
+            None => None,
+        };
+        // Encrypted parts continue under the header of the stored ones, as
+        // long as the password is still the same.
+        let previous = previous.and_then(|previous| match (cipher.as_deref(), &previous.header, &previous.key_check) {
+            (Some(cipher), Some(header), Some(check)) => cipher.resume_file(header, check).map(|sealer| (previous, Some(sealer))),
+            (None, None, _) => Some((previous, None)),
+            _ => None,
+        });
+        if let Some((previous, resumed)) = previous {
+            if let Ok(parts) = self.stored_parts(object_key, &previous.upload_id).await {
+                state = previous;
+                stored_parts = parts;
+                sealer = resumed;
+            }
+        }
+        if state.upload_id.is_empty() {
+            state.upload_id = self.create_upload(object_key, checksum, expiry_seconds).await?;
+            if let Some(path) = &state_path {
+                if let Err(e) = state.save(path).await {
+                    self.abort_upload(object_key, &state.upload_id).await;
+                    return Err(e);
+                }
+            }
+        }
 
-        Ok(resp)
-    }
-
-    pub async fn download_file(&self, key: impl Into<String>, path: &PathBuf) {
-        let resp = self.client
-            .get_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
-            .await.unwrap();
-
-        let data = resp.body.collect().await.unwrap();
-        let bytes = data.into_bytes();
-
-        let mut file = open_file(path).await;
-
-        let _ = file.write(&*bytes).await.unwrap();
-        file.flush().await.unwrap();
-        drop(file);
-    }
-
-    fn build_aws_client(access_key_id: impl Into<String>,
-                        secret_access_key: impl Into<String>,
-                        endpoint_url: impl Into<String>,
-                        region: impl Into<Cow<'static, str>>) -> Client {
-        let sdk_config = SdkConfig::builder().credentials_provider(
-            SharedCredentialsProvider::new(
-                Credentials::new(
-                    access_key_id,
-                    secret_access_key,
-                    None,
-                    None,
-                    "static",
-                )
-            ))
-            .endpoint_url(endpoint_url)
-            .region(Region::new(region))
-            .behavior_version(BehaviorVersion::latest())
-            .build();
+        let first_part = first_missing_part(&stored_parts.iter().filter_map(|part| part.part_number).collect::<Vec<_>>());
+        stored_parts.retain(|part| part.part_number.is_some_and(|number| number < first_part));
+        if first_part > 1 {
+            self.reporter().message(messages::resuming_upload(object_key, first_part));
+            file.seek(SeekFrom::Start((first_part - 1) as u64 * state.part_size as u64)).await
+                .map_err(|e| messages::cannot_seek(input_path.display(), e))?;
+        }
 
-        let s3_config_builder = config::Builder::from(&sdk_config);
-        let client = Client::from_conf(s3_config_builder.build());
-        client
+        let reader = BufReader::with_capacity(self.buffers.read, file);
+        let upload = OpenUpload { key: object_key, upload_id: &state.upload_id, part_size: state.part_size, stored_parts };
+        match (self.stream_parts(upload, reader, sealer, None).await, &state_path) {
+            (Ok(resp), Some(path)) => {
+                UploadState::remove(path).await;
+                Ok(resp)
+            }
+            (Ok(resp), None) => Ok(resp),
+            (Err(e), Some(path)) if self.is_cancelled() => {
+                UploadState::remove(path).await;
+                self.abort_upload(object_key, &state.upload_id).await;
+                Err(e)
+            }
+            // The stored parts are kept for the next run to continue from.
+            (Err(e), Some(_)) => {
+                self.reporter().warn(messages::upload_interrupted(object_key));
+                Err(e)
+            }
+            (Err(e), None) => {
+                self.abort_upload(object_key, &state.upload_id).await;
+                Err(e)
+            }
+        }
     }
-}
-
 
-impl AliyunOssCommandExecutor {
-    pub async fn new() -> Option<Self> {
-        let client = AliyunClient::load_from_env().await;
+    /// Streams `reader` into a multipart upload of `object_key`. `len`, when
+    /// known, lets the part size grow for very large sources; without it the
+    /// configured part size is used as is.
+    pub(crate) async fn upload_reader(&self,
+                                      object_key: &str,
+                                      reader: impl AsyncRead + Unpin + Send + 'static,
+                                      len: Option<u64>,
+                                      cipher: Option<Arc<Cipher>>,
+                                      expiry_seconds: Option<i64>,
+                                      checks: UploadChecks) -> Result<CompleteMultipartUploadOutput, String> {
+        self.ensure_writable(&messages::action_upload())?;
+        self.ensure_overwritable(object_key).await?;
+        let sealer = cipher.as_deref().map(|cipher| self.new_sealer(cipher)).transpose()?;
+        self.upload_sealed(object_key, reader, len, sealer, expiry_seconds, checks).await
+    }
 
-        if client.is_none() {
-            return None;
+    /// Re-encrypts `key` for `new`: streams the object down, opens it with
+    /// `old` and uploads the plaintext sealed again, keeping its tags,
+    /// checksum, compression and hidden file name. The old secret is checked
+    /// against the header before anything is uploaded, and the object is
+    /// only replaced once every chunk has opened, so a failure leaves it as
+    /// it was. A hidden name depends on the secret, so such an object moves
+    /// to its new name. Returns the key it ends up under.
+    pub(crate) async fn rekey(&self, key: &str, old: Arc<Cipher>, new: &Cipher) -> Result<String, String> {
+        self.ensure_writable(&messages::action_reencrypt())?;
+        let head = self.head_obj(key).await.map_err(|e| e.to_string())?;
+        let metadata = head.metadata();
+        if metadata.and_then(|metadata| metadata.get(SIDECAR_METADATA)).is_some_and(|value| value == "true") {
+            return Err(messages::not_encrypted(key));
         }
+        let checks = UploadChecks {
+            checksum: metadata.and_then(|metadata| metadata.get(CHECKSUM_METADATA)).cloned(),
+            expected_sha256: None,
+        };
+        let header = self.get_header(key).await?;
+        let name = old.file_name(&header)?;
 
-        let mut executor = Self {
-            client: Arc::new(Mutex::new(client.unwrap())),
-            registry: CommandRegistry::new(),
+        let mut sealer = self.new_sealer(new)?;
+        if self.compression.is_none() && is_compressed(&header) {
+            sealer = sealer.with_compression(DEFAULT_COMPRESSION_LEVEL)?;
+        }
+        let (dir, file_name) = key.rsplit_once('/').map_or(("", key), |(dir, name)| (dir, name));
+        let new_key = match &name {
+            Some(name) if is_hidden_name(file_name) => {
+                let hidden = new.hidden_name(name)?;
+                if dir.is_empty() { hidden } else { format!("{}/{}", dir, hidden) }
+            }
+            _ => key.to_string(),
         };
-        executor.init();
-        Some(executor)
-    }
+        if let Some(name) = &name {
+            sealer = sealer.with_name(name)?;
+        }
+        if new_key != key {
+            self.ensure_overwritable(&new_key).await?;
+        }
 
-    pub async fn execute(&mut self, args: impl IntoIterator<Item=impl Into<String>>) -> Result<(), String> {
+        let resp = match self.client.get_object().bucket(&self.bucket).key(key).send().await {
+            Ok(value) => value,
+            Err(e) => return Err(request_error("GetObject", e)),
+        };
+        // The plaintext is a little shorter than what is stored, which is
+        // close enough for picking the part size.
+        let len = resp.content_length().map(|len| len as u64);
+        let plain = futures_util::stream::try_unfold((resp.body, Some(StreamDecryptor::new(old))), |(mut body, decryptor)| async move {
+            let Some(mut decryptor) = decryptor else { return Ok::<_, std::io::Error>(None) };
+            match body.try_next().await.map_err(std::io::Error::other)? {
+                Some(bytes) => {
+                    let plain = decryptor.update(&bytes).map_err(std::io::Error::other)?;
+                    Ok(Some((Bytes::from(plain), (body, Some(decryptor)))))
+                }
+                None => Ok(Some((Bytes::from(decryptor.finish().map_err(std::io::Error::other)?), (body, None)))),
+            }
+        });
+        let acl = self.get_acl(key).await.map_err(|e| e.to_string())?;
+        let client = self.clone()
+            .with_upload_tags(self.get_tags(key).await.map_err(|e| e.to_string())?)
+            .with_upload_acl(Some(ObjectCannedAcl::from(acl)));
+        client.upload_sealed(&new_key, StreamReader::new(Box::pin(plain)), len, Some(sealer), None, checks).await?;
+        if new_key != key {
+            self.delete_obj(key).await.map_err(|e| e.to_string())?;
+        }
+        Ok(new_key)
+    }
+
+    /// Uploads `reader` as a new object, sealed by `sealer` if given. The
+    /// upload is aborted on failure.
+    async fn upload_sealed(&self,
+                           object_key: &str,
+                           reader: impl AsyncRead + Unpin + Send + 'static,
+                           len: Option<u64>,
+                           sealer: Option<FileCipher>,
+                           expiry_seconds: Option<i64>,
+                           checks: UploadChecks) -> Result<CompleteMultipartUploadOutput, String> {
+        self.emit(TransferEvent::Started { key: object_key.to_string(), total: len }).await;
+        let result = self.upload_stream(object_key, reader, len, sealer, expiry_seconds, checks).await;
+        self.report(object_key, result).await
+    }
+
+    async fn upload_stream(&self,
+                           object_key: &str,
+                           reader: impl AsyncRead + Unpin + Send + 'static,
+                           len: Option<u64>,
+                           sealer: Option<FileCipher>,
+                           expiry_seconds: Option<i64>,
+                           checks: UploadChecks) -> Result<CompleteMultipartUploadOutput, String> {
+        let upload_id = self.create_upload(object_key, checks.checksum, expiry_seconds).await?;
+        let part_size = match len {
+            Some(len) => transfer::part_size_for(len, self.part_size),
+            None => self.part_size,
+        };
+        let upload = OpenUpload { key: object_key, upload_id: &upload_id, part_size, stored_parts: Vec::new() };
+        let result = self.stream_parts(upload, reader, sealer, checks.expected_sha256).await;
+        if result.is_err() {
+            // A stream can't be resumed, so its parts would only linger.
+            self.abort_upload(object_key, &upload_id).await;
+        }
+        result
+    }
+
+    /// Starts a multipart upload carrying the checksum metadata and expiry.
+    async fn create_upload(&self,
+                           object_key: &str,
+                           checksum: Option<String>,
+                           expiry_seconds: Option<i64>) -> Result<String, String> {
+        let mut create = self.client.create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(object_key)
+            .set_metadata(checksum.map(|value| HashMap::from([(CHECKSUM_METADATA.to_string(), value)])));
+
+        let expires_at = expires_at(expiry_seconds);
+        if let Some(expires_at) = expires_at {
+            create = create.expires(DateTime::from_secs(expires_at));
+        }
+        create = create.set_tagging(object_tagging(&self.upload_tags, expires_at)).set_acl(self.upload_acl.clone());
+
+        match create.send().await {
+            Ok(value) => value.upload_id.ok_or_else(|| messages::missing_upload_id("CreateMultipartUpload")),
+            Err(e) => Err(request_error("CreateMultipartUpload", e)),
+        }
+    }
+
+    /// Parts already stored for an unfinished upload, following ListParts
+    /// pagination. Fails if the upload no longer exists.
+    async fn stored_parts(&self, object_key: &str, upload_id: &str) -> Result<Vec<CompletedPart>, String> {
+        let mut parts = Vec::new();
+        let mut marker = None;
+        loop {
+            let resp = self.client.list_parts()
+                .bucket(&self.bucket)
+                .key(object_key)
+                .upload_id(upload_id)
+                .set_part_number_marker(marker)
+                .send()
+                .await
+                .map_err(|e| request_error("ListParts", e))?;
+            parts.extend(resp.parts.unwrap_or_default().into_iter().map(|part| CompletedPart::builder()
+                .set_part_number(part.part_number)
+                .set_e_tag(part.e_tag)
+                .build()));
+            match resp.next_part_number_marker {
+                Some(next) if resp.is_truncated == Some(true) => marker = Some(next),
+                _ => break,
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Reads, optionally encrypts and uploads the rest of `reader` as parts
+    /// following the upload's stored parts, then completes the upload. With
+    /// `expected_sha256` it fails if the data read doesn't match. The upload
+    /// is left open on failure; callers decide whether to abort it.
+    async fn stream_parts(&self,
+                          upload: OpenUpload<'_>,
+                          reader: impl AsyncRead + Unpin + Send + 'static,
+                          sealer: Option<FileCipher>,
+                          expected_sha256: Option<String>) -> Result<CompleteMultipartUploadOutput, String> {
+        let OpenUpload { key: object_key, upload_id, part_size, stored_parts } = upload;
+        let first_part = stored_parts.len() as i32 + 1;
+        let reader = HashingReader::new(reader, expected_sha256.is_some());
+        let digest = reader.digest_handle();
+        let (parts, reader) = transfer::spawn_reader(reader, part_size, first_part, sealer.is_some(), self.memory_budget.clone(), self.rate_limiter.clone());
+        let (parts, encryptor) = match sealer {
+            Some(sealer) => {
+                let (parts, encryptor) = transfer::spawn_encryptor(parts, sealer, part_size);
+                (parts, Some(encryptor))
+            }
+            None => (parts, None),
+        };
+
+        let mut completed_parts = stored_parts;
+        completed_parts.extend(self.upload_parts(object_key, upload_id, parts).await?);
+
+        // The parts ending early only means the input ended if every stage
+        // finished cleanly. The encryptor goes first: when it fails, the
+        // reader merely reports that nobody took its parts.
+        if let Some(encryptor) = encryptor {
+            transfer::join_stage(encryptor).await?;
+        }
+        transfer::join_stage(reader).await?;
+
+        if let (Some(expected), Some(digest)) = (&expected_sha256, digest) {
+            let actual = to_hex(&digest.finish());
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(messages::sha256_mismatch(expected, actual));
+            }
+        }
+
+        let resp = self.client.complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(object_key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder()
+                .set_parts(Some(completed_parts))
+                .build())
+            .send()
+            .await
+            .map_err(|e| request_error("CompleteMultipartUpload", e))?;
+
+        Ok(resp)
+    }
+
+    /// Discards an unfinished multipart upload and its parts, ignoring errors
+    /// since the caller is already reporting a failure.
+    async fn abort_upload(&self, object_key: &str, upload_id: &str) {
+        let _ = self.abort_multipart_upload(object_key, upload_id).await;
+    }
+
+    /// Multipart uploads under `prefix` that were started but neither
+    /// completed nor aborted, following the key and upload id markers.
+    pub async fn list_multipart_uploads(&self, prefix: Option<String>) -> Result<Vec<MultipartUpload>, RotError> {
+        let mut uploads = Vec::new();
+        let mut key_marker = None;
+        let mut upload_id_marker = None;
+        loop {
+            let resp = self.client.list_multipart_uploads()
+                .bucket(&self.bucket)
+                .set_prefix(prefix.clone())
+                .set_key_marker(key_marker)
+                .set_upload_id_marker(upload_id_marker)
+                .send()
+                .await
+                .map_err(|e| RotError::Sdk(request_error("ListMultipartUploads", e)))?;
+            uploads.extend(resp.uploads.unwrap_or_default());
+            match resp.next_key_marker {
+                Some(next) if resp.is_truncated == Some(true) => {
+                    key_marker = Some(next);
+                    upload_id_marker = resp.next_upload_id_marker;
+                }
+                _ => break,
+            }
+        }
+        Ok(uploads)
+    }
+
+    /// Discards an unfinished multipart upload together with its stored parts.
+    pub async fn abort_multipart_upload(&self, object_key: &str, upload_id: &str) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_abort_upload())?;
+        self.client.abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(object_key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("AbortMultipartUpload", e)))?;
+        Ok(())
+    }
+
+    /// Final pipeline stage: uploads parts as they arrive, keeping up to the
+    /// configured number in flight and retrying each failed part a few times.
+    async fn upload_parts(&self,
+                          object_key: &str,
+                          upload_id: &str,
+                          mut parts: Receiver<Part>) -> Result<Vec<CompletedPart>, String> {
+        let mut controller = ConcurrencyController::new(self.concurrency);
+        let mut in_flight: JoinSet<PartResult> = JoinSet::new();
+        let mut attempts: HashMap<i32, u32> = HashMap::new();
+        let mut completed_parts = Vec::new();
+        let mut exhausted = false;
+        let mut transferred = 0;
+
+        loop {
+            while !exhausted && in_flight.len() < controller.limit() {
+                match parts.recv().await {
+                    Some(part) => self.spawn_part_upload(&mut in_flight, object_key, upload_id, PendingPart {
+                        number: part.number,
+                        data: Bytes::from(part.data),
+                        _permit: part.permit,
+                    }),
+                    None => exhausted = true,
+                }
+            }
+
+            // Dropping `in_flight` stops the parts still being sent.
+            let result = tokio::select! {
+                biased;
+                _ = self.cancelled() => return Err(messages::transfer_cancelled()),
+                result = in_flight.join_next() => match result {
+                    Some(result) => result.map_err(|e| e.to_string())?,
+                    None => break,
+                },
+            };
+
+            match result {
+                Ok((number, bytes, e_tag)) => {
+                    controller.record_success(bytes);
+                    transferred += bytes as u64;
+                    self.emit(TransferEvent::PartUploaded { key: object_key.to_string(), number, bytes: bytes as u64 }).await;
+                    self.emit(TransferEvent::BytesTransferred { key: object_key.to_string(), bytes: bytes as u64, transferred }).await;
+                    completed_parts.push(CompletedPart::builder()
+                        .part_number(number)
+                        .set_e_tag(e_tag)
+                        .build());
+                }
+                Err((part, e)) => {
+                    controller.record_failure();
+                    let attempt = attempts.entry(part.number).or_insert(0);
+                    *attempt += 1;
+                    if *attempt >= PART_RETRIES {
+                        return Err(messages::part_failed(part.number, e));
+                    }
+                    self.spawn_part_upload(&mut in_flight, object_key, upload_id, part);
+                }
+            }
+        }
+
+        completed_parts.sort_by_key(|part| part.part_number);
+        Ok(completed_parts)
+    }
+
+    fn spawn_part_upload(&self,
+                         in_flight: &mut JoinSet<PartResult>,
+                         object_key: &str,
+                         upload_id: &str,
+                         part: PendingPart) {
+        let request = self.client.upload_part()
+            .bucket(&self.bucket)
+            .key(object_key)
+            .upload_id(upload_id)
+            .part_number(part.number);
+        let verify = self.verify;
+        in_flight.spawn(async move {
+            let capture = Crc64Capture::default();
+            let sent = request.body(ByteStream::from(part.data.clone()))
+                .customize()
+                .interceptor(capture.clone())
+                .send()
+                .await;
+            let resp = match sent {
+                Ok(resp) => resp,
+                Err(e) => return Err((part, request_error("UploadPart", e))),
+            };
+            // A corrupted part is sent again like a failed one.
+            if verify {
+                if let Err(e) = checksum::compare(&messages::part(part.number), capture.value(), crc64(&part.data)) {
+                    return Err((part, e));
+                }
+            }
+            Ok((part.number, part.data.len(), resp.e_tag))
+        });
+    }
+
+    pub async fn head_obj(&self, key: impl Into<String>) -> Result<HeadObjectOutput, RotError> {
+        let key = key.into();
+        match self.client.head_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .set_version_id(self.version_id.clone())
+            .send()
+            .await {
+            Ok(value) => Ok(value),
+            // HEAD responses carry no error code, so a missing version is
+            // only told apart by its status.
+            Err(e) if e.raw_response().is_some_and(|resp| resp.status().as_u16() == 404) => Err(RotError::Sdk(match &self.version_id {
+                Some(version_id) => messages::version_not_found(key, version_id),
+                None => messages::object_not_found(key),
+            })),
+            Err(e) => Err(RotError::Sdk(request_error("HeadObject", e))),
+        }
+    }
+
+    /// Signs a URL that lets anyone holding it GET or PUT `key` until
+    /// `expires_in` has passed, without access to the credentials. SigV4
+    /// caps the lifetime at seven days. The offline store has no server to
+    /// sign for, so there GET gives the `file://` URL of the stored object,
+    /// which doesn't expire, and PUT is an error.
+    pub async fn presign(&self, key: &str, method: ShareMethod, expires_in: Duration) -> Result<String, RotError> {
+        if let Some(root) = &self.offline_root {
+            return match method {
+                ShareMethod::Get => {
+                    self.head_obj(key).await?;
+                    let path = std::path::absolute(root.join(&self.bucket).join(key))?;
+                    Ok(file_url(&path))
+                }
+                ShareMethod::Put => Err(RotError::Config(messages::offline_upload_link())),
+            };
+        }
+        let config = PresigningConfig::expires_in(expires_in).map_err(|e| RotError::Config(messages::invalid_expiry(e)))?;
+        let request = match method {
+            ShareMethod::Get => self.client.get_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .presigned(config)
+                .await
+                .map_err(|e| e.to_string()),
+            ShareMethod::Put => {
+                self.ensure_writable(&messages::action_presign_upload())?;
+                self.client.put_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .presigned(config)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        };
+        request.map(|request| request.uri().to_string())
+            .map_err(|e| RotError::Sdk(messages::request_failed("Presign", e)))
+    }
+
+    /// A GET URL like `presign`'s that has OSS process `key` on the fly, as
+    /// `process` says, e.g. `image/resize,w_800` or `style/thumb`. The
+    /// `x-oss-process` parameter is signed with the rest of the URL, so
+    /// whoever holds it cannot ask for the original instead.
+    pub async fn presign_processed(&self, key: &str, process: &str, expires_in: Duration) -> Result<String, RotError> {
+        if self.offline_root.is_some() {
+            return Err(RotError::Config(messages::offline_process()));
+        }
+        let config = PresigningConfig::expires_in(expires_in).map_err(|e| RotError::Config(messages::invalid_expiry(e)))?;
+        let parameter = format!("x-oss-process={}", percent_encode_component(process));
+        self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .customize()
+            .mutate_request(move |request| {
+                let uri = request.uri().to_string();
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let _ = request.set_uri(format!("{}{}{}", uri, separator, parameter));
+            })
+            .presigned(config)
+            .await
+            .map(|request| request.uri().to_string())
+            .map_err(|e| RotError::Sdk(messages::request_failed("Presign", e)))
+    }
+
+    /// Reads the expiry timestamp that rot tagged the object with at upload
+    /// time, if any.
+    pub(crate) async fn expiry_of(&self, key: impl Into<String>) -> Result<Option<i64>, String> {
+        match self.client.get_object_tagging()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await {
+            Ok(value) => Ok(find_expiry(value.tag_set())),
+            Err(e) => Err(request_error("GetObjectTagging", e)),
+        }
+    }
+
+    /// All tags of `key`, including the expiry tag rot sets.
+    pub async fn get_tags(&self, key: &str) -> Result<BTreeMap<String, String>, RotError> {
+        let resp = self.client.get_object_tagging()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("GetObjectTagging", e)))?;
+        Ok(resp.tag_set().iter().map(|tag| (tag.key().to_string(), tag.value().to_string())).collect())
+    }
+
+    /// Replaces all tags of `key` with `tags`.
+    pub async fn put_tags(&self, key: &str, tags: &BTreeMap<String, String>) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_set_tags())?;
+        let tag_set = tags.iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build().map_err(|e| RotError::Config(e.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tagging = Tagging::builder().set_tag_set(Some(tag_set)).build().map_err(|e| RotError::Config(e.to_string()))?;
+        self.client.put_object_tagging()
+            .bucket(&self.bucket)
+            .key(key)
+            .tagging(tagging)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("PutObjectTagging", e)))?;
+        Ok(())
+    }
+
+    /// Removes every tag of `key`.
+    pub async fn delete_tags(&self, key: &str) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_delete_tags())?;
+        self.client.delete_object_tagging()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("DeleteObjectTagging", e)))?;
+        Ok(())
+    }
+
+    /// The canned ACL `key` currently has: `private`, `public-read` or
+    /// `public-read-write`.
+    pub async fn get_acl(&self, key: &str) -> Result<&'static str, RotError> {
+        let resp = self.client.get_object_acl()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("GetObjectAcl", e)))?;
+        Ok(canned_acl(resp.grants()))
+    }
+
+    /// Replaces the ACL of `key` with `acl`.
+    pub async fn put_acl(&self, key: &str, acl: ObjectCannedAcl) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_set_acl())?;
+        self.client.put_object_acl()
+            .bucket(&self.bucket)
+            .key(key)
+            .acl(acl)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("PutObjectAcl", e)))?;
+        Ok(())
+    }
+
+    /// Appends `data` to the end of the appendable object `key`, creating it
+    /// if it does not exist, and returns the object's new length. When
+    /// another writer appends first, the append is retried at the new end.
+    pub async fn append_obj(&self, key: &str, data: Bytes) -> Result<u64, RotError> {
+        self.ensure_writable(&messages::action_append())?;
+        let mut attempts = 0;
+        loop {
+            let (position, crc) = self.append_position(key).await?;
+            match self.append_at(key, position, crc, data.clone()).await.map_err(RotError::Transfer)? {
+                Some(length) => return Ok(length),
+                None if attempts < PART_RETRIES => attempts += 1,
+                None => return Err(RotError::Transfer(messages::append_contended(key))),
+            }
+        }
+    }
+
+    /// Makes `link` a symlink to `target`, replacing whatever `link` was.
+    /// Reads of `link` return `target`'s data, so moving a "latest" pointer
+    /// is a single request that copies nothing. `target` need not exist.
+    pub async fn symlink(&self, target: &str, link: &str) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_symlink())?;
+        let header = percent_encode_key(target);
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(link)
+            .customize()
+            .mutate_request(move |request| {
+                let uri = request.uri().to_string();
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let _ = request.set_uri(format!("{}{}symlink", uri, separator));
+                request.headers_mut().insert(SYMLINK_TARGET_HEADER, header.clone());
+            })
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| RotError::Sdk(request_error("PutSymlink", e)))
+    }
+
+    /// The key the symlink `link` points to, read with OSS's GetSymlink.
+    pub async fn read_symlink(&self, link: &str) -> Result<String, RotError> {
+        let capture = SymlinkCapture::default();
+        let result = self.client.get_object()
+            .bucket(&self.bucket)
+            .key(link)
+            .customize()
+            .mutate_request(|request| {
+                let uri = request.uri().to_string();
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let _ = request.set_uri(format!("{}{}symlink", uri, separator));
+            })
+            .interceptor(capture.clone())
+            .send()
+            .await;
+        match result {
+            Ok(_) => capture.target().ok_or_else(|| RotError::Sdk(messages::not_a_symlink(link))),
+            Err(e) => Err(RotError::Sdk(match e.as_service_error().and_then(|e| e.code()) {
+                Some("NoSuchKey") => messages::object_not_found(link),
+                Some("NotSymlink") => messages::not_a_symlink(link),
+                _ => request_error("GetSymlink", &e),
+            })),
+        }
+    }
+
+    /// The length of `key` and its CRC64 if reported, where an append must
+    /// start; `(0, Some(0))` when the object does not exist yet.
+    async fn append_position(&self, key: &str) -> Result<(u64, Option<u64>), RotError> {
+        let capture = Crc64Capture::default();
+        let result = self.client.head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .customize()
+            .interceptor(capture.clone())
+            .send()
+            .await;
+        match result {
+            Ok(head) => Ok((head.content_length.unwrap_or(0).max(0) as u64, capture.value())),
+            Err(e) if e.raw_response().is_some_and(|resp| resp.status().as_u16() == 404) => Ok((0, Some(0))),
+            Err(e) => Err(RotError::Sdk(request_error("HeadObject", e))),
+        }
+    }
+
+    /// OSS's AppendObject, sent as a PutObject that is turned into
+    /// `POST ?append&position=` before signing, since the SDK has no such
+    /// operation. `None` means the object no longer ends at `position`.
+    async fn append_at(&self, key: &str, position: u64, crc: Option<u64>, data: Bytes) -> Result<Option<u64>, String> {
+        let length = position + data.len() as u64;
+        let expected = crc.map(|crc| {
+            let mut expected = Crc64::resume(crc);
+            expected.update(&data);
+            expected.value()
+        });
+        let capture = Crc64Capture::default();
+        let result = self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(data))
+            .customize()
+            .mutate_request(move |request| {
+                let uri = request.uri().to_string();
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let _ = request.set_method("POST");
+                let _ = request.set_uri(format!("{}{}append&position={}", uri, separator, position));
+            })
+            .interceptor(capture.clone())
+            .send()
+            .await;
+        match result {
+            Ok(_) => {
+                if let (true, Some(expected)) = (self.verify, expected) {
+                    checksum::compare(&messages::appended_object(key), capture.value(), expected)?;
+                }
+                Ok(Some(length))
+            }
+            Err(e) => match e.as_service_error().and_then(|e| e.code()) {
+                Some("PositionNotEqualToLength") => Ok(None),
+                Some("ObjectNotAppendable") => Err(messages::not_appendable(key)),
+                _ => Err(request_error("AppendObject", &e)),
+            },
+        }
+    }
+
+    /// Every bucket the credentials can see, in any region.
+    pub async fn list_buckets(&self) -> Result<Vec<Bucket>, RotError> {
+        let resp = self.client.list_buckets()
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("ListBuckets", e)))?;
+        Ok(resp.buckets.unwrap_or_default())
+    }
+
+    /// Creates the bucket `name`, in `region` when given (such as
+    /// `oss-cn-hangzhou`) and otherwise in the endpoint's region.
+    pub async fn create_bucket(&self, name: &str, region: Option<&str>) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_create_bucket())?;
+        let configuration = region.map(|region| CreateBucketConfiguration::builder()
+            .location_constraint(BucketLocationConstraint::from(region))
+            .build());
+        let result = self.client.create_bucket()
+            .bucket(name)
+            .set_create_bucket_configuration(configuration)
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RotError::Sdk(match e.as_service_error().and_then(|e| e.code()) {
+                Some("BucketAlreadyExists") => messages::bucket_name_taken(name),
+                Some("BucketAlreadyOwnedByYou") => messages::bucket_exists(name),
+                _ => request_error("CreateBucket", &e),
+            })),
+        }
+    }
+
+    /// Deletes the bucket `name`, which must be empty.
+    pub async fn delete_bucket(&self, name: &str) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_delete_bucket())?;
+        match self.client.delete_bucket().bucket(name).send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(RotError::Sdk(match e.as_service_error().and_then(|e| e.code()) {
+                Some("BucketNotEmpty") => messages::bucket_not_empty(name),
+                Some("NoSuchBucket") => messages::bucket_not_found(name),
+                _ => request_error("DeleteBucket", &e),
+            })),
+        }
+    }
+
+    /// The lifecycle rules of the bucket, empty when none are set.
+    pub async fn get_lifecycle(&self) -> Result<Vec<LifecycleRule>, RotError> {
+        match self.client.get_bucket_lifecycle_configuration().bucket(&self.bucket).send().await {
+            Ok(resp) => Ok(resp.rules.unwrap_or_default()),
+            Err(e) if e.as_service_error().and_then(|e| e.code()) == Some("NoSuchLifecycleConfiguration") => Ok(Vec::new()),
+            Err(e) => Err(RotError::Sdk(request_error("GetBucketLifecycle", e))),
+        }
+    }
+
+    /// Replaces all lifecycle rules of the bucket with `rules`.
+    pub async fn put_lifecycle(&self, rules: Vec<LifecycleRule>) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_set_lifecycle())?;
+        let configuration = BucketLifecycleConfiguration::builder()
+            .set_rules(Some(rules))
+            .build()
+            .map_err(|e| RotError::Config(e.to_string()))?;
+        self.client.put_bucket_lifecycle_configuration()
+            .bucket(&self.bucket)
+            .lifecycle_configuration(configuration)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("PutBucketLifecycle", e)))?;
+        Ok(())
+    }
+
+    /// Removes every lifecycle rule of the bucket.
+    pub async fn delete_lifecycle(&self) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_delete_lifecycle())?;
+        self.client.delete_bucket_lifecycle()
+            .bucket(&self.bucket)
+            .send()
+            .await
+            .map_err(|e| RotError::Sdk(request_error("DeleteBucketLifecycle", e)))?;
+        Ok(())
+    }
+
+    /// Returns false when the endpoint cannot be reached at all (DNS, connect
+    /// or timeout failures). Errors returned by the service still count as
+    /// reachable, since retrying later would not change them.
+    pub async fn is_reachable(&self) -> bool {
+        match self.client.head_bucket().bucket(&self.bucket).send().await {
+            Ok(_) => true,
+            Err(SdkError::DispatchFailure(_)) | Err(SdkError::TimeoutError(_)) => false,
+            Err(_) => true,
+        }
+    }
+
+    /// Copies an object server-side. Objects above `MULTIPART_COPY_THRESHOLD`
+    /// are copied as parallel UploadPartCopy ranges, since a single CopyObject
+    /// request is size limited. Either way the copy keeps the source's
+    /// metadata, content type and tags.
+    pub async fn copy_obj(&self, src_key: &str, dst_key: &str) -> Result<(), RotError> {
+        self.copy_from(&self.bucket, src_key, dst_key).await.map(|_| ())
+    }
+
+    /// Like `copy_obj`, but reads the source from `src_bucket`, which must be
+    /// reachable with the same credentials. Returns the version id of the new
+    /// object on versioned buckets.
+    pub async fn copy_from(&self, src_bucket: &str, src_key: &str, dst_key: &str) -> Result<Option<String>, RotError> {
+        self.ensure_writable(&messages::action_copy())?;
+        let source = self.clone().with_bucket(src_bucket);
+        let head = source.head_obj(src_key).await
+            .map_err(|_| RotError::Sdk(messages::source_not_found(src_bucket, src_key)))?;
+        let size = head.content_length().unwrap_or(0).max(0) as u64;
+        let copy_source = format!("{}/{}", src_bucket, percent_encode_key(src_key));
+
+        if size <= self.multipart_copy_threshold {
+            return match self.client.copy_object()
+                .bucket(&self.bucket)
+                .key(dst_key)
+                .copy_source(copy_source)
+                .send()
+                .await {
+                Ok(resp) => Ok(resp.version_id),
+                Err(e) => Err(RotError::Sdk(request_error("CopyObject", e))),
+            };
+        }
+
+        // Unlike CopyObject, a multipart upload starts out without any of
+        // the source's metadata or tags.
+        let tags = source.get_tags(src_key).await?;
+        let upload_id = match self.client.create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(dst_key)
+            .set_metadata(head.metadata)
+            .set_content_type(head.content_type)
+            .set_tagging(object_tagging(&tags, None))
+            .send()
+            .await {
+            Ok(value) => value.upload_id.ok_or_else(|| RotError::Sdk(messages::missing_upload_id("CreateMultipartUpload")))?,
+            Err(e) => return Err(RotError::Sdk(request_error("CreateMultipartUpload", e))),
+        };
+
+        match self.copy_parts(&copy_source, dst_key, &upload_id, size).await {
+            Ok(completed_parts) => {
+                self.client.complete_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(dst_key)
+                    .upload_id(&upload_id)
+                    .multipart_upload(CompletedMultipartUpload::builder()
+                        .set_parts(Some(completed_parts))
+                        .build())
+                    .send()
+                    .await
+                    .map(|resp| resp.version_id)
+                    .map_err(|e| RotError::Sdk(request_error("CompleteMultipartUpload", e)))
+            }
+            Err(e) => {
+                self.abort_upload(dst_key, &upload_id).await;
+                Err(RotError::Sdk(e))
+            }
+        }
+    }
+
+    async fn copy_parts(&self,
+                        copy_source: &str,
+                        dst_key: &str,
+                        upload_id: &str,
+                        size: u64) -> Result<Vec<CompletedPart>, String> {
+        let pool_size = self.concurrency.pool_size();
+        let mut ranges = (0..size).step_by(COPY_PART_SIZE as usize)
+            .enumerate()
+            .map(|(index, start)| (index as i32 + 1, start, (start + COPY_PART_SIZE).min(size) - 1));
+        let mut in_flight = JoinSet::new();
+        let mut completed_parts = Vec::new();
+
+        loop {
+            while in_flight.len() < pool_size {
+                let Some((number, start, end)) = ranges.next() else { break };
+                let request = self.client.upload_part_copy()
+                    .bucket(&self.bucket)
+                    .key(dst_key)
+                    .upload_id(upload_id)
+                    .part_number(number)
+                    .copy_source(copy_source)
+                    .copy_source_range(format!("bytes={}-{}", start, end));
+                in_flight.spawn(async move { (number, request.send().await) });
+            }
+
+            let Some(joined) = in_flight.join_next().await else { break };
+            match joined.map_err(|e| e.to_string())? {
+                (number, Ok(resp)) => completed_parts.push(CompletedPart::builder()
+                    .part_number(number)
+                    .set_e_tag(resp.copy_part_result.and_then(|result| result.e_tag))
+                    .build()),
+                (number, Err(e)) => return Err(messages::part_failed(number, request_error("UploadPartCopy", e))),
+            }
+        }
+
+        completed_parts.sort_by_key(|part| part.part_number);
+        Ok(completed_parts)
+    }
+
+    /// Deletes `key`, returning the version id of the delete marker when the
+    /// bucket is versioned.
+    pub async fn delete_obj(&self, key: impl Into<String>) -> Result<Option<String>, RotError> {
+        self.ensure_writable(&messages::action_delete())?;
+        match self.client.delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await {
+            Ok(resp) => Ok(resp.version_id),
+            Err(e) => Err(RotError::Sdk(request_error("DeleteObject", e))),
+        }
+    }
+
+    /// Deletes every object under `prefix`.
+    pub async fn delete_prefix(&self, prefix: impl Into<String>) -> Result<usize, RotError> {
+        let keys = self.list_all(Some(prefix.into())).await?.into_iter().filter_map(|obj| obj.key).collect();
+        let report = self.delete_objects(keys).await.map_err(RotError::Sdk)?;
+        match report.failed.first() {
+            Some((key, reason)) => Err(RotError::Sdk(messages::delete_failed(report.failed.len(), key, reason))),
+            None => Ok(report.deleted.len()),
+        }
+    }
+
+    /// Deletes `keys` with DeleteObjects, `MAX_DELETE_BATCH` keys per request.
+    /// Per-key failures are collected in the report; a failed request marks
+    /// its whole batch as failed.
+    pub(crate) async fn delete_objects(&self, keys: Vec<String>) -> Result<DeleteReport, String> {
+        self.ensure_writable(&messages::action_delete())?;
+        let mut report = DeleteReport::default();
+        for batch in keys.chunks(MAX_DELETE_BATCH) {
+            let objects = batch.iter()
+                .map(|key| ObjectIdentifier::builder().key(key).build().map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let delete = Delete::builder().set_objects(Some(objects)).build().map_err(|e| e.to_string())?;
+            match self.client.delete_objects()
+                .bucket(&self.bucket)
+                .delete(delete)
+                .send()
+                .await {
+                Ok(resp) => {
+                    for deleted in resp.deleted.unwrap_or_default() {
+                        if let Some(key) = deleted.key {
+                            report.deleted.push((key, deleted.delete_marker_version_id));
+                        }
+                    }
+                    for error in resp.errors.unwrap_or_default() {
+                        if let Some(key) = error.key {
+                            report.failed.push((key, error.message.or(error.code).unwrap_or_default()));
+                        }
+                    }
+                }
+                Err(e) => {
+                    let reason = request_error("DeleteObjects", e);
+                    report.failed.extend(batch.iter().map(|key| (key.clone(), reason.clone())));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Asks for a readable copy of the archived object `key` that is kept for
+    /// `days` days. `tier` picks how fast, and how costly, a Cold Archive
+    /// restore is. A restore already under way is not an error.
+    pub async fn restore_obj(&self, key: &str, days: i32, tier: Option<Tier>) -> Result<(), RotError> {
+        self.ensure_writable(&messages::action_restore())?;
+        let mut request = RestoreRequest::builder().days(days);
+        if let Some(tier) = tier {
+            let parameters = GlacierJobParameters::builder().tier(tier).build().map_err(|e| RotError::Config(e.to_string()))?;
+            request = request.glacier_job_parameters(parameters);
+        }
+        let result = self.client.restore_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .restore_request(request.build())
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => match e.as_service_error().and_then(|e| e.code()) {
+                Some("RestoreAlreadyInProgress") => Ok(()),
+                Some("OperationNotSupported") | Some("InvalidObjectState") => Err(RotError::Sdk(messages::not_archived(key))),
+                _ => Err(RotError::Sdk(request_error("RestoreObject", &e))),
+            },
+        }
+    }
+
+    pub async fn restore_status(&self, key: &str) -> Result<RestoreStatus, RotError> {
+        Ok(RestoreStatus::parse(self.head_obj(key).await?.restore()))
+    }
+
+    /// Permanently deletes one version of `key`. Deleting a delete marker
+    /// makes the version below it current again.
+    pub(crate) async fn delete_version(&self, key: &str, version_id: &str) -> Result<(), String> {
+        self.ensure_writable(&messages::action_delete())?;
+        match self.client.delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .version_id(version_id)
+            .send()
+            .await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(request_error("DeleteObject", e)),
+        }
+    }
+
+    /// The version of `key` written just before `version_id`, if any.
+    pub(crate) async fn previous_version(&self, key: &str, version_id: &str) -> Result<Option<String>, String> {
+        let resp = match self.client.list_object_versions()
+            .bucket(&self.bucket)
+            .prefix(key)
+            .send()
+            .await {
+            Ok(value) => value,
+            Err(e) => return Err(request_error("ListObjectVersions", e)),
+        };
+        // Versions of a key are listed newest first.
+        let versions: Vec<String> = resp.versions.unwrap_or_default().into_iter()
+            .filter(|version| version.key() == Some(key))
+            .filter_map(|version| version.version_id)
+            .collect();
+        Ok(versions.iter()
+            .position(|id| id == version_id)
+            .and_then(|index| versions.get(index + 1).cloned()))
+    }
+
+    /// Makes an older version of `key` current again by copying it over the
+    /// latest one.
+    pub(crate) async fn restore_version(&self, key: &str, version_id: &str) -> Result<Option<String>, String> {
+        self.ensure_writable(&messages::action_restore_version())?;
+        let copy_source = format!("{}/{}?versionId={}", self.bucket, percent_encode_key(key), version_id);
+        match self.client.copy_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .copy_source(copy_source)
+            .send()
+            .await {
+            Ok(resp) => Ok(resp.version_id),
+            Err(e) => Err(request_error("CopyObject", e)),
+        }
+    }
+
+    /// Stores a small unencrypted companion object such as a checksum manifest
+    /// or signature. It is tagged so that downloads never try to decrypt it.
+    pub(crate) async fn put_sidecar(&self,
+                                    key: impl Into<String>,
+                                    body: Vec<u8>,
+                                    expiry_seconds: Option<i64>) -> Result<(), String> {
+        self.ensure_writable(&messages::action_upload())?;
+        let mut put = self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .metadata(SIDECAR_METADATA, "true")
+            .body(ByteStream::from(body));
+        if let Some(expires_at) = expires_at(expiry_seconds) {
+            put = put.expires(DateTime::from_secs(expires_at))
+                .tagging(expiry_tagging(expires_at));
+        }
+        match put.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(request_error("PutObject", e)),
+        }
+    }
+
+    /// Reads a small object fully into memory.
+    pub(crate) async fn get_bytes(&self, key: impl Into<String>) -> Result<Vec<u8>, String> {
+        let resp = match self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await {
+            Ok(value) => value,
+            Err(e) => return Err(request_error("GetObject", e)),
+        };
+        let body = resp.body.collect().await.map_err(|e| e.to_string())?;
+        Ok(body.into_bytes().to_vec())
+    }
+
+    /// Streams the object's plaintext through `on_data` without storing it,
+    /// decrypting on the fly when a key is given. An error from `on_data`
+    /// stops the transfer. Returns the stored size.
+    pub(crate) async fn read_object(&self,
+                                    key: impl Into<String>,
+                                    cipher: Option<Arc<Cipher>>,
+                                    mut on_data: impl FnMut(&[u8]) -> Result<(), String>) -> Result<u64, String> {
+        let resp = match self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await {
+            Ok(value) => value,
+            Err(e) => return Err(request_error("GetObject", e)),
+        };
+
+        let is_sidecar = resp.metadata()
+            .and_then(|metadata| metadata.get(SIDECAR_METADATA))
+            .is_some_and(|value| value == "true");
+        let mut decryptor = cipher.filter(|_| !is_sidecar).map(StreamDecryptor::new);
+        let mut body = resp.body;
+        let mut received = 0u64;
+        while let Some(bytes) = body.try_next().await.map_err(|e| e.to_string())? {
+            self.throttle(bytes.len()).await;
+            received += bytes.len() as u64;
+            match decryptor.as_mut() {
+                Some(decryptor) => on_data(&decryptor.update(&bytes)?)?,
+                None => on_data(&bytes)?,
+            }
+        }
+        if let Some(decryptor) = decryptor {
+            on_data(&decryptor.finish()?)?;
+        }
+        Ok(received)
+    }
+
+    /// Streams the object body to `path` through a `BufWriter`, so that many
+    /// small network chunks become few large writes. With a password the body
+    /// is decrypted on the fly. The body is read until EOF and only then
+    /// checked against `Content-Length`, if the server sent one.
+    /// Returns the number of bytes received.
+    pub async fn download_file(&self,
+                               key: impl Into<String>,
+                               path: &Path,
+                               password: Option<impl Into<String>>) -> Result<u64, RotError> {
+        let cipher = password.map(|pwd| Arc::new(Cipher::new(pwd)));
+        self.download_file_with_key(key, path, cipher).await.map_err(RotError::Transfer)
+    }
+
+    /// Downloads into `<path>.part` and renames it into place once complete.
+    /// An interrupted download keeps its partial file; the next run for the
+    /// same object continues with a `Range` request from where it stopped, as
+    /// long as the object's ETag is unchanged. An encrypted body is continued
+    /// from the last whole chunk, since chunks can only be opened entirely.
+    pub(crate) async fn download_file_with_key(&self,
+                                               key: impl Into<String>,
+                                               path: &Path,
+                                               cipher: Option<Arc<Cipher>>) -> Result<u64, String> {
+        let key = key.into();
+        let result = self.download_to_path(&key, path, cipher).await;
+        self.report(&key, result).await
+    }
+
+    async fn download_to_path(&self, key: &str, path: &Path, cipher: Option<Arc<Cipher>>) -> Result<u64, String> {
+        let key = key.to_string();
+        if !self.overwrite && path.exists() {
+            return Err(messages::local_file_exists(path.display()));
+        }
+        let head = self.head_obj(&key).await?;
+        let total = head.content_length().and_then(|n| u64::try_from(n).ok()).unwrap_or(0);
+        self.emit(TransferEvent::Started { key: key.clone(), total: Some(total) }).await;
+        let is_sidecar = head.metadata()
+            .and_then(|metadata| metadata.get(SIDECAR_METADATA))
+            .is_some_and(|value| value == "true");
+        let cipher = cipher.filter(|_| !is_sidecar);
+        // A wrong password is caught by the key check in the header before
+        // anything is written.
+        let header = match &cipher {
+            Some(cipher) if total > 0 => {
+                let header = self.get_header(&key).await?;
+                cipher.check_key(&header)?;
+                header
+            }
+            _ => Vec::new(),
+        };
+
+        let part_path = part_path(path);
+        let state = head.e_tag()
+            .filter(|_| self.resume_downloads)
+            .map(|etag| DownloadState { bucket: self.bucket.clone(), key: key.clone(), path: path.to_path_buf(), etag: etag.to_string() });
+        let state_path = state.as_ref().and_then(|_| DownloadState::path_for(&self.bucket, &key, path));
+        let mut kept = 0;
+        if let (Some(state), Some(state_path)) = (&state, &state_path) {
+            if DownloadState::load(state_path).await.as_ref() == Some(state) {
+                kept = tokio::fs::metadata(&part_path).await.map(|metadata| metadata.len()).unwrap_or(0);
+            }
+            state.save(state_path).await?;
+        }
+
+        if kept > total {
+            kept = 0;
+        }
+        let (decryptor, start) = match cipher {
+            Some(cipher) if kept > 0 => {
+                let (decryptor, start, plain_len) = StreamDecryptor::resume(Arc::clone(&cipher), &header, kept)?;
+                if start <= total {
+                    kept = plain_len;
+                    (Some(decryptor), start)
+                } else {
+                    kept = 0;
+                    (Some(StreamDecryptor::new(cipher)), 0)
+                }
+            }
+            Some(cipher) => (Some(StreamDecryptor::new(cipher)), 0),
+            None => (None, kept),
+        };
+        if kept > 0 {
+            self.reporter().message(messages::resuming_download(&key, format_size(kept)));
+        }
+
+        let mut request = self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .set_version_id(self.version_id.clone());
+        if start > 0 {
+            request = request.range(format!("bytes={}-", start));
+        }
+        if let Some(state) = &state {
+            request = request.if_match(&state.etag);
+        }
+
+        let _permit = match &self.memory_budget {
+            Some(budget) => Some(budget.reserve(self.buffers.write + 2 * CHUNK_SIZE).await),
+            None => None,
+        };
+        let result = async {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(long_path(&part_path))
+                .await
+                .map_err(|e| messages::cannot_open(part_path.display(), e))?;
+            file.set_len(kept).await.map_err(|e| e.to_string())?;
+            file.seek(SeekFrom::End(0)).await.map_err(|e| e.to_string())?;
+            // The checksum covers the whole stored object. Bytes kept from an
+            // earlier run are re-read unless they were already decrypted, in
+            // which case the authenticated encryption vouches for them.
+            let crc = match (self.verify, start) {
+                (false, _) => None,
+                (true, 0) => Some(Crc64::new()),
+                (true, _) if decryptor.is_none() => Some(checksum::crc64_file_prefix(&long_path(&part_path), start).await?),
+                (true, _) => None,
+            };
+            let received = if start < total {
+                let capture = Crc64Capture::default();
+                let resp = request.customize()
+                    .interceptor(capture.clone())
+                    .send()
+                    .await
+                    .map_err(|e| request_error("GetObject", e))?;
+                let (received, crc) = self.write_body(&key, resp, file, decryptor, crc).await?;
+                if let Some(crc) = crc {
+                    if let Err(e) = checksum::compare(&key, capture.value(), crc.value()) {
+                        // Resuming from corrupted bytes would only repeat the failure.
+                        let _ = tokio::fs::remove_file(long_path(&part_path)).await;
+                        return Err(e);
+                    }
+                }
+                received
+            } else {
+                // Nothing is left to fetch; only an encrypted tail may remain.
+                let mut writer = BufWriter::new(file);
+                if let Some(decryptor) = decryptor {
+                    writer.write_all(&decryptor.finish()?).await.map_err(|e| e.to_string())?;
+                }
+                writer.flush().await.map_err(|e| e.to_string())?;
+                0
+            };
+            tokio::fs::rename(long_path(&part_path), long_path(path)).await
+                .map_err(|e| messages::cannot_rename(part_path.display(), path.display(), e))?;
+            Ok(received)
+        }.await;
+
+        match (&result, &state_path) {
+            (Ok(_), Some(state_path)) => DownloadState::remove(state_path).await,
+            (Err(_), _) if self.is_cancelled() => {
+                let _ = tokio::fs::remove_file(&part_path).await;
+                if let Some(state_path) = &state_path {
+                    DownloadState::remove(state_path).await;
+                }
+            }
+            (Err(_), Some(_)) if part_path.exists() => self.reporter().warn(messages::download_interrupted(key, part_path.display())),
+            // Never leave a truncated or partly decrypted file behind.
+            (Err(_), None) => { let _ = tokio::fs::remove_file(&part_path).await; }
+            _ => {}
+        }
+        result
+    }
+
+    /// The stored bytes `start..start + len` of `key`, fewer if the object is
+    /// shorter.
+    pub(crate) async fn get_range(&self, key: &str, start: u64, len: u64) -> Result<Vec<u8>, String> {
+        let resp = self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_version_id(self.version_id.clone())
+            .range(format!("bytes={}-{}", start, start + len - 1))
+            .send()
+            .await
+            .map_err(|e| request_error("GetObject", e))?;
+        let body = resp.body.collect().await.map_err(|e| e.to_string())?.into_bytes();
+        self.throttle(body.len()).await;
+        Ok(body.to_vec())
+    }
+
+    /// Waits until `bytes` more fit under the bandwidth limit, if any.
+    async fn throttle(&self, bytes: usize) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(bytes).await;
+        }
+    }
+
+    /// Writes the response body to `file`, decrypting it if needed, and adds
+    /// the bytes received to `crc`.
+    async fn write_body(&self,
+                        key: &str,
+                        resp: GetObjectOutput,
+                        file: File,
+                        mut decryptor: Option<StreamDecryptor>,
+                        mut crc: Option<Crc64>) -> Result<(u64, Option<Crc64>), String> {
+        let expected_length = resp.content_length().and_then(|n| u64::try_from(n).ok());
+        let mut writer = BufWriter::with_capacity(self.buffers.write, file);
+        let mut body = resp.body;
+        let mut received = 0u64;
+
+        loop {
+            let bytes = tokio::select! {
+                biased;
+                _ = self.cancelled() => return Err(messages::transfer_cancelled()),
+                bytes = body.try_next() => bytes.map_err(|e| e.to_string())?,
+            };
+            let Some(bytes) = bytes else { break };
+            self.throttle(bytes.len()).await;
+            received += bytes.len() as u64;
+            self.emit(TransferEvent::BytesTransferred { key: key.to_string(), bytes: bytes.len() as u64, transferred: received }).await;
+            if let Some(crc) = crc.as_mut() {
+                crc.update(&bytes);
+            }
+            match decryptor.as_mut() {
+                Some(decryptor) => writer.write_all(&decryptor.update(&bytes)?).await,
+                None => writer.write_all(&bytes).await,
+            }.map_err(|e| e.to_string())?;
+        }
+
+        if let Some(expected) = expected_length {
+            if expected != received {
+                return Err(messages::download_incomplete(expected, received));
+            }
+        }
+
+        if let Some(decryptor) = decryptor {
+            writer.write_all(&decryptor.finish()?).await.map_err(|e| e.to_string())?;
+        }
+        writer.flush().await.map_err(|e| e.to_string())?;
+
+        Ok((received, crc))
+    }
+
+    /// An SDK client for `endpoint_url`, or for AWS itself when there is
+    /// none. `path_style` addresses buckets as `endpoint/bucket`, which
+    /// MinIO and most other self-hosted services need. Without `checksums`,
+    /// request checksums are only computed when an operation demands one.
+    fn build_aws_client(credentials: Credentials,
+                        endpoint_url: Option<String>,
+                        region: impl Into<Cow<'static, str>>,
+                        path_style: bool,
+                        checksums: bool,
+                        http_client: Option<SharedHttpClient>) -> Client {
+        let mut builder = SdkConfig::builder().credentials_provider(SharedCredentialsProvider::new(credentials))
+            .region(Region::new(region))
+            .behavior_version(BehaviorVersion::latest());
+        builder.set_endpoint_url(endpoint_url);
+        builder.set_http_client(http_client);
+        let sdk_config = builder.build();
+
+        let mut s3_config_builder = config::Builder::from(&sdk_config).force_path_style(path_style);
+        if !checksums {
+            s3_config_builder = s3_config_builder
+                .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+                .response_checksum_validation(ResponseChecksumValidation::WhenRequired);
+        }
+        Client::from_conf(s3_config_builder.build())
+    }
+}
+
+
+impl AliyunOssCommandExecutor {
+    /// An executor for the `profile` of `rot.json`, or its default profile.
+    /// `Ok(None)` means the configuration still has to be filled in.
+    pub async fn new(profile: Option<&str>) -> Result<Option<Self>, RotError> {
+        let Some(config) = resolve(profile).await.map_err(RotError::Config)? else {
+            return Ok(None);
+        };
+        let store = store::open(&config)?;
+        let client = store.s3().cloned();
+        let mut store = StoreClient::new(store);
+        store.read_only = config.read_only;
+
+        let mut executor = Self {
+            store: Arc::new(Mutex::new(store)),
+            client: client.map(|client| Arc::new(Mutex::new(client))),
+            registry: CommandRegistry::new(),
+        };
+        executor.init();
+        Ok(Some(executor))
+    }
+
+    /// An executor whose bucket lives under `~/.cache/rot/offline`, used by
+    /// `rot --offline`.
+    pub fn new_offline() -> Option<Self> {
+        let client = AliyunClient::offline(cache_dir()?.join("offline"));
+        let mut executor = Self {
+            store: Arc::new(Mutex::new(StoreClient::from_client(client.clone()))),
+            client: Some(Arc::new(Mutex::new(client))),
+            registry: CommandRegistry::new(),
+        };
+        executor.init();
+        Some(executor)
+    }
+
+    /// Applies `update` to the S3 client, if there is one, and has the store
+    /// work on the updated client.
+    fn update_client(&mut self, update: impl FnOnce(AliyunClient) -> AliyunClient) {
+        if let Some(client) = &self.client {
+            let mut client = client.lock().unwrap();
+            *client = update(client.clone());
+            self.store.lock().unwrap().store = Arc::new(client.clone());
+        }
+    }
+
+    /// Switches the client to read-only mode, as `--read-only` does.
+    pub fn set_read_only(&mut self) {
+        self.store.lock().unwrap().read_only = true;
+        self.update_client(|client| client.with_read_only(true));
+    }
+
+    /// Makes every command only report what it would change, as `--dry-run`
+    /// does.
+    pub fn set_dry_run(&mut self) {
+        self.store.lock().unwrap().dry_run = true;
+        self.update_client(|client| client.with_dry_run(true));
+    }
+
+    /// Applies `--connect-timeout`, `--read-timeout` and `--max-attempts`,
+    /// given as on the command line. Only the S3 API has these settings.
+    pub fn set_network(&mut self, connect_timeout: Option<&str>, read_timeout: Option<&str>, max_attempts: Option<&str>) -> Result<(), RotError> {
+        let connect_timeout = connect_timeout.map(parse_duration).transpose().map_err(RotError::Config)?;
+        let read_timeout = read_timeout.map(parse_duration).transpose().map_err(RotError::Config)?;
+        let max_attempts = max_attempts.map(parse_attempts).transpose().map_err(RotError::Config)?;
+        self.update_client(|client| {
+            let client = client.with_timeouts(connect_timeout, read_timeout);
+            match max_attempts {
+                Some(attempts) => client.with_max_attempts(attempts),
+                None => client,
+            }
+        });
+        Ok(())
+    }
+
+    /// Caps transfer bandwidth, as `--bwlimit 5MB/s` does. Only transfers
+    /// through the S3 API are throttled.
+    pub fn set_bwlimit(&mut self, rate: &str) -> Result<(), RotError> {
+        let rate = parse_rate(rate).map_err(RotError::Config)?;
+        self.update_client(|client| client.with_bwlimit(rate));
+        Ok(())
+    }
+
+    /// Stops running uploads and downloads once `token` is cancelled, as
+    /// Ctrl+C does.
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.update_client(|client| client.with_cancellation(token));
+    }
+
+    /// Selects how results are printed, as `--output` does.
+    pub fn set_output(&mut self, output: OutputFormat) {
+        self.store.lock().unwrap().output = output;
+        self.update_client(|client| client.with_output(output));
+    }
+
+    pub async fn execute(&mut self, args: impl IntoIterator<Item=impl Into<String>>) -> Result<(), RotError> {
         let args = CommandParser::from_strings(args);
-        self.registry.execute(args).await
+        self.registry.execute(args).await.map_err(RotError::Command)
     }
 
     pub fn init(&mut self) {
-        self.registry.register("list", handler::get_obj_names(Arc::clone(&self.client)));
-        self.registry.register("upload", handler::upload_file(Arc::clone(&self.client)));
-        self.registry.register("download", handler::download_file(Arc::clone(&self.client)));
+        let store = Arc::clone(&self.store);
+        self.registry.register("list", handler::get_obj_names(Arc::clone(&store)));
+        self.registry.register("ls", handler::get_obj_names(Arc::clone(&store)));
+        self.registry.register("upload", handler::upload_file(Arc::clone(&store)));
+        self.registry.register("download", handler::download_file(Arc::clone(&store)));
+        self.registry.register("rm", handler::remove(Arc::clone(&store)));
+        self.registry.register("cp", handler::copy(Arc::clone(&store)));
+        self.registry.register("sync", handler::sync(Arc::clone(&store)));
+        self.registry.register("browse", handler::browse(store));
+
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        self.registry.register("index", handler::index(Arc::clone(&client)));
+        self.registry.register("bench", handler::bench(Arc::clone(&client)));
+        self.registry.register("queue", handler::queue(Arc::clone(&client)));
+        self.registry.register("upload-url", handler::upload_url(Arc::clone(&client)));
+        self.registry.register("diff", handler::diff(Arc::clone(&client)));
+        self.registry.register("expire", handler::expire(Arc::clone(&client)));
+        self.registry.register("expire-sweep", handler::expire(Arc::clone(&client)));
+        self.registry.register("history", handler::history(Arc::clone(&client)));
+        self.registry.register("undo", handler::undo(Arc::clone(&client)));
+        self.registry.register("versions", handler::versions(Arc::clone(&client)));
+        self.registry.register("undelete", handler::undelete(Arc::clone(&client)));
+        self.registry.register("mv", handler::move_obj(Arc::clone(&client)));
+        self.registry.register("ln", handler::link(Arc::clone(&client)));
+        self.registry.register("watch", handler::watch(Arc::clone(&client)));
+        self.registry.register("backup", handler::backup(Arc::clone(&client)));
+        self.registry.register("restore-backup", handler::restore_backup(Arc::clone(&client)));
+        self.registry.register("mount", handler::mount(Arc::clone(&client)));
+        self.registry.register("share", handler::share(Arc::clone(&client)));
+        self.registry.register("tmpshare", handler::tmpshare(Arc::clone(&client)));
+        self.registry.register("encrypt", handler::crypt(Arc::clone(&client)));
+        self.registry.register("decrypt", handler::crypt(Arc::clone(&client)));
+        self.registry.register("mpu", handler::multipart(Arc::clone(&client)));
+        self.registry.register("stat", handler::stat(Arc::clone(&client)));
+        self.registry.register("du", handler::du(Arc::clone(&client)));
+        self.registry.register("find", handler::find(Arc::clone(&client)));
+        self.registry.register("cat", handler::cat(Arc::clone(&client)));
+        self.registry.register("append", handler::append(Arc::clone(&client)));
+        self.registry.register("rekey", handler::rekey(Arc::clone(&client)));
+        self.registry.register("tag", handler::tag(Arc::clone(&client)));
+        self.registry.register("acl", handler::acl(Arc::clone(&client)));
+        self.registry.register("restore", handler::restore(Arc::clone(&client)));
+        self.registry.register("bucket", handler::bucket(Arc::clone(&client)));
+        self.registry.register("lifecycle", handler::lifecycle(Arc::clone(&client)));
     }
 }
 
 #[cfg(test)]
-mod test {
-    use crate::client::{Config};
+pub(crate) mod test {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::channel;
+    use tokio_util::sync::CancellationToken;
+    use aws_sdk_s3::config::Credentials;
+    use aws_sdk_s3::primitives::DateTime;
+    use aws_sdk_s3::types::{ObjectCannedAcl, Tag};
+    use crate::resume::{DownloadState, part_path, UploadState};
+    use std::collections::BTreeMap;
+    use crate::config::Config;
+    use crate::error::RotError;
+    use crate::crypt::Cipher;
+    use crate::event::TransferEvent;
+    use crate::lifecycle::{parse_rules, RuleSpec};
+    use crate::client::{AliyunClient, Concurrency, expiry_tagging, find_expiry, object_tagging, parse_acl, parse_tags, RestoreStatus, UploadChecks};
+
+    /// An offline client for the test `name`, storing its objects in the
+    /// `store` directory of the empty directory it returns.
+    pub(crate) async fn offline_client(name: &str) -> (AliyunClient, PathBuf) {
+        let dir = Path::new("target").join(format!("test_{}", name));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        (AliyunClient::offline(dir.join("store")), dir)
+    }
+
+    #[tokio::test]
+    async fn test_request_error_keeps_cause() {
+        let (client, _) = offline_client("request_error").await;
+        let error = client.get_acl("missing.txt").await.unwrap_err().to_string();
+        assert!(error.contains("GetObjectAcl") && error.contains("NoSuchKey"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let json = "{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"e\",\"read_only\":true}";
+        let config: Config = serde_json::from_str(json).expect("Couldn't deserialize config struct.");
+        assert!(config.read_only);
+
+        let (client, dir) = offline_client("read_only").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+
+        let client = client.with_read_only(true);
+        assert!(client.upload_file("", dir.join("a.txt"), None::<String>, None).await.is_err());
+        assert!(client.copy_obj("a.txt", "b.txt").await.is_err());
+        assert!(client.delete_obj("a.txt").await.is_err());
+        assert_eq!(client.list_all(None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let (client, dir) = offline_client("dry_run").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_if_changed("docs", dir.join("a.txt"), None, None).await.unwrap();
+
+        let client = client.with_dry_run(true);
+        assert!(client.delete_obj("docs/a.txt").await.is_err());
+        let cipher = Cipher::new("secret");
+        assert_eq!(client.preview_upload("docs", &dir.join("a.txt"), None, true).await.unwrap(), ("docs/a.txt".to_string(), false));
+        assert!(client.preview_upload("docs", &dir.join("a.txt"), Some(&cipher), true).await.is_err());
+        let client = client.with_overwrite(true);
+        assert_eq!(client.preview_upload("docs", &dir.join("a.txt"), Some(&cipher), true).await.unwrap(), ("docs/a.txt".to_string(), true));
+        let hidden = cipher.hidden_name("a.txt").unwrap();
+        let client = client.with_hidden_names(true);
+        assert_eq!(client.preview_upload("docs", &dir.join("a.txt"), Some(&cipher), true).await.unwrap(), (format!("docs/{}", hidden), true));
+        assert_eq!(client.list_all(None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_overwrite() {
+        let (client, dir) = offline_client("overwrite").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+
+        tokio::fs::write(dir.join("a.txt"), b"b").await.unwrap();
+        let error = client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap_err();
+        assert!(matches!(&error, RotError::Transfer(message) if message.contains("已存在")));
+        assert_eq!(client.get_bytes("a.txt").await.unwrap(), b"a");
+        client.clone().with_overwrite(true).upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert_eq!(client.get_bytes("a.txt").await.unwrap(), b"b");
+    }
+
+    #[tokio::test]
+    async fn test_resume_upload() {
+        let (client, dir) = offline_client("resume_upload").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 249) as u8 + 1).collect();
+        let input = dir.join("data.bin");
+        tokio::fs::write(&input, &data).await.unwrap();
+        let input = tokio::fs::canonicalize(&input).await.unwrap();
+        let client = client.with_part_size(8192);
+
+        // An earlier run stored part 1 and died. Zeros mark the stored part,
+        // so the result shows whether it was reused.
+        let upload_id = client.create_upload("data.bin", None, None).await.unwrap();
+        client.client.upload_part()
+            .bucket(&client.bucket)
+            .key("data.bin")
+            .upload_id(&upload_id)
+            .part_number(1)
+            .body(vec![0u8; 8192].into())
+            .send()
+            .await
+            .unwrap();
+        let metadata = tokio::fs::metadata(&input).await.unwrap();
+        let state = UploadState {
+            bucket: client.bucket.clone(),
+            key: "data.bin".into(),
+            path: input.clone(),
+            upload_id,
+            part_size: 8192,
+            file_len: metadata.len(),
+            modified: DateTime::from(metadata.modified().unwrap()).secs(),
+            key_check: None,
+            header: None,
+        };
+        let state_path = UploadState::path_for(&client.bucket, "data.bin", &input).unwrap();
+        state.save(&state_path).await.unwrap();
+
+        client.upload_file("", input.clone(), None::<String>, None).await.unwrap();
+        let stored = tokio::fs::read(dir.join("store/offline/data.bin")).await.unwrap();
+        assert_eq!(&stored[..8192], &[0u8; 8192][..]);
+        assert_eq!(&stored[8192..], &data[8192..]);
+        assert!(UploadState::load(&state_path).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let (client, dir) = offline_client("resume_download").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 249) as u8 + 1).collect();
+        tokio::fs::write(dir.join("data.bin"), &data).await.unwrap();
+        client.upload_file("", dir.join("data.bin"), Some("correct horse"), None).await.unwrap();
+        let output = tokio::fs::canonicalize(&dir).await.unwrap().join("out.bin");
+
+        // An earlier run wrote 9000 bytes before dying. Zeros mark what it
+        // wrote, so the result shows what was kept: the two whole chunks.
+        let etag = client.head_obj("data.bin").await.unwrap().e_tag.unwrap();
+        let state = DownloadState { bucket: client.bucket.clone(), key: "data.bin".into(), path: output.clone(), etag };
+        let state_path = DownloadState::path_for(&client.bucket, "data.bin", &output).unwrap();
+        state.save(&state_path).await.unwrap();
+        tokio::fs::write(part_path(&output), vec![0u8; 9000]).await.unwrap();
+
+        client.download_file("data.bin", &output, Some("correct horse")).await.unwrap();
+        let downloaded = tokio::fs::read(&output).await.unwrap();
+        assert_eq!(&downloaded[..8192], &[0u8; 8192][..]);
+        assert_eq!(&downloaded[8192..], &data[8192..]);
+        assert!(DownloadState::load(&state_path).await.is_none());
+        assert!(!part_path(&output).exists());
+
+        // An existing file is only replaced when overwriting is allowed, and
+        // with resuming off the partial file is ignored.
+        assert!(client.download_file("data.bin", &output, Some("correct horse")).await.is_err());
+        tokio::fs::write(part_path(&output), vec![0u8; 9000]).await.unwrap();
+        let client = client.with_download_resume(false).with_overwrite(true);
+        client.download_file("data.bin", &output, Some("correct horse")).await.unwrap();
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_verify_download() {
+        let (client, dir) = offline_client("verify_download").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 249) as u8 + 1).collect();
+        tokio::fs::write(dir.join("data.bin"), &data).await.unwrap();
+        client.upload_file("", dir.join("data.bin"), None::<String>, None).await.unwrap();
+        let output = tokio::fs::canonicalize(&dir).await.unwrap().join("out.bin");
+
+        // The kept bytes of an earlier run don't match the object.
+        let etag = client.head_obj("data.bin").await.unwrap().e_tag.unwrap();
+        let state = DownloadState { bucket: client.bucket.clone(), key: "data.bin".into(), path: output.clone(), etag };
+        let state_path = DownloadState::path_for(&client.bucket, "data.bin", &output).unwrap();
+        state.save(&state_path).await.unwrap();
+        tokio::fs::write(part_path(&output), vec![0u8; 9000]).await.unwrap();
+        let error = client.download_file("data.bin", &output, None::<String>).await.unwrap_err();
+        assert!(error.to_string().contains("CRC64"));
+        assert!(!part_path(&output).exists());
+
+        client.download_file("data.bin", &output, None::<String>).await.unwrap();
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_abort_failed_upload() {
+        let (client, _) = offline_client("abort_failed_upload").await;
+        let checks = UploadChecks { checksum: None, expected_sha256: Some("00".repeat(32)) };
+        let result = client.upload_reader("a.bin", &b"data"[..], Some(4), None, None, checks).await;
+        assert!(result.is_err());
+        assert!(client.list_multipart_uploads(None).await.unwrap().is_empty());
+        assert!(client.list_all(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rekey() {
+        let (client, dir) = offline_client("rekey").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 249) as u8 + 1).collect();
+        tokio::fs::write(dir.join("data.bin"), &data).await.unwrap();
+        let output = tokio::fs::canonicalize(&dir).await.unwrap().join("out.bin");
+        let client = client.with_overwrite(true);
+        client.upload_file("", dir.join("data.bin"), Some("correct horse"), None).await.unwrap();
+        let stored = client.get_bytes("data.bin").await.unwrap();
+
+        let (old, new) = (Arc::new(Cipher::new("correct horse")), Cipher::new("battery staple"));
+        assert!(client.rekey("data.bin", Arc::new(Cipher::new("wrong")), &new).await.is_err());
+        assert_eq!(client.get_bytes("data.bin").await.unwrap(), stored);
+
+        assert_eq!(client.rekey("data.bin", Arc::clone(&old), &new).await.unwrap(), "data.bin");
+        assert!(client.download_file("data.bin", &output, Some("correct horse")).await.is_err());
+        client.download_file("data.bin", &output, Some("battery staple")).await.unwrap();
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), data);
+
+        // A hidden name follows the secret.
+        let hiding = client.clone().with_hidden_names(true);
+        hiding.upload_file("docs", dir.join("data.bin"), Some("correct horse"), None).await.unwrap();
+        let hidden = format!("docs/{}", old.hidden_name("data.bin").unwrap());
+        let moved = client.rekey(&hidden, old, &new).await.unwrap();
+        assert_eq!(moved, format!("docs/{}", new.hidden_name("data.bin").unwrap()));
+        assert!(client.head_obj(&hidden).await.is_err());
+        assert_eq!(client.hidden_file_name(&moved, &new).await.unwrap().as_deref(), Some("data.bin"));
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let (client, dir) = offline_client("events").await;
+        tokio::fs::write(dir.join("data.bin"), vec![7u8; 20_000]).await.unwrap();
+        let output = tokio::fs::canonicalize(&dir).await.unwrap().join("out.bin");
+        let (sender, mut events) = channel(1024);
+        let client = client.with_events(sender);
+
+        client.upload_file("", dir.join("data.bin"), None::<String>, None).await.unwrap();
+        client.download_file("data.bin", &output, None::<String>).await.unwrap();
+        assert!(client.download_file("data.bin", &output, None::<String>).await.is_err());
+        drop(client);
+
+        let mut received = Vec::new();
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+        let key = || "data.bin".to_string();
+        assert_eq!(received[0], TransferEvent::Started { key: key(), total: Some(20_000) });
+        assert_eq!(received[1], TransferEvent::PartUploaded { key: key(), number: 1, bytes: 20_000 });
+        assert_eq!(received[2], TransferEvent::BytesTransferred { key: key(), bytes: 20_000, transferred: 20_000 });
+        assert_eq!(received[3], TransferEvent::Completed { key: key() });
+        assert_eq!(received[4], TransferEvent::Started { key: key(), total: Some(20_000) });
+        let uploaded_and_downloaded: u64 = received.iter().filter_map(|event| match event {
+            TransferEvent::BytesTransferred { bytes, .. } => Some(*bytes),
+            _ => None,
+        }).sum();
+        assert_eq!(uploaded_and_downloaded, 40_000);
+        let ends = &received[received.len() - 2..];
+        assert_eq!(ends[0], TransferEvent::Completed { key: key() });
+        assert!(matches!(&ends[1], TransferEvent::Failed { error, .. } if error.contains("已存在")));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation() {
+        let (client, dir) = offline_client("cancellation").await;
+        tokio::fs::write(dir.join("data.bin"), vec![7u8; 20_000]).await.unwrap();
+        let output = tokio::fs::canonicalize(&dir).await.unwrap().join("out.bin");
+        client.upload_file("", dir.join("data.bin"), None::<String>, None).await.unwrap();
+
+        let token = CancellationToken::new();
+        let cancelled = client.clone().with_overwrite(true).with_cancellation(token.clone());
+        token.cancel();
+        let error = cancelled.upload_file("", dir.join("data.bin"), Some("correct horse"), None).await.unwrap_err();
+        assert!(error.to_string().contains("取消"));
+        assert!(client.list_multipart_uploads(None).await.unwrap().is_empty());
+        assert!(UploadState::load(&UploadState::path_for(&client.bucket, "data.bin", &dir.join("data.bin")).unwrap()).await.is_none());
+
+        assert!(cancelled.download_file("data.bin", &output, None::<String>).await.is_err());
+        assert!(!output.exists());
+        assert!(!part_path(&output).exists());
+        assert!(DownloadState::load(&DownloadState::path_for(&client.bucket, "data.bin", &output).unwrap()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_uploads() {
+        let (client, _) = offline_client("multipart_uploads").await;
+        let first = client.create_upload("logs/a.bin", None, None).await.unwrap();
+        client.create_upload("logs/b.bin", None, None).await.unwrap();
+        client.create_upload("other.bin", None, None).await.unwrap();
+
+        assert_eq!(client.list_multipart_uploads(None).await.unwrap().len(), 3);
+        assert_eq!(client.list_multipart_uploads(Some("logs/".into())).await.unwrap().len(), 2);
+        client.abort_multipart_upload("logs/a.bin", &first).await.unwrap();
+        let remaining = client.list_multipart_uploads(Some("logs/".into())).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].key(), Some("logs/b.bin"));
+        assert!(client.with_read_only(true).abort_multipart_upload("other.bin", "x").await.is_err());
+    }
 
     #[test]
-    fn test_config_serialize() {
-        let config = Config::new_empty();
-        let json = serde_json::to_string(&config).expect("Couldn't serialize config struct.");
-        assert_eq!(json, "{\"access_key_id\":\"\",\"secret_access_key\":\"\",\"region\":\"\",\"endpoint_url\":\"\",\"bucket\":\"\"}")
+    fn test_parse_concurrency() {
+        assert_eq!("auto".parse::<Concurrency>(), Ok(Concurrency::Auto));
+        assert_eq!("8".parse::<Concurrency>(), Ok(Concurrency::Fixed(8)));
+        assert!("0".parse::<Concurrency>().is_err());
+        assert!("many".parse::<Concurrency>().is_err());
     }
-}
 
+    #[test]
+    fn test_find_expiry() {
+        let tags = vec![
+            Tag::builder().key("owner").value("raven").build().unwrap(),
+            Tag::builder().key("rot-expires-at").value("1700000000").build().unwrap(),
+        ];
+        assert_eq!(find_expiry(&tags), Some(1700000000));
+        assert_eq!(find_expiry(&tags[..1]), None);
+        assert_eq!(expiry_tagging(1700000000), "rot-expires-at=1700000000");
+    }
 
+    #[tokio::test]
+    async fn test_tags() {
+        let tags = parse_tags(["team=raven,env=prod", "note=a b"]).unwrap();
+        assert_eq!(object_tagging(&tags, Some(1700000000)).unwrap(), "env=prod&note=a%20b&team=raven&rot-expires-at=1700000000");
+        assert_eq!(object_tagging(&BTreeMap::new(), None), None);
+        assert!(parse_tags(["team"]).is_err());
+        assert!(parse_tags(["=x"]).is_err());
 
+        let (client, dir) = offline_client("tags").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        let client = client.with_upload_tags(tags.clone());
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert_eq!(client.get_tags("a.txt").await.unwrap(), tags);
 
+        let changed = BTreeMap::from([("env".to_string(), "dev".to_string())]);
+        client.put_tags("a.txt", &changed).await.unwrap();
+        assert_eq!(client.get_tags("a.txt").await.unwrap(), changed);
+        client.delete_tags("a.txt").await.unwrap();
+        assert!(client.get_tags("a.txt").await.unwrap().is_empty());
+    }
 
+    #[tokio::test]
+    async fn test_acl() {
+        assert_eq!(parse_acl("Public-Read").unwrap(), ObjectCannedAcl::PublicRead);
+        assert!(parse_acl("public-read-write").is_err());
+
+        let (client, dir) = offline_client("acl").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        tokio::fs::write(dir.join("b.txt"), b"b").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert_eq!(client.get_acl("a.txt").await.unwrap(), "private");
+        client.put_acl("a.txt", ObjectCannedAcl::PublicRead).await.unwrap();
+        assert_eq!(client.get_acl("a.txt").await.unwrap(), "public-read");
+
+        let public = client.clone().with_upload_acl(Some(ObjectCannedAcl::PublicRead));
+        public.upload_file("", dir.join("b.txt"), None::<String>, None).await.unwrap();
+        assert_eq!(client.get_acl("b.txt").await.unwrap(), "public-read");
+    }
+
+    #[tokio::test]
+    async fn test_append() {
+        let (client, dir) = offline_client("append").await;
+        assert_eq!(client.append_obj("logs/app.log", "one\n".into()).await.unwrap(), 4);
+        assert_eq!(client.append_obj("logs/app.log", "two\n".into()).await.unwrap(), 8);
+        assert_eq!(client.get_bytes("logs/app.log").await.unwrap(), b"one\ntwo\n");
+
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert!(matches!(client.append_obj("a.txt", "b".into()).await, Err(RotError::Transfer(message)) if message.contains("不是可追加对象")));
+    }
+
+    #[tokio::test]
+    async fn test_presign_processed() {
+        // The offline store can't sign, so this only builds a client.
+        let credentials = Credentials::new("id", "secret", None, None, "static");
+        let client = AliyunClient::with_client(AliyunClient::build_aws_client(credentials,
+                                                                             Some("https://oss-cn-hangzhou.aliyuncs.com".into()),
+                                                                             "oss-cn-hangzhou",
+                                                                             false,
+                                                                             false,
+                                                                             None),
+                                               "raven");
+        let url = client.presign_processed("photos/a.jpg", "image/resize,w_800", Duration::from_secs(60)).await.unwrap();
+        assert!(url.contains("/photos/a.jpg?"));
+        assert!(url.contains("x-oss-process=image%2Fresize%2Cw_800"));
+        assert!(url.contains("X-Amz-Signature="));
+    }
+
+    #[tokio::test]
+    async fn test_symlink() {
+        let (client, dir) = offline_client("symlink").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        tokio::fs::write(dir.join("b.txt"), b"bb").await.unwrap();
+        client.upload_file("backup/", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        client.upload_file("backup/", dir.join("b.txt"), None::<String>, None).await.unwrap();
+
+        client.symlink("backup/a.txt", "backup/latest").await.unwrap();
+        assert_eq!(client.read_symlink("backup/latest").await.unwrap(), "backup/a.txt");
+        assert_eq!(client.get_bytes("backup/latest").await.unwrap(), b"a");
+        client.symlink("backup/b.txt", "backup/latest").await.unwrap();
+        assert_eq!(client.get_bytes("backup/latest").await.unwrap(), b"bb");
+
+        let (page, links) = client.list_with_links(None, Some("backup/".into()), None, false).await.unwrap();
+        assert_eq!(page.contents().len(), 3);
+        assert_eq!(links.into_iter().collect::<Vec<_>>(), ["backup/latest"]);
+        assert!(matches!(client.read_symlink("backup/a.txt").await, Err(RotError::Sdk(message)) if message.contains("不是符号链接")));
+        assert!(matches!(client.read_symlink("backup/none").await, Err(RotError::Sdk(message)) if message.contains("不存在")));
+    }
+
+    #[tokio::test]
+    async fn test_restore() {
+        assert_eq!(RestoreStatus::parse(None), RestoreStatus::NotRestored);
+        assert_eq!(RestoreStatus::parse(Some("ongoing-request=\"true\"")), RestoreStatus::InProgress);
+        assert_eq!(RestoreStatus::parse(Some("ongoing-request=\"false\", expiry-date=\"Sat, 17 Oct 2026 00:00:00 GMT\"")),
+                   RestoreStatus::Restored(Some("Sat, 17 Oct 2026 00:00:00 GMT".into())));
+
+        let (client, dir) = offline_client("restore").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert_eq!(client.restore_status("a.txt").await.unwrap(), RestoreStatus::NotRestored);
+        client.restore_obj("a.txt", 2, None).await.unwrap();
+        assert!(matches!(client.restore_status("a.txt").await.unwrap(), RestoreStatus::Restored(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_buckets() {
+        let (client, dir) = offline_client("buckets").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.create_bucket("photos", None).await.unwrap();
+        assert!(client.create_bucket("photos", None).await.is_err());
+        let names: Vec<_> = client.list_buckets().await.unwrap().into_iter().filter_map(|bucket| bucket.name).collect();
+        assert_eq!(names, ["photos"]);
+
+        let photos = client.clone().with_bucket("photos");
+        photos.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        assert!(matches!(client.delete_bucket("photos").await, Err(RotError::Sdk(message)) if message.contains("不为空")));
+        photos.delete_obj("a.txt").await.unwrap();
+        client.delete_bucket("photos").await.unwrap();
+        assert!(client.list_buckets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle() {
+        let (client, _) = offline_client("lifecycle").await;
+        assert!(client.get_lifecycle().await.unwrap().is_empty());
+
+        let rules = parse_rules("rules:\n  - id: logs\n    prefix: logs/\n    archive_days: 30\n    expire_days: 365\n").unwrap();
+        client.put_lifecycle(rules.iter().map(RuleSpec::to_rule).collect::<Result<_, _>>().unwrap()).await.unwrap();
+        let stored: Vec<RuleSpec> = client.get_lifecycle().await.unwrap().iter().map(RuleSpec::from_rule).collect();
+        assert_eq!(stored, rules);
+
+        client.delete_lifecycle().await.unwrap();
+        assert!(client.get_lifecycle().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_versions() {
+        let (client, dir) = offline_client("versions").await;
+        tokio::fs::write(dir.join("a.txt"), b"a").await.unwrap();
+        client.upload_file("", dir.join("a.txt"), None::<String>, None).await.unwrap();
+        tokio::fs::write(dir.join("ab.txt"), b"ab").await.unwrap();
+        client.upload_file("", dir.join("ab.txt"), None::<String>, None).await.unwrap();
+
+        let versions = client.list_versions("a.txt").await.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!((versions[0].version_id.as_str(), versions[0].is_latest, versions[0].size), ("null", true, Some(1)));
+
+        let output = dir.join("out.txt");
+        client.clone().with_version_id("null").download_file("a.txt", &output, None::<String>).await.unwrap();
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), b"a");
+        let missing = client.with_version_id("v2").download_file("a.txt", &dir.join("v2.txt"), None::<String>).await;
+        assert!(matches!(missing, Err(RotError::Transfer(message)) if message.contains("版本 v2 不存在")));
+    }
+
+    #[test]
+    fn test_object_key() {
+        let path = Path::new("/data/report.pdf");
+        assert_eq!(AliyunClient::object_key("docs".into(), path).unwrap(), "docs/report.pdf");
+        assert_eq!(AliyunClient::object_key("docs/".into(), path).unwrap(), "docs/report.pdf");
+        assert_eq!(AliyunClient::object_key(String::new(), path).unwrap(), "report.pdf");
+        assert!(AliyunClient::object_key("docs".into(), Path::new("/")).is_err());
+        assert_eq!(AliyunClient::object_key("docs".into(), Path::new("/data/Cafe\u{301} 🦀.txt")).unwrap(), "docs/Caf\u{e9} 🦀.txt");
+    }
+}
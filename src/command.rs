@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use crate::messages;
 use crate::parser::Arguments;
 
 pub type CommandHandler = Box<dyn Fn(Arguments) -> Pin<Box<dyn Future<Output=Result<(), String>>>>>;
@@ -23,14 +24,14 @@ impl CommandRegistry {
     pub async fn execute(&mut self, arguments: Arguments) -> Result<(), String> {
         let main_command = arguments.main_command.clone();
         if main_command.is_none() {
-            println!("缺少主指令！");
+            println!("{}", messages::missing_command());
             return Ok(())
         }
 
         match self.commands.get(&main_command.unwrap()) {
             Some(handler) => handler(arguments).await,
             None => {
-                println!("未找到命令： {:?}", arguments.main_command.unwrap());
+                println!("{}", messages::unknown_command(format!("{:?}", arguments.main_command.unwrap())));
                 Ok(())
             }
         }
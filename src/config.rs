@@ -0,0 +1,748 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::backup::BackupJob;
+use crate::constant::DEFAULT_S3_REGION;
+use crate::credentials::{AccessKey, find_access_key};
+use crate::error::RotError;
+use crate::keyring::{get_secret, KEYRING_SENTINEL, set_secret};
+use crate::locale::Locale;
+use crate::messages;
+use crate::parser::{Arguments, CommandParser};
+use crate::proxy;
+use crate::utils::{config_dir, parse_duration, parse_rate, parse_size};
+
+/// Settings `rot config set` accepts for a profile.
+const FIELDS: [&str; 22] = [
+    "provider", "access_key_id", "secret_access_key", "security_token", "region", "endpoint_url", "bucket", "path_style",
+    "root", "host", "user", "key_path", "password",
+    "proxy", "connect_timeout", "read_timeout", "max_attempts",
+    "read_buffer_size", "write_buffer_size", "max_memory", "bwlimit", "read_only",
+];
+
+/// Settings whose values `rot config set` and `rot config show --redact`
+/// never print.
+const SECRET_FIELDS: [&str; 3] = ["secret_access_key", "security_token", "password"];
+
+/// The kind of service a profile talks to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    /// Aliyun OSS.
+    #[default]
+    Aliyun,
+    /// Any other S3-compatible service, such as AWS S3, MinIO, Wasabi or
+    /// Cloudflare R2.
+    S3,
+    /// Backblaze B2, through its S3-compatible API.
+    B2,
+    /// A local directory, with each bucket a subdirectory of `root`. Needs
+    /// no credentials.
+    Local,
+    /// A directory on an SSH server, below `root`.
+    Sftp,
+    /// A WebDAV collection at `endpoint_url`, such as Nextcloud or
+    /// Jianguoyun.
+    WebDav,
+}
+
+impl Provider {
+    fn is_default(&self) -> bool {
+        *self == Provider::default()
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "aliyun" | "oss" => Ok(Provider::Aliyun),
+            "s3" => Ok(Provider::S3),
+            "b2" | "backblaze" => Ok(Provider::B2),
+            "local" => Ok(Provider::Local),
+            "sftp" => Ok(Provider::Sftp),
+            "webdav" | "dav" => Ok(Provider::WebDav),
+            _ => Err(messages::unknown_provider(s)),
+        }
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Provider::Aliyun => "aliyun",
+            Provider::S3 => "s3",
+            Provider::B2 => "b2",
+            Provider::Local => "local",
+            Provider::Sftp => "sftp",
+            Provider::WebDav => "webdav",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Provider::is_default")]
+    pub(crate) provider: Provider,
+    #[serde(default)]
+    pub(crate) access_key_id: String,
+    #[serde(default)]
+    pub(crate) secret_access_key: String,
+    /// Session token of temporary STS credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) security_token: Option<String>,
+    /// May be left empty for S3, which then uses `us-east-1`.
+    #[serde(default)]
+    pub(crate) region: String,
+    /// May be left empty for S3, which then talks to AWS.
+    #[serde(default)]
+    pub(crate) endpoint_url: String,
+    pub(crate) bucket: String,
+    /// Addresses buckets as `endpoint/bucket` rather than
+    /// `bucket.endpoint`. Defaults to on for S3 services other than AWS,
+    /// since MinIO and most self-hosted ones need it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path_style: Option<bool>,
+    /// The directory a `local` profile keeps its buckets in, or an `sftp`
+    /// profile its objects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) root: Option<String>,
+    /// SSH server of an `sftp` profile, optionally with `:port`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) host: Option<String>,
+    /// Login of an `sftp` or `webdav` profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// Private key to log in with, instead of the SSH agent and defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) key_path: Option<String>,
+    /// Password, or app password, of a `webdav` profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) password: Option<String>,
+    /// HTTP(S) proxy for every request, credentials included in the URL.
+    /// `HTTPS_PROXY` and `HTTP_PROXY` are honoured when it is unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy: Option<String>,
+    /// Seconds to wait for a connection to the endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) connect_timeout: Option<u64>,
+    /// Seconds to wait for more data on an open connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) read_timeout: Option<u64>,
+    /// Attempts per request, retried with jittered exponential backoff.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_attempts: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) read_buffer_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) write_buffer_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_memory: Option<u64>,
+    /// Transfer bandwidth cap in bytes per second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) bwlimit: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) read_only: bool,
+}
+
+/// The contents of `rot.json`: a single set of credentials at the top level,
+/// as before profiles existed, and/or named `profiles` chosen with
+/// `rot --profile <name>`.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_profile: Option<String>,
+    /// Language of rot's messages, `zh-CN` or `en-US`; `ROT_LANG` overrides it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Config>,
+    /// Jobs for `rot backup run`, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    backups: BTreeMap<String, BackupJob>,
+    #[serde(flatten)]
+    default: Option<Config>,
+}
+
+impl Config {
+    pub fn new_empty() -> Self {
+        Config {
+            provider: Provider::default(),
+            access_key_id: "".into(),
+            secret_access_key: "".into(),
+            security_token: None,
+            region: "".into(),
+            endpoint_url: "".into(),
+            bucket: "".into(),
+            path_style: None,
+            root: None,
+            host: None,
+            user: None,
+            key_path: None,
+            password: None,
+            proxy: None,
+            connect_timeout: None,
+            read_timeout: None,
+            max_attempts: None,
+            read_buffer_size: None,
+            write_buffer_size: None,
+            max_memory: None,
+            bwlimit: None,
+            read_only: false,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let set = |value: &Option<String>| value.as_ref().is_some_and(|value| !value.is_empty());
+        match self.provider {
+            Provider::Local => return set(&self.root) && !self.bucket.is_empty(),
+            Provider::Sftp => return set(&self.host),
+            Provider::WebDav => return !self.endpoint_url.is_empty(),
+            _ => {}
+        }
+        let needs_endpoint = self.provider == Provider::Aliyun;
+        let needs_region = self.provider == Provider::Aliyun || self.provider == Provider::B2;
+        !(self.access_key_id.is_empty()
+            || self.secret_access_key.is_empty()
+            || (needs_region && self.region.is_empty())
+            || (needs_endpoint && self.endpoint_url.is_empty())
+            || self.bucket.is_empty())
+    }
+
+    /// The endpoint to send requests to, or `None` for the SDK's own (AWS).
+    /// B2 endpoints follow from the region, e.g. `us-west-004`.
+    pub(crate) fn service_endpoint(&self) -> Option<String> {
+        match self.provider {
+            _ if !self.endpoint_url.is_empty() => Some(self.endpoint_url.clone()),
+            Provider::B2 => Some(format!("https://s3.{}.backblazeb2.com", self.region)),
+            _ => None,
+        }
+    }
+
+    /// Whether the service accepts the `x-amz-checksum-*` headers the SDK
+    /// adds to uploads by default. B2 rejects them, so they are only sent
+    /// when an operation requires one.
+    pub(crate) fn supports_checksum_headers(&self) -> bool {
+        self.provider != Provider::B2
+    }
+
+    /// The region to sign requests for.
+    pub(crate) fn signing_region(&self) -> &str {
+        match self.provider {
+            Provider::S3 if self.region.is_empty() => DEFAULT_S3_REGION,
+            _ => &self.region,
+        }
+    }
+
+    /// Whether buckets are addressed by path rather than by host name.
+    pub(crate) fn uses_path_style(&self) -> bool {
+        self.path_style.unwrap_or(match self.provider {
+            Provider::Aliyun | Provider::B2 | Provider::Local | Provider::Sftp | Provider::WebDav => false,
+            Provider::S3 => !(self.endpoint_url.is_empty() || self.endpoint_url.contains(".amazonaws.com")),
+        })
+    }
+
+    /// Replaces fields with the `ROT_*` variables `var` returns, so that CI
+    /// jobs can pass credentials without writing them to disk.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let fields = [
+            ("ROT_ACCESS_KEY_ID", &mut self.access_key_id),
+            ("ROT_SECRET_ACCESS_KEY", &mut self.secret_access_key),
+            ("ROT_ENDPOINT", &mut self.endpoint_url),
+            ("ROT_REGION", &mut self.region),
+            ("ROT_BUCKET", &mut self.bucket),
+        ];
+        for (name, field) in fields {
+            if let Some(value) = var(name).filter(|value| !value.is_empty()) {
+                *field = value;
+            }
+        }
+        if let Some(token) = var("ROT_SECURITY_TOKEN").filter(|token| !token.is_empty()) {
+            self.security_token = Some(token);
+        }
+    }
+
+    /// Fills in the access key, and the region and endpoint when unset, from
+    /// another tool's configuration.
+    fn fill_access_key(&mut self, key: AccessKey) {
+        if self.access_key_id.is_empty() || self.secret_access_key.is_empty() {
+            self.access_key_id = key.access_key_id;
+            self.secret_access_key = key.secret_access_key;
+        }
+        if let Some(region) = key.region.filter(|_| self.region.is_empty()) {
+            if self.endpoint_url.is_empty() && region.starts_with("oss-") {
+                self.endpoint_url = format!("https://{}.aliyuncs.com", region);
+            }
+            self.region = region;
+        }
+    }
+
+    /// The value of `field` as `rot config get` prints it, `None` when unset.
+    fn get(&self, field: &str) -> Result<Option<String>, String> {
+        let text = |value: &String| (!value.is_empty()).then(|| value.clone());
+        Ok(match field {
+            "provider" => Some(self.provider.to_string()),
+            "access_key_id" => text(&self.access_key_id),
+            "secret_access_key" => text(&self.secret_access_key),
+            "security_token" => self.security_token.clone(),
+            "region" => text(&self.region),
+            "endpoint_url" => text(&self.endpoint_url),
+            "bucket" => text(&self.bucket),
+            "path_style" => self.path_style.map(|path_style| path_style.to_string()),
+            "root" => self.root.clone(),
+            "host" => self.host.clone(),
+            "user" => self.user.clone(),
+            "key_path" => self.key_path.clone(),
+            "password" => self.password.clone(),
+            "proxy" => self.proxy.clone(),
+            "connect_timeout" => self.connect_timeout.map(|seconds| format!("{}s", seconds)),
+            "read_timeout" => self.read_timeout.map(|seconds| format!("{}s", seconds)),
+            "max_attempts" => self.max_attempts.map(|attempts| attempts.to_string()),
+            "read_buffer_size" => self.read_buffer_size.map(|size| size.to_string()),
+            "write_buffer_size" => self.write_buffer_size.map(|size| size.to_string()),
+            "max_memory" => self.max_memory.map(|size| size.to_string()),
+            "bwlimit" => self.bwlimit.map(|rate| format!("{}/s", rate)),
+            "read_only" => Some(self.read_only.to_string()),
+            _ => return Err(unknown_field(field)),
+        })
+    }
+
+    /// Sets `field` from its command-line form. An empty value clears it.
+    fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let size = |value: &str| if value.is_empty() { Ok(None) } else { parse_size(value).map(Some) };
+        let seconds = |value: &str| if value.is_empty() { Ok(None) } else { parse_duration(value).map(|duration| Some(duration.as_secs())) };
+        match field {
+            "provider" => self.provider = match value {
+                "" => Provider::default(),
+                _ => value.parse()?,
+            },
+            "access_key_id" => self.access_key_id = value.into(),
+            "secret_access_key" => self.secret_access_key = value.into(),
+            "security_token" => self.security_token = (!value.is_empty()).then(|| value.to_string()),
+            "region" => self.region = value.into(),
+            "endpoint_url" => self.endpoint_url = value.into(),
+            "bucket" => self.bucket = value.into(),
+            "path_style" => self.path_style = match value {
+                "" => None,
+                _ => Some(parse_bool(field, value)?),
+            },
+            "root" => self.root = (!value.is_empty()).then(|| value.to_string()),
+            "host" => self.host = (!value.is_empty()).then(|| value.to_string()),
+            "user" => self.user = (!value.is_empty()).then(|| value.to_string()),
+            "key_path" => self.key_path = (!value.is_empty()).then(|| value.to_string()),
+            "password" => self.password = (!value.is_empty()).then(|| value.to_string()),
+            "proxy" => self.proxy = match value {
+                "" => None,
+                url => {
+                    proxy::validate(url)?;
+                    Some(url.to_string())
+                }
+            },
+            "connect_timeout" => self.connect_timeout = seconds(value)?,
+            "read_timeout" => self.read_timeout = seconds(value)?,
+            "max_attempts" => self.max_attempts = match value {
+                "" => None,
+                _ => Some(parse_attempts(value)?),
+            },
+            "read_buffer_size" => self.read_buffer_size = size(value)?.map(|size| size as usize),
+            "write_buffer_size" => self.write_buffer_size = size(value)?.map(|size| size as usize),
+            "max_memory" => self.max_memory = size(value)?,
+            "bwlimit" => self.bwlimit = match value {
+                "" => None,
+                _ => Some(parse_rate(value)?),
+            },
+            "read_only" => self.read_only = !value.is_empty() && parse_bool(field, value)?,
+            _ => return Err(unknown_field(field)),
+        }
+        Ok(())
+    }
+
+    /// Hides the secrets and proxy password, and all but the start of the
+    /// access key id, for `rot config show --redact`.
+    fn redact(&mut self) {
+        self.proxy = self.proxy.as_deref().map(proxy::redact);
+        for field in SECRET_FIELDS {
+            if let Ok(Some(value)) = self.get(field) {
+                self.set(field, &shown(field, &value)).expect("secret fields take any text");
+            }
+        }
+        if !self.access_key_id.is_empty() {
+            self.access_key_id = format!("{}****", self.access_key_id.chars().take(4).collect::<String>());
+        }
+    }
+}
+
+/// `value` of `field` as rot prints it: secrets masked, unless they only
+/// point at the keyring, and proxies without their password.
+fn shown(field: &str, value: &str) -> String {
+    match field {
+        _ if SECRET_FIELDS.contains(&field) && !value.is_empty() && value != KEYRING_SENTINEL => "********".into(),
+        "proxy" => proxy::redact(value),
+        _ => value.to_string(),
+    }
+}
+
+/// Parses a number of attempts per request, at least one.
+pub(crate) fn parse_attempts(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(attempts) if attempts > 0 => Ok(attempts),
+        _ => Err(messages::invalid_attempts(value)),
+    }
+}
+
+fn parse_bool(field: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(messages::invalid_bool(field, value)),
+    }
+}
+
+fn unknown_field(field: &str) -> String {
+    messages::unknown_config_field(field, FIELDS.join(&messages::name_separator()))
+}
+
+impl ConfigFile {
+    /// The profile named `name`, falling back to `default_profile` and then
+    /// to the top-level credentials.
+    pub fn profile(&self, name: Option<&str>) -> Result<&Config, RotError> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self.profiles.get(name).ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                let names = if names.is_empty() { messages::none() } else { names.join(&messages::name_separator()) };
+                RotError::Config(messages::unknown_profile(name, names))
+            }),
+            None => self.default.as_ref()
+                .ok_or_else(|| RotError::Config(messages::no_default_profile())),
+        }
+    }
+
+    pub(crate) fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub(crate) fn backups(&self) -> &BTreeMap<String, BackupJob> {
+        &self.backups
+    }
+
+    /// Like `profile`, but creates the profile when it doesn't exist yet.
+    pub(crate) fn profile_mut(&mut self, name: Option<&str>) -> &mut Config {
+        match name.map(str::to_string).or(self.default_profile.clone()) {
+            Some(name) => self.profiles.entry(name).or_insert_with(Config::new_empty),
+            None => self.default.get_or_insert_with(Config::new_empty),
+        }
+    }
+
+    /// Reads `rot.json`, or `None` when there is none yet.
+    pub async fn load() -> Result<Option<Self>, RotError> {
+        let path = config_path().map_err(RotError::Config)?;
+        let text = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(RotError::Config(messages::cannot_read(path.display(), e))),
+        };
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| RotError::Config(messages::config_file_invalid(path.display(), e)))
+    }
+
+    pub(crate) async fn save(&self) -> Result<(), String> {
+        let path = config_path()?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(|e| messages::cannot_create_dir(dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(&path, text).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    config_dir().map(|dir| dir.join("rot.json")).ok_or_else(messages::config_dir_missing)
+}
+
+/// Settings for `profile` from `rot.json`, the `ROT_*` environment variables
+/// and, failing those, the AWS or aliyun CLI credentials. `None` means the
+/// credentials are still incomplete.
+pub(crate) async fn resolve(profile: Option<&str>) -> Result<Option<Config>, String> {
+    let file = ConfigFile::load().await?;
+    // The environment may complete or entirely stand in for the chosen
+    // profile, so a missing profile only counts once it is in.
+    let (mut value, missing) = match file.as_ref().map(|file| file.profile(profile)) {
+        Some(Ok(value)) => (value.clone(), None),
+        Some(Err(e)) => (Config::new_empty(), Some(e)),
+        None => (Config::new_empty(), None),
+    };
+    value.apply_env(|name| std::env::var(name).ok());
+    if value.secret_access_key == KEYRING_SENTINEL {
+        value.secret_access_key = get_secret(&value.access_key_id).await?;
+    }
+    if !value.is_valid() {
+        if let Some(key) = find_access_key(profile).await {
+            value.fill_access_key(key);
+        }
+    }
+    match missing {
+        Some(e) if !value.is_valid() => Err(e.into()),
+        _ => Ok(value.is_valid().then_some(value)),
+    }
+}
+
+/// `rot config set <field> <value>`, `rot config get <field>` and
+/// `rot config show [--redact]`, working on the profile chosen with
+/// `--profile`, else on `default_profile` or the top-level settings.
+/// `rot config set secret_access_key [<value>] --keyring` puts the secret in
+/// the system keychain, asking for it when no value is given, and leaves
+/// only the `keyring` placeholder in `rot.json`.
+pub async fn execute(args: impl IntoIterator<Item=impl Into<String>>, profile: Option<&str>) -> Result<(), RotError> {
+    run(CommandParser::from_strings(args), profile).await.map_err(RotError::Command)
+}
+
+async fn run(args: Arguments, profile: Option<&str>) -> Result<(), String> {
+    let positional: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    match positional.as_slice() {
+        ["set", "secret_access_key", rest @ ..] if args.has_flag(&["keyring"]) && rest.len() <= 1 => {
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            let config = file.profile_mut(profile);
+            if config.access_key_id.is_empty() {
+                return Err(messages::keyring_needs_key_id());
+            }
+            let secret = match rest.first() {
+                Some(secret) => secret.to_string(),
+                None => rpassword::prompt_password(messages::secret_prompt()).map_err(|e| e.to_string())?,
+            };
+            set_secret(&config.access_key_id, &secret).await?;
+            config.secret_access_key = KEYRING_SENTINEL.into();
+            file.save().await?;
+            println!("{}", messages::secret_saved_to_keyring());
+        }
+        ["set", "default_profile", value] => {
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            file.default_profile = (!value.is_empty()).then(|| value.to_string());
+            file.save().await?;
+            println!("{}", messages::config_set("default_profile", value));
+        }
+        ["set", "language", value] => {
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            file.language = match value.is_empty() {
+                true => None,
+                false => Some(value.parse::<Locale>()?.to_string()),
+            };
+            file.save().await?;
+            println!("{}", messages::config_set("language", file.language.as_deref().unwrap_or_default()));
+        }
+        ["set", field, value] => {
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            file.profile_mut(profile).set(field, value)?;
+            file.save().await?;
+            println!("{}", messages::config_set(field, shown(field, value)));
+        }
+        ["get", "default_profile"] => {
+            let file = ConfigFile::load().await?.unwrap_or_default();
+            if let Some(name) = file.default_profile {
+                println!("{}", name);
+            }
+        }
+        ["get", "language"] => {
+            let file = ConfigFile::load().await?.unwrap_or_default();
+            if let Some(language) = file.language {
+                println!("{}", language);
+            }
+        }
+        ["get", field] => {
+            let file = ConfigFile::load().await?.unwrap_or_default();
+            let empty = Config::new_empty();
+            let config = match file.profile(profile) {
+                Ok(config) => config,
+                // Nothing has been set at the top level yet.
+                Err(_) if profile.is_none() && file.default_profile.is_none() => &empty,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(value) = config.get(field)? {
+                println!("{}", value);
+            }
+        }
+        ["show"] => {
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            if args.has_flag(&["redact"]) {
+                file.default.iter_mut().chain(file.profiles.values_mut()).for_each(Config::redact);
+            }
+            let text = match profile {
+                Some(_) => serde_json::to_string_pretty(file.profile(profile)?),
+                None => serde_json::to_string_pretty(&file),
+            };
+            println!("{}", text.map_err(|e| e.to_string())?);
+        }
+        _ => return Err(messages::config_usage()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crate::config::{Config, ConfigFile, Provider, shown};
+    use crate::credentials::AccessKey;
+
+    #[test]
+    fn test_config_serialize() {
+        let config = Config::new_empty();
+        let json = serde_json::to_string(&config).expect("Couldn't serialize config struct.");
+        assert_eq!(json, "{\"access_key_id\":\"\",\"secret_access_key\":\"\",\"region\":\"\",\"endpoint_url\":\"\",\"bucket\":\"\"}")
+    }
+
+    #[test]
+    fn test_config_buffer_sizes() {
+        let json = "{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"e\",\"read_buffer_size\":65536}";
+        let config: Config = serde_json::from_str(json).expect("Couldn't deserialize config struct.");
+        assert_eq!(config.read_buffer_size, Some(65536));
+        assert_eq!(config.write_buffer_size, None);
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let legacy: ConfigFile = serde_json::from_str("{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"e\"}").unwrap();
+        assert_eq!(legacy.profile(None).unwrap().bucket, "e");
+        assert!(legacy.profile(Some("work")).is_err());
+
+        let json = "{\"default_profile\":\"work\",\"profiles\":{\
+            \"work\":{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"work-bucket\"},\
+            \"personal\":{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"photos\",\"read_only\":true}}}";
+        let file: ConfigFile = serde_json::from_str(json).unwrap();
+        assert_eq!(file.profile(None).unwrap().bucket, "work-bucket");
+        assert!(file.profile(Some("personal")).unwrap().read_only);
+        let error = file.profile(Some("home")).unwrap_err().to_string();
+        assert!(error.contains("personal") && error.contains("work"));
+
+        let no_default: ConfigFile = serde_json::from_str("{\"profiles\":{}}").unwrap();
+        assert!(no_default.profile(None).is_err());
+
+        let backups: ConfigFile = serde_json::from_str("{\"bucket\":\"e\",\"backups\":{\"photos\":{\"sources\":[\"/p\"],\"destination\":\"backup/photos\"}}}").unwrap();
+        assert_eq!(backups.profile(None).unwrap().bucket, "e");
+        assert_eq!(backups.backups()["photos"].destination, "backup/photos");
+
+        let language: ConfigFile = serde_json::from_str("{\"language\":\"en-US\",\"bucket\":\"e\"}").unwrap();
+        assert_eq!((language.language(), language.profile(None).unwrap().bucket.as_str()), (Some("en-US"), "e"));
+    }
+
+    #[test]
+    fn test_config_env() {
+        let env: BTreeMap<&str, &str> = [("ROT_ACCESS_KEY_ID", "ci-key"), ("ROT_SECRET_ACCESS_KEY", "ci-secret"),
+            ("ROT_ENDPOINT", "https://oss-cn-hangzhou.aliyuncs.com"), ("ROT_REGION", "oss-cn-hangzhou"), ("ROT_BUCKET", "ci"), ("ROT_SECURITY_TOKEN", "sts-token")].into();
+        let mut config = Config::new_empty();
+        config.apply_env(|name| env.get(name).map(|value| value.to_string()));
+        assert!(config.is_valid());
+        assert_eq!(config.bucket, "ci");
+        assert_eq!(config.security_token.as_deref(), Some("sts-token"));
+
+        let json = "{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"e\",\"read_only\":true}";
+        let mut config: Config = serde_json::from_str(json).unwrap();
+        config.apply_env(|name| (name == "ROT_BUCKET").then(|| "other".to_string()));
+        assert_eq!((config.bucket.as_str(), config.access_key_id.as_str(), config.read_only), ("other", "a", true));
+        config.apply_env(|_| Some(String::new()));
+        assert_eq!(config.bucket, "other");
+
+        let mut config = Config::new_empty();
+        config.bucket = "photos".into();
+        config.fill_access_key(AccessKey { access_key_id: "AK".into(), secret_access_key: "SK".into(), region: Some("oss-cn-shanghai".into()) });
+        assert!(config.is_valid());
+        assert_eq!(config.endpoint_url, "https://oss-cn-shanghai.aliyuncs.com");
+    }
+
+    #[test]
+    fn test_config_set_get() {
+        let mut file = ConfigFile::default();
+        file.profile_mut(None).set("bucket", "photos").unwrap();
+        file.profile_mut(Some("work")).set("max_memory", "512M").unwrap();
+        file.profile_mut(Some("work")).set("read_only", "true").unwrap();
+        file.profile_mut(Some("work")).set("read_timeout", "2m").unwrap();
+        file.profile_mut(Some("work")).set("max_attempts", "5").unwrap();
+        file.profile_mut(Some("work")).set("bwlimit", "5MB/s").unwrap();
+        assert!(file.profile_mut(Some("work")).set("max_attempts", "0").is_err());
+        assert!(file.profile_mut(None).set("colour", "blue").is_err());
+        assert!(file.profile_mut(None).set("read_only", "maybe").is_err());
+
+        assert_eq!(file.profile(None).unwrap().get("bucket").unwrap(), Some("photos".to_string()));
+        let work = file.profile(Some("work")).unwrap();
+        assert_eq!(work.get("max_memory").unwrap(), Some((512u64 << 20).to_string()));
+        assert_eq!(work.get("bucket").unwrap(), None);
+        assert_eq!((work.read_timeout, work.max_attempts), (Some(120), Some(5)));
+        assert_eq!(work.get("bwlimit").unwrap(), Some(format!("{}/s", 5u64 << 20)));
+        assert!(work.read_only);
+
+        file.profile_mut(Some("work")).set("max_memory", "").unwrap();
+        assert_eq!(file.profile(Some("work")).unwrap().max_memory, None);
+
+        let mut config = Config::new_empty();
+        config.set("access_key_id", "LTAI5tExample").unwrap();
+        config.set("secret_access_key", "secret").unwrap();
+        config.redact();
+        assert_eq!((config.access_key_id.as_str(), config.secret_access_key.as_str()), ("LTAI****", "********"));
+        config.set("secret_access_key", "keyring").unwrap();
+        config.set("password", "dav-secret").unwrap();
+        config.redact();
+        assert_eq!(config.secret_access_key, "keyring");
+        assert_eq!(config.password.as_deref(), Some("********"));
+        assert_eq!(shown("password", "dav-secret"), "********");
+        assert_eq!(shown("security_token", "token"), "********");
+        assert_eq!(shown("bucket", "photos"), "photos");
+    }
+    #[test]
+    fn test_config_provider() {
+        let mut config = Config::new_empty();
+        config.set("access_key_id", "minio").unwrap();
+        config.set("secret_access_key", "minio123").unwrap();
+        config.set("bucket", "photos").unwrap();
+        assert!(!config.is_valid());
+        config.set("provider", "s3").unwrap();
+        assert_eq!(config.provider, Provider::S3);
+        assert!(config.is_valid());
+        assert_eq!((config.signing_region(), config.uses_path_style()), ("us-east-1", false));
+
+        config.set("endpoint_url", "http://localhost:9000").unwrap();
+        assert!(config.uses_path_style());
+        config.set("path_style", "false").unwrap();
+        assert!(!config.uses_path_style());
+        config.set("endpoint_url", "https://s3.eu-west-1.amazonaws.com").unwrap();
+        config.set("path_style", "").unwrap();
+        assert!(!config.uses_path_style());
+        assert_eq!(config.get("provider").unwrap(), Some("s3".to_string()));
+
+        config.set("provider", "b2").unwrap();
+        config.set("endpoint_url", "").unwrap();
+        assert!(!config.is_valid());
+        config.set("region", "us-west-004").unwrap();
+        assert!(config.is_valid());
+        assert_eq!(config.service_endpoint(), Some("https://s3.us-west-004.backblazeb2.com".to_string()));
+        assert!(!config.supports_checksum_headers());
+        assert!(config.set("provider", "gcs").is_err());
+
+        let local: Config = serde_json::from_str("{\"provider\":\"local\",\"root\":\"/mnt/backup\",\"bucket\":\"photos\"}").unwrap();
+        assert!(local.is_valid());
+        assert_eq!(local.get("root").unwrap(), Some("/mnt/backup".to_string()));
+
+        let mut sftp = Config::new_empty();
+        sftp.set("provider", "sftp").unwrap();
+        assert!(!sftp.is_valid());
+        sftp.set("host", "backup.example.com").unwrap();
+        sftp.set("key_path", "~/.ssh/backup").unwrap();
+        assert!(sftp.is_valid());
+
+        let mut dav = Config::new_empty();
+        dav.set("provider", "webdav").unwrap();
+        dav.set("endpoint_url", "https://dav.jianguoyun.com/dav/backup").unwrap();
+        dav.set("password", "app-password").unwrap();
+        assert!(dav.is_valid());
+        dav.redact();
+        assert_eq!(dav.password.as_deref(), Some("********"));
+        let aliyun: Config = serde_json::from_str("{\"access_key_id\":\"a\",\"secret_access_key\":\"b\",\"region\":\"c\",\"endpoint_url\":\"d\",\"bucket\":\"e\"}").unwrap();
+        assert_eq!(aliyun.provider, Provider::Aliyun);
+        assert!(!aliyun.uses_path_style());
+    }
+}
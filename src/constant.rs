@@ -1,7 +1,97 @@
+use std::time::Duration;
 use ring::aead::NONCE_LEN;
 
-pub(crate) const NONCE: [u8; 12] = [200u8; NONCE_LEN];
+pub(crate) const LEGACY_NONCE: [u8; 12] = [200u8; NONCE_LEN];
 pub(crate) const AAD: &[u8; 36] = b"cfaf0256-beec-4495-9175-b9800dd2e2d7";
-pub(crate) const SALT: &[u8; 36] = b"5462d05a-cbf4-465a-956f-2b98770beabb";
+pub(crate) const LEGACY_SALT: &[u8; 36] = b"5462d05a-cbf4-465a-956f-2b98770beabb";
 pub(crate) const CHUNK_SIZE: usize = 4096;
-pub(crate) const TEMP_FOLDER: &str = "raven-oss-tmp";
\ No newline at end of file
+pub(crate) const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+pub(crate) const FORMAT_MAGIC: &[u8; 4] = b"RVNC";
+pub(crate) const FORMAT_VERSION: u8 = 7;
+pub(crate) const SALT_LEN: usize = 16;
+pub(crate) const KDF_PARAMS_LEN: usize = 13;
+pub(crate) const V1_HEADER_LEN: usize = 9;
+pub(crate) const V3_HEADER_LEN: usize = V1_HEADER_LEN + 2 * SALT_LEN + NONCE_LEN;
+pub(crate) const V4_HEADER_LEN: usize = V3_HEADER_LEN + KDF_PARAMS_LEN;
+pub(crate) const V5_HEADER_LEN: usize = V4_HEADER_LEN + 2;
+pub(crate) const KEY_CHECK_LEN: usize = 16;
+pub(crate) const V6_HEADER_LEN: usize = V5_HEADER_LEN + KEY_CHECK_LEN;
+pub(crate) const HEADER_LEN: usize = V6_HEADER_LEN + 1;
+/// Header flag of files whose chunks are compressed.
+pub(crate) const COMPRESSED_FLAG: u8 = 1;
+/// Length prefix of each chunk of a compressed file.
+pub(crate) const FRAME_LEN_SIZE: usize = 4;
+pub(crate) const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+pub(crate) const PBKDF2_ITERATIONS: u32 = 100_000;
+pub(crate) const ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+pub(crate) const ARGON2_PASSES: u32 = 3;
+pub(crate) const ARGON2_LANES: u32 = 4;
+pub(crate) const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+pub(crate) const MAX_KDF_PASSES: u32 = 64;
+pub(crate) const KEY_FILE_LEN: usize = 32;
+pub(crate) const KEY_FILE_NAME: &str = "key.bin";
+pub(crate) const IDENTITY_FILE_NAME: &str = "identity.bin";
+/// Directory below the configuration directory holding `rot key add` keys.
+pub(crate) const NAMED_KEYS_DIR: &str = "keys";
+pub(crate) const MAX_KEY_NAME_LEN: usize = 64;
+pub(crate) const MAX_RECIPIENTS: usize = 64;
+/// An ephemeral public key and a wrapped 32-byte key with its tag.
+pub(crate) const STANZA_LEN: usize = 32 + 32 + 16;
+/// Longest file name kept in a header, before its tag.
+pub(crate) const MAX_NAME_LEN: usize = 1024;
+pub(crate) const MAX_HEADER_LEN: usize = HEADER_LEN + MAX_RECIPIENTS * STANZA_LEN + MAX_NAME_LEN + 16;
+pub(crate) const NAME_SALT: &[u8; 36] = b"0d7c3a4e-5b8f-4e21-9a6d-c3f1e2b7a950";
+/// Suffix of the files written by `encrypt_dir`.
+pub(crate) const ENCRYPTED_EXT: &str = ".enc";
+/// Extension of the keys of objects uploaded with `--hide-name`.
+pub(crate) const HIDDEN_NAME_EXT: &str = ".rot";
+pub(crate) const TEMP_FOLDER: &str = "raven-oss-tmp";
+pub(crate) const PART_SIZE: usize = CHUNK_SIZE * 1280;
+pub(crate) const PIPELINE_DEPTH: usize = 2;
+pub(crate) const DEFAULT_CONCURRENCY: usize = 4;
+pub(crate) const AUTO_CONCURRENCY_START: usize = 2;
+pub(crate) const MAX_CONCURRENCY: usize = 16;
+pub(crate) const PART_RETRIES: u32 = 3;
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const READ_TIMEOUT: Duration = Duration::from_secs(60);
+pub(crate) const MAX_ATTEMPTS: u32 = 3;
+pub(crate) const MAX_BACKOFF: Duration = Duration::from_secs(20);
+pub(crate) const DEFAULT_S3_REGION: &str = "us-east-1";
+pub(crate) const BENCH_PREFIX: &str = "rot-bench";
+pub(crate) const READ_BUFFER_SIZE: usize = 1024 * 1024;
+pub(crate) const WRITE_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+pub(crate) const MULTIPART_COPY_THRESHOLD: u64 = 1024 * 1024 * 1024;
+pub(crate) const COPY_PART_SIZE: u64 = 128 * 1024 * 1024;
+pub(crate) const SIDECAR_METADATA: &str = "rot-sidecar";
+pub(crate) const SIGNATURE_SUFFIX: &str = ".sig";
+pub(crate) const CHECKSUM_METADATA: &str = "rot-sha256";
+pub(crate) const EXPIRY_TAG: &str = "rot-expires-at";
+/// Where `rot tmpshare` uploads unless told otherwise.
+pub(crate) const TMPSHARE_PREFIX: &str = "tmpshare/";
+pub(crate) const TMPSHARE_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a file must stay unchanged before `rot watch` uploads it.
+pub(crate) const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+/// How old the index read by `rot ls --cached` may get before rot suggests
+/// refreshing it.
+pub(crate) const INDEX_STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+/// The object in every `rot backup` snapshot that lists its files.
+pub(crate) const BACKUP_MANIFEST: &str = "manifest.json";
+/// The longest lifetime SigV4 allows a presigned URL.
+pub(crate) const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+pub(crate) const MAX_TAGS: usize = 10;
+pub(crate) const MAX_TAG_KEY_LEN: usize = 128;
+pub(crate) const MAX_TAG_VALUE_LEN: usize = 256;
+pub(crate) const ARCHIVE_STORAGE_CLASS: &str = "Archive";
+/// Grantee URI that stands for everyone in an object's ACL.
+pub(crate) const ALL_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+pub(crate) const CRC64_HEADER: &str = "x-oss-hash-crc64ecma";
+pub(crate) const NEXT_APPEND_POSITION_HEADER: &str = "x-oss-next-append-position";
+pub(crate) const SYMLINK_TARGET_HEADER: &str = "x-oss-symlink-target";
+pub(crate) const MAX_PARTS: u64 = 10_000;
+/// OSS rejects smaller parts, except for the last one of an upload.
+pub(crate) const MIN_PART_SIZE: usize = 100 * 1024;
+pub(crate) const MAX_PART_SIZE: usize = 5 * 1024 * 1024 * 1024;
+pub(crate) const MAX_DELETE_BATCH: usize = 1000;
+pub(crate) const OFFLINE_BUCKET: &str = "offline";
+/// Placeholder endpoint for the offline store; requests never leave the process.
+pub(crate) const OFFLINE_ENDPOINT: &str = "http://offline.rot.local";
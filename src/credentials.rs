@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+use serde::Deserialize;
+
+/// An access key found in another tool's configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AccessKey {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// OSS region, such as `oss-cn-hangzhou`, when the file names one.
+    pub region: Option<String>,
+}
+
+/// The profile named `profile` (or `default`) in an INI-style
+/// `~/.aws/credentials` file.
+pub(crate) fn parse_aws_credentials(text: &str, profile: Option<&str>) -> Option<AccessKey> {
+    let wanted = profile.unwrap_or("default");
+    let mut section = None;
+    let (mut access_key_id, mut secret_access_key, mut region) = (None, None, None);
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = Some(name.trim().to_string());
+            continue;
+        }
+        if section.as_deref() != Some(wanted) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = Some(value.trim().to_string());
+        match key.trim() {
+            "aws_access_key_id" => access_key_id = value,
+            "aws_secret_access_key" => secret_access_key = value,
+            "region" => region = value,
+            _ => {}
+        }
+    }
+    Some(AccessKey { access_key_id: access_key_id?, secret_access_key: secret_access_key?, region })
+}
+
+#[derive(Debug, Deserialize)]
+struct AliyunConfig {
+    #[serde(default)]
+    current: String,
+    #[serde(default)]
+    profiles: Vec<AliyunProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AliyunProfile {
+    name: String,
+    #[serde(default)]
+    mode: String,
+    #[serde(default)]
+    access_key_id: String,
+    #[serde(default)]
+    access_key_secret: String,
+    #[serde(default)]
+    region_id: String,
+}
+
+/// The profile named `profile`, or the current one, in the aliyun CLI's
+/// `~/.aliyun/config.json`. Only plain access key (`AK`) profiles are used.
+pub(crate) fn parse_aliyun_config(text: &str, profile: Option<&str>) -> Option<AccessKey> {
+    let config: AliyunConfig = serde_json::from_str(text).ok()?;
+    let wanted = profile.unwrap_or(&config.current);
+    let found = config.profiles.into_iter().find(|found| found.name == wanted)?;
+    if !(found.mode.is_empty() || found.mode == "AK") || found.access_key_id.is_empty() || found.access_key_secret.is_empty() {
+        return None;
+    }
+    Some(AccessKey {
+        access_key_id: found.access_key_id,
+        secret_access_key: found.access_key_secret,
+        region: (!found.region_id.is_empty()).then(|| format!("oss-{}", found.region_id)),
+    })
+}
+
+/// Looks for an access key in `~/.aws/credentials` (or
+/// `AWS_SHARED_CREDENTIALS_FILE`) and then in `~/.aliyun/config.json`, for
+/// users who already configured those tools.
+pub(crate) async fn find_access_key(profile: Option<&str>) -> Option<AccessKey> {
+    let home = home::home_dir()?;
+    let aws_path = std::env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".aws").join("credentials"));
+    let aws_profile = profile.map(str::to_string).or_else(|| std::env::var("AWS_PROFILE").ok());
+    if let Ok(text) = tokio::fs::read_to_string(&aws_path).await {
+        if let Some(key) = parse_aws_credentials(&text, aws_profile.as_deref()) {
+            return Some(key);
+        }
+    }
+    let text = tokio::fs::read_to_string(home.join(".aliyun").join("config.json")).await.ok()?;
+    parse_aliyun_config(&text, profile)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::credentials::{AccessKey, parse_aliyun_config, parse_aws_credentials};
+
+    #[test]
+    fn test_aws_credentials() {
+        let text = "# comment\n[default]\naws_access_key_id = AK1\naws_secret_access_key = SK1\n\n[work]\naws_access_key_id=AK2\naws_secret_access_key=SK2\nregion=oss-cn-beijing\n[partial]\naws_access_key_id=AK3\n";
+        assert_eq!(parse_aws_credentials(text, None),
+                   Some(AccessKey { access_key_id: "AK1".into(), secret_access_key: "SK1".into(), region: None }));
+        assert_eq!(parse_aws_credentials(text, Some("work")).unwrap().region.as_deref(), Some("oss-cn-beijing"));
+        assert_eq!(parse_aws_credentials(text, Some("partial")), None);
+        assert_eq!(parse_aws_credentials(text, Some("missing")), None);
+    }
+
+    #[test]
+    fn test_aliyun_config() {
+        let text = r#"{"current":"work","profiles":[
+            {"name":"default","mode":"AK","access_key_id":"AK1","access_key_secret":"SK1","region_id":"cn-hangzhou"},
+            {"name":"work","mode":"AK","access_key_id":"AK2","access_key_secret":"SK2","region_id":"cn-shanghai"},
+            {"name":"sso","mode":"StsToken","access_key_id":"AK3","access_key_secret":"SK3"}]}"#;
+        let current = parse_aliyun_config(text, None).unwrap();
+        assert_eq!((current.access_key_id.as_str(), current.region.as_deref()), ("AK2", Some("oss-cn-shanghai")));
+        assert_eq!(parse_aliyun_config(text, Some("default")).unwrap().access_key_id, "AK1");
+        assert_eq!(parse_aliyun_config(text, Some("sso")), None);
+        assert_eq!(parse_aliyun_config("not json", None), None);
+    }
+}
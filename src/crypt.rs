@@ -1,15 +1,35 @@
+use std::collections::HashMap;
 use std::num::NonZeroU32;
-use std::path::Path;
-use ring::aead::{Aad, AES_256_GCM, LessSafeKey, Nonce, UnboundKey};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, ready};
+use argon2::{Algorithm, Argon2, Params, Version};
+use ring::aead::{Aad, AES_256_GCM, LessSafeKey, Nonce, NONCE_LEN, UnboundKey};
 use ring::error::Unspecified;
-use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::digest::{digest, SHA256};
+use ring::{hkdf, hmac, pbkdf2};
 use tokio::fs::File;
 use tokio::io;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use crate::constant::{AAD, CHUNK_SIZE, NONCE, SALT};
+use x25519_dalek::{PublicKey, StaticSecret};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, ReadBuf};
+use tokio_util::io::SyncIoBridge;
+use zstd::bulk::Compressor;
+use crate::error::RotError;
+use crate::keyfile::load_key_file;
+use crate::manifest::to_hex;
+use crate::messages;
+use crate::utils::walk_dir;
+use crate::constant::{AAD, ARGON2_LANES, ARGON2_MEMORY_KIB, ARGON2_PASSES, CHUNK_SIZE, COMPRESSED_FLAG, ENCRYPTED_EXT, FORMAT_MAGIC, FORMAT_VERSION, FRAME_LEN_SIZE, HEADER_LEN, HIDDEN_NAME_EXT, KDF_PARAMS_LEN, KEY_CHECK_LEN, KEY_FILE_LEN, LEGACY_NONCE, LEGACY_SALT, MAX_ARGON2_MEMORY_KIB, MAX_CHUNK_SIZE, MAX_KDF_PASSES, MAX_NAME_LEN, MAX_RECIPIENTS, NAME_SALT, PBKDF2_ITERATIONS, READ_BUFFER_SIZE, SALT_LEN, STANZA_LEN, V1_HEADER_LEN, V3_HEADER_LEN, V4_HEADER_LEN, V5_HEADER_LEN, V6_HEADER_LEN, WRITE_BUFFER_SIZE};
 
-pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<[u8; 32], Unspecified> {
-    let iterations = NonZeroU32::new(100_000).unwrap();
+pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<[u8; 32], RotError> {
+    Ok(pbkdf2_key(password, salt, PBKDF2_ITERATIONS))
+}
+
+fn pbkdf2_key(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let iterations = NonZeroU32::new(iterations).unwrap();
     let mut key = [0u8; 32];
 
     pbkdf2::derive(
@@ -20,31 +40,187 @@ pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<[u8; 32], Unspecified>
         &mut key,
     );
 
-    Ok(key)
+    key
 }
 
+/// Cost parameters of Argon2id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Argon2Params {
+    /// Memory in KiB, at least 8 per lane.
+    pub memory_kib: u32,
+    pub passes: u32,
+    pub lanes: u32,
+}
 
-async fn process_file(input_path: impl AsRef<Path>,
-                      output_path: impl AsRef<Path>,
-                      chunk_size: usize,
-                      password: impl Into<String>,
-                      operation: fn(&LessSafeKey, Nonce, &[u8]) -> Vec<u8>) -> io::Result<()> {
-    let mut input_file = File::open(input_path).await?;
-    let mut output_file = File::create(output_path).await?;
-    let less_safe_key = setup_key(password);
+/// How a password is stretched into a key. New files record theirs in the
+/// header; files from before format version 4 always used PBKDF2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Kdf {
+    Pbkdf2 { iterations: u32 },
+    Argon2id(Argon2Params),
+    /// No stretching: the file was encrypted with a key file, whose random
+    /// bytes already are the key.
+    KeyFile,
+    /// A random key wrapped to `count` recipient public keys, which follow
+    /// the header.
+    Recipients { count: u32 },
+}
 
-    while let Some(buffer) = read_chunk(&mut input_file, chunk_size).await? {
-        let nonce = Nonce::try_assume_unique_for_key(&NONCE).unwrap();
-        let processed_data = operation(&less_safe_key, nonce, &buffer);
-        output_file.write_all(&processed_data).await?;
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::Pbkdf2 { iterations: PBKDF2_ITERATIONS }
     }
+}
+
+impl FromStr for Kdf {
+    type Err = String;
 
-    Ok(())
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pbkdf2" => Ok(Kdf::default()),
+            "argon2id" | "argon2" => Ok(Kdf::Argon2id(Argon2Params {
+                memory_kib: ARGON2_MEMORY_KIB,
+                passes: ARGON2_PASSES,
+                lanes: ARGON2_LANES,
+            })),
+            _ => Err(messages::unknown_kdf(s)),
+        }
+    }
 }
 
-async fn read_chunk(file: &mut File, chunk_size: usize) -> io::Result<Option<Vec<u8>>> {
+impl Kdf {
+    fn derive(&self, password: &[u8], salt: &[u8]) -> [u8; 32] {
+        match self {
+            Kdf::Pbkdf2 { iterations } => pbkdf2_key(password, salt, *iterations),
+            Kdf::Argon2id(params) => {
+                // `parse` keeps parameters from headers within what Argon2 takes.
+                let params = Params::new(params.memory_kib, params.passes, params.lanes, Some(32)).expect("valid Argon2 parameters");
+                let mut key = [0u8; 32];
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key)
+                    .expect("salts are long enough for Argon2");
+                key
+            }
+            Kdf::KeyFile | Kdf::Recipients { .. } => unreachable!("only passwords are stretched"),
+        }
+    }
+
+    /// Algorithm id and three parameters, as recorded in the header.
+    fn to_bytes(self) -> [u8; KDF_PARAMS_LEN] {
+        let (id, params) = match self {
+            Kdf::Pbkdf2 { iterations } => (1, [iterations, 0, 0]),
+            Kdf::Argon2id(params) => (2, [params.memory_kib, params.passes, params.lanes]),
+            Kdf::KeyFile => (3, [0, 0, 0]),
+            Kdf::Recipients { count } => (4, [count, 0, 0]),
+        };
+        let mut bytes = [0u8; KDF_PARAMS_LEN];
+        bytes[0] = id;
+        for (i, param) in params.iter().enumerate() {
+            bytes[1 + i * 4..5 + i * 4].copy_from_slice(&param.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Reads `to_bytes`, refusing parameters that would make opening a
+    /// file cost more than any rot would have chosen.
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let param = |i: usize| u32::from_be_bytes(bytes[1 + i * 4..5 + i * 4].try_into().unwrap());
+        let kdf = match bytes[0] {
+            1 => Kdf::Pbkdf2 { iterations: param(0) },
+            2 => Kdf::Argon2id(Argon2Params { memory_kib: param(0), passes: param(1), lanes: param(2) }),
+            3 => Kdf::KeyFile,
+            4 => Kdf::Recipients { count: param(0) },
+            id => return Err(messages::header_unknown_kdf(id)),
+        };
+        let valid = match kdf {
+            Kdf::Pbkdf2 { iterations } => (1..=PBKDF2_ITERATIONS * MAX_KDF_PASSES).contains(&iterations),
+            Kdf::Argon2id(params) => (1..=MAX_KDF_PASSES).contains(&params.passes)
+                && (1..=255).contains(&params.lanes)
+                && (8 * params.lanes..=MAX_ARGON2_MEMORY_KIB).contains(&params.memory_kib),
+            Kdf::KeyFile => true,
+            Kdf::Recipients { count } => (1..=MAX_RECIPIENTS as u32).contains(&count),
+        };
+        match valid {
+            true => Ok(kdf),
+            false => Err(messages::header_kdf_out_of_range(format!("{:?}", kdf))),
+        }
+    }
+}
+
+
+/// Capacities of the buffered reader and writer used for file IO. These are
+/// independent of the encryption chunk size and the multipart part size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub read: usize,
+    pub write: usize,
+}
+
+impl Default for BufferSizes {
+    fn default() -> Self {
+        Self {
+            read: READ_BUFFER_SIZE,
+            write: WRITE_BUFFER_SIZE,
+        }
+    }
+}
+
+async fn encrypt_stream(input_path: impl AsRef<Path>,
+                        output_path: impl AsRef<Path>,
+                        chunk_size: usize,
+                        cipher: &Cipher,
+                        buffers: BufferSizes) -> io::Result<()> {
+    let input_file = BufReader::with_capacity(buffers.read, File::open(input_path).await?);
+    let mut output_file = BufWriter::with_capacity(buffers.write, File::create(output_path).await?);
+    let file = cipher.new_file(chunk_size).map_err(io::Error::other)?;
+    tokio::io::copy(&mut EncryptingReader::with_file(input_file, file), &mut output_file).await?;
+    output_file.flush().await
+}
+
+/// Decrypts into `output_path`, removing it again if decryption fails so
+/// that no partial plaintext is left behind.
+async fn decrypt_stream(input_path: impl AsRef<Path>,
+                        output_path: impl AsRef<Path>,
+                        cipher: Arc<Cipher>,
+                        buffers: BufferSizes) -> Result<(), RotError> {
+    let output_path = output_path.as_ref();
+    let result = decrypt_into(input_path.as_ref(), output_path, cipher, buffers).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(output_path).await;
+    }
+    result
+}
+
+async fn decrypt_into(input_path: &Path,
+                      output_path: &Path,
+                      cipher: Arc<Cipher>,
+                      buffers: BufferSizes) -> Result<(), RotError> {
+    let mut input_file = BufReader::with_capacity(buffers.read, File::open(input_path).await?);
+    let output_file = BufWriter::with_capacity(buffers.write, File::create(output_path).await?);
+    let mut writer = DecryptingWriter::with_cipher(output_file, cipher);
+    let result = match tokio::io::copy_buf(&mut input_file, &mut writer).await {
+        Ok(_) => writer.shutdown().await,
+        Err(e) => Err(e),
+    };
+    // `DecryptingWriter` reports decryption failures as invalid data.
+    result.map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => RotError::Crypto(e.to_string()),
+        _ => RotError::Io(e),
+    })
+}
+
+/// Reads up to `chunk_size` bytes, retrying short reads so that every chunk
+/// except the last one is exactly `chunk_size` long.
+pub(crate) async fn read_chunk(file: &mut (impl AsyncRead + Unpin), chunk_size: usize) -> io::Result<Option<Vec<u8>>> {
     let mut buffer = vec![0; chunk_size];
-    let bytes_read = file.read(&mut buffer).await?;
+    let mut bytes_read = 0;
+    while bytes_read < chunk_size {
+        let n = file.read(&mut buffer[bytes_read..]).await?;
+        if n == 0 {
+            break;
+        }
+        bytes_read += n;
+    }
     if bytes_read == 0 {
         return Ok(None);
     }
@@ -52,70 +228,1617 @@ async fn read_chunk(file: &mut File, chunk_size: usize) -> io::Result<Option<Vec
     Ok(Some(buffer))
 }
 
-fn setup_key(password: impl Into<String>) -> LessSafeKey {
-    let password_str = password.into();
-    let key = derive_key(password_str.as_bytes(), SALT).unwrap();
-    let unbound_key = UnboundKey::new(&AES_256_GCM, &key).expect("AES_256_GCM key setup failed");
-    LessSafeKey::new(unbound_key)
-}
-
 pub async fn encrypt_file(input_path: impl AsRef<Path>,
                           output_path: impl AsRef<Path>,
-                          password: impl Into<String>) -> io::Result<()> {
-    process_file(input_path,
-                 output_path,
-                 CHUNK_SIZE,
-                 password,
-                 |less_safe_key, nonce, buffer: &[u8]| {
-                     let mut in_out = buffer.to_vec();
-                     let aad = Aad::from(AAD);
-                     let _ = &less_safe_key.seal_in_place_append_tag(nonce, aad, &mut in_out).unwrap();
-                     in_out
-                 }).await
+                          password: impl Into<String>) -> Result<(), RotError> {
+    encrypt_file_buffered(input_path, output_path, password, BufferSizes::default()).await
+}
+
+pub async fn encrypt_file_buffered(input_path: impl AsRef<Path>,
+                                   output_path: impl AsRef<Path>,
+                                   password: impl Into<String>,
+                                   buffers: BufferSizes) -> Result<(), RotError> {
+    Ok(encrypt_stream(input_path, output_path, CHUNK_SIZE, &Cipher::new(password), buffers).await?)
+}
+
+/// Encrypts with the 32-byte key in `key_path` instead of a password, as
+/// written by `rot keygen`.
+pub async fn encrypt_file_with_key_file(input_path: impl AsRef<Path>,
+                                        output_path: impl AsRef<Path>,
+                                        key_path: impl AsRef<Path>) -> Result<(), RotError> {
+    let cipher = Cipher::from_key(load_key_file(key_path.as_ref()).await.map_err(RotError::Crypto)?);
+    Ok(encrypt_stream(input_path, output_path, CHUNK_SIZE, &cipher, BufferSizes::default()).await?)
 }
 
+/// Decrypts a file written by `encrypt_file`. Every chunk is sealed with its
+/// position and the last one is marked as such, so a file that was cut
+/// short, even at a chunk boundary, fails to decrypt and leaves no output.
 pub async fn decrypt_file(input_path: impl AsRef<Path>,
                           output_path: impl AsRef<Path>,
-                          password: impl Into<String>) -> io::Result<()> {
-    process_file(input_path,
-                 output_path,
-                 CHUNK_SIZE + AES_256_GCM.tag_len(),
-                 password,
-                 |less_safe_key, nonce, buffer: &[u8]| {
-                     let mut in_out = buffer.to_vec();
-                     let aad = Aad::from(AAD);
-                     let decrypted_data = &less_safe_key.open_in_place(nonce, aad, &mut in_out).unwrap();
-                     decrypted_data.to_vec()
-                 }).await
-}
-
-pub fn _encrypt(secret: &[u8], payload: &[u8]) -> Result<Vec<u8>, Unspecified> {
-    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &secret).unwrap());
-    let nonce = Nonce::try_assume_unique_for_key(&NONCE).unwrap();
+                          password: impl Into<String>) -> Result<(), RotError> {
+    decrypt_file_buffered(input_path, output_path, password, BufferSizes::default()).await
+}
+
+pub async fn decrypt_file_buffered(input_path: impl AsRef<Path>,
+                                   output_path: impl AsRef<Path>,
+                                   password: impl Into<String>,
+                                   buffers: BufferSizes) -> Result<(), RotError> {
+    decrypt_stream(input_path, output_path, Arc::new(Cipher::new(password)), buffers).await
+}
+
+/// Decrypts a file written by `encrypt_file_with_key_file`.
+pub async fn decrypt_file_with_key_file(input_path: impl AsRef<Path>,
+                                        output_path: impl AsRef<Path>,
+                                        key_path: impl AsRef<Path>) -> Result<(), RotError> {
+    let cipher = Cipher::from_key(load_key_file(key_path.as_ref()).await.map_err(RotError::Crypto)?);
+    decrypt_stream(input_path, output_path, Arc::new(cipher), BufferSizes::default()).await
+}
+
+/// Encrypts so that only the holders of the private keys matching
+/// `recipients`, hex public keys as printed by `rot keygen --identity`, can
+/// decrypt.
+pub async fn encrypt_file_for_recipients(input_path: impl AsRef<Path>,
+                                         output_path: impl AsRef<Path>,
+                                         recipients: &[impl AsRef<str>]) -> Result<(), RotError> {
+    let recipients = recipients.iter()
+        .map(|recipient| parse_recipient(recipient.as_ref()))
+        .collect::<Result<_, _>>()
+        .map_err(RotError::Crypto)?;
+    let cipher = Cipher::for_recipients(recipients).map_err(RotError::Crypto)?;
+    Ok(encrypt_stream(input_path, output_path, CHUNK_SIZE, &cipher, BufferSizes::default()).await?)
+}
+
+/// Decrypts a file written by `encrypt_file_for_recipients` with the
+/// identity (private key) file at `identity_path`.
+pub async fn decrypt_file_with_identity(input_path: impl AsRef<Path>,
+                                        output_path: impl AsRef<Path>,
+                                        identity_path: impl AsRef<Path>) -> Result<(), RotError> {
+    let cipher = Cipher::from_identity(load_key_file(identity_path.as_ref()).await.map_err(RotError::Crypto)?);
+    decrypt_stream(input_path, output_path, Arc::new(cipher), BufferSizes::default()).await
+}
+
+/// Encrypts every file below `input_dir` into the same tree below
+/// `output_dir`, each one as `encrypt_file` would, with `.enc` appended to
+/// its name. The password is stretched once for the whole tree. Returns the
+/// number of files encrypted.
+pub async fn encrypt_dir(input_dir: impl AsRef<Path>,
+                         output_dir: impl AsRef<Path>,
+                         password: impl Into<String>) -> Result<usize, RotError> {
+    let (input_dir, output_dir) = (input_dir.as_ref(), output_dir.as_ref());
+    let cipher = Cipher::new(password);
+    let files = walk_dir(input_dir).await?;
+    for file in &files {
+        let mut output_path = output_dir.join(file.strip_prefix(input_dir).unwrap()).into_os_string();
+        output_path.push(ENCRYPTED_EXT);
+        let output_path = PathBuf::from(output_path);
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        encrypt_stream(file, &output_path, CHUNK_SIZE, &cipher, BufferSizes::default()).await?;
+    }
+    Ok(files.len())
+}
+
+/// Reverses `encrypt_dir`: decrypts every `.enc` file below `input_dir`
+/// into the same tree below `output_dir` without the suffix. Other files
+/// are left out. Returns the number of files decrypted.
+pub async fn decrypt_dir(input_dir: impl AsRef<Path>,
+                         output_dir: impl AsRef<Path>,
+                         password: impl Into<String>) -> Result<usize, RotError> {
+    let (input_dir, output_dir) = (input_dir.as_ref(), output_dir.as_ref());
+    let cipher = Arc::new(Cipher::new(password));
+    let mut count = 0;
+    for file in walk_dir(input_dir).await? {
+        let relative = file.strip_prefix(input_dir).unwrap().to_string_lossy().into_owned();
+        let Some(relative) = relative.strip_suffix(ENCRYPTED_EXT).filter(|name| !name.is_empty()) else { continue };
+        let output_path = output_dir.join(relative);
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        decrypt_stream(&file, &output_path, Arc::clone(&cipher), BufferSizes::default()).await
+            .map_err(|e| RotError::Crypto(messages::file_error(file.display(), e)))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Packs every file below `input_dir` into a tar archive and encrypts it
+/// into the single file `output_path`, which also hides how many files there
+/// are and what they are called. The archive is streamed, so no plaintext
+/// copy of it is written. Returns the number of files packed.
+pub async fn encrypt_dir_archive(input_dir: impl AsRef<Path>,
+                                 output_path: impl AsRef<Path>,
+                                 password: impl Into<String>) -> Result<usize, RotError> {
+    let (input_dir, output_path) = (input_dir.as_ref().to_path_buf(), output_path.as_ref());
+    let file = Cipher::new(password).new_file(CHUNK_SIZE).map_err(RotError::Crypto)?;
+    let files = walk_dir(&input_dir).await?;
+    let count = files.len();
+    let (reader, writer) = io::duplex(READ_BUFFER_SIZE);
+    let packer = tokio::task::spawn_blocking(move || -> io::Result<()> {
+        let mut builder = tar::Builder::new(SyncIoBridge::new(writer));
+        for file in &files {
+            builder.append_path_with_name(file, file.strip_prefix(&input_dir).unwrap())?;
+        }
+        // Dropping the writer ends the stream the archive is encrypted from.
+        builder.into_inner().map(drop)
+    });
+
+    let mut output = BufWriter::with_capacity(WRITE_BUFFER_SIZE, File::create(output_path).await?);
+    let sealed = async {
+        io::copy(&mut EncryptingReader::with_file(reader, file), &mut output).await?;
+        output.flush().await
+    }.await;
+    // A file that can't be read ends the archive early, so its error comes first.
+    let packed = packer.await.map_err(io::Error::other)?;
+    if let Err(e) = packed.and(sealed) {
+        let _ = tokio::fs::remove_file(output_path).await;
+        return Err(e.into());
+    }
+    Ok(count)
+}
+
+/// Reverses `encrypt_dir_archive`: decrypts `input_path` and unpacks the
+/// archive into `output_dir`. The whole archive is decrypted and verified
+/// into a temporary file in `output_dir` before anything is unpacked, so a
+/// damaged or truncated file leaves no files behind. Returns the number of
+/// files unpacked.
+pub async fn decrypt_dir_archive(input_path: impl AsRef<Path>,
+                                 output_dir: impl AsRef<Path>,
+                                 password: impl Into<String>) -> Result<usize, RotError> {
+    let output_dir = output_dir.as_ref().to_path_buf();
+    tokio::fs::create_dir_all(&output_dir).await?;
+    // A fresh name, so that neither an earlier run nor an archive entry is
+    // overwritten; the file goes away when `archive_file` is dropped.
+    let archive_file = tempfile::Builder::new().prefix(".rot-archive-").suffix(".tar").tempfile_in(&output_dir)?;
+    let archive_path = archive_file.path().to_path_buf();
+    decrypt_file(input_path, &archive_path, password).await?;
+    let unpacked = tokio::task::spawn_blocking(move || -> io::Result<usize> {
+        let mut archive = tar::Archive::new(std::fs::File::open(&archive_path)?);
+        let mut count = 0;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let target: PathBuf = entry.path()?.components().filter(|part| *part != Component::CurDir).collect();
+            if output_dir.join(target) == archive_path {
+                continue;
+            }
+            if entry.header().entry_type().is_file() {
+                count += 1;
+            }
+            // `unpack_in` refuses entries that would land outside `output_dir`.
+            entry.unpack_in(&output_dir)?;
+        }
+        Ok(count)
+    }).await.map_err(io::Error::other);
+    drop(archive_file);
+    Ok(unpacked??)
+}
+
+/// Encrypts whatever `inner` yields, reading as the `encrypt_file` format:
+/// the header, then the sealed chunks. Wrap a reader that isn't `Unpin` in
+/// `Box::pin`.
+pub struct EncryptingReader<R> {
+    inner: R,
+    file: FileCipher,
+    /// Plaintext not sealed yet. A full chunk is only the last one if
+    /// nothing follows it, so each one waits until more has been read.
+    pending: Vec<u8>,
+    /// Sealed bytes not handed out yet, starting with the header.
+    sealed: Vec<u8>,
+    position: usize,
+    index: u64,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> EncryptingReader<R> {
+    pub fn new(inner: R, password: impl Into<String>) -> Result<Self, RotError> {
+        let file = Cipher::new(password).new_file(CHUNK_SIZE).map_err(RotError::Crypto)?;
+        Ok(Self::with_file(inner, file))
+    }
+
+    pub(crate) fn with_file(inner: R, file: FileCipher) -> Self {
+        let sealed = file.header().to_vec();
+        Self { inner, file, pending: Vec::new(), sealed, position: 0, index: 0, eof: false, finished: false }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for EncryptingReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let chunk_size = this.file.chunk_size;
+        loop {
+            if this.position < this.sealed.len() {
+                let n = buf.remaining().min(this.sealed.len() - this.position);
+                buf.put_slice(&this.sealed[this.position..this.position + n]);
+                this.position += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.finished {
+                return Poll::Ready(Ok(()));
+            }
+            this.sealed.clear();
+            this.position = 0;
+            if this.pending.len() > chunk_size {
+                this.sealed = this.file.seal_chunk(this.index, &this.pending[..chunk_size], false);
+                this.pending.drain(..chunk_size);
+                this.index += 1;
+                continue;
+            }
+            if this.eof {
+                this.sealed = this.file.seal_chunk(this.index, &this.pending, true);
+                this.finished = true;
+                continue;
+            }
+            let start = this.pending.len();
+            this.pending.resize(chunk_size + 1, 0);
+            let mut read_buf = ReadBuf::new(&mut this.pending[start..]);
+            let result = Pin::new(&mut this.inner).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            this.pending.truncate(start + n);
+            match result {
+                Poll::Ready(Ok(())) => this.eof = n == 0,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Decrypts data in the `encrypt_file` format as it is written, passing the
+/// plaintext on to `inner`. The last chunk is only checked on `shutdown`,
+/// which must be called to tell a complete stream from a truncated one.
+/// Decryption failures are reported as `InvalidData` errors.
+pub struct DecryptingWriter<W> {
+    inner: W,
+    /// `None` once shut down.
+    decryptor: Option<StreamDecryptor>,
+    /// Plaintext not yet written to `inner`.
+    plain: Vec<u8>,
+    position: usize,
+}
+
+impl<W: AsyncWrite + Unpin> DecryptingWriter<W> {
+    pub fn new(inner: W, password: impl Into<String>) -> Self {
+        Self::with_cipher(inner, Arc::new(Cipher::new(password)))
+    }
+
+    pub(crate) fn with_cipher(inner: W, cipher: Arc<Cipher>) -> Self {
+        Self { inner, decryptor: Some(StreamDecryptor::new(cipher)), plain: Vec::new(), position: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Writes out the plaintext held back from `inner`.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.position < self.plain.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.plain[self.position..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.position += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        self.plain.clear();
+        self.position = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DecryptingWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let Some(decryptor) = this.decryptor.as_mut() else {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, messages::decryption_stream_closed())));
+        };
+        this.plain = decryptor.update(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if let Some(decryptor) = this.decryptor.take() {
+            this.plain = decryptor.finish().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            ready!(this.poll_drain(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Stretched keys by KDF and salt, including the legacy salt. Each is set
+/// once by whichever file needs it first, while others needing it wait.
+type StretchedKeys = HashMap<(Kdf, Vec<u8>), Arc<OnceLock<[u8; 32]>>>;
+
+/// A password, key file or recipient keys to encrypt or decrypt files with.
+/// The password is stretched with the KDF once per KDF salt, and every file
+/// gets its own AES key from the stretched key and a random file salt. The
+/// files written through one `Cipher` share its random KDF salt, so a batch
+/// runs the slow KDF once while no two files share a key or a nonce.
+pub(crate) struct Cipher {
+    secret: Secret,
+    /// KDF for the files this cipher writes.
+    kdf: Kdf,
+    stretched: Mutex<StretchedKeys>,
+    /// KDF salt of the files this cipher writes, drawn on first use.
+    salt: Mutex<Option<[u8; SALT_LEN]>>,
+}
+
+enum Secret {
+    Password(String),
+    /// The contents of a key file, which take the place of the stretched
+    /// password.
+    Key([u8; KEY_FILE_LEN]),
+    /// Public keys to encrypt for. In place of the stretched password there
+    /// is a random key, wrapped to each recipient in the header, so such a
+    /// cipher can only open the files it wrote itself.
+    Recipients(Vec<[u8; KEY_FILE_LEN]>),
+    /// Private key of one recipient.
+    Identity([u8; KEY_FILE_LEN]),
+}
+
+impl Cipher {
+    pub fn new(password: impl Into<String>) -> Self {
+        Self::with_secret(Secret::Password(password.into()))
+    }
+
+    pub fn from_key(key: [u8; KEY_FILE_LEN]) -> Self {
+        Self::with_secret(Secret::Key(key))
+    }
+
+    pub fn for_recipients(recipients: Vec<[u8; KEY_FILE_LEN]>) -> Result<Self, String> {
+        if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+            return Err(messages::recipient_count(MAX_RECIPIENTS));
+        }
+        Ok(Self::with_secret(Secret::Recipients(recipients)))
+    }
+
+    pub fn from_identity(identity: [u8; KEY_FILE_LEN]) -> Self {
+        Self::with_secret(Secret::Identity(identity))
+    }
+
+    fn with_secret(secret: Secret) -> Self {
+        Self { secret, kdf: Kdf::default(), stretched: Mutex::new(HashMap::new()), salt: Mutex::new(None) }
+    }
+
+    pub fn with_kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// The key a file's AES key is derived from. `recipients` are the
+    /// wrapped keys that followed its header.
+    fn stretch(&self, kdf: Kdf, salt: &[u8], recipients: &[Stanza]) -> Result<[u8; 32], String> {
+        let password = match (&self.secret, kdf) {
+            (Secret::Key(key), Kdf::KeyFile) => return Ok(*key),
+            (Secret::Identity(identity), Kdf::Recipients { .. }) => return unwrap_key(identity, recipients)
+                .ok_or_else(messages::not_a_recipient),
+            // Only the files this cipher encrypted, whose key `new_file` kept.
+            (Secret::Recipients(_), Kdf::Recipients { .. }) => return self.stretched.lock().unwrap()
+                .get(&(kdf, salt.to_vec()))
+                .and_then(|key| key.get().copied())
+                .ok_or_else(messages::only_recipients_decrypt),
+            (_, Kdf::KeyFile) => return Err(messages::needs_key_file()),
+            (_, Kdf::Recipients { .. }) => return Err(messages::needs_identity()),
+            (Secret::Password(password), _) => password,
+            (Secret::Key(_) | Secret::Recipients(_) | Secret::Identity(_), _) => {
+                return Err(messages::needs_password());
+            }
+        };
+        // The KDF takes long on purpose, so it runs outside the map's lock:
+        // files with other salts go on meanwhile.
+        let key = Arc::clone(self.stretched.lock().unwrap().entry((kdf, salt.to_vec())).or_default());
+        Ok(*key.get_or_init(|| kdf.derive(password.as_bytes(), salt)))
+    }
+
+    /// Keys a new file with a fresh file salt and base nonce.
+    pub fn new_file(&self, chunk_size: usize) -> Result<FileCipher, String> {
+        let rng = SystemRandom::new();
+        let kdf = {
+            let mut salt = self.salt.lock().unwrap();
+            match *salt {
+                Some(kdf) => kdf,
+                None => *salt.insert(random(&rng)?),
+            }
+        };
+        let salts = Salts { kdf, file: random(&rng)?, nonce: random(&rng)? };
+        let (kdf, recipients) = match &self.secret {
+            Secret::Password(_) => (self.kdf, Vec::new()),
+            Secret::Key(_) => (Kdf::KeyFile, Vec::new()),
+            Secret::Recipients(public_keys) => {
+                let kdf = Kdf::Recipients { count: public_keys.len() as u32 };
+                let fresh = random(&rng)?;
+                let key = *self.stretched.lock().unwrap().entry((kdf, salts.kdf.to_vec())).or_default().get_or_init(|| fresh);
+                let recipients = public_keys.iter()
+                    .map(|public_key| wrap_key(&key, public_key, &rng))
+                    .collect::<Result<_, _>>()?;
+                (kdf, recipients)
+            }
+            Secret::Identity(_) => return Err(messages::identity_cannot_encrypt()),
+        };
+        let stretched = self.stretch(kdf, &salts.kdf, &recipients)?;
+        let key_check = Some(key_check(&stretched, &salts.file));
+        self.open_file(Some(&Header { version: FORMAT_VERSION, chunk_size, kdf, salts: Some(salts), recipients, key_check, compressed: false, name: Vec::new() }))
+    }
+
+    /// Keys an existing file by its header, `None` for headerless data.
+    fn open_file(&self, header: Option<&Header>) -> Result<FileCipher, String> {
+        let chunk_size = header.map_or(CHUNK_SIZE, |header| header.chunk_size);
+        let header_bytes = header.map(Header::to_bytes).unwrap_or_default();
+        let kdf = header.map_or(Kdf::default(), |header| header.kdf);
+        match header.and_then(|header| header.salts) {
+            Some(salts) => {
+                let stretched = self.stretch(kdf, &salts.kdf, header.map_or(&[], |header| &header.recipients))?;
+                let check = key_check(&stretched, &salts.file);
+                if header.and_then(|header| header.key_check).is_some_and(|expected| expected != check) {
+                    return Err(match self.secret {
+                        Secret::Key(_) => messages::wrong_key_file(),
+                        _ => messages::wrong_password(),
+                    });
+                }
+                let mut file = FileCipher {
+                    key: file_key(&stretched, &salts.file),
+                    check: to_hex(&check),
+                    nonce: Some(salts.nonce),
+                    framed: header.is_some_and(|header| header.version >= 3),
+                    chunk_size,
+                    compressed: header.is_some_and(|header| header.compressed),
+                    compressor: None,
+                    header: header_bytes,
+                    name: None,
+                };
+                // A name that doesn't open is left to the first chunk to
+                // report, which explains the failure better.
+                file.name = header.and_then(|header| file.open_name(&header.name));
+                Ok(file)
+            }
+            None => {
+                let stretched = self.stretch(kdf, LEGACY_SALT, &[])?;
+                Ok(FileCipher {
+                    key: LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &stretched).unwrap()),
+                    check: to_hex(&key_check(&stretched, &[])),
+                    nonce: None,
+                    framed: false,
+                    chunk_size,
+                    compressed: false,
+                    compressor: None,
+                    header: header_bytes,
+                    name: None,
+                })
+            }
+        }
+    }
+
+    /// Size of the stored object for `plain_len` bytes of plaintext, like
+    /// `stored_len` but counting the wrapped keys this cipher writes.
+    pub fn stored_len(&self, plain_len: u64) -> u64 {
+        let recipients = match &self.secret {
+            Secret::Recipients(public_keys) => public_keys.len(),
+            _ => 0,
+        };
+        stored_len(plain_len) + (recipients * STANZA_LEN) as u64
+    }
+
+    /// The key name an upload of the file called `name` gets with
+    /// `--hide-name`: a keyed hash of the name, the same on every run with
+    /// the same secret. For recipients the hash is keyed with their public
+    /// keys, which hides the name from anyone who doesn't know them.
+    pub fn hidden_name(&self, name: &str) -> Result<String, String> {
+        let key = self.mac_key(b"rot file name")?;
+        Ok(format!("{}{}", to_hex(&hmac::sign(&key, name.as_bytes()).as_ref()[..16]), HIDDEN_NAME_EXT))
+    }
+
+    /// The checksum `--skip-unchanged` records on an encrypted upload: the
+    /// plaintext's SHA-256 hashed again under a key from the secret, so the
+    /// stored value tells nothing about the content to anyone without it.
+    pub fn content_checksum(&self, sha256: &str) -> Result<String, String> {
+        let key = self.mac_key(b"rot content checksum")?;
+        Ok(to_hex(hmac::sign(&key, sha256.as_bytes()).as_ref()))
+    }
+
+    /// An HMAC key derived from the secret for `purpose`, the same on every
+    /// run with the same secret.
+    fn mac_key(&self, purpose: &[u8]) -> Result<hmac::Key, String> {
+        let secret = match &self.secret {
+            Secret::Password(_) => self.stretch(self.kdf, NAME_SALT, &[])?,
+            Secret::Key(key) => *key,
+            Secret::Recipients(public_keys) => digest(&SHA256, &public_keys.concat()).as_ref().try_into().unwrap(),
+            Secret::Identity(_) => return Err(messages::identity_cannot_encrypt()),
+        };
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, NAME_SALT).extract(&secret);
+        Ok(hmac::Key::from(prk.expand(&[purpose], hmac::HMAC_SHA256).expect("one hash length is within the HKDF output limit")))
+    }
+
+    /// The original name kept in the header at the start of `head`, as
+    /// read by `header_len`, of a file uploaded with `--hide-name`.
+    pub fn file_name(&self, head: &[u8]) -> Result<Option<String>, String> {
+        match Header::parse(head)? {
+            Some(header) => Ok(self.open_file(Some(&header))?.name),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks the secret against the header at the start of `head`, as read
+    /// by `header_len`, before any of the data is fetched. Files from before
+    /// format version 6 carry no key check and always pass.
+    pub fn check_key(&self, head: &[u8]) -> Result<(), String> {
+        match Header::parse(head)? {
+            Some(header) => self.open_file(Some(&header)).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Keys the file whose header was saved as `header_hex` by an earlier
+    /// run, but only if `key_check` shows it used the same password.
+    pub fn resume_file(&self, header_hex: &str, key_check: &str) -> Option<FileCipher> {
+        let header = Header::parse(&from_hex(header_hex)?).ok()??;
+        self.open_file(Some(&header)).ok().filter(|file| file.check == key_check)
+    }
+}
+
+fn random<const N: usize>(rng: &SystemRandom) -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    rng.fill(&mut bytes).map_err(|_| messages::random_failed())?;
+    Ok(bytes)
+}
+
+/// Reads a recipient public key given as hex.
+pub(crate) fn parse_recipient(text: &str) -> Result<[u8; KEY_FILE_LEN], String> {
+    from_hex(text.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| messages::invalid_recipient_hex(text, KEY_FILE_LEN * 2))
+}
+
+/// The public key, as hex, that files for the holder of `identity` are
+/// encrypted to.
+pub(crate) fn recipient_of(identity: &[u8; KEY_FILE_LEN]) -> String {
+    to_hex(PublicKey::from(&StaticSecret::from(*identity)).as_bytes())
+}
+
+/// A file key wrapped to one recipient: an ephemeral X25519 public key and
+/// the file key sealed under a key agreed between it and the recipient.
+type Stanza = [u8; STANZA_LEN];
+
+fn wrap_key(key: &[u8; 32], recipient: &[u8; KEY_FILE_LEN], rng: &SystemRandom) -> Result<Stanza, String> {
+    let ephemeral = StaticSecret::from(random::<32>(rng)?);
+    let share = PublicKey::from(&ephemeral).to_bytes();
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    if !shared.was_contributory() {
+        return Err(messages::invalid_recipient(to_hex(recipient)));
+    }
+    let mut stanza = [0u8; STANZA_LEN];
+    stanza[..32].copy_from_slice(&share);
+    let mut in_out = key.to_vec();
+    wrapping_key(shared.as_bytes(), &share, recipient)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key([0; NONCE_LEN]), Aad::empty(), &mut in_out)
+        .unwrap();
+    stanza[32..].copy_from_slice(&in_out);
+    Ok(stanza)
+}
+
+/// The file key from whichever of `stanzas` was wrapped to `identity`.
+fn unwrap_key(identity: &[u8; KEY_FILE_LEN], stanzas: &[Stanza]) -> Option<[u8; 32]> {
+    let secret = StaticSecret::from(*identity);
+    let public_key = PublicKey::from(&secret).to_bytes();
+    stanzas.iter().find_map(|stanza| {
+        let share: [u8; 32] = stanza[..32].try_into().unwrap();
+        let shared = secret.diffie_hellman(&PublicKey::from(share));
+        let mut in_out = stanza[32..].to_vec();
+        let key = wrapping_key(shared.as_bytes(), &share, &public_key)
+            .open_in_place(Nonce::assume_unique_for_key([0; NONCE_LEN]), Aad::empty(), &mut in_out)
+            .ok()?;
+        key.try_into().ok()
+    })
+}
+
+/// Every ephemeral key is used for a single wrap, so its key can take a
+/// fixed nonce.
+fn wrapping_key(shared: &[u8; 32], share: &[u8; 32], recipient: &[u8; 32]) -> LessSafeKey {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[&share[..], &recipient[..]].concat()).extract(shared);
+    let okm = prk.expand(&[b"rot recipient"], &AES_256_GCM).expect("AES_256_GCM key length is valid for HKDF");
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len()).step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// The AES key of one file: HKDF-SHA256 of the stretched password, salted
+/// with the file salt.
+fn file_key(stretched: &[u8; 32], file_salt: &[u8]) -> LessSafeKey {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, file_salt).extract(stretched);
+    let okm = prk.expand(&[b"rot file key"], &AES_256_GCM).expect("AES_256_GCM key length is valid for HKDF");
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+/// Fingerprint of a file's key for telling whether a later run uses the
+/// same password, also kept in the header since format version 6. It comes
+/// from the same HKDF under another label, so it reveals nothing about the
+/// key itself.
+fn key_check(stretched: &[u8; 32], file_salt: &[u8]) -> [u8; KEY_CHECK_LEN] {
+    let mut check = [0u8; 32];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, file_salt)
+        .extract(stretched)
+        .expand(&[b"rot key check"], hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut check))
+        .expect("one hash length is within the HKDF output limit");
+    check[..KEY_CHECK_LEN].try_into().unwrap()
+}
+
+/// The key and nonces of one encrypted file. Chunks are numbered from 0 and
+/// each one is sealed under the base nonce XOR its index. The last chunk is
+/// marked as such in its AAD, and an empty file still has one, empty, chunk.
+pub(crate) struct FileCipher {
+    key: LessSafeKey,
+    /// See `key_check`.
+    check: String,
+    /// Base of the chunk nonces; `None` for data from before format version 2,
+    /// which sealed every chunk under the same fixed nonce.
+    nonce: Option<[u8; NONCE_LEN]>,
+    /// Since format version 3 the AAD also carries the chunk index and
+    /// whether the chunk is the last one, so reordered, repeated or dropped
+    /// chunks fail to open.
+    framed: bool,
+    chunk_size: usize,
+    /// Since format version 7 chunks may be compressed before sealing; each
+    /// stored chunk is then prefixed with its length.
+    compressed: bool,
+    /// Compresses the chunks of a file being written.
+    compressor: Option<Mutex<Compressor<'static>>>,
+    header: Vec<u8>,
+    /// Original name of a file uploaded with `--hide-name`.
+    name: Option<String>,
+}
+
+impl FileCipher {
+    /// The header to write in front of the encrypted chunks.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Keeps `name` in the header, sealed like the chunks. Only names that
+    /// are a single path component are kept, since downloads write to them.
+    pub fn with_name(mut self, name: &str) -> Result<Self, String> {
+        if !is_plain_name(name) || name.len() > MAX_NAME_LEN {
+            return Err(messages::cannot_hide_name(name));
+        }
+        let mut sealed = name.as_bytes().to_vec();
+        self.key.seal_in_place_append_tag(self.chunk_nonce(NAME_INDEX), Aad::from(NAME_AAD), &mut sealed).unwrap();
+        self.header[V4_HEADER_LEN..V5_HEADER_LEN].copy_from_slice(&(sealed.len() as u16).to_be_bytes());
+        self.header.extend_from_slice(&sealed);
+        self.name = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// Compresses every chunk with zstd at `level` before sealing it.
+    pub fn with_compression(mut self, level: i32) -> Result<Self, String> {
+        let compressor = Compressor::new(level).map_err(|e| messages::invalid_compression_level(level, e))?;
+        self.header[V6_HEADER_LEN] |= COMPRESSED_FLAG;
+        self.compressed = true;
+        self.compressor = Some(Mutex::new(compressor));
+        Ok(self)
+    }
+
+    fn open_name(&self, sealed: &[u8]) -> Option<String> {
+        let mut in_out = sealed.to_vec();
+        let name = self.key.open_in_place(self.chunk_nonce(NAME_INDEX), Aad::from(NAME_AAD), &mut in_out).ok()?;
+        String::from_utf8(name.to_vec()).ok().filter(|name| is_plain_name(name))
+    }
+
+    fn chunk_nonce(&self, index: u64) -> Nonce {
+        let Some(mut nonce) = self.nonce else {
+            return Nonce::assume_unique_for_key(LEGACY_NONCE);
+        };
+        for (byte, counter) in nonce[NONCE_LEN - 8..].iter_mut().zip(index.to_be_bytes()) {
+            *byte ^= counter;
+        }
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    fn chunk_aad(&self, index: u64, is_last: bool) -> Aad<Vec<u8>> {
+        let mut aad = AAD.to_vec();
+        if self.framed {
+            aad.extend_from_slice(&index.to_be_bytes());
+            aad.push(is_last as u8);
+        }
+        Aad::from(aad)
+    }
+
+    fn seal(&self, index: u64, chunk: &[u8], is_last: bool) -> Vec<u8> {
+        let mut in_out = chunk.to_vec();
+        self.key.seal_in_place_append_tag(self.chunk_nonce(index), self.chunk_aad(index, is_last), &mut in_out).unwrap();
+        in_out
+    }
+
+    /// Seals one chunk as it is stored. A chunk of a compressed file starts
+    /// with a byte telling whether it is compressed, since chunks that don't
+    /// shrink are kept as they are.
+    fn seal_chunk(&self, index: u64, chunk: &[u8], is_last: bool) -> Vec<u8> {
+        if !self.compressed {
+            return self.seal(index, chunk, is_last);
+        }
+        let compressed = self.compressor.as_ref()
+            .and_then(|compressor| compressor.lock().unwrap().compress(chunk).ok())
+            .filter(|compressed| compressed.len() < chunk.len());
+        let mut payload = Vec::with_capacity(chunk.len() + 1);
+        match compressed {
+            Some(compressed) => {
+                payload.push(ZSTD_CHUNK);
+                payload.extend_from_slice(&compressed);
+            }
+            None => {
+                payload.push(RAW_CHUNK);
+                payload.extend_from_slice(chunk);
+            }
+        }
+        let sealed = self.seal(index, &payload, is_last);
+        let mut frame = Vec::with_capacity(FRAME_LEN_SIZE + sealed.len());
+        frame.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&sealed);
+        frame
+    }
+
+    /// Length of the stored chunk at the start of `data`, `None` while too
+    /// little of it has arrived to tell.
+    fn frame_len(&self, data: &[u8]) -> Result<Option<usize>, String> {
+        let tag_len = AES_256_GCM.tag_len();
+        if !self.compressed {
+            return Ok(Some(self.chunk_size + tag_len));
+        }
+        let Some(prefix) = data.get(..FRAME_LEN_SIZE) else { return Ok(None) };
+        match u32::from_be_bytes(prefix.try_into().unwrap()) as usize {
+            len if (tag_len + 1..=self.chunk_size + 1 + tag_len).contains(&len) => Ok(Some(FRAME_LEN_SIZE + len)),
+            len => Err(messages::invalid_chunk_len(len)),
+        }
+    }
+
+    fn open(&self, index: u64, frame: &[u8], is_last: bool) -> Result<Vec<u8>, Unspecified> {
+        let sealed = if self.compressed { &frame[FRAME_LEN_SIZE..] } else { frame };
+        let mut in_out = sealed.to_vec();
+        self.key.open_in_place(self.chunk_nonce(index), self.chunk_aad(index, is_last), &mut in_out).map(|plain| plain.to_vec())
+    }
+
+    /// Undoes the compression of an opened chunk.
+    fn decompress(&self, plain: Vec<u8>) -> Result<Vec<u8>, String> {
+        if !self.compressed {
+            return Ok(plain);
+        }
+        match plain.split_first() {
+            Some((&RAW_CHUNK, chunk)) if chunk.len() <= self.chunk_size => Ok(chunk.to_vec()),
+            Some((&ZSTD_CHUNK, compressed)) => zstd::bulk::decompress(compressed, self.chunk_size)
+                .map_err(messages::decompress_failed),
+            _ => Err(messages::invalid_compressed_chunk()),
+        }
+    }
+
+    pub fn key_check(&self) -> &str {
+        &self.check
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+/// First byte of a chunk of a compressed file.
+const RAW_CHUNK: u8 = 0;
+const ZSTD_CHUNK: u8 = 1;
+
+/// The name is sealed under the nonce of a chunk index no file reaches.
+const NAME_INDEX: u64 = u64::MAX;
+const NAME_AAD: &[u8] = b"rot file name";
+
+/// Whether `name` is a single path component, and so safe to download to.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+/// Whether the header at the start of `head`, as read by `header_len`,
+/// marks a file with compressed chunks.
+pub(crate) fn is_compressed(head: &[u8]) -> bool {
+    matches!(Header::parse(head), Ok(Some(header)) if header.compressed)
+}
+
+/// Whether `file_name` is a key name given by `--hide-name`.
+pub(crate) fn is_hidden_name(file_name: &str) -> bool {
+    file_name.strip_suffix(HIDDEN_NAME_EXT)
+        .is_some_and(|stem| stem.len() == 32 && stem.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+/// Size of `plain_len` bytes once encrypted, counting one tag per chunk and
+/// the empty chunk of an empty file.
+pub(crate) fn encrypted_len(plain_len: usize) -> usize {
+    plain_len + plain_len.div_ceil(CHUNK_SIZE).max(1) * AES_256_GCM.tag_len()
+}
+
+/// Size of the stored object for `plain_len` bytes of plaintext, header
+/// included.
+pub(crate) fn stored_len(plain_len: u64) -> u64 {
+    HEADER_LEN as u64 + encrypted_len(plain_len as usize) as u64
+}
+
+/// Header written in front of encrypted data: magic, format version and the
+/// plaintext chunk size, so that readers don't depend on `CHUNK_SIZE`, then
+/// since version 2 the KDF salt, file salt and base nonce. Version 1 used the
+/// fixed legacy salt and nonce, and data without a header predates both and
+/// always used 4096-byte chunks. Version 3 has the layout of version 2 and
+/// binds each chunk's position into its AAD, together with a flag set only
+/// for the last chunk. That flag is why the format needs no trailer after
+/// the chunks: a file cut short, even at a chunk boundary, has no chunk
+/// that opens as the last one, so its truncation is caught when the stream
+/// ends. Version 4 adds the KDF and its parameters after the chunk size;
+/// earlier versions used PBKDF2. Files for recipients end the header with
+/// one wrapped key per recipient. Version 5 adds the length of a sealed file
+/// name, which follows the wrapped keys. Version 6 adds a key check after
+/// it, so that a wrong password is caught by the header alone rather than
+/// by the first chunk. Version 7 adds a flags byte after that, which marks
+/// files with compressed chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    version: u8,
+    chunk_size: usize,
+    kdf: Kdf,
+    /// `None` for version 1.
+    salts: Option<Salts>,
+    recipients: Vec<Stanza>,
+    /// See `key_check`; `None` before version 6.
+    key_check: Option<[u8; KEY_CHECK_LEN]>,
+    /// Whether the chunks are compressed; never before version 7.
+    compressed: bool,
+    /// The sealed file name, empty unless uploaded with `--hide-name`.
+    name: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Salts {
+    kdf: [u8; SALT_LEN],
+    file: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+}
+
+impl Header {
+    /// Encoded length, which depends on the version.
+    fn len(&self) -> usize {
+        match self.version {
+            1 => V1_HEADER_LEN,
+            2 | 3 => V3_HEADER_LEN,
+            4 => V4_HEADER_LEN + self.recipients.len() * STANZA_LEN,
+            5 => V5_HEADER_LEN + self.recipients.len() * STANZA_LEN + self.name.len(),
+            6 => V6_HEADER_LEN + self.recipients.len() * STANZA_LEN + self.name.len(),
+            _ => HEADER_LEN + self.recipients.len() * STANZA_LEN + self.name.len(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(FORMAT_MAGIC);
+        header.push(self.version);
+        header.extend_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        if self.version >= 4 {
+            header.extend_from_slice(&self.kdf.to_bytes());
+        }
+        if let Some(salts) = &self.salts {
+            header.extend_from_slice(&salts.kdf);
+            header.extend_from_slice(&salts.file);
+            header.extend_from_slice(&salts.nonce);
+        }
+        if self.version >= 5 {
+            header.extend_from_slice(&(self.name.len() as u16).to_be_bytes());
+        }
+        if self.version >= 6 {
+            header.extend_from_slice(&self.key_check.unwrap_or_default());
+        }
+        if self.version >= 7 {
+            header.push(if self.compressed { COMPRESSED_FLAG } else { 0 });
+        }
+        for stanza in &self.recipients {
+            header.extend_from_slice(stanza);
+        }
+        header.extend_from_slice(&self.name);
+        header
+    }
+
+    /// Reads the header at the start of `data`, `None` for legacy data
+    /// without one.
+    fn parse(data: &[u8]) -> Result<Option<Self>, String> {
+        if data.len() < V1_HEADER_LEN || &data[..FORMAT_MAGIC.len()] != FORMAT_MAGIC {
+            return Ok(None);
+        }
+        let (version, len) = match data[FORMAT_MAGIC.len()] {
+            1 => (1, V1_HEADER_LEN),
+            version @ 2..=3 => (version, V3_HEADER_LEN),
+            4 => (4, V4_HEADER_LEN),
+            5 => (5, V5_HEADER_LEN),
+            6 => (6, V6_HEADER_LEN),
+            FORMAT_VERSION => (FORMAT_VERSION, HEADER_LEN),
+            version => return Err(messages::unsupported_format_version(version)),
+        };
+        if data.len() < len {
+            return Err(messages::header_truncated(data.len(), len));
+        }
+        let size_bytes: [u8; 4] = data[FORMAT_MAGIC.len() + 1..V1_HEADER_LEN].try_into().unwrap();
+        let chunk_size = match u32::from_be_bytes(size_bytes) as usize {
+            chunk_size @ 1..=MAX_CHUNK_SIZE => chunk_size,
+            chunk_size => return Err(messages::header_chunk_size(chunk_size)),
+        };
+        let (kdf, salts_start) = match version {
+            4.. => (Kdf::parse(&data[V1_HEADER_LEN..V1_HEADER_LEN + KDF_PARAMS_LEN])?, V1_HEADER_LEN + KDF_PARAMS_LEN),
+            _ => (Kdf::default(), V1_HEADER_LEN),
+        };
+        let salts = (version >= 2).then(|| {
+            let (kdf, rest) = data[salts_start..salts_start + 2 * SALT_LEN + NONCE_LEN].split_at(SALT_LEN);
+            let (file, nonce) = rest.split_at(SALT_LEN);
+            Salts { kdf: kdf.try_into().unwrap(), file: file.try_into().unwrap(), nonce: nonce.try_into().unwrap() }
+        });
+        let recipients_len = match kdf {
+            Kdf::Recipients { count } => count as usize * STANZA_LEN,
+            _ => 0,
+        };
+        let name_len = match version {
+            5.. => u16::from_be_bytes(data[V4_HEADER_LEN..V5_HEADER_LEN].try_into().unwrap()) as usize,
+            _ => 0,
+        };
+        if name_len > MAX_NAME_LEN + AES_256_GCM.tag_len() {
+            return Err(messages::header_name_len(name_len));
+        }
+        let full_len = len + recipients_len + name_len;
+        if data.len() < full_len {
+            return Err(messages::header_truncated(data.len(), full_len));
+        }
+        let recipients = data[len..len + recipients_len].chunks(STANZA_LEN).map(|stanza| stanza.try_into().unwrap()).collect();
+        let key_check = (version >= 6).then(|| data[V5_HEADER_LEN..V6_HEADER_LEN].try_into().unwrap());
+        let compressed = match data.get(V6_HEADER_LEN).filter(|_| version >= 7) {
+            Some(&flags) if flags & !COMPRESSED_FLAG != 0 => return Err(messages::unsupported_format_flags(format!("{:#04x}", flags))),
+            Some(&flags) => flags & COMPRESSED_FLAG != 0,
+            None => false,
+        };
+        let name = data[len + recipients_len..full_len].to_vec();
+        Ok(Some(Self { version, chunk_size, kdf, salts, recipients, key_check, compressed, name }))
+    }
+}
+
+/// Length of the header at the start of `head`, given at least its first
+/// `HEADER_LEN` bytes: longer than `HEADER_LEN` only when wrapped keys for
+/// recipients or a file name follow it.
+pub(crate) fn header_len(head: &[u8]) -> usize {
+    let Some(head) = head.get(..V5_HEADER_LEN).filter(|head| head.starts_with(FORMAT_MAGIC)) else {
+        return HEADER_LEN;
+    };
+    let version = head[FORMAT_MAGIC.len()];
+    let recipients_len = match Kdf::parse(&head[V1_HEADER_LEN..V1_HEADER_LEN + KDF_PARAMS_LEN]) {
+        Ok(Kdf::Recipients { count }) if version >= 4 => count as usize * STANZA_LEN,
+        _ => 0,
+    };
+    let name_len = (u16::from_be_bytes(head[V4_HEADER_LEN..V5_HEADER_LEN].try_into().unwrap()) as usize)
+        .min(MAX_NAME_LEN + AES_256_GCM.tag_len());
+    match version {
+        4 => V4_HEADER_LEN + recipients_len,
+        5 => V5_HEADER_LEN + recipients_len + name_len,
+        6 => V6_HEADER_LEN + recipients_len + name_len,
+        FORMAT_VERSION => HEADER_LEN + recipients_len + name_len,
+        _ => HEADER_LEN,
+    }
+}
+
+/// Plaintext length of a stored object `stored_len` bytes long that starts
+/// with `head`, at least its header. `None` for compressed files, whose
+/// chunks vary in length.
+#[cfg(any(test, all(feature = "mount", target_os = "linux")))]
+pub(crate) fn plain_len(head: &[u8], stored_len: u64) -> Result<Option<u64>, String> {
+    let header = Header::parse(head)?;
+    if header.as_ref().is_some_and(|header| header.compressed) {
+        return Ok(None);
+    }
+    let chunk_size = header.as_ref().map_or(CHUNK_SIZE, |header| header.chunk_size) as u64;
+    let tag_len = AES_256_GCM.tag_len() as u64;
+    let body = stored_len.saturating_sub(header.as_ref().map_or(0, Header::len) as u64);
+    // Every chunk carries a tag, and even an empty file has one chunk.
+    let chunks = body.div_ceil(chunk_size + tag_len).max(1);
+    Ok(Some(body.saturating_sub(chunks * tag_len)))
+}
+
+/// Encrypts an in-memory part chunk by chunk, producing exactly the bytes
+/// `encrypt_file` would write for the same plaintext range. `data` must start
+/// on a chunk boundary of the original file, at chunk `first_chunk`, and
+/// `is_last` tells whether it runs to the end of the file.
+pub(crate) fn encrypt_part(file: &FileCipher, first_chunk: u64, data: &[u8], is_last: bool) -> Vec<u8> {
+    let mut output = Vec::with_capacity(encrypted_len(data.len()));
+    let count = data.len().div_ceil(file.chunk_size).max(is_last as usize);
+    for i in 0..count {
+        let chunk = &data[i * file.chunk_size..data.len().min((i + 1) * file.chunk_size)];
+        output.extend_from_slice(&file.seal_chunk(first_chunk + i as u64, chunk, is_last && i + 1 == count));
+    }
+    output
+}
+
+/// Decrypts a stream of `encrypt_file` output arriving in arbitrarily sized
+/// pieces, regrouping it into whole encrypted chunks before opening them.
+/// The chunk size comes from the header; headerless data is read as legacy
+/// `CHUNK_SIZE` chunks.
+pub(crate) struct StreamDecryptor {
+    cipher: Arc<Cipher>,
+    pending: Vec<u8>,
+    /// Keyed once the header has been read.
+    file: Option<FileCipher>,
+    /// Number of chunks opened so far, counting those skipped by `resume`.
+    opened: u64,
+    /// Chunks already decrypted by an earlier run.
+    skipped: u64,
+}
+
+impl StreamDecryptor {
+    pub fn new(cipher: Arc<Cipher>) -> Self {
+        Self { cipher, pending: Vec::new(), file: None, opened: 0, skipped: 0 }
+    }
+
+    /// A decryptor continuing a download of which `plain_len` bytes were
+    /// already decrypted. `head` is the start of the stored object, at least
+    /// `header_len` bytes unless the object is shorter. Returns the decryptor,
+    /// the stored offset to continue reading at, and how much of the existing
+    /// plaintext to keep, which is rounded down to whole chunks.
+    pub fn resume(cipher: Arc<Cipher>, head: &[u8], plain_len: u64) -> Result<(Self, u64, u64), String> {
+        let header = Header::parse(head)?;
+        let header_len = header.as_ref().map_or(0, Header::len);
+        let file = cipher.open_file(header.as_ref())?;
+        // Compressed chunks vary in length, so there is no telling where a
+        // later one starts; such downloads start over.
+        if file.compressed {
+            return Ok((Self::new(cipher), 0, 0));
+        }
+        let chunk_size = file.chunk_size as u64;
+        // The last kept chunk may be the final one, which is only opened
+        // with the end of the stream, so a whole last chunk is fetched again.
+        let chunks = plain_len.saturating_sub(1) / chunk_size;
+        let stored_offset = header_len as u64 + chunks * (chunk_size + AES_256_GCM.tag_len() as u64);
+        let decryptor = Self { cipher, pending: Vec::new(), file: Some(file), opened: chunks, skipped: chunks };
+        Ok((decryptor, stored_offset, chunks * chunk_size))
+    }
+
+    /// Feeds received bytes and returns the plaintext of every chunk that is
+    /// now complete.
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        self.pending.extend_from_slice(data);
+        if self.file.is_none() {
+            if self.pending.len() < header_len(&self.pending) {
+                return Ok(Vec::new());
+            }
+            self.read_header()?;
+        }
+        let Some(file) = &self.file else { return Ok(Vec::new()) };
+
+        // The last frame is held back until more data follows it, since
+        // only `finish` knows that it is the final one.
+        let mut output = Vec::with_capacity(self.pending.len());
+        let mut start = 0;
+        while let Some(len) = file.frame_len(&self.pending[start..])? {
+            if self.pending.len() - start <= len {
+                break;
+            }
+            let plain = file.open(self.opened, &self.pending[start..start + len], false).map_err(|_| self.auth_error(false))?;
+            output.extend_from_slice(&file.decompress(plain)?);
+            self.opened += 1;
+            start += len;
+        }
+        self.pending.drain(..start);
+        Ok(output)
+    }
+
+    /// Opens the trailing short chunk once the stream has ended.
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        if self.file.is_none() {
+            self.read_header()?;
+        }
+        let Some(file) = &self.file else { return Ok(Vec::new()) };
+        if self.pending.is_empty() {
+            return match file.framed {
+                true => Err(messages::missing_last_chunk(self.opened)),
+                false => Ok(Vec::new()),
+            };
+        }
+        if self.pending.len() < AES_256_GCM.tag_len() {
+            return Err(messages::last_chunk_without_tag(self.pending.len()));
+        }
+        if file.compressed && file.frame_len(&self.pending)? != Some(self.pending.len()) {
+            return Err(messages::last_chunk_truncated(self.pending.len()));
+        }
+        let plain = file.open(self.opened, &self.pending, true).map_err(|_| self.auth_error(true))?;
+        file.decompress(plain)
+    }
+
+    /// Explains a failed chunk. A failure on the very first chunk usually
+    /// means a wrong password, since any password opens nothing; once a
+    /// chunk has opened the password is known good, so later failures mean
+    /// the data was altered or cut short.
+    fn auth_error(&self, is_last: bool) -> String {
+        let chunk_size = self.file.as_ref().map_or(CHUNK_SIZE, |file| file.chunk_size) as u64;
+        let offset = self.opened * chunk_size;
+        if self.opened == 0 {
+            messages::first_chunk_failed()
+        } else if self.opened == self.skipped {
+            messages::resumed_chunk_failed(self.opened + 1)
+        } else if is_last {
+            messages::last_chunk_failed(self.opened + 1, offset)
+        } else {
+            messages::chunk_failed(self.opened + 1, offset)
+        }
+    }
+
+    fn read_header(&mut self) -> Result<(), String> {
+        let header = Header::parse(&self.pending)?;
+        if let Some(header) = &header {
+            self.pending.drain(..header.len());
+        }
+        self.file = Some(self.cipher.open_file(header.as_ref())?);
+        Ok(())
+    }
+}
+
+fn raw_key(secret: &[u8]) -> Result<LessSafeKey, RotError> {
+    UnboundKey::new(&AES_256_GCM, secret)
+        .map(LessSafeKey::new)
+        .map_err(|_| RotError::Crypto(messages::invalid_key_len(AES_256_GCM.key_len(), secret.len())))
+}
+
+pub fn _encrypt(secret: &[u8], payload: &[u8]) -> Result<Vec<u8>, RotError> {
+    let key = raw_key(secret)?;
+    let nonce = Nonce::assume_unique_for_key(LEGACY_NONCE);
     let aad = Aad::from(AAD);
 
     let mut in_out = payload.to_vec();
-    key.seal_in_place_append_tag(nonce, aad, &mut in_out).unwrap();
+    key.seal_in_place_append_tag(nonce, aad, &mut in_out)
+        .map_err(|_| RotError::Crypto(messages::encryption_failed()))?;
 
     Ok(in_out)
 }
 
-pub fn _decrypt(secret: &[u8], payload: &[u8]) -> Result<Vec<u8>, Unspecified> {
-    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &secret).unwrap());
-    let nonce = Nonce::try_assume_unique_for_key(&NONCE).unwrap();
+pub fn _decrypt(secret: &[u8], payload: &[u8]) -> Result<Vec<u8>, RotError> {
+    let key = raw_key(secret)?;
+    let nonce = Nonce::assume_unique_for_key(LEGACY_NONCE);
     let aad = Aad::from(AAD);
 
     let mut in_out = payload.to_vec();
-    key.open_in_place(nonce, aad, &mut in_out).unwrap();
+    key.open_in_place(nonce, aad, &mut in_out)
+        .map_err(|_| RotError::Crypto(messages::wrong_key()))?;
 
     Ok(in_out)
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+    use ring::digest::{digest, SHA256};
+    use ring::rand::{SecureRandom, SystemRandom};
     use tokio::fs::{DirBuilder, File, OpenOptions};
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
-    use crate::crypt::{_decrypt, decrypt_file, derive_key, _encrypt, encrypt_file};
+    use crate::constant::{CHUNK_SIZE, HEADER_LEN, V1_HEADER_LEN, V5_HEADER_LEN};
+    use crate::error::RotError;
+    use crate::manifest::to_hex;
+    use crate::crypt::{_decrypt, Argon2Params, Cipher, decrypt_dir, decrypt_dir_archive, decrypt_file, decrypt_file_with_key_file, derive_key, _encrypt, encrypt_dir, encrypt_dir_archive, encrypt_file, encrypt_file_with_key_file, encrypt_part, DecryptingWriter, EncryptingReader, FileCipher, Header, header_len, is_hidden_name, Kdf, parse_recipient, plain_len, recipient_of, StreamDecryptor};
+
+    fn encrypt(file: &FileCipher, plain: &[u8]) -> Vec<u8> {
+        let mut encrypted = file.header().to_vec();
+        encrypted.extend(encrypt_part(file, 0, plain, true));
+        encrypted
+    }
+
+    fn decrypt(password: &str, encrypted: &[u8], piece_size: usize) -> Result<Vec<u8>, String> {
+        let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::new(password)));
+        let mut output = Vec::new();
+        for piece in encrypted.chunks(piece_size) {
+            output.extend(decryptor.update(piece)?);
+        }
+        output.extend(decryptor.finish()?);
+        Ok(output)
+    }
+
+    #[test]
+    fn test_stream_decryptor() {
+        let plain: Vec<u8> = (0..4096 * 2 + 17).map(|i| i as u8).collect();
+        let encrypted = encrypt(&Cipher::new("RAVEN_BOOK").new_file(4096).unwrap(), &plain);
+
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 1000).unwrap(), plain);
+        assert!(decrypt("WRONG", &encrypted, 1000).is_err());
+    }
+
+    #[test]
+    fn test_stream_decryptor_errors() {
+        let plain = vec![3u8; 4096 * 3 + 10];
+        let encrypted = encrypt(&Cipher::new("RAVEN_BOOK").new_file(4096).unwrap(), &plain);
+
+        let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::new("WRONG")));
+        assert!(decryptor.update(&encrypted).unwrap_err().contains("密码不正确"));
+
+        let mut corrupted = encrypted.clone();
+        corrupted[HEADER_LEN + 4112 + 100] ^= 1;
+        let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::new("RAVEN_BOOK")));
+        let error = decryptor.update(&corrupted).unwrap_err();
+        assert!(error.contains("第 2 个分块") && error.contains("偏移 4096"));
+
+        let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::new("RAVEN_BOOK")));
+        decryptor.update(&encrypted[..encrypted.len() - 5]).unwrap();
+        assert!(decryptor.finish().unwrap_err().contains("截断"));
+    }
+
+    #[test]
+    fn test_random_salt_and_nonce() {
+        let plain = vec![0u8; 4096 * 2];
+        let cipher = Cipher::new("RAVEN_BOOK");
+        let first = encrypt(&cipher.new_file(4096).unwrap(), &plain);
+        let second = encrypt(&cipher.new_file(4096).unwrap(), &plain);
+
+        assert_ne!(first, second);
+        // Same KDF salt within one cipher, different file salt and nonce.
+        assert_eq!(first[V1_HEADER_LEN..V1_HEADER_LEN + 16], second[V1_HEADER_LEN..V1_HEADER_LEN + 16]);
+        assert_ne!(first[V1_HEADER_LEN + 16..HEADER_LEN], second[V1_HEADER_LEN + 16..HEADER_LEN]);
+        // Identical chunks are sealed under different nonces.
+        assert_ne!(first[HEADER_LEN..HEADER_LEN + 4112], first[HEADER_LEN + 4112..]);
+        assert_eq!(decrypt("RAVEN_BOOK", &second, 4096).unwrap(), plain);
+
+        let resumed = cipher.resume_file(&to_hex(&first[..HEADER_LEN]), cipher.new_file(4096).unwrap().key_check());
+        assert!(resumed.is_none());
+        let file = cipher.new_file(4096).unwrap();
+        let resumed = Cipher::new("RAVEN_BOOK").resume_file(&to_hex(file.header()), file.key_check()).unwrap();
+        assert_eq!(encrypt_part(&resumed, 1, &plain, false), encrypt_part(&file, 1, &plain, false));
+        assert!(Cipher::new("WRONG").resume_file(&to_hex(file.header()), file.key_check()).is_none());
+    }
+
+    #[test]
+    fn test_key_check() {
+        let plain = vec![5u8; 4096 * 2];
+        let cipher = Cipher::new("RAVEN_BOOK");
+        let encrypted = encrypt(&cipher.new_file(4096).unwrap(), &plain);
+
+        // The header alone is enough to reject a wrong password.
+        let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::new("WRONG")));
+        assert!(decryptor.update(&encrypted[..HEADER_LEN]).unwrap_err().contains("密码不正确"));
+        assert!(Cipher::new("WRONG").check_key(&encrypted[..HEADER_LEN]).is_err());
+        Cipher::new("RAVEN_BOOK").check_key(&encrypted[..HEADER_LEN]).unwrap();
+
+        // Version 5 files have no key check and fail on the first chunk.
+        let mut header = Header::parse(&encrypted).unwrap().unwrap();
+        header.version = 5;
+        header.key_check = None;
+        let v5 = encrypt(&cipher.open_file(Some(&header)).unwrap(), &plain);
+        assert_eq!(header_len(&v5), V5_HEADER_LEN);
+        Cipher::new("WRONG").check_key(&v5).unwrap();
+        assert_eq!(decrypt("RAVEN_BOOK", &v5, 1000).unwrap(), plain);
+        assert!(decrypt("WRONG", &v5, 1000).unwrap_err().contains("第 1 个分块"));
+    }
+
+    #[test]
+    fn test_plain_len() {
+        let cipher = Cipher::new("RAVEN_BOOK");
+        for len in [0, 1, 4095, 4096, 4097, 4096 * 3] {
+            let encrypted = encrypt(&cipher.new_file(4096).unwrap(), &vec![1u8; len]);
+            assert_eq!(plain_len(&encrypted, encrypted.len() as u64).unwrap(), Some(len as u64));
+        }
+        let compressed = encrypt(&cipher.new_file(4096).unwrap().with_compression(3).unwrap(), &[1u8; 100]);
+        assert_eq!(plain_len(&compressed, compressed.len() as u64).unwrap(), None);
+    }
+
+    #[test]
+    fn test_compression() {
+        let text: Vec<u8> = b"rot compresses each chunk before sealing it. ".iter().cycle().take(4096 * 5 + 123).copied().collect();
+        let cipher = Cipher::new("RAVEN_BOOK");
+        let encrypted = encrypt(&cipher.new_file(4096).unwrap().with_compression(3).unwrap(), &text);
+        assert!(encrypted.len() < text.len() / 4);
+        assert!(Header::parse(&encrypted).unwrap().unwrap().compressed);
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 7).unwrap(), text);
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 1 << 20).unwrap(), text);
+        let (_, start, kept) = StreamDecryptor::resume(Arc::new(Cipher::new("RAVEN_BOOK")), &encrypted, 5000).unwrap();
+        assert_eq!((start, kept), (0, 0));
+
+        // Chunks that don't shrink are kept as they are.
+        let mut noise = vec![0u8; 4096 * 2];
+        SystemRandom::new().fill(&mut noise).unwrap();
+        let encrypted_noise = encrypt(&cipher.new_file(4096).unwrap().with_compression(3).unwrap(), &noise);
+        assert_eq!(encrypted_noise.len(), HEADER_LEN + 2 * (4 + 1 + 4096 + 16));
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted_noise, 1000).unwrap(), noise);
+
+        let frame_end = |start: usize| start + 4 + u32::from_be_bytes(encrypted[start..start + 4].try_into().unwrap()) as usize;
+        let two_frames = frame_end(frame_end(HEADER_LEN));
+        assert!(decrypt("RAVEN_BOOK", &encrypted[..two_frames], 1000).unwrap_err().contains("截断"));
+        assert!(decrypt("RAVEN_BOOK", &encrypted[..encrypted.len() - 1], 1000).unwrap_err().contains("截断"));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_adapters() {
+        let plain: Vec<u8> = (0..CHUNK_SIZE * 3).map(|i| (i % 251) as u8).collect();
+        let mut encrypted = Vec::new();
+        EncryptingReader::new(plain.as_slice(), "RAVEN_BOOK").unwrap().read_to_end(&mut encrypted).await.unwrap();
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 1000).unwrap(), plain);
+
+        let mut writer = DecryptingWriter::new(Vec::new(), "RAVEN_BOOK");
+        for piece in encrypted.chunks(777) {
+            writer.write_all(piece).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        assert_eq!(writer.into_inner(), plain);
+
+        let mut truncated = DecryptingWriter::new(Vec::new(), "RAVEN_BOOK");
+        truncated.write_all(&encrypted[..encrypted.len() - 10]).await.unwrap();
+        assert_eq!(truncated.shutdown().await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        let mut wrong = DecryptingWriter::new(Vec::new(), "WRONG");
+        assert!(wrong.write_all(&encrypted).await.unwrap_err().to_string().contains("密码不正确"));
+
+        let mut empty = Vec::new();
+        EncryptingReader::new(&[][..], "RAVEN_BOOK").unwrap().read_to_end(&mut empty).await.unwrap();
+        assert_eq!(empty.len(), HEADER_LEN + 16);
+        assert!(decrypt("RAVEN_BOOK", &empty, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chunk_order_and_truncation() {
+        let plain: Vec<u8> = (0..4096 * 3).map(|i| (i / 4096) as u8).collect();
+        let encrypted = encrypt(&Cipher::new("RAVEN_BOOK").new_file(4096).unwrap(), &plain);
+        let frame = 4096 + 16;
+        let frames: Vec<&[u8]> = encrypted[HEADER_LEN..].chunks(frame).collect();
+
+        let mut swapped = encrypted[..HEADER_LEN].to_vec();
+        swapped.extend([frames[1], frames[0], frames[2]].concat());
+        assert!(decrypt("RAVEN_BOOK", &swapped, 1000).unwrap_err().contains("第 1 个分块"));
+
+        // Cut at a chunk boundary: the new last chunk wasn't sealed as last.
+        let truncated = &encrypted[..HEADER_LEN + 2 * frame];
+        assert!(decrypt("RAVEN_BOOK", truncated, 1000).unwrap_err().contains("最后一个分块"));
+        assert!(decrypt("RAVEN_BOOK", &encrypted[..HEADER_LEN], 1000).unwrap_err().contains("截断"));
+
+        let empty = encrypt(&Cipher::new("RAVEN_BOOK").new_file(4096).unwrap(), &[]);
+        assert_eq!(empty.len(), HEADER_LEN + 16);
+        assert!(decrypt("RAVEN_BOOK", &empty, 1000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_argon2id_header() {
+        let kdf = Kdf::Argon2id(Argon2Params { memory_kib: 64, passes: 1, lanes: 1 });
+        let plain = vec![5u8; 5000];
+        let encrypted = encrypt(&Cipher::new("RAVEN_BOOK").with_kdf(kdf).new_file(4096).unwrap(), &plain);
+
+        // The reader learns the KDF from the header.
+        assert_eq!(Header::parse(&encrypted).unwrap().unwrap().kdf, kdf);
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 1000).unwrap(), plain);
+        assert!(decrypt("WRONG", &encrypted, 1000).is_err());
+
+        let mut costly = encrypted.clone();
+        costly[V1_HEADER_LEN + 1..V1_HEADER_LEN + 5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(decrypt("RAVEN_BOOK", &costly, 1000).unwrap_err().contains("超出范围"));
+        assert!(matches!("argon2id".parse::<Kdf>(), Ok(Kdf::Argon2id(_))));
+
+        // Keys from before the switch to the argon2 crate must not change.
+        let kdf = Kdf::Argon2id(Argon2Params { memory_kib: 64, passes: 2, lanes: 2 });
+        assert_eq!(to_hex(&kdf.derive(b"correct horse", b"0123456789abcdef")), "7a1a864655e1358f74bb5997a24b9a1a9a2bf4e686e1649955be2309f7afe32b");
+        assert_eq!("PBKDF2".parse::<Kdf>(), Ok(Kdf::default()));
+        assert!("scrypt".parse::<Kdf>().is_err());
+    }
+
+    #[test]
+    fn test_stretch_outside_lock() {
+        let kdf = Kdf::Argon2id(Argon2Params { memory_kib: 32 * 1024, passes: 2, lanes: 1 });
+        let cipher = Arc::new(Cipher::new("RAVEN_BOOK").with_kdf(kdf));
+        let worker = {
+            let cipher = Arc::clone(&cipher);
+            std::thread::spawn(move || cipher.new_file(4096).unwrap())
+        };
+        // The key's entry appears before the KDF runs, and the map can be
+        // looked at while it does.
+        loop {
+            let stretched = cipher.stretched.lock().unwrap();
+            if let Some(key) = stretched.values().next() {
+                assert!(key.get().is_none());
+                break;
+            }
+        }
+        let file = worker.join().unwrap();
+        let again = cipher.new_file(4096).unwrap();
+        assert_eq!(cipher.stretched.lock().unwrap().len(), 1);
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypt(&file, b"first"), 1000).unwrap(), b"first");
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypt(&again, b"second"), 1000).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_key_file_cipher() {
+        let plain = vec![9u8; 5000];
+        let key = [7u8; 32];
+        let encrypted = encrypt(&Cipher::from_key(key).new_file(4096).unwrap(), &plain);
+        assert_eq!(Header::parse(&encrypted).unwrap().unwrap().kdf, Kdf::KeyFile);
+
+        let decrypt_with_key = |key: [u8; 32], encrypted: &[u8]| {
+            let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::from_key(key)));
+            let mut output = decryptor.update(encrypted)?;
+            output.extend(decryptor.finish()?);
+            Ok::<_, String>(output)
+        };
+        assert_eq!(decrypt_with_key(key, &encrypted).unwrap(), plain);
+        assert!(decrypt_with_key([8u8; 32], &encrypted).unwrap_err().contains("密钥文件与加密时使用的不一致"));
+        assert!(decrypt("RAVEN_BOOK", &encrypted, 1000).unwrap_err().contains("--key-file"));
+
+        let with_password = encrypt(&Cipher::new("RAVEN_BOOK").new_file(4096).unwrap(), &plain);
+        assert!(decrypt_with_key(key, &with_password).unwrap_err().contains("-p"));
+    }
+
+    #[test]
+    fn test_recipients() {
+        let (alice, bob, eve) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let recipients = vec![parse_recipient(&recipient_of(&alice)).unwrap(), parse_recipient(&recipient_of(&bob)).unwrap()];
+        let cipher = Cipher::for_recipients(recipients).unwrap();
+        let plain: Vec<u8> = (0..4096 * 2 + 3).map(|i| i as u8).collect();
+        let file = cipher.new_file(4096).unwrap();
+        let encrypted = encrypt(&file, &plain);
+        assert_eq!(header_len(&encrypted), HEADER_LEN + 2 * 80);
+        assert_eq!(encrypted.len() as u64, cipher.stored_len(plain.len() as u64));
+
+        let decrypt_with_identity = |identity: [u8; 32], piece_size: usize| {
+            let mut decryptor = StreamDecryptor::new(Arc::new(Cipher::from_identity(identity)));
+            let mut output = Vec::new();
+            for piece in encrypted.chunks(piece_size) {
+                output.extend(decryptor.update(piece)?);
+            }
+            output.extend(decryptor.finish()?);
+            Ok::<_, String>(output)
+        };
+        assert_eq!(decrypt_with_identity(alice, 7).unwrap(), plain);
+        assert_eq!(decrypt_with_identity(bob, 5000).unwrap(), plain);
+        assert!(decrypt_with_identity(eve, 5000).unwrap_err().contains("--identity"));
+        assert!(decrypt("RAVEN_BOOK", &encrypted, 1000).unwrap_err().contains("--identity"));
+
+        // Only the cipher that wrapped the key can resume sealing the file.
+        assert!(cipher.resume_file(&to_hex(file.header()), file.key_check()).is_some());
+        assert!(Cipher::for_recipients(vec![[9u8; 32]]).unwrap().resume_file(&to_hex(file.header()), file.key_check()).is_none());
+        assert!(Cipher::from_identity(alice).new_file(4096).is_err());
+        assert!(Cipher::for_recipients(Vec::new()).is_err());
+        assert!(Cipher::for_recipients(vec![[0u8; 32]]).unwrap().new_file(4096).is_err());
+        assert!(parse_recipient("not hex").is_err());
+
+        // RFC 7748, section 6.1.
+        let identity = parse_recipient("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").unwrap();
+        assert_eq!(recipient_of(&identity), "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+    }
+
+    #[test]
+    fn test_content_checksum() {
+        let sha256 = to_hex(digest(&SHA256, b"content").as_ref());
+        let checksum = Cipher::new("RAVEN_BOOK").content_checksum(&sha256).unwrap();
+        assert!(!checksum.contains(&sha256));
+        assert_eq!(Cipher::new("RAVEN_BOOK").content_checksum(&sha256).unwrap(), checksum);
+        assert_ne!(Cipher::new("WRONG").content_checksum(&sha256).unwrap(), checksum);
+    }
+
+    #[test]
+    fn test_hidden_name() {
+        let cipher = Cipher::new("RAVEN_BOOK");
+        let hidden = cipher.hidden_name("report.pdf").unwrap();
+        assert!(is_hidden_name(&hidden));
+        assert_eq!(Cipher::new("RAVEN_BOOK").hidden_name("report.pdf").unwrap(), hidden);
+        assert_ne!(Cipher::new("WRONG").hidden_name("report.pdf").unwrap(), hidden);
+        assert!(!is_hidden_name("report.pdf"));
+
+        let file = cipher.new_file(4096).unwrap().with_name("report.pdf").unwrap();
+        let encrypted = encrypt(&file, b"content");
+        assert_eq!(header_len(&encrypted), file.header().len());
+        assert_eq!(Cipher::new("RAVEN_BOOK").file_name(&encrypted).unwrap().as_deref(), Some("report.pdf"));
+        assert!(Cipher::new("WRONG").file_name(&encrypted).unwrap_err().contains("密码不正确"));
+        assert_eq!(decrypt("RAVEN_BOOK", &encrypted, 10).unwrap(), b"content");
+
+        let resumed = cipher.resume_file(&to_hex(file.header()), file.key_check()).unwrap();
+        assert_eq!(resumed.header(), file.header());
+        assert!(cipher.new_file(4096).unwrap().with_name("../escape").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_crypt_key_file() {
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        tokio::fs::write("target/test/key_file.bin", [3u8; 32]).await.unwrap();
+        tokio::fs::write("target/test/key_file.txt", b"secret").await.unwrap();
+
+        encrypt_file_with_key_file("target/test/key_file.txt", "target/test/key_file.enc", "target/test/key_file.bin").await.unwrap();
+        decrypt_file_with_key_file("target/test/key_file.enc", "target/test/key_file.dec", "target/test/key_file.bin").await.unwrap();
+        assert_eq!(tokio::fs::read("target/test/key_file.dec").await.unwrap(), b"secret");
+
+        let result = decrypt_file("target/test/key_file.enc", "target/test/key_file.dec", "RAVEN_BOOK").await;
+        assert!(matches!(result, Err(RotError::Crypto(_))));
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_file_wrong_password() {
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        tokio::fs::write("target/test/wrong_password.txt", b"secret").await.unwrap();
+        encrypt_file("target/test/wrong_password.txt", "target/test/wrong_password.enc", "RAVEN_BOOK").await.unwrap();
+
+        let result = decrypt_file("target/test/wrong_password.enc", "target/test/wrong_password.dec", "WRONG").await;
+        assert!(matches!(result, Err(RotError::Crypto(_))));
+        assert!(!std::path::Path::new("target/test/wrong_password.dec").exists());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_file_truncated() {
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        let plain: Vec<u8> = (0..CHUNK_SIZE * 3 + 100).map(|i| i as u8).collect();
+        tokio::fs::write("target/test/truncated.txt", &plain).await.unwrap();
+        encrypt_file("target/test/truncated.txt", "target/test/truncated.enc", "RAVEN_BOOK").await.unwrap();
+        let encrypted = tokio::fs::read("target/test/truncated.enc").await.unwrap();
+
+        let frame = CHUNK_SIZE + 16;
+        for len in [HEADER_LEN + 3 * frame, HEADER_LEN + 2 * frame, HEADER_LEN + frame + 100, HEADER_LEN] {
+            tokio::fs::write("target/test/truncated.enc", &encrypted[..len]).await.unwrap();
+            let result = decrypt_file("target/test/truncated.enc", "target/test/truncated.dec", "RAVEN_BOOK").await;
+            assert!(matches!(result, Err(RotError::Crypto(error)) if error.contains("截断")), "{} bytes", len);
+            assert!(!std::path::Path::new("target/test/truncated.dec").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crypt_dir() {
+        let _ = tokio::fs::remove_dir_all("target/test/crypt_dir").await;
+        DirBuilder::new().recursive(true).create("target/test/crypt_dir/plain/sub").await.unwrap();
+        tokio::fs::write("target/test/crypt_dir/plain/a.txt", b"first").await.unwrap();
+        tokio::fs::write("target/test/crypt_dir/plain/sub/b.txt", b"second").await.unwrap();
+
+        assert_eq!(encrypt_dir("target/test/crypt_dir/plain", "target/test/crypt_dir/enc", "RAVEN_BOOK").await.unwrap(), 2);
+        assert!(std::path::Path::new("target/test/crypt_dir/enc/sub/b.txt.enc").exists());
+        tokio::fs::write("target/test/crypt_dir/enc/notes.txt", b"not encrypted").await.unwrap();
+
+        assert_eq!(decrypt_dir("target/test/crypt_dir/enc", "target/test/crypt_dir/dec", "RAVEN_BOOK").await.unwrap(), 2);
+        assert_eq!(tokio::fs::read("target/test/crypt_dir/dec/a.txt").await.unwrap(), b"first");
+        assert_eq!(tokio::fs::read("target/test/crypt_dir/dec/sub/b.txt").await.unwrap(), b"second");
+        assert!(!std::path::Path::new("target/test/crypt_dir/dec/notes.txt").exists());
+
+        let result = decrypt_dir("target/test/crypt_dir/enc", "target/test/crypt_dir/wrong", "WRONG").await;
+        assert!(matches!(result, Err(RotError::Crypto(error)) if error.contains("a.txt.enc")));
+    }
+
+    #[tokio::test]
+    async fn test_crypt_dir_archive() {
+        let _ = tokio::fs::remove_dir_all("target/test/crypt_archive").await;
+        DirBuilder::new().recursive(true).create("target/test/crypt_archive/plain/sub").await.unwrap();
+        tokio::fs::write("target/test/crypt_archive/plain/a.txt", b"first").await.unwrap();
+        let large: Vec<u8> = (0..CHUNK_SIZE * 3 + 11).map(|i| (i * 13) as u8).collect();
+        tokio::fs::write("target/test/crypt_archive/plain/sub/b.bin", &large).await.unwrap();
+        // Named like the archive used to be while unpacking.
+        tokio::fs::write("target/test/crypt_archive/plain/.rot-archive.tar", b"mine").await.unwrap();
+
+        assert_eq!(encrypt_dir_archive("target/test/crypt_archive/plain", "target/test/crypt_archive/plain.enc", "RAVEN_BOOK").await.unwrap(), 3);
+        assert_eq!(decrypt_dir_archive("target/test/crypt_archive/plain.enc", "target/test/crypt_archive/dec", "RAVEN_BOOK").await.unwrap(), 3);
+        assert_eq!(tokio::fs::read("target/test/crypt_archive/dec/a.txt").await.unwrap(), b"first");
+        assert_eq!(tokio::fs::read("target/test/crypt_archive/dec/sub/b.bin").await.unwrap(), large);
+        assert_eq!(tokio::fs::read("target/test/crypt_archive/dec/.rot-archive.tar").await.unwrap(), b"mine");
+        assert_eq!(std::fs::read_dir("target/test/crypt_archive/dec").unwrap().count(), 3);
+
+        let result = decrypt_dir_archive("target/test/crypt_archive/plain.enc", "target/test/crypt_archive/wrong", "WRONG").await;
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_dir("target/test/crypt_archive/wrong").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_stream_decryptor_legacy() {
+        let plain: Vec<u8> = (0..4096 * 3 + 5).map(|i| (i * 7) as u8).collect();
+        let cipher = Cipher::new("RAVEN_BOOK");
+        let headerless = encrypt(&cipher.open_file(None).unwrap(), &plain);
+        assert_eq!(decrypt("RAVEN_BOOK", &headerless, 1000).unwrap(), plain);
+
+        let v1 = encrypt(&cipher.open_file(Some(&Header { version: 1, chunk_size: 4096, kdf: Kdf::default(), salts: None, recipients: Vec::new(), key_check: None, compressed: false, name: Vec::new() })).unwrap(), &plain);
+        assert_eq!(&v1[..5], b"RVNC\x01");
+        assert_eq!(decrypt("RAVEN_BOOK", &v1, 3).unwrap(), plain);
+        assert!(decrypt("RAVEN_BOOK", &[], 1).unwrap().is_empty());
+
+        let mut unsupported = v1[..V1_HEADER_LEN].to_vec();
+        unsupported[4] = 99;
+        assert!(decrypt("RAVEN_BOOK", &unsupported, 100).is_err());
+    }
 
     #[test]
     fn test_crypt() {
@@ -129,7 +1852,9 @@ mod test {
         let decrypt_data = _decrypt(&secret, &encrypt_data).unwrap();
 
         println!("tag_len = {}", decrypt_data.len() - payload_u8.len());
-        assert_eq!(payload.as_bytes(), &decrypt_data[..payload.len()])
+        assert_eq!(payload.as_bytes(), &decrypt_data[..payload.len()]);
+        assert!(matches!(_decrypt(&[0u8; 32], &encrypt_data), Err(RotError::Crypto(_))));
+        assert!(matches!(_encrypt(b"short", payload_u8), Err(RotError::Crypto(_))));
     }
 
     #[tokio::test]
@@ -146,6 +1871,7 @@ mod test {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(encrypt_input_path).await.unwrap();
         file.write_all("HELLO WORLD!".as_bytes()).await.unwrap();
         file.flush().await.unwrap();
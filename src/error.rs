@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+/// Errors returned by the public API. The messages are the ones rot shows
+/// its users; the variant tells callers where the failure came from.
+#[derive(Debug, Error)]
+pub enum RotError {
+    /// Reading or writing local files failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A request to the object store failed or was rejected.
+    #[error("{0}")]
+    Sdk(String),
+    /// Encryption or decryption failed, usually because of a wrong password
+    /// or damaged data.
+    #[error("{0}")]
+    Crypto(String),
+    /// The configuration or an option is missing or invalid.
+    #[error("{0}")]
+    Config(String),
+    /// A transfer failed part way, for any of the reasons above.
+    #[error("{0}")]
+    Transfer(String),
+    /// A command given to `AliyunOssCommandExecutor` failed.
+    #[error("{0}")]
+    Command(String),
+}
+
+/// Lets the crate's internal code, which reports errors as messages, use
+/// `?` on the public functions.
+impl From<RotError> for String {
+    fn from(error: RotError) -> Self {
+        error.to_string()
+    }
+}
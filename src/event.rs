@@ -0,0 +1,19 @@
+/// What happens during an upload or download, for frontends that draw
+/// their own progress instead of reading rot's output. Events are sent to
+/// the channel given to [`crate::client::AliyunClient::with_events`]; every
+/// transfer ends with `Completed` or `Failed`, the latter without `Started`
+/// when the transfer fails before it begins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferEvent {
+    /// The transfer of `key` began. `total` is the number of bytes to
+    /// send or receive, when known.
+    Started { key: String, total: Option<u64> },
+    /// Part `number` of an upload was stored, `bytes` long.
+    PartUploaded { key: String, number: i32, bytes: u64 },
+    /// `bytes` more were sent or received, `transferred` so far in this
+    /// run. Both count stored bytes, so encrypted transfers include the
+    /// header and tags.
+    BytesTransferred { key: String, bytes: u64, transferred: u64 },
+    Completed { key: String },
+    Failed { key: String, error: String },
+}
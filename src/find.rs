@@ -0,0 +1,112 @@
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use regex::Regex;
+use crate::glob;
+use crate::messages;
+use crate::parser::Arguments;
+use crate::store::ObjectInfo;
+use crate::utils::{parse_duration, parse_size};
+
+/// The filters of `rot find`. An object matches when it passes all of them.
+#[derive(Debug, Default)]
+pub(crate) struct ObjectFilter {
+    /// Wildcard pattern for the last component of the key.
+    name: Option<String>,
+    /// Pattern searched for anywhere in the key.
+    regex: Option<Regex>,
+    larger_than: Option<u64>,
+    smaller_than: Option<u64>,
+    /// Last modified before this time, in seconds since the Unix epoch.
+    modified_before: Option<i64>,
+    modified_after: Option<i64>,
+}
+
+impl ObjectFilter {
+    /// Reads `--name`, `--regex`, `--larger-than`, `--smaller-than`,
+    /// `--older-than` and `--newer-than`, taking ages relative to `now`.
+    pub fn from_args(args: &Arguments, now: i64) -> Result<Self, String> {
+        let time = |name: &str| args.optional.get(name).map(|value| parse_time(value, now)).transpose();
+        let size = |name: &str| args.optional.get(name).map(|value| parse_size(value)).transpose();
+        Ok(Self {
+            name: args.optional.get("name").cloned(),
+            regex: args.optional.get("regex")
+                .map(|pattern| Regex::new(pattern).map_err(messages::regex_invalid))
+                .transpose()?,
+            larger_than: size("larger-than")?,
+            smaller_than: size("smaller-than")?,
+            modified_before: time("older-than")?,
+            modified_after: time("newer-than")?,
+        })
+    }
+
+    /// Objects whose time is unknown never pass a time filter.
+    pub fn matches(&self, object: &ObjectInfo) -> bool {
+        let name = object.key.rsplit('/').next().unwrap_or_default();
+        self.name.as_ref().is_none_or(|pattern| glob::matches(pattern, name))
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(&object.key))
+            && self.larger_than.is_none_or(|size| object.size > size)
+            && self.smaller_than.is_none_or(|size| object.size < size)
+            && self.modified_before.is_none_or(|time| object.last_modified.is_some_and(|modified| modified < time))
+            && self.modified_after.is_none_or(|time| object.last_modified.is_some_and(|modified| modified > time))
+    }
+}
+
+/// Reads a point in time given either as an age such as `30d`, counted back
+/// from `now`, or as a date such as `2024-01-01` (UTC midnight) or
+/// `2024-01-01T08:00:00Z`.
+fn parse_time(text: &str, now: i64) -> Result<i64, String> {
+    if let Ok(age) = parse_duration(text) {
+        return Ok(now - age.as_secs() as i64);
+    }
+    let text = text.trim();
+    let full = if text.contains('T') { text.to_string() } else { format!("{}T00:00:00Z", text) };
+    DateTime::from_str(&full, DateTimeFormat::DateTime)
+        .map(|time| time.secs())
+        .map_err(|_| messages::time_invalid(text))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crate::find::{ObjectFilter, parse_time};
+    use crate::parser::CommandParser;
+    use crate::store::ObjectInfo;
+
+    const NOW: i64 = 1_717_200_000;
+
+    fn object(key: &str, size: u64, last_modified: Option<i64>) -> ObjectInfo {
+        ObjectInfo { key: key.into(), size, last_modified, etag: None, metadata: BTreeMap::new() }
+    }
+
+    fn filter(args: &[&str]) -> ObjectFilter {
+        ObjectFilter::from_args(&CommandParser::from_strings(["rot", "find"].iter().chain(args).copied()), NOW).unwrap()
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1d", NOW).unwrap(), NOW - 86_400);
+        assert_eq!(parse_time("2024-01-01", NOW).unwrap(), 1_704_067_200);
+        assert_eq!(parse_time("2024-01-01T08:00:00Z", NOW).unwrap(), 1_704_096_000);
+        assert!(parse_time("yesterday", NOW).is_err());
+    }
+
+    #[test]
+    fn test_object_filter() {
+        let photo = object("photos/2024/a.jpg", 2_000_000, Some(NOW - 40 * 86_400));
+        let note = object("docs/note.txt", 100, Some(NOW - 86_400));
+
+        assert!(filter(&[]).matches(&photo));
+        assert!(filter(&["--name", "*.jpg"]).matches(&photo));
+        assert!(!filter(&["--name", "*.jpg"]).matches(&note));
+        assert!(filter(&["--regex", "^photos/20[0-9]{2}/"]).matches(&photo));
+        assert!(!filter(&["--regex", "^photos/19"]).matches(&photo));
+        assert!(filter(&["--larger-than", "1M"]).matches(&photo));
+        assert!(!filter(&["--larger-than", "1M"]).matches(&note));
+        assert!(filter(&["--smaller-than", "1K"]).matches(&note));
+        assert!(filter(&["--older-than", "30d"]).matches(&photo));
+        assert!(!filter(&["--older-than", "30d"]).matches(&note));
+        assert!(filter(&["--newer-than", "2024-05-01"]).matches(&note));
+        assert!(!filter(&["--newer-than", "7d", "--name", "*.jpg"]).matches(&photo));
+        assert!(!filter(&["--older-than", "1d"]).matches(&object("a", 1, None)));
+        assert!(ObjectFilter::from_args(&CommandParser::from_strings(["rot", "find", "--regex", "("]), NOW).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::messages;
+use crate::utils::walk_dir;
+
+/// Whether `text` uses any pattern syntax.
+pub(crate) fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Splits `pattern` into the directory to search and the pattern for paths
+/// relative to it, so that `photos/2024/*.jpg` only walks `photos/2024`.
+pub(crate) fn split_base(pattern: &Path) -> (PathBuf, String) {
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !is_pattern(&text) {
+            base.push(component);
+        } else {
+            rest.push(text.to_string());
+        }
+    }
+    (base, rest.join("/"))
+}
+
+/// Matches a `/`-separated relative `path` against `pattern`. `*` and `?`
+/// stay within one path component, a `**` component spans any number of
+/// directories, and `[abc]`, `[a-z]` and `[!abc]` match one character from a
+/// class. As in a shell, names starting with `.` only match a pattern that
+/// spells out the dot.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    match_path(pattern, path, true)
+}
+
+fn match_path(pattern: &str, path: &str, hide_dotfiles: bool) -> bool {
+    let patterns: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    match_components(&patterns, &parts, hide_dotfiles)
+}
+
+fn match_components(patterns: &[&str], parts: &[&str], hide_dotfiles: bool) -> bool {
+    let hidden = |part: &str| hide_dotfiles && part.starts_with('.');
+    match patterns.split_first() {
+        None => parts.is_empty(),
+        Some((&"**", rest)) => (0..=parts.len())
+            .take_while(|&skipped| skipped == 0 || !hidden(parts[skipped - 1]))
+            .any(|skipped| match_components(rest, &parts[skipped..], hide_dotfiles)),
+        Some((pattern, rest)) => match parts.split_first() {
+            Some((part, _)) if hidden(part) && !pattern.starts_with('.') => false,
+            Some((part, remaining)) => {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let part: Vec<char> = part.chars().collect();
+                match_segment(&pattern, &part) && match_components(rest, remaining, hide_dotfiles)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|skipped| match_segment(&pattern[1..], &text[skipped..])),
+        Some('?') => !text.is_empty() && match_segment(&pattern[1..], &text[1..]),
+        Some('[') => match (class_end(pattern), text.first()) {
+            (Some(end), Some(c)) => in_class(&pattern[1..end], *c) && match_segment(&pattern[end + 1..], &text[1..]),
+            (Some(_), None) => false,
+            // An unclosed `[` is an ordinary character.
+            (None, _) => text.first() == Some(&'[') && match_segment(&pattern[1..], &text[1..]),
+        },
+        Some(c) => text.first() == Some(c) && match_segment(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Index of the `]` closing the class that starts at `pattern[0]`. A `]`
+/// right after the opening bracket (or its `!`) belongs to the class.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let first = if pattern.get(1) == Some(&'!') { 2 } else { 1 };
+    (first + 1..pattern.len()).find(|&i| pattern[i] == ']')
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// `--include` / `--exclude` rules, checked in command-line order with the
+/// first match deciding, as rsync does. A pattern without a `/` matches a
+/// name at any depth; one with a `/` matches the whole relative path. Rules
+/// see every directory on the way to a file, so excluding a directory
+/// excludes everything below it, and a file no rule matches is included.
+/// Unlike upload patterns, `*` matches names starting with `.` here.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Filter {
+    /// `(include, pattern)` pairs.
+    rules: Vec<(bool, String)>,
+}
+
+impl Filter {
+    /// Builds the filter from `(option name, pattern)` pairs as given on the
+    /// command line.
+    pub fn new<'a>(options: impl IntoIterator<Item=(&'a str, &'a str)>) -> Self {
+        let rules = options.into_iter()
+            .map(|(name, pattern)| (name == "include", pattern.to_string()))
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether the `/`-separated relative `path` of a file passes the rules.
+    pub fn allows(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        (1..=parts.len()).all(|depth| {
+            let is_dir = depth < parts.len();
+            let candidate = parts[..depth].join("/");
+            self.rules.iter()
+                .find(|(_, pattern)| Self::rule_matches(pattern, &candidate, is_dir))
+                .is_none_or(|(include, _)| *include)
+        })
+    }
+
+    fn rule_matches(pattern: &str, candidate: &str, is_dir: bool) -> bool {
+        let dir_only = pattern.ends_with('/');
+        if dir_only && !is_dir {
+            return false;
+        }
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.contains('/') {
+            match_path(pattern.trim_start_matches('/'), candidate, false)
+        } else {
+            let name = candidate.rsplit('/').next().unwrap_or(candidate);
+            match_path(pattern, name, false)
+        }
+    }
+}
+
+/// The files matching `pattern`, an absolute path that may contain pattern
+/// syntax, together with the directory that was searched.
+pub(crate) async fn expand(pattern: &Path) -> Result<(PathBuf, Vec<PathBuf>), String> {
+    let (base, relative_pattern) = split_base(pattern);
+    let files = walk_dir(&base).await
+        .map_err(|e| messages::cannot_read(base.display(), e))?
+        .into_iter()
+        .filter(|file| matches(&relative_pattern, &relative_path(&base, file)))
+        .collect();
+    Ok((base, files))
+}
+
+/// `file` relative to `root`, `/`-separated as patterns expect.
+pub(crate) fn relative_path(root: &Path, file: &Path) -> String {
+    file.strip_prefix(root).unwrap_or(file)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use crate::glob::{Filter, matches, split_base};
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.jpg", "a.jpg"));
+        assert!(!matches("*.jpg", "sub/a.jpg"));
+        assert!(!matches("*.jpg", "a.jpeg"));
+        assert!(matches("img_??.png", "img_01.png"));
+        assert!(!matches("img_??.png", "img_1.png"));
+        assert!(matches("[ab]*.txt", "b1.txt"));
+        assert!(!matches("[!ab]*.txt", "b1.txt"));
+        assert!(matches("file[0-9].log", "file7.log"));
+        assert!(matches("**/*.jpg", "a.jpg"));
+        assert!(matches("**/*.jpg", "2024/05/a.jpg"));
+        assert!(matches("2024/**", "2024/05/a.jpg"));
+        assert!(!matches("*", ".hidden"));
+        assert!(matches(".*", ".hidden"));
+        assert!(!matches("**/*.jpg", ".cache/a.jpg"));
+        assert!(matches("[x", "[x"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::new([("exclude", "node_modules"), ("exclude", ".git/"), ("include", "keep.tmp"), ("exclude", "*.tmp")]);
+        assert!(filter.allows("src/main.rs"));
+        assert!(!filter.allows("web/node_modules/react/index.js"));
+        assert!(!filter.allows(".git/config"));
+        assert!(filter.allows("notes/.git"));
+        assert!(!filter.allows("cache/a.tmp"));
+        assert!(filter.allows("cache/keep.tmp"));
+
+        let only_jpg = Filter::new([("include", "*/"), ("include", "*.jpg"), ("exclude", "*")]);
+        assert!(only_jpg.allows("2024/a.jpg"));
+        assert!(!only_jpg.allows("2024/a.png"));
+
+        let anchored = Filter::new([("exclude", "/build/*.o")]);
+        assert!(!anchored.allows("build/a.o"));
+        assert!(anchored.allows("src/build/a.o"));
+        assert!(Filter::default().allows("anything"));
+    }
+
+    #[test]
+    fn test_split_base() {
+        assert_eq!(split_base(Path::new("/data/photos/*.jpg")), (PathBuf::from("/data/photos"), "*.jpg".to_string()));
+        assert_eq!(split_base(Path::new("/data/*/raw/*.cr2")), (PathBuf::from("/data"), "*/raw/*.cr2".to_string()));
+    }
+}
@@ -1,151 +1,3177 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::io::{Cursor, ErrorKind, Write};
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-use crate::client::AliyunClient;
+use std::time::{Duration, SystemTime};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::unbounded_channel;
+use crate::{bench, crypt, glob, history, messages, sync, transfer};
+use crate::bench::BenchPlan;
+use crate::client::{AliyunClient, parse_acl, parse_tags, RestoreStatus, ShareMethod, UploadChecks, UploadOutcome};
 use crate::command::CommandHandler;
-use crate::constant::TEMP_FOLDER;
-use crate::crypt::decrypt_file;
 use crate::parser::Arguments;
-use crate::utils::{create_dir, DeleteFolder, ensure_absolute_path, HidePath, sanitize_path_prefix};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::Tier;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::Ed25519KeyPair;
+use crate::crypt::{Cipher, is_hidden_name, Kdf, parse_recipient};
+use crate::keyfile::{load_key_file, load_named_key};
+use crate::constant::{BACKUP_MANIFEST, CHECKSUM_METADATA, DEFAULT_COMPRESSION_LEVEL, INDEX_STALE_AFTER, MAX_PRESIGN_EXPIRY, MAX_TAGS, MIN_PART_SIZE, PART_SIZE, READ_BUFFER_SIZE, SIGNATURE_SUFFIX, TMPSHARE_EXPIRY, TMPSHARE_PREFIX, WATCH_DEBOUNCE};
+use crate::glob::Filter;
+use crate::history::{History, HistoryAction, HistoryEntry};
+use crate::lifecycle::{format_rules, parse_rules, RuleSpec, RulesFile};
+use crate::backup::{BackupJob, SnapshotFile, SnapshotManifest, snapshot_name, snapshot_time};
+use crate::config::ConfigFile;
+use crate::find::ObjectFilter;
+use crate::index::{describe_age, ListingIndex};
+use crate::sync::ChangeTracker;
+use crate::manifest::{BlockHasher, Manifest, ManifestFormat, sha256_file, to_hex};
+use crate::output::{AclRecord, BucketRecord, FolderRecord, ObjectRecord, Reporter, ShareRecord, StatRecord, TagRecord, TransferRecord, TransferStatus, UsageRecord};
+use crate::store::{ObjectInfo, ObjectStore, StoreClient};
+use crate::password::{check_new_password, check_strength, prompt_password};
+use crate::queue::{QueuedUpload, UploadQueue};
+use crate::signing::{load_signing_key, load_verifying_key, public_key_hex, sign_file, verify_file};
+use crate::utils::{confirm, create_dir, ensure_absolute_path, format_size, key_prefix_below, key_to_relative_path, normalize_key, parse_duration, parse_remote, parse_size, relative_key_prefix, sanitize_path_prefix, usage_group, validate_bucket_name, walk_dir};
 
-pub fn download_file(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+pub fn download_file(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
     Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
         let client_clone = Arc::clone(&client);
         Box::pin(async move {
-            if args.positional.len() < 1 {
-                return Err("请输入正确的文件路径！".into());
+            if args.positional.is_empty() {
+                return Err(messages::path_invalid());
             }
 
-            let key = args.positional.get(0).unwrap();
-            let key_path = PathBuf::from(key);
-            let filename = key_path.file_name()
-                .expect("failed to get filename")
-                .to_string_lossy()
-                .to_string();
-            let mut password: Option<String> = None;
+            let key = &normalize_key(args.positional.first().unwrap());
             let mut download_path = if let Some(o) = args.optional.get("o") {
-                let tmp = ensure_absolute_path(o);
-                tmp
+                ensure_absolute_path(o)
             } else {
-                env::current_dir().expect("failed to get file")
+                env::current_dir().map_err(messages::current_dir_missing)?
             };
-
-            if let Some(p) = args.optional.get("p") {
-                password = Some(p.to_string());
+            let store = store_client(&client_clone, &args);
+            let Some(client) = store.s3() else {
+                return get_file(&store, key, download_path, args.has_flag(&["f", "force"])).await;
+            };
+            let mut client = client.clone();
+            let cipher = cipher_from_args(&args, false).await?;
+            if let Some(value) = args.option(&["c", "concurrency"]) {
+                client = client.with_concurrency(value.parse()?);
             }
-
-            let has_password = !password.is_none();
-            if has_password {
-                download_path.push(TEMP_FOLDER);
-                create_dir(&download_path).await;
-                download_path.hide_path().await;
+            if let Some(value) = args.optional.get("write-buffer") {
+                client = client.with_write_buffer_size(parse_size(value)? as usize);
+            }
+            if let Some(value) = args.optional.get("max-memory") {
+                client = client.with_max_memory(parse_size(value)?);
+            }
+            if args.has_flag(&["no-resume"]) {
+                client = client.with_download_resume(false);
+            }
+            if args.has_flag(&["f", "force"]) {
+                client = client.with_overwrite(true);
             }
+            if args.has_flag(&["no-verify"]) {
+                client = client.with_verify(false);
+            }
+            if let Some(version_id) = args.optional.get("version-id") {
+                if args.has_flag(&["r", "recursive"]) {
+                    return Err(messages::version_id_single_object());
+                }
+                client = client.with_version_id(version_id);
+            }
+
+            let verify_key = match args.optional.get("verify-key") {
+                Some(key_path) => Some(load_verifying_key(&ensure_absolute_path(key_path)).await?),
+                None => None,
+            };
+
+            if args.has_flag(&["r", "recursive"]) {
+                let filter = Filter::new(args.options(&["include", "exclude"]));
+                let mut jobs: Vec<(String, PathBuf)> = client.list_all(Some(key.clone())).await?
+                    .into_iter()
+                    .filter_map(|obj| obj.key)
+                    .filter(|obj_key| !obj_key.ends_with('/'))
+                    .filter(|obj_key| verify_key.is_none() || !obj_key.ends_with(SIGNATURE_SUFFIX))
+                    .filter(|obj_key| filter.allows(obj_key.strip_prefix(key.as_str()).unwrap_or(obj_key)))
+                    .map(|obj_key| {
+                        let path = download_path.join(key_to_relative_path(&obj_key, key));
+                        (obj_key, path)
+                    })
+                    .collect();
+                if let Some(cipher) = &cipher {
+                    for (obj_key, path) in jobs.iter_mut() {
+                        if let Some(name) = original_name(&client, obj_key, cipher).await? {
+                            path.set_file_name(name);
+                        }
+                    }
+                }
+                if jobs.is_empty() {
+                    client.reporter().message(messages::no_objects());
+                    return Ok(());
+                }
+
+                if client.is_dry_run() {
+                    preview_downloads(&client, &jobs);
+                    return Ok(());
+                }
+
+                let total = jobs.len();
+                let failed = transfer::download_many(&client, jobs.clone(), cipher, client.concurrency().pool_size()).await;
+                client.reporter().message(messages::downloads_summary(total - failed, failed));
+                if failed > 0 {
+                    return Err(messages::downloads_failed(failed));
+                }
 
+                if let Some(public_key) = &verify_key {
+                    let mut unverified = 0;
+                    for (obj_key, path) in &jobs {
+                        if let Err(e) = verify_download(&client, public_key, obj_key, path).await {
+                            unverified += 1;
+                            client.reporter().warn(e);
+                        }
+                    }
+                    if unverified > 0 {
+                        return Err(messages::signatures_failed(unverified));
+                    }
+                    client.reporter().message(messages::signatures_verified(total));
+                }
+                return Ok(());
+            }
 
+            let mut filename = PathBuf::from(key).file_name()
+                .ok_or_else(|| messages::key_without_file_name(key))?
+                .to_string_lossy()
+                .to_string();
+            if let Some(cipher) = &cipher {
+                if let Some(name) = original_name(&client, key, cipher).await? {
+                    filename = name;
+                }
+            }
             download_path.push(&filename);
-            let _ = client_clone.lock().unwrap()
-                .download_file(key, &download_path).await;
-
-            if has_password {
-                let mut output_path = download_path.clone();
-                output_path.pop();
-                output_path.pop();
-                output_path.push(&filename);
-                decrypt_file(&download_path, &output_path, password.unwrap())
-                    .await
-                    .expect("解密失败！请确认密码是否正确");
-                println!("文件下载成功！所在路径：{}。", output_path.to_string_lossy());
-                download_path.pop();
-                download_path.delete().await;
-            } else {
-                println!("文件下载成功！所在路径：{}。", download_path.to_string_lossy());
+            if client.is_dry_run() {
+                client.head_obj(key).await?;
+                preview_downloads(&client, &[(key.clone(), download_path)]);
+                return Ok(());
             }
+            let bytes = client.download_file_with_key(key, &download_path, cipher).await?;
+            if let Some(public_key) = &verify_key {
+                verify_download(&client, public_key, key, &download_path).await?;
+                client.reporter().message(messages::signature_verified());
+            }
+            let text = messages::downloaded(download_path.to_string_lossy());
+            let mut record = TransferRecord::new("download", TransferStatus::Done, Some(key.clone()), download_path);
+            record.bytes = Some(bytes);
+            client.reporter().record(&record, text);
             Ok(())
         })
     })
 }
 
-pub fn upload_file(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+/// `rot download` on a provider without an S3 API: a single object, saved
+/// as it is stored.
+async fn get_file(client: &StoreClient, key: &str, dir: PathBuf, force: bool) -> Result<(), String> {
+    let name = key.rsplit('/').next().filter(|name| !name.is_empty()).ok_or_else(|| messages::not_an_object_key(key))?;
+    let target = dir.join(key_to_relative_path(name, ""));
+    if target.exists() && !force {
+        return Err(messages::local_file_exists(target.display()));
+    }
+    let reporter = client.reporter();
+    if client.dry_run {
+        reporter.message(messages::planned_store_download(key, target.display()));
+        return Ok(());
+    }
+    let size = client.store.get(key, &target).await?;
+    let text = messages::downloaded_key(key, target.display(), format_size(size));
+    let mut record = TransferRecord::new("download", TransferStatus::Done, Some(key.to_string()), target);
+    record.bytes = Some(size);
+    reporter.record(&record, text);
+    Ok(())
+}
+
+/// The name to download `key` under if it was uploaded with `--hide-name`.
+async fn original_name(client: &AliyunClient, key: &str, cipher: &Cipher) -> Result<Option<String>, String> {
+    let file_name = key.rsplit('/').next().unwrap_or(key);
+    if !is_hidden_name(file_name) {
+        return Ok(None);
+    }
+    client.hidden_file_name(key, cipher).await
+}
+
+/// Prints where downloading `jobs` would write, without transferring anything.
+fn preview_downloads(client: &AliyunClient, jobs: &[(String, PathBuf)]) {
+    let reporter = client.reporter();
+    let mut conflicts = 0;
+    for (key, path) in jobs {
+        let mut record = TransferRecord::new("download", TransferStatus::Planned, Some(key.clone()), path.clone());
+        if !client.overwrites() && path.exists() {
+            conflicts += 1;
+            record.status = TransferStatus::Failed;
+            let error = messages::local_file_exists(path.display());
+            reporter.failure(&record, messages::skipped(key, &error));
+            record.error = Some(error);
+            continue;
+        }
+        reporter.record(&record, messages::planned_download(key, path.display()));
+    }
+    reporter.message(messages::planned_downloads(jobs.len() - conflicts, conflicts));
+}
+
+/// Checks the downloaded `path` against the `<key>.sig` stored next to the
+/// object, removing the file when it does not verify.
+async fn verify_download(client: &AliyunClient, public_key: &[u8], key: &str, path: &Path) -> Result<(), String> {
+    let result = match client.get_bytes(format!("{}{}", key, SIGNATURE_SUFFIX)).await {
+        Ok(signature) => verify_file(public_key, path, &signature).await,
+        Err(_) => Err(messages::signature_missing(key, format!("{}{}", key, SIGNATURE_SUFFIX))),
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    result
+}
+
+/// Applies a `--dry-run` given after the command name; one given before it
+/// has already been set on the client by `rot`.
+fn apply_dry_run(client: AliyunClient, args: &Arguments) -> AliyunClient {
+    if args.has_flag(&["dry-run"]) { client.with_dry_run(true) } else { client }
+}
+
+/// The store commands work on, with `--dry-run` applied as `apply_dry_run`
+/// does, to the S3 client behind it too.
+fn store_client(client: &Arc<Mutex<StoreClient>>, args: &Arguments) -> StoreClient {
+    let mut client = client.lock().unwrap().clone();
+    if args.has_flag(&["dry-run"]) {
+        if let Some(s3) = client.s3() {
+            client.store = Arc::new(s3.clone().with_dry_run(true));
+        }
+        client.dry_run = true;
+    }
+    client
+}
+
+/// The cipher for `-p`, `--key`, `--key-file`, `--recipient` or
+/// `--identity`, `None` when none is given. `--encrypt` or `--decrypt`
+/// without any of them asks for the password on the terminal, so that it
+/// stays out of the shell history. A password for `encrypting` must be strong enough and, when
+/// typed interactively, entered twice. Passwords for new files are
+/// stretched with the KDF chosen by `--kdf` (PBKDF2 by default); existing
+/// files name theirs in the header.
+async fn cipher_from_args(args: &Arguments, encrypting: bool) -> Result<Option<Arc<Cipher>>, String> {
+    cipher_with_named_key(args, args.optional.get("key"), encrypting).await
+}
+
+/// `cipher_from_args` for commands whose `--key` means something else, with
+/// the named key, if any, given separately.
+async fn cipher_with_named_key(args: &Arguments, named_key: Option<&String>, encrypting: bool) -> Result<Option<Arc<Cipher>>, String> {
+    let kdf = match args.optional.get("kdf") {
+        Some(value) => value.parse()?,
+        None => Kdf::default(),
+    };
+    let recipients = args.options(&["recipient"]);
+    let given = [args.optional.contains_key("p"), named_key.is_some(), args.optional.contains_key("key-file"), !recipients.is_empty(), args.optional.contains_key("identity")];
+    if given.iter().filter(|given| **given).count() > 1 {
+        return Err(messages::key_options_conflict());
+    }
+    let prompted = !given.contains(&true) && args.has_flag(&["encrypt", "decrypt"]);
+    let password = match args.optional.get("p") {
+        Some(password) => Some(password.clone()),
+        None if prompted => Some(prompt_password(&match encrypting {
+            true => messages::encryption_password_prompt(),
+            false => messages::decryption_password_prompt(),
+        })?),
+        None => None,
+    };
+    if let (Some(password), true) = (&password, encrypting) {
+        check_new_password(password, args.has_flag(&["allow-weak"]))?;
+    }
+    let cipher = if let Some(password) = password {
+        Cipher::new(password)
+    } else if let Some(name) = named_key {
+        Cipher::from_key(load_named_key(name).await?)
+    } else if let Some(path) = args.optional.get("key-file") {
+        Cipher::from_key(load_key_file(&ensure_absolute_path(path)).await?)
+    } else if let Some(path) = args.optional.get("identity") {
+        Cipher::from_identity(load_key_file(&ensure_absolute_path(path)).await?)
+    } else if !recipients.is_empty() {
+        Cipher::for_recipients(recipients.iter().map(|(_, value)| parse_recipient(value)).collect::<Result<_, _>>()?)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Arc::new(cipher.with_kdf(kdf))))
+}
+
+pub fn upload_file(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
     Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
         let client_clone = Arc::clone(&client);
         Box::pin(async move {
-            if args.positional.len() < 1 {
-                return Err("请输入正确的文件路径！".into());
+            if args.positional.is_empty() {
+                return Err(messages::path_invalid());
             }
 
-            let file_path = args.positional.get(0).unwrap();
+            let file_path = args.positional.first().unwrap();
             let mut upload_dir_path = String::from("");
             let mut expiry_seconds: Option<i64> = None;
-            let mut password: Option<String> = None;
-
-            if let Some(value) = args.optional.get("u") {
-                upload_dir_path.push_str(sanitize_path_prefix(value));
-            }
 
-            if let Some(value) = args.optional.get("p") {
-                password = Some(value.into())
+            if let Some(value) = args.option(&["u", "prefix-path"]) {
+                upload_dir_path.push_str(sanitize_path_prefix(&normalize_key(value)));
             }
 
             if let Some(value) = args.optional.get("t") {
                 expiry_seconds = Some(match value.parse() {
-                    Ok(n) => n,
-                    Err(_) => {
-                        return Err(format!("无法将 `-t` 参数的值 '{}' 解析为整数，请确保你提供的是一个有效的整数值。", value));
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        return Err(messages::invalid_seconds("-t", value));
                     }
                 });
             }
 
-            let resp = client_clone.lock().unwrap().upload_file(upload_dir_path,
-                                                                ensure_absolute_path(file_path),
-                                                                password,
-                                                                expiry_seconds).await.expect("failed to upload file");
+            let store = store_client(&client_clone, &args);
+            let Some(client) = store.s3() else {
+                return put_file(&store, ensure_absolute_path(file_path), &upload_dir_path).await;
+            };
+            let mut client = client.clone();
+            if !client.is_dry_run() {
+                client.ensure_writable(&messages::action_upload())?;
+            }
+            if let Some(value) = args.option(&["c", "concurrency"]) {
+                client = client.with_concurrency(value.parse()?);
+            }
+            if args.has_flag(&["f", "force"]) {
+                client = client.with_overwrite(true);
+            }
+            if args.has_flag(&["no-verify"]) {
+                client = client.with_verify(false);
+            }
+            let tags = parse_tags(args.options(&["tagging"]).into_iter().map(|(_, value)| value))?;
+            client = client.with_upload_tags(tags.clone());
+            let acl = args.optional.get("acl").cloned();
+            client = client.with_upload_acl(acl.as_deref().map(parse_acl).transpose()?);
+            if let Some(value) = args.optional.get("read-buffer") {
+                client = client.with_read_buffer_size(parse_size(value)? as usize);
+            }
+            if let Some(value) = args.optional.get("max-memory") {
+                client = client.with_max_memory(parse_size(value)?);
+            }
+
+            let manifest = if args.has_flag(&["manifest"]) {
+                Some(ManifestFormat::Sha256Sums)
+            } else {
+                args.optional.get("manifest").map(|value| value.parse()).transpose()?
+            };
+
+            let sign_key = args.optional.get("sign-key").map(|value| ensure_absolute_path(value));
+            let signer = match &sign_key {
+                Some(key_path) => {
+                    let signer = load_signing_key(key_path).await?;
+                    client.reporter().message(messages::signing_with(public_key_hex(&signer)));
+                    Some(Arc::new(signer))
+                }
+                None => None,
+            };
+
+            let path = ensure_absolute_path(file_path);
+            let options = UploadOptions {
+                expiry_seconds,
+                recursive: args.has_flag(&["r", "recursive"]),
+                manifest,
+                signer,
+                skip_unchanged: args.has_flag(&["skip-unchanged"]),
+                filter: Filter::new(args.options(&["include", "exclude"])),
+            };
+            let cipher = cipher_from_args(&args, true).await?;
+            let hide_name = args.has_flag(&["hide-name"]);
+            if hide_name && cipher.is_none() {
+                return Err(messages::hide_name_needs_encryption());
+            }
+            if hide_name && (options.signer.is_some() || options.manifest.is_some()) {
+                return Err(messages::hide_name_conflict());
+            }
+            let compress = compression_from_args(&args)?;
+            if compress.is_some() && cipher.is_none() {
+                return Err(messages::compress_needs_encryption());
+            }
+            client = client.with_hidden_names(hide_name).with_compression(compress);
+            let queue = args.has_flag(&["queue"]) && !client.is_dry_run();
+            let job = QueuedUpload {
+                id: 0,
+                path: path.clone(),
+                upload_dir: upload_dir_path.clone(),
+                recursive: options.recursive,
+                encrypted: cipher.is_some(),
+                expiry_seconds,
+                manifest,
+                sign_key,
+                skip_unchanged: options.skip_unchanged,
+                filter: options.filter.clone(),
+                force: client.overwrites(),
+                tags,
+                acl,
+                hide_name,
+                compress,
+                queued_at: 0,
+            };
+
+            if queue && !client.is_reachable().await {
+                return enqueue_upload(job, client.reporter()).await;
+            }
+
+            match upload_path(&client, path, &upload_dir_path, cipher, &options).await {
+                Err(_) if queue && !client.is_reachable().await => enqueue_upload(job, client.reporter()).await,
+                result => result,
+            }
+        })
+    })
+}
+
+/// `rot upload` on a provider without an S3 API: a single file, stored as
+/// it is.
+async fn put_file(client: &StoreClient, file: PathBuf, prefix: &str) -> Result<(), String> {
+    if !file.is_file() {
+        return Err(messages::not_a_file(file.display()));
+    }
+    let name = file.file_name().and_then(|name| name.to_str()).ok_or_else(|| messages::file_name_not_utf8(file.display()))?;
+    let key = sync::join_key(prefix, name);
+    let reporter = client.reporter();
+    if client.dry_run {
+        reporter.message(messages::planned_store_upload(file.display(), &key));
+        return Ok(());
+    }
+    client.ensure_writable(&messages::action_upload())?;
+    client.store.put(&key, &file).await?;
+    let text = messages::uploaded_key(&key);
+    let mut record = TransferRecord::new("upload", TransferStatus::Done, Some(key), file.clone());
+    record.bytes = tokio::fs::metadata(&file).await.ok().map(|metadata| metadata.len());
+    reporter.record(&record, text);
+    Ok(())
+}
+
+/// The zstd level of `--compress [级别]`, `None` without it.
+fn compression_from_args(args: &Arguments) -> Result<Option<i32>, String> {
+    match args.optional.get("compress") {
+        Some(value) => match value.parse() {
+            Ok(level @ 1..=22) => Ok(Some(level)),
+            _ => Err(messages::compression_level_invalid(value)),
+        },
+        None => Ok(args.has_flag(&["compress"]).then_some(DEFAULT_COMPRESSION_LEVEL)),
+    }
+}
+
+struct UploadOptions {
+    expiry_seconds: Option<i64>,
+    recursive: bool,
+    /// Checksum manifest to store next to a recursive upload.
+    manifest: Option<ManifestFormat>,
+    /// Key used to upload a detached `.sig` next to every object.
+    signer: Option<Arc<Ed25519KeyPair>>,
+    skip_unchanged: bool,
+    /// `--include` / `--exclude` rules for recursive and pattern uploads.
+    filter: Filter,
+}
+
+/// Uploads a single file, or every file below a directory when recursive.
+async fn upload_path(client: &AliyunClient,
+                     path: PathBuf,
+                     upload_dir_path: &str,
+                     cipher: Option<Arc<Cipher>>,
+                     options: &UploadOptions) -> Result<(), String> {
+    // A pattern is taken literally when a file of that name exists.
+    if !path.exists() && glob::is_pattern(&path.to_string_lossy()) {
+        let (base, files) = glob::expand(&path).await?;
+        let (files, invalid) = split_unicode_paths(&base, files);
+        if files.is_empty() {
+            return Err(messages::no_matching_files(path.display()));
+        }
+        let jobs = files.into_iter()
+            .map(|file| (key_prefix_below(upload_dir_path, &base, &file), file))
+            .collect();
+        let manifest_key = options.manifest.map(|format| sync::join_key(upload_dir_path, format.filename()));
+        let batch = UploadBatch { root: base, jobs, invalid, manifest_key, table: true };
+        return upload_batch(client, batch, cipher, options).await;
+    }
+
+    if options.recursive {
+        let files = walk_dir(&path).await
+            .map_err(|e| messages::cannot_read(path.display(), e))?;
+        let (files, invalid) = split_unicode_paths(&path, files);
+        if files.is_empty() && invalid.is_empty() {
+            client.reporter().message(messages::no_files_in_dir());
+            return Ok(());
+        }
+        let jobs = files.into_iter()
+            .map(|file| (relative_key_prefix(upload_dir_path, &path, &file), file))
+            .collect();
+        let manifest_key = options.manifest
+            .map(|format| format!("{}{}", relative_key_prefix(upload_dir_path, &path, &path.join(format.filename())), format.filename()));
+        let batch = UploadBatch { root: path, jobs, invalid, manifest_key, table: false };
+        return upload_batch(client, batch, cipher, options).await;
+    }
+
+    if client.is_dry_run() {
+        let jobs = [(upload_dir_path.to_string(), path)];
+        return preview_uploads(client, &jobs, cipher.as_deref(), options, None).await;
+    }
+    let outcome = if options.skip_unchanged {
+        client.upload_if_changed(upload_dir_path, path.clone(), cipher, options.expiry_seconds).await?
+    } else {
+        UploadOutcome::Uploaded(Box::new(client.upload_file_with_key(upload_dir_path, path.clone(), cipher, options.expiry_seconds).await?))
+    };
+    let key = match &outcome {
+        UploadOutcome::Uploaded(resp) => {
+            let size = tokio::fs::metadata(&path).await.ok().map(|metadata| metadata.len());
+            let mut record = TransferRecord::new("upload", TransferStatus::Done, resp.key.clone(), path.clone());
+            record.bytes = size;
+            record.etag = resp.e_tag.clone();
+            record.version_id = resp.version_id.clone();
             if let Some(e_tag) = resp.e_tag() {
-                println!("文件上传成功！ETag: {}。", e_tag);
+                client.reporter().record(&record, messages::uploaded(e_tag));
             } else {
-                eprintln!("文件上传失败！");
+                record.status = TransferStatus::Failed;
+                client.reporter().failure(&record, messages::upload_failed_plain());
+            }
+            history::record(vec![HistoryEntry::new(HistoryAction::Upload, resp.key().unwrap_or_default(), size, resp.version_id.clone())]).await;
+            resp.key()
+        }
+        UploadOutcome::Unchanged(key) => {
+            let record = TransferRecord::new("upload", TransferStatus::Unchanged, Some(key.clone()), path.clone());
+            client.reporter().record(&record, messages::upload_skipped_unchanged(key));
+            Some(key.as_str())
+        }
+    };
+    if let (Some(signer), Some(key)) = (&options.signer, key) {
+        upload_signature(client, signer, &path, key, options.expiry_seconds).await?;
+    }
+    Ok(())
+}
+
+/// Adds the paths a watch notification names to `changed`, or asks for the
+/// whole tree to be scanned again when notifications were lost.
+fn collect_changes(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>, rescan: &mut bool, reporter: Reporter) {
+    match event {
+        Ok(event) if event.need_rescan() => *rescan = true,
+        // Reading a file, as uploading it does, changes nothing.
+        Ok(event) if event.kind.is_access() => {}
+        Ok(event) => changed.extend(event.paths),
+        Err(e) => {
+            reporter.warn(messages::watch_error(e));
+            *rescan = true;
+        }
+    }
+}
+
+/// Separates the files whose path below `root` is valid UTF-8, and so can
+/// become an object key, from those that can't.
+fn split_unicode_paths(root: &Path, files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    files.into_iter().partition(|file| file.strip_prefix(root).unwrap_or(file).to_str().is_some())
+}
+
+/// Files below `root` selected by a recursive or pattern upload.
+struct UploadBatch {
+    root: PathBuf,
+    /// `(key prefix, path)` of every file to upload.
+    jobs: Vec<(String, PathBuf)>,
+    /// Files skipped because their path can't become a key.
+    invalid: Vec<PathBuf>,
+    /// Where the checksum manifest is stored, if one is wanted.
+    manifest_key: Option<String>,
+    /// Print a per-file summary at the end.
+    table: bool,
+}
+
+/// Uploads the batch's files, then their signatures and checksum manifest.
+async fn upload_batch(client: &AliyunClient,
+                      batch: UploadBatch,
+                      cipher: Option<Arc<Cipher>>,
+                      options: &UploadOptions) -> Result<(), String> {
+    let UploadBatch { root, mut jobs, invalid, manifest_key, table } = batch;
+    let root = root.as_path();
+    jobs.retain(|(_, file)| options.filter.allows(&glob::relative_path(root, file)));
+    for file in &invalid {
+        client.reporter().warn(messages::skipped_not_utf8(file.display()));
+    }
+    if client.is_dry_run() {
+        return preview_uploads(client, &jobs, cipher.as_deref(), options, manifest_key.as_deref()).await;
+    }
+    let manifest = match options.manifest {
+        Some(_) => {
+            let files: Vec<PathBuf> = jobs.iter().map(|(_, file)| file.clone()).collect();
+            Some(Manifest::build(root, &files).await?)
+        }
+        None => None,
+    };
+    let total = jobs.len();
+    let report = transfer::upload_many(client, jobs.clone(), cipher, options.expiry_seconds,
+                                       options.skip_unchanged, client.concurrency().pool_size()).await;
+    if table {
+        for line in result_table(root, &report.records) {
+            client.reporter().message(line);
+        }
+    }
+    client.reporter().message(messages::uploads_summary(total - report.failed - report.unchanged, report.unchanged, report.failed));
+    history::record(report.uploaded).await;
+    if report.failed > 0 {
+        return Err(messages::uploads_failed(report.failed));
+    }
+
+    if let Some(signer) = &options.signer {
+        for (prefix, file) in &jobs {
+            let filename = file.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            upload_signature(client, signer, file, &format!("{}{}", prefix, filename), options.expiry_seconds).await?;
+        }
+    }
+
+    if let (Some(manifest), Some(format), Some(key)) = (manifest, options.manifest, manifest_key) {
+        client.put_sidecar(&key, manifest.render(format).into_bytes(), options.expiry_seconds).await?;
+        client.reporter().message(messages::manifest_uploaded(key));
+    }
+    Ok(())
+}
+
+/// Prints what uploading `jobs` would write, without transferring anything.
+async fn preview_uploads(client: &AliyunClient,
+                         jobs: &[(String, PathBuf)],
+                         cipher: Option<&Cipher>,
+                         options: &UploadOptions,
+                         manifest_key: Option<&str>) -> Result<(), String> {
+    let reporter = client.reporter();
+    let mut planned = 0;
+    let mut conflicts = 0;
+    for (prefix, file) in jobs {
+        let (key, changed) = match client.preview_upload(prefix.as_str(), file, cipher, options.skip_unchanged).await {
+            Ok(preview) => preview,
+            Err(e) => {
+                conflicts += 1;
+                let mut record = TransferRecord::new("upload", TransferStatus::Failed, None, file.clone());
+                record.error = Some(e.clone());
+                reporter.failure(&record, messages::skipped(file.display(), e));
+                continue;
+            }
+        };
+        if changed {
+            planned += 1;
+            let mut record = TransferRecord::new("upload", TransferStatus::Planned, Some(key.clone()), file.clone());
+            record.bytes = tokio::fs::metadata(file).await.ok().map(|metadata| metadata.len());
+            reporter.record(&record, messages::planned_upload(file.display(), &key));
+        } else {
+            let record = TransferRecord::new("upload", TransferStatus::Unchanged, Some(key.clone()), file.clone());
+            reporter.record(&record, messages::planned_unchanged(&key));
+        }
+        if options.signer.is_some() {
+            reporter.message(messages::planned_signature(format!("{}{}", key, SIGNATURE_SUFFIX)));
+        }
+    }
+    if let Some(key) = manifest_key {
+        reporter.message(messages::planned_manifest(key));
+    }
+    reporter.message(messages::planned_uploads(planned, jobs.len() - planned - conflicts, conflicts));
+    Ok(())
+}
+
+/// One aligned line per file: outcome, size, path below `root` and the
+/// object written, or the error.
+fn result_table(root: &Path, records: &[TransferRecord]) -> Vec<String> {
+    let mut records: Vec<&TransferRecord> = records.iter().collect();
+    records.sort_by(|a, b| a.path.cmp(&b.path));
+    let rows: Vec<[String; 4]> = records.into_iter()
+        .map(|record| [
+            match record.status {
+                TransferStatus::Done => messages::status_done(),
+                TransferStatus::Planned => messages::status_planned(),
+                TransferStatus::Unchanged => messages::status_unchanged(),
+                TransferStatus::Failed => messages::status_failed(),
+            },
+            record.bytes.map(format_size).unwrap_or_else(|| "-".into()),
+            record.path.strip_prefix(root).unwrap_or(&record.path).display().to_string(),
+            match &record.error {
+                Some(error) => error.clone(),
+                None => record.key.clone().unwrap_or_default(),
+            },
+        ])
+        .collect();
+    let mut widths = [0; 3];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.into_iter()
+        .map(|[status, size, path, target]| {
+            // CJK status labels are two columns wide each.
+            let status_pad = widths[0].saturating_sub(status.chars().count()) * 2;
+            format!("{}{}  {:>w1$}  {:<w2$}  {}", status, " ".repeat(status_pad), size, path, target,
+                    w1 = widths[1], w2 = widths[2])
+        })
+        .collect()
+}
+
+/// Uploads a detached signature of the local file `path` as `<key>.sig`.
+async fn upload_signature(client: &AliyunClient,
+                          signer: &Ed25519KeyPair,
+                          path: &Path,
+                          key: &str,
+                          expiry_seconds: Option<i64>) -> Result<(), String> {
+    let signature = sign_file(signer, path).await?;
+    let signature_key = format!("{}{}", key, SIGNATURE_SUFFIX);
+    client.put_sidecar(&signature_key, signature, expiry_seconds).await?;
+    client.reporter().message(messages::signature_uploaded(signature_key));
+    Ok(())
+}
+
+async fn enqueue_upload(job: QueuedUpload, reporter: Reporter) -> Result<(), String> {
+    let queue_path = UploadQueue::default_path().ok_or_else(messages::config_dir_missing)?;
+    let mut queue = UploadQueue::load(&queue_path).await?;
+    let id = queue.push(job);
+    queue.save(&queue_path).await?;
+    reporter.message(messages::upload_queued(id));
+    Ok(())
+}
+
+pub fn queue(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        let reporter = client.lock().unwrap().reporter();
+        Box::pin(async move {
+            let queue_path = UploadQueue::default_path().ok_or_else(messages::config_dir_missing)?;
+            let mut queue = UploadQueue::load(&queue_path).await?;
+            let action = args.positional.first().map(String::as_str).unwrap_or("ls");
+
+            match action {
+                "ls" => {
+                    if queue.jobs().is_empty() {
+                        reporter.message(messages::queue_empty());
+                    }
+                    for job in queue.jobs() {
+                        reporter.message(format!("{:>4}  {}  {}{} -> {}/{}",
+                                                 job.id,
+                                                 DateTime::from_secs(job.queued_at as i64).fmt(DateTimeFormat::DateTime).unwrap_or_default(),
+                                                 job.path.display(),
+                                                 if job.recursive { " (-r)" } else { "" },
+                                                 job.upload_dir,
+                                                 if job.encrypted { messages::queued_encrypted() } else { String::new() }));
+                    }
+                    Ok(())
+                }
+                "flush" => {
+                    let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+                    if !client.is_reachable().await {
+                        return Err(messages::still_offline());
+                    }
+                    let cipher = cipher_from_args(&args, true).await?;
+                    let mut failed = 0;
+                    for job in queue.jobs().to_vec() {
+                        if job.encrypted && cipher.is_none() {
+                            client.reporter().message(messages::queued_job_needs_key(job.id));
+                            continue;
+                        }
+                        let job_cipher = if job.encrypted { cipher.clone() } else { None };
+                        let client = client.clone()
+                            .with_overwrite(job.force)
+                            .with_upload_tags(job.tags.clone())
+                            .with_upload_acl(job.acl.as_deref().map(parse_acl).transpose()?)
+                            .with_hidden_names(job.hide_name)
+                            .with_compression(job.compress);
+                        let signer = match &job.sign_key {
+                            Some(key_path) => Some(Arc::new(load_signing_key(key_path).await?)),
+                            None => None,
+                        };
+                        let options = UploadOptions {
+                            expiry_seconds: job.expiry_seconds,
+                            recursive: job.recursive,
+                            manifest: job.manifest,
+                            signer,
+                            skip_unchanged: job.skip_unchanged,
+                            filter: job.filter.clone(),
+                        };
+                        match upload_path(&client, job.path.clone(), &job.upload_dir, job_cipher, &options).await {
+                            Ok(()) if client.is_dry_run() => {}
+                            Ok(()) => {
+                                queue.remove(job.id);
+                                queue.save(&queue_path).await?;
+                            }
+                            Err(e) => {
+                                failed += 1;
+                                client.reporter().warn(messages::queued_job_failed(job.id, e));
+                            }
+                        }
+                    }
+                    if failed > 0 { Err(messages::queued_jobs_failed(failed)) } else { Ok(()) }
+                }
+                "drop" => {
+                    let target = args.positional.get(1).ok_or_else(messages::queue_drop_usage)?;
+                    if target == "all" {
+                        queue.clear();
+                    } else {
+                        let id: u64 = target.parse().map_err(|_| messages::queued_job_invalid(target))?;
+                        if !queue.remove(id) {
+                            return Err(messages::queued_job_not_found(id));
+                        }
+                    }
+                    queue.save(&queue_path).await?;
+                    reporter.message(messages::queued_jobs_dropped());
+                    Ok(())
+                }
+                other => Err(messages::unknown_queue_action(other)),
             }
-            Ok(())
         })
     })
 }
 
-pub fn get_obj_names(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+pub fn get_obj_names(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
     Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
         let client_clone = Arc::clone(&client);
         Box::pin(async move {
             let mut prefix_path: Option<String> = None;
             let mut max_keys: Option<i32> = None;
 
-            if let Some(value) = args.optional.get("u") {
-                prefix_path = Some(value.into());
+            if let Some(value) = args.optional.get("u").or(args.positional.first()) {
+                prefix_path = Some(normalize_key(value.trim_start_matches('/')));
             }
 
             if let Some(value) = args.optional.get("m") {
                 max_keys = Some(match value.parse() {
                     Ok(n) => n,
                     Err(_) => {
-                        return Err(format!("无法将 `-m` 参数的值 '{}' 解析为整数，请确保你提供的是一个有效的整数值。", value));
+                        return Err(messages::invalid_integer("-m", value));
                     }
                 });
             }
 
-            let resp = client_clone.lock().unwrap().list_obj(max_keys, prefix_path, None).await;
-            match resp.contents {
-                Some(objs) => {
-                    for (index, obj) in objs.iter().enumerate() {
-                        if let Some(key) = &obj.key {
-                            println!("{}: {:?}", index + 1, key);
+            // With `--all` every page is printed as soon as it arrives, `-m`
+            // then being the page size. Without `-r` only the level below
+            // the prefix is listed, deeper keys being shown as folders.
+            let all = args.has_flag(&["a", "all"]);
+            let recursive = args.has_flag(&["r", "recursive"]);
+            let long = args.has_flag(&["l", "long"]);
+            let human = args.has_flag(&["H", "human-readable"]);
+            let store = client_clone.lock().unwrap().clone();
+            let Some(client) = store.s3().cloned() else {
+                return list_store(&store, prefix_path.as_deref().unwrap_or_default(), long, human).await;
+            };
+            let reporter = client.reporter();
+            if args.has_flag(&["cached"]) {
+                return list_cached(&client, prefix_path.as_deref().unwrap_or_default(), max_keys, all, recursive, long, human).await;
+            }
+            let mut index = 0;
+            let mut token = None;
+            loop {
+                let (resp, links) = client.list_with_links(max_keys, prefix_path.clone(), token, recursive).await?;
+                let folders: Vec<FolderRecord> = resp.common_prefixes().iter()
+                    .filter_map(|common| common.prefix())
+                    .map(|prefix| FolderRecord { prefix: prefix.to_string() })
+                    .collect();
+                let mut records: Vec<ObjectRecord> = resp.contents().iter().map(ObjectRecord::from).collect();
+                if long {
+                    for record in records.iter_mut().filter(|record| links.contains(&record.key)) {
+                        record.symlink_target = Some(client.read_symlink(&record.key).await?);
+                    }
+                }
+                let lines = if long {
+                    long_listing(&folders, &records, human)
+                } else {
+                    folders.iter().map(|folder| &folder.prefix).chain(records.iter().map(|record| &record.key))
+                        .enumerate()
+                        .map(|(i, key)| format!("{}: {:?}", index + i + 1, key))
+                        .collect()
+                };
+                index += folders.len() + records.len();
+                let mut lines = lines.into_iter();
+                for (folder, line) in folders.iter().zip(lines.by_ref()) {
+                    reporter.record(folder, line);
+                }
+                for (record, line) in records.iter().zip(lines) {
+                    reporter.record(record, line);
+                }
+                token = resp.next_continuation_token.filter(|_| resp.is_truncated == Some(true));
+                match &token {
+                    Some(_) if all => continue,
+                    Some(_) => reporter.message(messages::listing_truncated(index)),
+                    None => {}
+                }
+                break;
+            }
+            if index == 0 {
+                reporter.message(messages::no_objects());
+            }
+            Ok(())
+        })
+    })
+}
+
+/// `rot ls` on a provider without an S3 API, which lists every key below
+/// the prefix at once.
+async fn list_store(client: &StoreClient, prefix: &str, long: bool, human: bool) -> Result<(), String> {
+    let reporter = client.reporter();
+    let records: Vec<ObjectRecord> = client.store.list(prefix).await?.iter().map(ObjectRecord::from).collect();
+    let lines = if long {
+        long_listing(&[], &records, human)
+    } else {
+        records.iter().enumerate().map(|(i, record)| format!("{}: {:?}", i + 1, record.key)).collect()
+    };
+    for (record, line) in records.iter().zip(lines) {
+        reporter.record(record, line);
+    }
+    if records.is_empty() {
+        reporter.message(messages::no_objects());
+    }
+    Ok(())
+}
+
+/// `rot ls --cached` answered from the index of `rot index refresh`, with a
+/// note of how old it is. Symlinks aren't recorded there, so `-l` shows no
+/// targets.
+async fn list_cached(client: &AliyunClient,
+                     prefix: &str,
+                     max_keys: Option<i32>,
+                     all: bool,
+                     recursive: bool,
+                     long: bool,
+                     human: bool) -> Result<(), String> {
+    let reporter = client.reporter();
+    let path = ListingIndex::default_path(client.endpoint(), client.bucket()).ok_or_else(messages::cache_dir_missing)?;
+    let index = ListingIndex::load(&path).await?
+        .ok_or_else(|| messages::index_missing(client.bucket()))?;
+    let limit = if all { None } else { Some(max_keys.map_or(1000, |n| n.max(0) as usize)) };
+    let listing = index.list(prefix, recursive, limit);
+    let folders: Vec<FolderRecord> = listing.folders.into_iter().map(|prefix| FolderRecord { prefix }).collect();
+    let records: Vec<ObjectRecord> = listing.objects.into_iter().map(ObjectRecord::from).collect();
+    let lines = if long {
+        long_listing(&folders, &records, human)
+    } else {
+        folders.iter().map(|folder| &folder.prefix).chain(records.iter().map(|record| &record.key))
+            .enumerate()
+            .map(|(i, key)| format!("{}: {:?}", i + 1, key))
+            .collect()
+    };
+    let mut lines = lines.into_iter();
+    for (folder, line) in folders.iter().zip(lines.by_ref()) {
+        reporter.record(folder, line);
+    }
+    for (record, line) in records.iter().zip(lines) {
+        reporter.record(record, line);
+    }
+    if listing.truncated {
+        reporter.message(messages::listing_truncated(folders.len() + records.len()));
+    }
+    if folders.is_empty() && records.is_empty() {
+        reporter.message(messages::no_objects());
+    }
+    let now = DateTime::from(SystemTime::now()).secs();
+    let stale = now - index.refreshed_at > INDEX_STALE_AFTER.as_secs() as i64;
+    let refreshed = DateTime::from_secs(index.refreshed_at).fmt(DateTimeFormat::DateTime).unwrap_or_default();
+    let age = describe_age(index.refreshed_at, now);
+    reporter.message(match stale {
+        true => messages::listed_from_stale_index(refreshed, age),
+        false => messages::listed_from_index(refreshed, age),
+    });
+    Ok(())
+}
+
+/// `rot index refresh` lists the whole bucket and saves it for `rot ls
+/// --cached`; `rot index status` tells how old that index is.
+pub fn index(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = client_clone.lock().unwrap().clone();
+            let reporter = client.reporter();
+            let path = ListingIndex::default_path(client.endpoint(), client.bucket()).ok_or_else(messages::cache_dir_missing)?;
+            let now = DateTime::from(SystemTime::now()).secs();
+            match args.positional.first().map(String::as_str) {
+                Some("refresh") => {
+                    let objects = client.list_all(None).await?;
+                    let index = ListingIndex::new(client.bucket(), &objects, now);
+                    index.save(&path).await?;
+                    reporter.message(messages::index_refreshed(client.bucket(), index.objects.len()));
+                    Ok(())
+                }
+                Some("status") => {
+                    match ListingIndex::load(&path).await? {
+                        Some(index) => {
+                            let refreshed = DateTime::from_secs(index.refreshed_at).fmt(DateTimeFormat::DateTime).unwrap_or_default();
+                            let size: u64 = index.objects.iter().map(|object| object.size).sum();
+                            reporter.message(messages::index_status(&index.bucket, index.objects.len(), format_size(size), refreshed,
+                                                                    describe_age(index.refreshed_at, now)));
                         }
+                        None => reporter.message(messages::no_index(client.bucket())),
                     }
+                    Ok(())
                 }
-                None => {
-                    println!("该路径下不存在文件！");
+                _ => Err(messages::index_usage()),
+            }
+        })
+    })
+}
+
+/// `rot du [前缀] [--depth N]` adds up the objects and bytes under a
+/// prefix, which is taken as a directory. With `--depth` each "directory"
+/// that many levels down gets its own line before the total.
+pub fn du(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let prefix = match args.positional.first().map(|value| normalize_key(value.trim_start_matches('/'))) {
+                Some(prefix) if !prefix.is_empty() && !prefix.ends_with('/') => format!("{}/", prefix),
+                Some(prefix) => prefix,
+                None => String::new(),
+            };
+            let depth = match args.optional.get("depth") {
+                Some(value) => value.parse().map_err(|_| messages::invalid_depth(value))?,
+                None => 0,
+            };
+            let client = client_clone.lock().unwrap().clone();
+
+            let mut total = UsageRecord { prefix: prefix.clone(), objects: 0, size: 0 };
+            let mut groups: BTreeMap<String, UsageRecord> = BTreeMap::new();
+            let mut token = None;
+            loop {
+                let resp = client.list_obj(None, Some(prefix.clone()).filter(|prefix| !prefix.is_empty()), token).await?;
+                for object in resp.contents() {
+                    let size = object.size().unwrap_or(0).max(0) as u64;
+                    total.objects += 1;
+                    total.size += size;
+                    if let Some(group) = usage_group(&prefix, object.key().unwrap_or_default(), depth) {
+                        let record = groups.entry(group.to_string())
+                            .or_insert_with(|| UsageRecord { prefix: group.to_string(), objects: 0, size: 0 });
+                        record.objects += 1;
+                        record.size += size;
+                    }
+                }
+                match resp.next_continuation_token {
+                    Some(next) if resp.is_truncated == Some(true) => token = Some(next),
+                    _ => break,
+                }
+            }
+
+            let reporter = client.reporter();
+            let line = |record: &UsageRecord, label: &str| messages::usage_line(format!("{:>10}", format_size(record.size)), format!("{:>8}", record.objects), label);
+            for record in groups.values() {
+                reporter.record(record, line(record, &record.prefix));
+            }
+            reporter.record(&total, line(&total, &if prefix.is_empty() { messages::whole_bucket() } else { prefix.clone() }));
+            Ok(())
+        })
+    })
+}
+
+/// `rot stat <key>` prints the headers of one object, failing when it
+/// doesn't exist.
+pub fn stat(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let Some(key) = args.positional.first().map(|value| normalize_key(value)) else {
+                return Err(messages::stat_usage());
+            };
+            let client = client_clone.lock().unwrap().clone();
+            let record = StatRecord::new(key.as_str(), &client.head_obj(&key).await?);
+
+            let mut lines = vec![
+                messages::stat_key(&record.key),
+                messages::stat_size(record.content_length, format_size(record.content_length)),
+                messages::stat_type(record.content_type.as_deref().unwrap_or("-")),
+                messages::stat_etag(record.etag.as_deref().unwrap_or("-")),
+                messages::stat_modified(record.last_modified.as_deref().unwrap_or("-")),
+                messages::stat_storage_class(&record.storage_class),
+            ];
+            if !record.metadata.is_empty() {
+                lines.push(messages::stat_metadata());
+                lines.extend(record.metadata.iter().map(|(name, value)| format!("  {}: {}", name, value)));
+            }
+            client.reporter().record(&record, lines.join("\n"));
+            Ok(())
+        })
+    })
+}
+
+/// `rot cat <key> [-p 密码 | --decrypt | --key 名称 | --key-file 密钥文件 | --identity 私钥文件]` writes the object's plaintext to stdout without
+/// touching disk. A reader that goes away early, as `head` does, simply ends
+/// the transfer.
+pub fn cat(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let Some(key) = args.positional.first().map(|value| normalize_key(value)) else {
+                return Err(messages::cat_usage());
+            };
+            let cipher = cipher_from_args(&args, false).await?;
+            let client = client_clone.lock().unwrap().clone();
+
+            let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+            let mut closed = false;
+            let result = client.read_object(&key, cipher, |data| match stdout.write_all(data) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    closed = true;
+                    Err(e.to_string())
+                }
+                Err(e) => Err(messages::cannot_write_stdout(e)),
+            }).await;
+            match result {
+                Err(_) if closed => Ok(()),
+                Err(e) => Err(e),
+                Ok(_) => match stdout.flush() {
+                    Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+                    result => result.map_err(messages::cannot_write_stdout),
+                },
+            }
+        })
+    })
+}
+
+/// `rot append <key> <file|->` adds a file, or stdin, to the end of an
+/// appendable object, creating it on first use. Meant for logs and journals
+/// that grow in small records; objects uploaded any other way can't be
+/// appended to, and appended data is stored unencrypted.
+pub fn append(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let [key, source] = args.positional.as_slice() else {
+                return Err(messages::append_usage());
+            };
+            let key = normalize_key(key);
+            let mut data = Vec::new();
+            if source == "-" {
+                tokio::io::stdin().read_to_end(&mut data).await.map_err(messages::cannot_read_stdin)?;
+            } else {
+                let path = ensure_absolute_path(source);
+                data = tokio::fs::read(&path).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+            }
+            let mut client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            if args.has_flag(&["no-verify"]) {
+                client = client.with_verify(false);
+            }
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_append(&key, format_size(data.len() as u64)));
+                return Ok(());
+            }
+            let appended = data.len() as u64;
+            let length = client.append_obj(&key, data.into()).await?;
+            client.reporter().message(messages::appended(&key, format_size(appended), format_size(length)));
+            Ok(())
+        })
+    })
+}
+
+/// The secret `rot rekey` encrypts with: `--new-password`, `--new-key`,
+/// `--new-key-file` or `--new-recipient`, or else a password asked for on
+/// the terminal.
+/// Passwords are checked and stretched like those of new uploads.
+async fn new_cipher_from_args(args: &Arguments) -> Result<Arc<Cipher>, String> {
+    let kdf = match args.optional.get("kdf") {
+        Some(value) => value.parse()?,
+        None => Kdf::default(),
+    };
+    let recipients = args.options(&["new-recipient"]);
+    let given = [args.optional.contains_key("new-password"), args.optional.contains_key("new-key"), args.optional.contains_key("new-key-file"), !recipients.is_empty()];
+    if given.iter().filter(|given| **given).count() > 1 {
+        return Err(messages::new_key_options_conflict());
+    }
+    let cipher = if let Some(name) = args.optional.get("new-key") {
+        Cipher::from_key(load_named_key(name).await?)
+    } else if let Some(path) = args.optional.get("new-key-file") {
+        Cipher::from_key(load_key_file(&ensure_absolute_path(path)).await?)
+    } else if !recipients.is_empty() {
+        Cipher::for_recipients(recipients.iter().map(|(_, value)| parse_recipient(value)).collect::<Result<_, _>>()?)?
+    } else {
+        let password = match args.optional.get("new-password") {
+            Some(password) => password.clone(),
+            None => prompt_password(&messages::new_password_prompt())?,
+        };
+        check_new_password(&password, args.has_flag(&["allow-weak"]))?;
+        Cipher::new(password)
+    };
+    Ok(Arc::new(cipher.with_kdf(kdf)))
+}
+
+/// `rot rekey <key>...` re-encrypts objects under a new secret without
+/// keeping a local copy, so that a leaked password can be retired.
+pub fn rekey(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            if args.positional.is_empty() {
+                return Err(messages::rekey_usage());
+            }
+            let keys: Vec<String> = args.positional.iter().map(|key| normalize_key(key)).collect();
+            let client = client_clone.lock().unwrap().clone().with_compression(compression_from_args(&args)?);
+            if client.is_dry_run() {
+                for key in &keys {
+                    client.head_obj(key).await?;
+                    client.reporter().message(messages::planned_rekey(key));
+                }
+                return Ok(());
+            }
+            client.ensure_writable(&messages::action_reencrypt())?;
+            let old = match cipher_from_args(&args, false).await? {
+                Some(cipher) => cipher,
+                None => Arc::new(Cipher::new(prompt_password(&messages::old_password_prompt())?)),
+            };
+            let new = new_cipher_from_args(&args).await?;
+
+            for key in keys {
+                let new_key = client.rekey(&key, Arc::clone(&old), &new).await?;
+                if new_key == key {
+                    client.reporter().message(messages::rekeyed(&key));
+                } else {
+                    client.reporter().message(messages::rekeyed_as(&key, &new_key));
+                }
+            }
+            Ok(())
+        })
+    })
+}
+
+/// `ls -l` lines: size, last-modified time, storage class, ETag and key,
+/// aligned in columns across `folders` and `records`, folders first.
+/// Symlinks show their target as `key -> target`.
+fn long_listing(folders: &[FolderRecord], records: &[ObjectRecord], human: bool) -> Vec<String> {
+    let folder_rows = folders.iter()
+        .map(|folder| ["DIR".into(), "-".into(), "-".into(), "-".into(), folder.prefix.clone()]);
+    let rows: Vec<[String; 5]> = folder_rows.chain(records.iter()
+        .map(|record| [
+            if human { format_size(record.size) } else { record.size.to_string() },
+            record.last_modified.clone().unwrap_or_else(|| "-".into()),
+            record.storage_class.clone().unwrap_or_else(|| "-".into()),
+            record.etag.clone().unwrap_or_else(|| "-".into()),
+            match &record.symlink_target {
+                Some(target) => format!("{} -> {}", record.key, target),
+                None => record.key.clone(),
+            },
+        ]))
+        .collect();
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.into_iter()
+        .map(|[size, modified, class, etag, key]| format!("{:>w0$}  {:<w1$}  {:<w2$}  {:<w3$}  {}",
+                                                         size, modified, class, etag, key,
+                                                         w0 = widths[0], w1 = widths[1], w2 = widths[2], w3 = widths[3]))
+        .collect()
+}
+
+/// `rot tag get|set|del <key>` shows or edits an object's tags. `set` adds
+/// or replaces the `键=值` pairs given and keeps the other tags; `del` removes
+/// the named tags, or all of them when none are named.
+pub fn tag(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let (action, key, rest) = match args.positional.as_slice() {
+                [action, key, rest @ ..] => (action.as_str(), normalize_key(key), rest),
+                _ => return Err(messages::tag_usage()),
+            };
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            client.head_obj(&key).await?;
+            let mut tags = client.get_tags(&key).await?;
+
+            match action {
+                "get" => {
+                    let text = if tags.is_empty() {
+                        messages::no_tags(&key)
+                    } else {
+                        tags.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("\n")
+                    };
+                    client.reporter().record(&TagRecord { key, tags }, text);
                     return Ok(());
                 }
+                "set" => {
+                    // The parser takes bare `键=值` arguments for options.
+                    let pairs: Vec<String> = args.ordered.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+                    let changes = parse_tags(rest.iter().chain(&pairs).map(String::as_str))?;
+                    if changes.is_empty() {
+                        return Err(messages::tag_usage());
+                    }
+                    tags.extend(changes);
+                    if tags.len() > MAX_TAGS {
+                        return Err(messages::too_many_tags_after(MAX_TAGS, tags.len()));
+                    }
+                }
+                "del" if rest.is_empty() => tags.clear(),
+                "del" => tags.retain(|name, _| !rest.contains(name)),
+                other => return Err(messages::unknown_tag_action(other)),
+            }
+
+            let summary = tags.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(", ");
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_tags(&key, if tags.is_empty() { messages::none() } else { summary }));
+                return Ok(());
+            }
+            if tags.is_empty() {
+                client.delete_tags(&key).await?;
+                client.reporter().message(messages::tags_deleted(&key));
+            } else {
+                client.put_tags(&key, &tags).await?;
+                client.reporter().message(messages::tags_updated(&key, summary));
             }
             Ok(())
         })
     })
-}
\ No newline at end of file
+}
+
+/// `rot acl get <key>` shows whether an object is private or public, and
+/// `rot acl set <key> private|public-read` changes it.
+pub fn acl(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            match args.positional.as_slice() {
+                [action, key] if action == "get" => {
+                    let key = normalize_key(key);
+                    client.head_obj(&key).await?;
+                    let acl = client.get_acl(&key).await?;
+                    client.reporter().record(&AclRecord { key: key.clone(), acl: acl.into() }, format!("{}  {}", key, acl));
+                    Ok(())
+                }
+                [action, key, value] if action == "set" => {
+                    let key = normalize_key(key);
+                    let acl = parse_acl(value)?;
+                    client.head_obj(&key).await?;
+                    if client.is_dry_run() {
+                        client.reporter().message(messages::planned_acl(&key, acl.as_str()));
+                        return Ok(());
+                    }
+                    client.put_acl(&key, acl.clone()).await?;
+                    client.reporter().message(messages::acl_set(&key, acl.as_str()));
+                    Ok(())
+                }
+                _ => Err(messages::acl_usage()),
+            }
+        })
+    })
+}
+
+/// `rot restore <key> [--days N] [--tier Expedited|Standard|Bulk] [--wait]`
+/// starts thawing an Archive or Cold Archive object. With `--wait` it polls
+/// every `--interval` (default 1m) until the object can be read.
+pub fn restore(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let Some(key) = args.positional.first().map(|value| normalize_key(value)) else {
+                return Err(messages::restore_usage());
+            };
+            let days = match args.option(&["d", "days"]) {
+                Some(value) => match value.parse::<i32>() {
+                    Ok(days) if days > 0 => days,
+                    _ => return Err(messages::invalid_days(value)),
+                },
+                None => 1,
+            };
+            let tier = match args.option(&["tier"]).map(|value| value.to_ascii_lowercase()) {
+                None => None,
+                Some(value) => Some(match value.as_str() {
+                    "expedited" => Tier::Expedited,
+                    "standard" => Tier::Standard,
+                    "bulk" => Tier::Bulk,
+                    _ => return Err(messages::unknown_tier(value)),
+                }),
+            };
+            let interval = args.option(&["interval"]).map(|value| parse_duration(value)).transpose()?
+                .unwrap_or(Duration::from_secs(60));
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+
+            match client.restore_status(&key).await? {
+                RestoreStatus::Restored(until) => {
+                    client.reporter().message(until.map_or_else(|| messages::restored(&key), |date| messages::restored_until(&key, date)));
+                    return Ok(());
+                }
+                RestoreStatus::InProgress => client.reporter().message(messages::restoring(&key)),
+                RestoreStatus::NotRestored if client.is_dry_run() => {
+                    client.reporter().message(messages::planned_restore(&key, days));
+                    return Ok(());
+                }
+                RestoreStatus::NotRestored => {
+                    client.restore_obj(&key, days, tier).await?;
+                    client.reporter().message(messages::restore_requested(&key, days));
+                }
+            }
+
+            if !args.has_flag(&["wait"]) {
+                client.reporter().message(messages::restore_takes_time(&key));
+                return Ok(());
+            }
+            loop {
+                match client.restore_status(&key).await? {
+                    RestoreStatus::Restored(until) => {
+                        client.reporter().message(until.map_or_else(|| messages::restored(&key), |date| messages::restored_until(&key, date)));
+                        return Ok(());
+                    }
+                    RestoreStatus::NotRestored => return Err(messages::restore_lapsed(&key)),
+                    RestoreStatus::InProgress => tokio::time::sleep(interval).await,
+                }
+            }
+        })
+    })
+}
+
+/// `rot bucket ls`, `rot bucket create <name> [--region 地域]` and
+/// `rot bucket rm <name> [--yes]`.
+pub fn bucket(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            match (args.positional.first().map(String::as_str), args.positional.get(1)) {
+                (Some("ls"), _) => {
+                    let reporter = client.reporter();
+                    let buckets = client.list_buckets().await?;
+                    if buckets.is_empty() {
+                        reporter.message(messages::no_buckets());
+                    }
+                    for bucket in &buckets {
+                        let record = BucketRecord::from(bucket);
+                        let text = format!("{}  {}{}", record.name, record.creation_date.as_deref().unwrap_or("-"),
+                                           record.region.as_deref().map(|region| format!("  ({})", region)).unwrap_or_default());
+                        reporter.record(&record, text);
+                    }
+                    Ok(())
+                }
+                (Some("create"), Some(name)) => {
+                    validate_bucket_name(name)?;
+                    let region = args.option(&["region"]).map(String::as_str);
+                    if client.is_dry_run() {
+                        client.reporter().message(messages::planned_bucket_creation(name));
+                        return Ok(());
+                    }
+                    client.create_bucket(name, region).await?;
+                    client.reporter().message(messages::bucket_created(name));
+                    Ok(())
+                }
+                (Some("rm"), Some(name)) => {
+                    if client.is_dry_run() {
+                        client.reporter().message(messages::planned_bucket_deletion(name));
+                        return Ok(());
+                    }
+                    if !args.has_flag(&["y", "yes"]) && !confirm(&messages::confirm_bucket_deletion(name))? {
+                        client.reporter().message(messages::cancelled());
+                        return Ok(());
+                    }
+                    client.delete_bucket(name).await?;
+                    client.reporter().message(messages::bucket_deleted(name));
+                    Ok(())
+                }
+                _ => Err(messages::bucket_usage()),
+            }
+        })
+    })
+}
+
+/// `rot lifecycle get`, `rot lifecycle set <规则文件>` and `rot lifecycle rm
+/// [--yes]` manage the bucket's expiration and transition-to-Archive rules.
+/// `set` replaces all rules with those in the YAML or JSON file, and `get`
+/// prints them in the same format.
+pub fn lifecycle(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let bucket = client.bucket().to_string();
+            match (args.positional.first().map(String::as_str), args.positional.get(1)) {
+                (Some("get"), None) => {
+                    let rules: Vec<RuleSpec> = client.get_lifecycle().await?.iter().map(RuleSpec::from_rule).collect();
+                    if rules.is_empty() {
+                        client.reporter().message(messages::no_lifecycle_rules(&bucket));
+                        return Ok(());
+                    }
+                    let file = RulesFile { rules };
+                    client.reporter().record(&file, format_rules(&file).trim_end());
+                    Ok(())
+                }
+                (Some("set"), Some(path)) => {
+                    let path = ensure_absolute_path(path);
+                    let text = tokio::fs::read_to_string(&path).await.map_err(|e| messages::rules_file_unreadable(path.display(), e))?;
+                    let rules = parse_rules(&text)?;
+                    if rules.is_empty() {
+                        return Err(messages::rules_file_empty());
+                    }
+                    let summary: Vec<String> = rules.iter().map(|rule| format!("  {}", rule.describe())).collect();
+                    if client.is_dry_run() {
+                        client.reporter().message(format!("{}\n{}", messages::planned_lifecycle(&bucket, rules.len()), summary.join("\n")));
+                        return Ok(());
+                    }
+                    let rules = rules.iter().map(RuleSpec::to_rule).collect::<Result<Vec<_>, _>>()?;
+                    client.put_lifecycle(rules).await?;
+                    client.reporter().message(format!("{}\n{}", messages::lifecycle_set(&bucket, summary.len()), summary.join("\n")));
+                    Ok(())
+                }
+                (Some("rm"), None) => {
+                    if client.is_dry_run() {
+                        client.reporter().message(messages::planned_lifecycle_deletion(&bucket));
+                        return Ok(());
+                    }
+                    if !args.has_flag(&["y", "yes"]) && !confirm(&messages::confirm_lifecycle_deletion(&bucket))? {
+                        client.reporter().message(messages::cancelled());
+                        return Ok(());
+                    }
+                    client.delete_lifecycle().await?;
+                    client.reporter().message(messages::lifecycle_deleted(&bucket));
+                    Ok(())
+                }
+                _ => Err(messages::lifecycle_usage()),
+            }
+        })
+    })
+}
+
+/// Streams an HTTP(S) source straight into a multipart upload, optionally
+/// checking it against a SHA-256 digest before the object is completed.
+pub fn upload_url(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let url = match args.positional.first() {
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => url,
+                _ => return Err(messages::url_invalid()),
+            };
+
+            let key = match args.option(&["k", "key"]) {
+                Some(key) => normalize_key(key.trim_start_matches('/')),
+                None => {
+                    let path = url.split(['?', '#']).next().unwrap_or(url)
+                        .split_once("://")
+                        .and_then(|(_, rest)| rest.split_once('/'))
+                        .map(|(_, path)| path)
+                        .unwrap_or_default();
+                    let name = path.rsplit('/')
+                        .find(|segment| !segment.is_empty())
+                        .ok_or_else(messages::url_without_file_name)?;
+                    let prefix = args.optional.get("u").map(|value| sanitize_path_prefix(value).trim_matches('/').to_string());
+                    match prefix {
+                        Some(prefix) if !prefix.is_empty() => normalize_key(&format!("{}/{}", prefix, name)),
+                        _ => normalize_key(name),
+                    }
+                }
+            };
+
+            let expiry_seconds = match args.optional.get("t") {
+                Some(value) => Some(match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(messages::invalid_seconds("-t", value)),
+                }),
+                None => None,
+            };
+            let expected_sha256 = args.optional.get("sha256").cloned();
+            // `--key` names the object here.
+            let cipher = cipher_with_named_key(&args, None, true).await?;
+
+            let mut client = client_clone.lock().unwrap().clone();
+            if let Some(value) = args.option(&["c", "concurrency"]) {
+                client = client.with_concurrency(value.parse()?);
+            }
+            if args.has_flag(&["f", "force"]) {
+                client = client.with_overwrite(true);
+            }
+            if args.has_flag(&["no-verify"]) {
+                client = client.with_verify(false);
+            }
+
+            let (reader, len) = transfer::open_url(url, client.proxy()).await?;
+            client.reporter().message(match len {
+                Some(len) => messages::uploading_url_sized(url, &key, format_size(len)),
+                None => messages::uploading_url(url, &key),
+            });
+            let checks = UploadChecks { checksum: None, expected_sha256 };
+            let resp = client.upload_reader(&key, reader, len, cipher, expiry_seconds, checks).await?;
+            client.reporter().message(messages::uploaded(resp.e_tag().unwrap_or_default()));
+            history::record(vec![HistoryEntry::new(HistoryAction::Upload, key, len, resp.version_id)]).await;
+            Ok(())
+        })
+    })
+}
+
+/// Compares a local file with a remote object by size and SHA-256 of the
+/// plaintext, listing the byte ranges that differ.
+pub fn diff(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let (local_path, key) = match args.positional.as_slice() {
+                [local_path, key, ..] => (ensure_absolute_path(local_path), normalize_key(key)),
+                _ => return Err(messages::diff_usage()),
+            };
+            let block_size = match args.optional.get("block") {
+                Some(value) => parse_size(value)? as usize,
+                None => PART_SIZE,
+            };
+            let cipher = cipher_from_args(&args, false).await?;
+            let client = client_clone.lock().unwrap().clone();
+
+            let mut local_hasher = BlockHasher::new(block_size);
+            let mut file = File::open(&local_path).await.map_err(|e| messages::cannot_open(local_path.display(), e))?;
+            let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+            loop {
+                let n = file.read(&mut buffer).await.map_err(|e| e.to_string())?;
+                if n == 0 {
+                    break;
+                }
+                local_hasher.update(&buffer[..n]);
+            }
+            let local = local_hasher.finish();
+
+            let mut remote_hasher = BlockHasher::new(block_size);
+            client.read_object(&key, cipher, |data| {
+                remote_hasher.update(data);
+                Ok(())
+            }).await?;
+            let remote = remote_hasher.finish();
+
+            if local.sha256 == remote.sha256 && local.len == remote.len {
+                client.reporter().message(messages::diff_same(local_path.display(), &key, format_size(local.len), &local.sha256));
+                return Ok(());
+            }
+
+            client.reporter().message(messages::diff_local(format_size(local.len), &local.sha256));
+            client.reporter().message(messages::diff_remote(format_size(remote.len), &remote.sha256));
+            for (start, end) in local.differing_ranges(&remote) {
+                client.reporter().message(format!("  {}", messages::diff_range(start, end, format_size(end - start))));
+            }
+            Err(messages::diff_differs(local_path.display(), key))
+        })
+    })
+}
+
+/// `rot expire [前缀]` deletes objects whose expiry, set with `upload -t` or
+/// by `rot tmpshare`, has passed, and ends with a summary. With `--dry-run`
+/// the expired objects are only listed. Also registered under its old name,
+/// `expire-sweep`.
+pub fn expire(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let prefix = args.optional.get("u").or(args.positional.first()).map(|value| normalize_key(value));
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let dry_run = client.is_dry_run();
+            if !dry_run {
+                client.ensure_writable(&messages::action_delete())?;
+            }
+            let reporter = client.reporter();
+            let now = DateTime::from(SystemTime::now()).secs();
+
+            let objects = client.list_all(prefix).await?;
+            let mut expired = 0;
+            let mut expired_bytes = 0;
+            let mut failed = 0;
+            let mut deleted = Vec::new();
+            for object in &objects {
+                let Some(key) = object.key.clone() else { continue };
+                let expires_at = match client.expiry_of(&key).await {
+                    Ok(Some(expires_at)) if expires_at <= now => expires_at,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        failed += 1;
+                        client.reporter().warn(messages::cannot_read_tags(&key, e));
+                        continue;
+                    }
+                };
+                let size = object.size.map(|size| size.max(0) as u64);
+                let expired_time = DateTime::from_secs(expires_at).fmt(DateTimeFormat::DateTime).unwrap_or_default();
+                if dry_run {
+                    expired += 1;
+                    expired_bytes += size.unwrap_or(0);
+                    reporter.record(&ObjectRecord::from(object), messages::expired(&key, &expired_time));
+                    continue;
+                }
+                match client.delete_obj(&key).await {
+                    Ok(version_id) => {
+                        expired += 1;
+                        expired_bytes += size.unwrap_or(0);
+                        reporter.record(&ObjectRecord::from(object), messages::expired_deleted(&key, &expired_time));
+                        deleted.push(HistoryEntry::new(HistoryAction::Delete, key, size, version_id));
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        client.reporter().warn(messages::delete_failed_for(&key, e));
+                    }
+                }
+            }
+            history::record(deleted).await;
+
+            reporter.message(match dry_run {
+                true => messages::expired_found(objects.len(), expired, format_size(expired_bytes)),
+                false => messages::expired_summary(objects.len(), expired, format_size(expired_bytes)),
+            });
+            if failed > 0 { Err(messages::objects_failed(failed)) } else { Ok(()) }
+        })
+    })
+}
+
+/// `rot mpu clean [前缀] [--older-than 7d] [--dry-run]` aborts multipart
+/// uploads left behind by failed or abandoned uploads, which otherwise keep
+/// their parts stored and billed.
+pub fn multipart(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            if args.positional.first().map(String::as_str) != Some("clean") {
+                return Err(messages::mpu_usage());
+            }
+            let prefix = args.positional.get(1).map(|value| normalize_key(value));
+            let older_than = args.option(&["older-than"]).map(|value| parse_duration(value)).transpose()?;
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let dry_run = client.is_dry_run();
+            if !dry_run {
+                client.ensure_writable(&messages::action_abort_upload())?;
+            }
+            let cutoff = older_than.map(|age| DateTime::from(SystemTime::now() - age).secs());
+
+            let mut aborted = 0;
+            let mut failed = 0;
+            for upload in client.list_multipart_uploads(prefix).await? {
+                let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) else { continue };
+                let initiated = upload.initiated().map(|time| time.secs()).unwrap_or(0);
+                if cutoff.is_some_and(|cutoff| initiated > cutoff) {
+                    continue;
+                }
+                let initiated_time = DateTime::from_secs(initiated).fmt(DateTimeFormat::DateTime).unwrap_or_default();
+                if dry_run {
+                    client.reporter().message(messages::multipart_upload(key, &initiated_time));
+                    aborted += 1;
+                    continue;
+                }
+                match client.abort_multipart_upload(key, upload_id).await {
+                    Ok(()) => {
+                        client.reporter().message(messages::multipart_upload_aborted(key, &initiated_time));
+                        aborted += 1;
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        client.reporter().warn(messages::multipart_abort_failed(key, e));
+                    }
+                }
+            }
+
+            client.reporter().message(match dry_run {
+                true => messages::multipart_uploads_found(aborted),
+                false => messages::multipart_uploads_aborted(aborted),
+            });
+            if failed > 0 { Err(messages::multipart_aborts_failed(failed)) } else { Ok(()) }
+        })
+    })
+}
+
+/// `rot bench` times uploads and downloads of random objects for each
+/// combination of `-s` sizes, `-k` part sizes and `-c` concurrencies.
+pub fn bench(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let option = |short: &str, long: &str, default: &str| -> String {
+                args.option(&[short, long]).cloned().unwrap_or_else(|| default.into())
+            };
+
+            let plan = BenchPlan {
+                object_sizes: option("s", "sizes", "1M,16M").split(',').map(parse_size).collect::<Result<_, _>>()?,
+                part_sizes: option("k", "part-sizes", "5M").split(',').map(parse_size).collect::<Result<_, _>>()?,
+                concurrencies: option("c", "concurrency", "1,4").split(',').map(str::parse).collect::<Result<_, _>>()?,
+            };
+            if let Some(part_size) = plan.part_sizes.iter().find(|size| **size < MIN_PART_SIZE as u64) {
+                return Err(messages::part_size_too_small(format_size(*part_size), format_size(MIN_PART_SIZE as u64)));
+            }
+            let output_path = ensure_absolute_path(&option("o", "output", "rot-bench.json"));
+
+            let client = client_clone.lock().unwrap().clone();
+            let results = bench::run(&client, &plan).await?;
+            bench::print_table(client.reporter(), &results);
+
+            let json = serde_json::to_string_pretty(&results).map_err(|e| e.to_string())?;
+            tokio::fs::write(&output_path, json).await.map_err(|e| e.to_string())?;
+            client.reporter().message(messages::bench_written(output_path.to_string_lossy()));
+            Ok(())
+        })
+    })
+}
+
+/// Lists recorded mutating operations, newest first. `-n` limits the count.
+pub fn history(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let reporter = client.lock().unwrap().reporter();
+        Box::pin(async move {
+            let limit = match args.optional.get("n") {
+                Some(value) => value.parse::<usize>().map_err(|_| messages::invalid_count("-n", value))?,
+                None => 20,
+            };
+            let history_path = History::default_path().ok_or_else(messages::config_dir_missing)?;
+            let history = History::load(&history_path).await?;
+            if history.entries().is_empty() {
+                reporter.message(messages::history_empty());
+            }
+            for entry in history.entries().iter().rev().take(limit) {
+                reporter.message(format!("{:>5}  {}  {:<6}  {}{}{}{}",
+                                         entry.id,
+                                         DateTime::from_secs(entry.at).fmt(DateTimeFormat::DateTime).unwrap_or_default(),
+                                         entry.action,
+                                         entry.key,
+                                         entry.size.map(|size| messages::history_size(format_size(size))).unwrap_or_default(),
+                                         entry.version_id.as_ref().map(messages::history_version).unwrap_or_default(),
+                                         if entry.undone { messages::history_undone() } else { String::new() }));
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Reverts a recorded deletion or overwrite. This relies on bucket
+/// versioning: a deletion is undone by removing its delete marker and an
+/// upload by restoring the version it replaced.
+pub fn undo(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let history_path = History::default_path().ok_or_else(messages::config_dir_missing)?;
+            let mut history = History::load(&history_path).await?;
+            let id = match args.positional.first() {
+                Some(value) => value.parse::<u64>().map_err(|_| messages::history_id_invalid(value))?,
+                None => history.entries().iter().rev()
+                    .find(|entry| !entry.undone && entry.action != HistoryAction::Copy)
+                    .map(|entry| entry.id)
+                    .ok_or_else(messages::nothing_to_undo)?,
+            };
+            let entry = history.get_mut(id).ok_or_else(|| messages::history_entry_not_found(id))?;
+            if entry.undone {
+                return Err(messages::already_undone(id));
+            }
+            let version_id = entry.version_id.clone()
+                .ok_or_else(|| messages::undo_needs_versioning(id))?;
+
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            match entry.action {
+                HistoryAction::Delete if client.is_dry_run() => {
+                    client.reporter().message(messages::planned_undelete(&entry.key));
+                    return Ok(());
+                }
+                HistoryAction::Delete => {
+                    client.delete_version(&entry.key, &version_id).await?;
+                    client.reporter().message(messages::undeleted(&entry.key));
+                }
+                HistoryAction::Upload => {
+                    let previous = client.previous_version(&entry.key, &version_id).await?
+                        .ok_or_else(|| messages::no_previous_version(&entry.key))?;
+                    if client.is_dry_run() {
+                        client.reporter().message(messages::planned_version_restore(&entry.key, &previous));
+                        return Ok(());
+                    }
+                    client.restore_version(&entry.key, &previous).await?;
+                    client.reporter().message(messages::version_restored(&entry.key, &previous));
+                }
+                HistoryAction::Copy => return Err(messages::copy_not_undoable()),
+            }
+            entry.undone = true;
+            history.save(&history_path).await
+        })
+    })
+}
+
+/// `rot versions <key>` lists the versions and delete markers of an object in
+/// a versioned bucket, newest first. A version can then be fetched with
+/// `rot download <key> --version-id <版本>`.
+pub fn versions(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let Some(key) = args.positional.first().map(|value| normalize_key(value)) else {
+                return Err(messages::versions_usage());
+            };
+            let client = client_clone.lock().unwrap().clone();
+            let reporter = client.reporter();
+            let versions = client.list_versions(&key).await?;
+            if versions.is_empty() {
+                reporter.message(messages::no_versions(&key));
+            }
+            for version in &versions {
+                let size = match version.size {
+                    _ if version.delete_marker => messages::delete_marker(),
+                    Some(size) => format_size(size),
+                    None => "-".to_string(),
+                };
+                let text = format!("{}  {:>10}  {}{}", version.version_id, size,
+                                   version.last_modified.as_deref().unwrap_or("-"), if version.is_latest { messages::latest_version() } else { String::new() });
+                reporter.record(version, text);
+            }
+            Ok(())
+        })
+    })
+}
+
+/// `rot undelete <key>` brings back an object deleted in a versioned bucket
+/// by removing the delete markers above its newest version.
+pub fn undelete(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let Some(key) = args.positional.first().map(|value| normalize_key(value)) else {
+                return Err(messages::undelete_usage());
+            };
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let versions = client.list_versions(&key).await?;
+            let markers: Vec<&str> = versions.iter()
+                .take_while(|version| version.delete_marker)
+                .map(|version| version.version_id.as_str())
+                .collect();
+            let restored = versions.get(markers.len())
+                .ok_or_else(|| messages::no_version_to_restore(&key))?;
+            if markers.is_empty() {
+                return Err(messages::not_deleted(&key, &restored.version_id));
+            }
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_marker_removal(&key, markers.join(&messages::name_separator()), &restored.version_id));
+                return Ok(());
+            }
+            for marker in &markers {
+                client.delete_version(&key, marker).await?;
+            }
+            client.reporter().message(messages::restored_version(&key, &restored.version_id));
+            Ok(())
+        })
+    })
+}
+
+/// Deletes a single object, or with `-r` every object under a prefix, asking
+/// for confirmation unless `--yes` is given.
+pub fn remove(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            // `-r <prefix>` parses as an option, `<prefix> -r` as a flag.
+            let recursive_prefix = args.option(&["r", "recursive"]);
+            let recursive = recursive_prefix.is_some() || args.has_flag(&["r", "recursive"]);
+            let key = normalize_key(args.positional.first().or(recursive_prefix).ok_or_else(messages::rm_usage)?);
+            let client = store_client(&client_clone, &args);
+            if !client.dry_run {
+                client.ensure_writable(&messages::action_delete())?;
+            }
+            let yes = args.has_flag(&["y", "yes"]);
+            if recursive {
+                return remove_prefix(&client, &key, yes).await;
+            }
+
+            let size = client.store.head(&key).await
+                .map_err(|_| messages::object_missing(&key))?
+                .size;
+            if client.dry_run {
+                client.reporter().message(messages::planned_deletion(&key));
+                return Ok(());
+            }
+
+            if !yes && !confirm(&messages::confirm_deletion(&key))? {
+                client.reporter().message(messages::cancelled());
+                return Ok(());
+            }
+            let version_id = match client.s3() {
+                Some(s3) => s3.delete_obj(&key).await?,
+                None => client.store.delete(&key).await.map(|_| None)?,
+            };
+            client.reporter().message(messages::deleted(&key));
+            record_history(&client, vec![HistoryEntry::new(HistoryAction::Delete, key, Some(size), version_id)]).await;
+            Ok(())
+        })
+    })
+}
+
+/// Records changes for `rot undo`, which only works through the S3 API.
+async fn record_history(client: &StoreClient, entries: Vec<HistoryEntry>) {
+    if client.s3().is_some() {
+        history::record(entries).await;
+    }
+}
+
+async fn remove_prefix(client: &StoreClient, prefix: &str, yes: bool) -> Result<(), String> {
+    let objects = client.store.list(prefix).await?;
+    if objects.is_empty() {
+        client.reporter().message(messages::no_objects_under(prefix));
+        return Ok(());
+    }
+    remove_objects(client, objects, |count, size| messages::confirm_prefix_deletion(prefix, count, size), yes).await
+}
+
+/// Deletes `objects` in batches after asking, recording them in the
+/// history. `prompt` words the question from their count and total size.
+async fn remove_objects(client: &StoreClient,
+                        objects: Vec<ObjectInfo>,
+                        prompt: impl FnOnce(usize, String) -> String,
+                        yes: bool) -> Result<(), String> {
+    let sizes: BTreeMap<String, u64> = objects.into_iter().map(|obj| (obj.key, obj.size)).collect();
+    let total: u64 = sizes.values().sum();
+    if client.dry_run {
+        for key in sizes.keys() {
+            client.reporter().message(messages::planned_object_deletion(key));
+        }
+        client.reporter().message(messages::planned_deletions(sizes.len(), format_size(total)));
+        return Ok(());
+    }
+    if !yes && !confirm(&prompt(sizes.len(), format_size(total)))? {
+        client.reporter().message(messages::cancelled());
+        return Ok(());
+    }
+
+    let report = client.delete_many(sizes.keys().cloned().collect()).await?;
+    for (key, reason) in &report.failed {
+        client.reporter().warn(messages::delete_failed_for(key, reason));
+    }
+    client.reporter().message(messages::deletions_summary(report.deleted.len(), report.failed.len()));
+    let entries = report.deleted.into_iter()
+        .map(|(key, version_id)| {
+            let size = sizes.get(&key).copied();
+            HistoryEntry::new(HistoryAction::Delete, key, size, version_id)
+        })
+        .collect();
+    record_history(client, entries).await;
+    if report.failed.is_empty() { Ok(()) } else { Err(messages::deletions_failed(report.failed.len())) }
+}
+
+/// `rot find [前缀] [--name 通配符] [--regex 正则] [--larger-than 大小]
+/// [--smaller-than 大小] [--older-than 时长或日期] [--newer-than 时长或日期]`
+/// prints the keys under a prefix that pass every filter given. With
+/// `--exec rm` the matches are deleted instead, after asking.
+pub fn find(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let prefix = args.positional.first().map(|value| normalize_key(value.trim_start_matches('/'))).unwrap_or_default();
+            let now = DateTime::from(SystemTime::now()).secs();
+            let filter = ObjectFilter::from_args(&args, now)?;
+            let remove = match args.optional.get("exec").map(String::as_str) {
+                Some("rm") => true,
+                Some(other) => return Err(messages::unsupported_exec(other)),
+                None => false,
+            };
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            if remove && !client.is_dry_run() {
+                client.ensure_writable(&messages::action_delete())?;
+            }
+
+            let matches: Vec<ObjectInfo> = client.list(&prefix).await?.into_iter().filter(|object| filter.matches(object)).collect();
+            if matches.is_empty() {
+                client.reporter().message(messages::no_matches());
+                return Ok(());
+            }
+            if remove {
+                let client = StoreClient::from_client(client);
+                return remove_objects(&client, matches, messages::confirm_matches_deletion, args.has_flag(&["y", "yes"])).await;
+            }
+            let reporter = client.reporter();
+            for object in &matches {
+                reporter.record(&ObjectRecord::from(object), &object.key);
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Copies an object server-side. Either side may name another bucket as
+/// `oss://bucket/key`; a destination ending in `/` keeps the source name.
+pub fn copy(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let store = store_client(&client_clone, &args);
+            let Some(client) = store.s3() else {
+                return copy_in_store(&store, &args).await;
+            };
+            let (client, src, dst) = copy_endpoints(client.clone(), &args, "cp")?;
+            let (from, to) = (format!("oss://{}/{}", src.0, src.1), format!("oss://{}/{}", dst.0, dst.1));
+            if client.is_dry_run() {
+                client.clone().with_bucket(&src.0).head_obj(&src.1).await
+                    .map_err(|_| messages::source_missing(&from))?;
+                client.reporter().message(messages::planned_copy(&from, &to));
+                return Ok(());
+            }
+            let version_id = client.copy_from(&src.0, &src.1, &dst.1).await?;
+            client.reporter().message(messages::copied(&from, &to));
+            history::record(vec![HistoryEntry::new(HistoryAction::Copy, to, None, version_id)]).await;
+            Ok(())
+        })
+    })
+}
+
+/// `rot cp <source key> <target key>` on a provider without an S3 API.
+async fn copy_in_store(client: &StoreClient, args: &Arguments) -> Result<(), String> {
+    let [src, dst, ..] = args.positional.as_slice() else {
+        return Err(messages::store_copy_usage());
+    };
+    let (src, dst) = (normalize_key(src), normalize_key(dst));
+    client.store.head(&src).await.map_err(|_| messages::source_missing(&src))?;
+    if client.dry_run {
+        client.reporter().message(messages::planned_copy(&src, &dst));
+        return Ok(());
+    }
+    client.ensure_writable(&messages::action_copy())?;
+    client.store.copy(&src, &dst).await?;
+    client.reporter().message(messages::copied(&src, &dst));
+    Ok(())
+}
+
+/// Moves an object with a server-side copy followed by deleting the source.
+/// An existing destination is only replaced with `--force`.
+pub fn move_obj(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let (client, src, dst) = copy_endpoints(client, &args, "mv")?;
+            if src == dst {
+                return Err(messages::same_source_and_destination());
+            }
+            let (from, to) = (format!("oss://{}/{}", src.0, src.1), format!("oss://{}/{}", dst.0, dst.1));
+            if !client.is_dry_run() {
+                client.ensure_writable(&messages::action_move())?;
+            }
+            if !args.has_flag(&["f", "force"]) && client.head_obj(&dst.1).await.is_ok() {
+                return Err(messages::destination_exists(&to));
+            }
+
+            let source_client = client.clone().with_bucket(&src.0);
+            let size = source_client.head_obj(&src.1).await
+                .map_err(|_| messages::source_missing(&from))?
+                .content_length
+                .map(|size| size.max(0) as u64);
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_move(&from, &to));
+                return Ok(());
+            }
+            let copy_version = client.copy_from(&src.0, &src.1, &dst.1).await?;
+            let delete_version = source_client.delete_obj(&src.1).await
+                .map_err(|e| messages::move_source_not_deleted(&to, e))?;
+            client.reporter().message(messages::moved(&from, &to));
+            history::record(vec![
+                HistoryEntry::new(HistoryAction::Copy, to, size, copy_version),
+                HistoryEntry::new(HistoryAction::Delete, src.1, size, delete_version),
+            ]).await;
+            Ok(())
+        })
+    })
+}
+
+/// `rot ln <target> <link>` makes `link` a symlink to `target` in the same
+/// bucket. Re-pointing a link is a single request, so "latest" pointers
+/// such as `backup/latest.tar.enc` move atomically without copying data.
+/// An existing object that is not a symlink is only replaced with `--force`.
+pub fn link(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let [target, link] = args.positional.as_slice() else {
+                return Err(messages::ln_usage());
+            };
+            let (target, link) = (normalize_key(target), normalize_key(link));
+            if target.is_empty() || link.is_empty() {
+                return Err(messages::empty_key());
+            }
+            if target == link {
+                return Err(messages::link_to_itself());
+            }
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            if !args.has_flag(&["f", "force"]) && client.head_obj(&link).await.is_ok() && client.read_symlink(&link).await.is_err() {
+                return Err(messages::link_target_exists(&link));
+            }
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_symlink(&link, &target));
+                return Ok(());
+            }
+            client.symlink(&target, &link).await?;
+            client.reporter().message(messages::symlink_created(&link, &target));
+            Ok(())
+        })
+    })
+}
+
+/// A `(bucket, key)` pair.
+type RemoteObject = (String, String);
+
+/// Resolves the `<src> <dst>` arguments of `cp` and `mv`, returning a client
+/// pointed at the destination bucket.
+fn copy_endpoints(mut client: AliyunClient, args: &Arguments, command: &str) -> Result<(AliyunClient, RemoteObject, RemoteObject), String> {
+    let (src, dst) = match args.positional.as_slice() {
+        [src, dst, ..] => (parse_remote(src), parse_remote(dst)),
+        _ => return Err(messages::copy_usage(command)),
+    };
+    if let Some(value) = args.option(&["c", "concurrency"]) {
+        client = client.with_concurrency(value.parse()?);
+    }
+    let src_bucket = src.0.unwrap_or_else(|| client.bucket().to_string());
+    if let Some(bucket) = dst.0 {
+        client = client.with_bucket(bucket);
+    }
+    let src_key = src.1;
+    let dst_key = if dst.1.is_empty() || dst.1.ends_with('/') {
+        format!("{}{}", dst.1, src_key.rsplit('/').next().unwrap_or_default())
+    } else {
+        dst.1
+    };
+    if src_key.is_empty() || dst_key.is_empty() {
+        return Err(messages::empty_key());
+    }
+    let dst_bucket = client.bucket().to_string();
+    Ok((client, (src_bucket, src_key), (dst_bucket, dst_key)))
+}
+
+/// Mirrors a local directory to a remote prefix, or with an `oss://` source a
+/// remote prefix to a local directory, transferring only new and changed
+/// files. `--delete` removes what no longer exists on the source side and
+/// `--dry-run` only prints what would be done.
+pub fn sync(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let (source, target) = match args.positional.as_slice() {
+                [source, target, ..] => (source, target),
+                _ => return Err(messages::sync_usage()),
+            };
+            let download = source.starts_with("oss://");
+            let (bucket, prefix) = parse_remote(if download { source } else { target });
+            let store = store_client(&client_clone, &args);
+            let options = SyncOptions {
+                delete: args.has_flag(&["delete"]),
+                dry_run: store.dry_run,
+                by_checksum: args.has_flag(&["checksum"]),
+                filter: Filter::new(args.options(&["include", "exclude"])),
+            };
+            let (client, prefix, cipher) = match store.s3() {
+                Some(client) => {
+                    let mut client = client.clone();
+                    if let Some(value) = args.option(&["c", "concurrency"]) {
+                        client = client.with_concurrency(value.parse()?);
+                    }
+                    // Mirroring replaces whatever changed on the other side.
+                    client = client.with_overwrite(true);
+                    if args.has_flag(&["no-verify"]) {
+                        client = client.with_verify(false);
+                    }
+                    if download && args.has_flag(&["no-resume"]) {
+                        client = client.with_download_resume(false);
+                    }
+                    if let Some(bucket) = bucket {
+                        client = client.with_bucket(bucket);
+                    }
+                    let cipher = cipher_from_args(&args, !download).await?;
+                    (store.clone().with_store(Arc::new(client)), prefix, cipher)
+                }
+                // There are no buckets here, so `oss://a/b` simply means `a/b`.
+                None => {
+                    let prefix = match bucket {
+                        Some(bucket) => sync::join_key(&format!("{}/", bucket), &prefix),
+                        None => prefix,
+                    };
+                    (store.clone(), prefix, None)
+                }
+            };
+
+            if download {
+                return sync_down(&client, &prefix, &ensure_absolute_path(target), cipher, &options).await;
+            }
+
+            let root = ensure_absolute_path(source);
+            if !root.is_dir() {
+                return Err(messages::not_a_dir(root.display()));
+            }
+            let expiry_seconds = match args.optional.get("t") {
+                Some(value) => Some(match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(messages::invalid_seconds("-t", value)),
+                }),
+                None => None,
+            };
+            sync_up(&client, &root, &prefix, cipher, expiry_seconds, &options).await
+        })
+    })
+}
+
+/// `rot watch <本地目录> <远程前缀>` keeps uploading a directory as files in
+/// it are created or modified, until Ctrl+C. It starts with a one-way sync
+/// and then follows the filesystem's change notifications, rescanning only
+/// what they name and uploading files once they have stayed unchanged for
+/// `--debounce` (2s). `--interval` polls at that interval instead, for
+/// network filesystems whose notifications miss changes made elsewhere.
+/// Deletions are not mirrored; `--include`, `--exclude`, `-t` and the
+/// encryption options work as for `sync`.
+pub fn watch(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let [source, target] = args.positional.as_slice() else {
+                return Err(messages::watch_usage());
+            };
+            let interval = args.optional.get("interval").map(|value| parse_duration(value)).transpose()?;
+            let debounce = args.optional.get("debounce").map(|value| parse_duration(value)).transpose()?.unwrap_or(WATCH_DEBOUNCE);
+            if interval.is_some_and(|interval| interval.is_zero()) {
+                return Err(messages::zero_interval());
+            }
+            let root = ensure_absolute_path(source);
+            if !root.is_dir() {
+                return Err(messages::not_a_dir(root.display()));
+            }
+            let mut client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            let (bucket, prefix) = parse_remote(target);
+            if let Some(bucket) = bucket {
+                client = client.with_bucket(bucket);
+            }
+            if let Some(value) = args.option(&["c", "concurrency"]) {
+                client = client.with_concurrency(value.parse()?);
+            }
+            client = client.with_overwrite(true);
+            let options = SyncOptions {
+                delete: false,
+                dry_run: client.is_dry_run(),
+                by_checksum: false,
+                filter: Filter::new(args.options(&["include", "exclude"])),
+            };
+            let cipher = cipher_from_args(&args, true).await?;
+            let expiry_seconds = match args.optional.get("t") {
+                Some(value) => Some(match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(messages::invalid_seconds("-t", value)),
+                }),
+                None => None,
+            };
+            let prefix = sync::join_key(&prefix, "");
+            let states_of = |files: Vec<PathBuf>| async {
+                let mut states = sync::local_states(&root, split_unicode_paths(&root, files).0).await;
+                states.retain(|path, _| options.filter.allows(path));
+                states
+            };
+            let scan = |dir: PathBuf| async move {
+                walk_dir(&dir).await.map_err(|e| messages::cannot_read(dir.display(), e))
+            };
+
+            if !options.dry_run {
+                client.ensure_writable(&messages::action_watch())?;
+            }
+            // Watching starts first, so files changed while the first sync
+            // runs are picked up afterwards. If some failed, every file is
+            // checked again, those already uploaded by checksum only.
+            let (_watcher, mut events) = match options.dry_run {
+                true => (None, unbounded_channel().1),
+                false => sync::watch_dir(&root, interval).map(|(watcher, events)| (Some(watcher), events))?,
+            };
+            let mut states = states_of(scan(root.clone()).await?).await;
+            let synced = sync_up(&StoreClient::from_client(client.clone()), &root, &prefix, cipher.clone(), expiry_seconds, &options).await;
+            if options.dry_run {
+                return synced;
+            }
+            let mut tracker = match synced {
+                Ok(()) => ChangeTracker::new(states.clone()),
+                Err(e) => {
+                    client.reporter().warn(e);
+                    ChangeTracker::default()
+                }
+            };
+            client.reporter().message(messages::watching(root.display()));
+            let mut rescan = true;
+            loop {
+                // Changed files are looked at again once they have had the
+                // debounce period to settle, and failed uploads are retried.
+                let mut changed = BTreeSet::new();
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Some(event) => collect_changes(event, &mut changed, &mut rescan, client.reporter()),
+                        None => break,
+                    },
+                    _ = tokio::time::sleep(debounce), if tracker.has_pending() => {}
+                    _ = client.cancelled() => break,
+                }
+                while let Ok(event) = events.try_recv() {
+                    collect_changes(event, &mut changed, &mut rescan, client.reporter());
+                }
+                // Paths outside the watched directory mean the notifications
+                // name it differently, so it is scanned as a whole.
+                rescan |= changed.iter().any(|path| !path.starts_with(&root) || path == &root);
+                if rescan {
+                    match scan(root.clone()).await {
+                        Ok(files) => {
+                            states = states_of(files).await;
+                            rescan = false;
+                        }
+                        Err(e) => client.reporter().warn(e),
+                    }
+                } else {
+                    let mut files = Vec::new();
+                    for path in &changed {
+                        let Some(relative) = path.strip_prefix(&root).ok().and_then(Path::to_str) else { continue };
+                        let relative = normalize_key(&relative.replace('\\', "/"));
+                        states.retain(|key, _| key != &relative && !key.starts_with(&format!("{}/", relative)));
+                        if path.is_dir() {
+                            files.extend(scan(path.clone()).await.unwrap_or_default());
+                        } else if path.is_file() {
+                            files.push(path.clone());
+                        }
+                    }
+                    states.extend(states_of(files).await);
+                }
+                let now = DateTime::from(SystemTime::now()).secs();
+                let ready = tracker.scan(&states, now, debounce.as_secs() as i64);
+                if ready.is_empty() {
+                    continue;
+                }
+                let jobs = ready.iter()
+                    .map(|path| {
+                        let key = sync::join_key(&prefix, path);
+                        let key_prefix = key[..key.rfind('/').map(|i| i + 1).unwrap_or(0)].to_string();
+                        (key_prefix, root.join(path))
+                    })
+                    .collect();
+                let report = transfer::upload_many(&client, jobs, cipher.clone(), expiry_seconds, true, client.concurrency().pool_size()).await;
+                history::record(report.uploaded).await;
+                for record in report.records.iter().filter(|record| record.status != TransferStatus::Failed) {
+                    let uploaded = ready.iter().find(|path| root.join(path) == record.path);
+                    if let Some(path) = uploaded {
+                        tracker.uploaded(path, states[path]);
+                    }
+                }
+            }
+            client.reporter().message(messages::watch_stopped());
+            Ok(())
+        })
+    })
+}
+
+/// `rot backup run [--job 名称] [--force]` runs the jobs in the `backups`
+/// section of `rot.json`, each one writing a snapshot of its sources below
+/// `<destination>/<时间>/` and then deleting snapshots beyond `keep`. Only
+/// files whose SHA-256 differs from the previous snapshot are uploaded; the
+/// snapshot's manifest points the others at the objects already holding
+/// them. Jobs whose `schedule` says they aren't due yet are skipped unless
+/// `--force`, so the command can be called from cron or a systemd timer as
+/// often as the most frequent job needs. `rot backup ls [--job 名称]` lists
+/// the snapshots of each job.
+pub fn backup(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let action = args.positional.first().map(String::as_str).ok_or_else(messages::backup_usage)?;
+            let file = ConfigFile::load().await?.unwrap_or_default();
+            let jobs: Vec<(&String, &BackupJob)> = match args.optional.get("job") {
+                Some(name) => vec![(name, file.backups().get(name).ok_or_else(|| messages::backup_job_not_found(name))?)],
+                None => file.backups().iter().collect(),
+            };
+            if jobs.is_empty() {
+                return Err(messages::no_backup_jobs());
+            }
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            match action {
+                "run" => {
+                    let mut failed = 0;
+                    for (name, job) in jobs {
+                        if let Err(e) = run_backup(&client, name, job, args.has_flag(&["f", "force"])).await {
+                            failed += 1;
+                            client.reporter().warn(messages::backup_job_failed(name, e));
+                        }
+                    }
+                    if failed > 0 { Err(messages::backup_jobs_failed(failed)) } else { Ok(()) }
+                }
+                "ls" => {
+                    for (name, job) in jobs {
+                        let (client, prefix) = backup_destination(&client, job);
+                        let snapshots = list_snapshots(&client, &prefix).await?;
+                        let cipher = backup_cipher(job).await?;
+                        client.reporter().message(messages::backup_snapshots(name, snapshots.len()));
+                        for snapshot in snapshots {
+                            let manifest = load_manifest(&client, &prefix, &snapshot, cipher.clone()).await?;
+                            client.reporter().message(format!("{}{}/\t{}", prefix, snapshot, messages::snapshot_contents(manifest.files.len(), format_size(manifest.total_size()))));
+                        }
+                    }
+                    Ok(())
+                }
+                _ => Err(messages::backup_usage()),
+            }
+        })
+    })
+}
+
+/// The client for a job's destination bucket and its prefix, ending with `/`.
+fn backup_destination(client: &AliyunClient, job: &BackupJob) -> (AliyunClient, String) {
+    let (bucket, prefix) = parse_remote(&job.destination);
+    let client = match bucket {
+        Some(bucket) => client.clone().with_bucket(bucket),
+        None => client.clone(),
+    };
+    (client, sync::join_key(&normalize_key(&prefix), ""))
+}
+
+/// The snapshots below `prefix`, oldest first. Folders without a manifest,
+/// such as what is left of a deleted snapshot, are ignored.
+async fn list_snapshots(client: &AliyunClient, prefix: &str) -> Result<Vec<String>, String> {
+    let mut snapshots = Vec::new();
+    for folder in client.list_folders(Some(prefix.to_string())).await? {
+        let Some(name) = folder.strip_prefix(prefix).map(|name| name.trim_end_matches('/')) else { continue };
+        if snapshot_time(name).is_none() {
+            continue;
+        }
+        let manifest_key = format!("{}{}", folder, BACKUP_MANIFEST);
+        if client.list_all(Some(manifest_key.clone())).await?.iter().any(|object| object.key() == Some(manifest_key.as_str())) {
+            snapshots.push(name.to_string());
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+async fn load_manifest(client: &AliyunClient,
+                       prefix: &str,
+                       snapshot: &str,
+                       cipher: Option<Arc<Cipher>>) -> Result<SnapshotManifest, String> {
+    let mut data = Vec::new();
+    client.read_object(format!("{}{}/{}", prefix, snapshot, BACKUP_MANIFEST), cipher, |chunk| {
+        data.extend_from_slice(chunk);
+        Ok(())
+    }).await.map_err(|e| messages::snapshot_manifest_unreadable(format!("{}{}", prefix, snapshot), e))?;
+    SnapshotManifest::parse(&data)
+}
+
+/// Stores `manifest`, encrypted like the snapshot's files when they are.
+async fn save_manifest(client: &AliyunClient,
+                       snapshot_prefix: &str,
+                       manifest: &SnapshotManifest,
+                       cipher: Option<Arc<Cipher>>) -> Result<(), String> {
+    let key = format!("{}{}", snapshot_prefix, BACKUP_MANIFEST);
+    let body = manifest.to_json();
+    match cipher {
+        Some(cipher) => {
+            let len = body.len() as u64;
+            client.upload_reader(&key, Cursor::new(body), Some(len), Some(cipher), None, UploadChecks::default()).await?;
+            Ok(())
+        }
+        None => client.put_sidecar(key, body, None).await,
+    }
+}
+
+/// The cipher set in a backup job, `None` when it stores files unencrypted.
+async fn backup_cipher(job: &BackupJob) -> Result<Option<Arc<Cipher>>, String> {
+    let cipher = if let Some(password) = &job.password {
+        check_strength(password, false)?;
+        Cipher::new(password)
+    } else if let Some(name) = &job.key {
+        Cipher::from_key(load_named_key(name).await?)
+    } else if let Some(path) = &job.key_file {
+        Cipher::from_key(load_key_file(&ensure_absolute_path(path)).await?)
+    } else if !job.recipients.is_empty() {
+        Cipher::for_recipients(job.recipients.iter().map(|value| parse_recipient(value)).collect::<Result<_, _>>()?)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Arc::new(cipher)))
+}
+
+async fn run_backup(client: &AliyunClient, name: &str, job: &BackupJob, force: bool) -> Result<(), String> {
+    job.validate(name)?;
+    let (client, prefix) = backup_destination(client, job);
+    let reporter = client.reporter();
+    let mut snapshots = list_snapshots(&client, &prefix).await?;
+    let now = DateTime::from(SystemTime::now()).secs();
+    if !force && !job.is_due(snapshots.last().and_then(|last| snapshot_time(last)), now)? {
+        reporter.message(messages::backup_not_due(name));
+        return Ok(());
+    }
+
+    let cipher = backup_cipher(job).await?;
+    let previous = match snapshots.last() {
+        Some(last) => load_manifest(&client, &prefix, last, cipher.clone()).await?,
+        None => SnapshotManifest::default(),
+    };
+    let snapshot = snapshot_name(now);
+    let snapshot_prefix = format!("{}{}/", prefix, snapshot);
+    let filter = Filter::new(job.exclude.iter().map(|pattern| ("exclude", pattern.as_str())));
+    let mut files = Vec::new();
+    for source in &job.sources {
+        let path = ensure_absolute_path(source);
+        if path.is_file() {
+            files.push((snapshot_prefix.clone(), path));
+        } else if path.is_dir() {
+            let found = walk_dir(&path).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+            let (found, invalid) = split_unicode_paths(&path, found);
+            for file in invalid {
+                client.reporter().warn(messages::skipped_not_utf8(file.display()));
+            }
+            files.extend(found.into_iter()
+                .filter(|file| file.strip_prefix(&path).ok()
+                    .and_then(|relative| relative.to_str())
+                    .is_some_and(|relative| filter.allows(&relative.replace('\\', "/"))))
+                .map(|file| (relative_key_prefix(&snapshot_prefix, &path, &file), file)));
+        } else {
+            return Err(messages::backup_source_missing(path.display()));
+        }
+    }
+
+    // Files whose contents are already stored keep pointing at that object.
+    let mut manifest = SnapshotManifest::default();
+    let mut changed = BTreeMap::new();
+    let mut uploads = Vec::new();
+    for (key_prefix, file) in files {
+        let path = format!("{}{}", key_prefix.strip_prefix(&snapshot_prefix).unwrap_or_default(),
+                           file.file_name().and_then(|name| name.to_str()).unwrap_or_default());
+        let (sha256, size) = sha256_file(&file).await?;
+        let modified = tokio::fs::metadata(&file).await.ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .map(|time| DateTime::from(time).secs());
+        let mut entry = SnapshotFile { size, sha256, modified, object: String::new() };
+        match previous.unchanged(&path, size, &entry.sha256) {
+            Some(object) => {
+                entry.object = object.to_string();
+                manifest.files.insert(path, entry);
+            }
+            None => {
+                changed.insert(file.clone(), (path, entry));
+                uploads.push((key_prefix, file));
+            }
+        }
+    }
+    let unchanged = manifest.files.len();
+
+    let expired = {
+        snapshots.push(snapshot.clone());
+        job.expired(&snapshots).to_vec()
+    };
+    if client.is_dry_run() {
+        reporter.message(messages::planned_backup(name, uploads.len(), &snapshot_prefix, unchanged, expired.len()));
+        return Ok(());
+    }
+    client.ensure_writable(&messages::action_backup())?;
+    let report = transfer::upload_many(&client, uploads, cipher.clone(), None, false, client.concurrency().pool_size()).await;
+    history::record(report.uploaded).await;
+    if report.failed > 0 {
+        // Without a manifest the partial snapshot would never be cleaned up.
+        client.delete_prefix(snapshot_prefix.clone()).await?;
+        return Err(messages::snapshot_abandoned(&snapshot_prefix, report.failed));
+    }
+    for record in report.records.into_iter().filter(|record| record.status == TransferStatus::Done) {
+        if let (Some(object), Some((path, mut entry))) = (record.key, changed.remove(&record.path)) {
+            entry.object = object;
+            manifest.files.insert(path, entry);
+        }
+    }
+    save_manifest(&client, &snapshot_prefix, &manifest, cipher.clone()).await?;
+
+    // Objects of an expired snapshot that a kept one still points at stay.
+    let mut kept: BTreeSet<String> = manifest.objects().into_iter().map(String::from).collect();
+    for snapshot in &snapshots[expired.len()..snapshots.len() - 1] {
+        kept.extend(load_manifest(&client, &prefix, snapshot, cipher.clone()).await?.objects().into_iter().map(String::from));
+    }
+    for old in &expired {
+        let old_prefix = format!("{}{}/", prefix, old);
+        let manifest_key = format!("{}{}", old_prefix, BACKUP_MANIFEST);
+        let mut candidates: BTreeSet<String> = load_manifest(&client, &prefix, old, cipher.clone()).await?
+            .objects().into_iter().map(String::from).collect();
+        candidates.extend(client.list_all(Some(old_prefix.clone())).await?.into_iter().filter_map(|object| object.key));
+        let keys: Vec<String> = candidates.into_iter().filter(|key| key != &manifest_key && !kept.contains(key)).collect();
+        let deleted = client.delete_objects(keys).await?;
+        if let Some((key, e)) = deleted.failed.first() {
+            return Err(messages::snapshot_not_deleted(&old_prefix, key, e));
+        }
+        // The manifest goes last, so that a failed deletion can be retried.
+        client.delete_obj(&manifest_key).await?;
+        reporter.message(messages::snapshot_deleted(&old_prefix, deleted.deleted.len() + 1));
+    }
+    reporter.message(messages::backup_done(name, &snapshot_prefix, manifest.files.len(), manifest.files.len() - unchanged, unchanged));
+    Ok(())
+}
+
+/// `rot restore-backup <快照> <本地目录>` downloads the files of a snapshot
+/// written by `rot backup run` into `<本地目录>`, laid out as they were
+/// backed up, and checks each one against the SHA-256 in the manifest
+/// before giving it back its modification time. An encrypted snapshot needs
+/// the secret of its job, taken from `rot.json` with `--job 名称` or given
+/// with `-p`, `--key` and the like.
+pub fn restore_backup(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let (Some(snapshot), Some(dir)) = (args.positional.first(), args.positional.get(1)) else {
+                return Err(messages::restore_backup_usage());
+            };
+            let (bucket, key) = parse_remote(snapshot);
+            let key = normalize_key(&key);
+            let (prefix, name) = match key.trim_end_matches('/').rsplit_once('/') {
+                Some((prefix, name)) => (format!("{}/", prefix), name.to_string()),
+                None => (String::new(), key.trim_end_matches('/').to_string()),
+            };
+            if snapshot_time(&name).is_none() {
+                return Err(messages::not_a_snapshot(snapshot));
+            }
+            let cipher = match args.optional.get("job") {
+                Some(job) => {
+                    if cipher_from_args(&args, false).await?.is_some() {
+                        return Err(messages::job_keys_conflict());
+                    }
+                    let file = ConfigFile::load().await?.unwrap_or_default();
+                    backup_cipher(file.backups().get(job).ok_or_else(|| messages::backup_job_not_found(job))?).await?
+                }
+                None => cipher_from_args(&args, false).await?,
+            };
+
+            let mut client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            if let Some(bucket) = bucket {
+                client = client.with_bucket(bucket);
+            }
+            if args.has_flag(&["f", "force"]) {
+                client = client.with_overwrite(true);
+            }
+            if let Some(value) = args.option(&["c", "concurrency"]) {
+                client = client.with_concurrency(value.parse()?);
+            }
+            let manifest = load_manifest(&client, &prefix, &name, cipher.clone()).await.map_err(|e| match cipher {
+                Some(_) => e,
+                None => messages::snapshot_may_need_key(e),
+            })?;
+            let dir = ensure_absolute_path(dir);
+            let files: Vec<(PathBuf, &SnapshotFile)> = manifest.files.iter()
+                .map(|(path, file)| (dir.join(key_to_relative_path(path, "")), file))
+                .collect();
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_snapshot_restore(files.len(), format_size(manifest.total_size()), dir.display()));
+                return Ok(());
+            }
+
+            let jobs = files.iter().map(|(path, file)| (file.object.clone(), path.clone())).collect();
+            let mut failed = transfer::download_many(&client, jobs, cipher, client.concurrency().pool_size()).await;
+            for (path, file) in &files {
+                if !path.exists() {
+                    continue;
+                }
+                let (sha256, _) = sha256_file(path).await?;
+                if sha256 != file.sha256 {
+                    failed += 1;
+                    client.reporter().warn(messages::restored_file_corrupt(path.display()));
+                    continue;
+                }
+                if let Some(modified) = file.modified {
+                    set_modified(path, modified).await
+                        .map_err(|e| messages::cannot_set_modified(path.display(), e))?;
+                }
+            }
+            if failed > 0 {
+                return Err(messages::files_not_restored(failed));
+            }
+            client.reporter().message(messages::snapshot_restored(format!("{}{}/", prefix, name), files.len(), dir.display()));
+            Ok(())
+        })
+    })
+}
+
+async fn set_modified(path: &Path, secs: i64) -> std::io::Result<()> {
+    let time = SystemTime::try_from(DateTime::from_secs(secs)).map_err(std::io::Error::other)?;
+    let file = File::options().write(true).open(path).await?.into_std().await;
+    tokio::task::spawn_blocking(move || file.set_modified(time)).await?
+}
+
+struct SyncOptions {
+    delete: bool,
+    dry_run: bool,
+    by_checksum: bool,
+    filter: Filter,
+}
+
+/// Mirrors `root` to `prefix`. Only the S3 API encrypts, checks uploads by
+/// checksum and records them for `rot undo`; other stores get plain copies.
+async fn sync_up(client: &StoreClient,
+                 root: &Path,
+                 prefix: &str,
+                 cipher: Option<Arc<Cipher>>,
+                 expiry_seconds: Option<i64>,
+                 options: &SyncOptions) -> Result<(), String> {
+    if !options.dry_run {
+        client.ensure_writable(&messages::action_sync())?;
+    }
+    let prefix = sync::join_key(prefix, "");
+    let files = walk_dir(root).await.map_err(|e| messages::cannot_read(root.display(), e))?;
+    let mut local = sync::local_states(root, files).await;
+    if let Some(cipher) = &cipher {
+        // Encrypted objects are larger than the files they were made from.
+        for state in local.values_mut() {
+            state.size = cipher.stored_len(state.size);
+        }
+    }
+    let mut remote = sync::remote_states(&prefix, client.store.list(&prefix).await?);
+    // Excluded paths are neither sent nor, with --delete, removed.
+    local.retain(|path, _| options.filter.allows(path));
+    remote.retain(|path, _| options.filter.allows(path));
+    let plan = sync::plan(&local, &remote, options.by_checksum, options.delete);
+
+    if options.dry_run {
+        for path in &plan.transfer {
+            client.reporter().message(messages::planned_sync_upload(sync::join_key(&prefix, path)));
+        }
+        for path in &plan.verify {
+            client.reporter().message(messages::planned_sync_check(sync::join_key(&prefix, path)));
+        }
+        for path in &plan.delete {
+            client.reporter().message(messages::planned_object_deletion(sync::join_key(&prefix, path)));
+        }
+        client.reporter().message(messages::planned_sync_up(plan.transfer.len(), plan.verify.len(), plan.delete.len(), plan.unchanged));
+        return Ok(());
+    }
+
+    let files: Vec<(String, PathBuf)> = plan.transfer.iter().chain(&plan.verify)
+        .map(|path| (sync::join_key(&prefix, path), root.join(path)))
+        .collect();
+    let total = files.len();
+    let (upload_failed, unchanged) = match client.s3() {
+        Some(s3) => {
+            // Uploading through the checksum path records the file's
+            // SHA-256, which later `--checksum` runs compare against.
+            let jobs = files.into_iter()
+                .map(|(key, file)| (key[..key.rfind('/').map(|i| i + 1).unwrap_or(0)].to_string(), file))
+                .collect();
+            let report = transfer::upload_many(s3, jobs, cipher, expiry_seconds, true, s3.concurrency().pool_size()).await;
+            history::record(report.uploaded).await;
+            (report.failed, report.unchanged)
+        }
+        None => {
+            let mut failed = 0;
+            for (key, file) in &files {
+                if let Err(e) = client.store.put(key, file).await {
+                    failed += 1;
+                    client.reporter().warn(messages::upload_failed_for(key, e));
+                }
+            }
+            (failed, 0)
+        }
+    };
+
+    let mut deleted = 0;
+    let mut failed = upload_failed;
+    if !plan.delete.is_empty() {
+        let keys = plan.delete.iter().map(|path| sync::join_key(&prefix, path)).collect();
+        let delete_report = client.delete_many(keys).await?;
+        for (key, reason) in &delete_report.failed {
+            client.reporter().warn(messages::delete_failed_for(key, reason));
+        }
+        deleted = delete_report.deleted.len();
+        failed += delete_report.failed.len();
+        let entries = delete_report.deleted.into_iter()
+            .map(|(key, version_id)| {
+                let size = key.strip_prefix(&prefix).and_then(|path| remote.get(path)).map(|state| state.size);
+                HistoryEntry::new(HistoryAction::Delete, key, size, version_id)
+            })
+            .collect();
+        record_history(client, entries).await;
+    }
+
+    client.reporter().message(messages::synced_up(total - upload_failed - unchanged, deleted, plan.unchanged + unchanged, failed));
+    if failed > 0 { Err(messages::sync_failed(failed)) } else { Ok(()) }
+}
+
+async fn sync_down(client: &StoreClient,
+                   prefix: &str,
+                   root: &Path,
+                   cipher: Option<Arc<Cipher>>,
+                   options: &SyncOptions) -> Result<(), String> {
+    let prefix = sync::join_key(prefix, "");
+    let mut remote = sync::remote_states(&prefix, client.store.list(&prefix).await?);
+    remote.retain(|path, _| {
+        let safe = key_to_relative_path(path, "") == Path::new(path);
+        if !safe {
+            client.reporter().warn(messages::skipped_unsafe_key(sync::join_key(&prefix, path)));
+        }
+        safe
+    });
+
+    let files = if root.is_dir() {
+        walk_dir(root).await.map_err(|e| messages::cannot_read(root.display(), e))?
+    } else {
+        Vec::new()
+    };
+    let mut local = sync::local_states(root, files).await;
+    if let Some(cipher) = &cipher {
+        // Compare local plaintext with the size the object would have when encrypted.
+        for state in local.values_mut() {
+            state.size = cipher.stored_len(state.size);
+        }
+    }
+    remote.retain(|path, _| options.filter.allows(path));
+    local.retain(|path, _| options.filter.allows(path));
+    let mut plan = sync::plan(&remote, &local, options.by_checksum, options.delete);
+
+    // Same-sized files are only fetched when the checksum recorded at upload
+    // time differs from the local file's; without one they are fetched.
+    for path in std::mem::take(&mut plan.verify) {
+        let key = sync::join_key(&prefix, path.as_str());
+        let stored = client.store.head(&key).await.ok()
+            .and_then(|mut head| head.metadata.remove(CHECKSUM_METADATA));
+        let (sha256, _) = sha256_file(&root.join(&path)).await?;
+        let expected = if cipher.is_some() { format!("{}:encrypted", sha256) } else { sha256 };
+        if stored == Some(expected) {
+            plan.unchanged += 1;
+        } else {
+            plan.transfer.push(path);
+        }
+    }
+
+    if options.dry_run {
+        for path in &plan.transfer {
+            client.reporter().message(messages::planned_sync_download(sync::join_key(&prefix, path)));
+        }
+        for path in &plan.delete {
+            client.reporter().message(messages::planned_local_deletion(root.join(path).display()));
+        }
+        client.reporter().message(messages::planned_sync_down(plan.transfer.len(), plan.delete.len(), plan.unchanged));
+        return Ok(());
+    }
+
+    create_dir(root).await;
+    let jobs: Vec<(String, PathBuf)> = plan.transfer.iter()
+        .map(|path| (sync::join_key(&prefix, path), root.join(key_to_relative_path(path, ""))))
+        .collect();
+    let download_failed = match client.s3() {
+        Some(s3) => transfer::download_many(s3, jobs, cipher, s3.concurrency().pool_size()).await,
+        None => {
+            let mut failed = 0;
+            for (key, file) in &jobs {
+                if let Err(e) = client.store.get(key, file).await {
+                    failed += 1;
+                    client.reporter().warn(messages::download_failed_for(key, e));
+                }
+            }
+            failed
+        }
+    };
+    let mut failed = download_failed;
+
+    let mut deleted = 0;
+    for path in &plan.delete {
+        let file = root.join(path);
+        match tokio::fs::remove_file(&file).await {
+            Ok(()) => deleted += 1,
+            Err(e) => {
+                failed += 1;
+                client.reporter().warn(messages::delete_failed_for(file.display(), e));
+            }
+        }
+    }
+
+    client.reporter().message(messages::synced_down(plan.transfer.len() - download_failed, deleted, plan.unchanged, failed));
+    if failed > 0 { Err(messages::sync_failed(failed)) } else { Ok(()) }
+}
+
+/// `rot mount <挂载点> [前缀]` shows a prefix, or `oss://bucket/prefix`, as a
+/// read-only local directory until Ctrl+C. Objects are only read as far as
+/// programs read them; with `-p`, `--key`, `--key-file` or `--identity`,
+/// `.enc` objects show decrypted and without the suffix.
+#[cfg(all(feature = "mount", target_os = "linux"))]
+pub fn mount(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let (mountpoint, remote) = match args.positional.as_slice() {
+                [mountpoint] => (mountpoint, None),
+                [mountpoint, remote] => (mountpoint, Some(remote)),
+                _ => return Err(messages::mount_usage()),
+            };
+            let mountpoint = ensure_absolute_path(mountpoint);
+            if !mountpoint.is_dir() {
+                return Err(messages::not_a_dir(mountpoint.display()));
+            }
+            let mut client = client_clone.lock().unwrap().clone();
+            let (bucket, prefix) = remote.map(|remote| parse_remote(remote)).unwrap_or_default();
+            if let Some(bucket) = bucket {
+                client = client.with_bucket(bucket);
+            }
+            let prefix = match normalize_key(&prefix).trim_matches('/') {
+                "" => String::new(),
+                prefix => format!("{}/", prefix),
+            };
+            let cipher = cipher_from_args(&args, false).await?;
+            client.reporter().message(messages::mounted(format!("{}/{}", client.bucket(), prefix), mountpoint.display()));
+            crate::mount::serve(client, prefix, cipher, &mountpoint).await
+        })
+    })
+}
+
+/// Without the `mount` feature, `rot mount` only says how to get it.
+#[cfg(not(all(feature = "mount", target_os = "linux")))]
+pub fn mount(_client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |_args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        Box::pin(async move {
+            Err(messages::mount_not_built())
+        })
+    })
+}
+
+/// `rot browse [本地目录] [远程前缀|oss://bucket/prefix]` opens a two-pane
+/// file browser in the terminal: the local directory (the current one by
+/// default) beside the bucket. Objects are transferred as stored, without
+/// encryption.
+pub fn browse(client: Arc<Mutex<StoreClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let client = store_client(&client_clone, &args);
+            let (bucket, prefix) = args.positional.get(1).map(|remote| parse_remote(remote)).unwrap_or_default();
+            let (client, prefix) = match (bucket, client.s3()) {
+                (Some(bucket), Some(s3)) => (client.clone().with_store(Arc::new(s3.clone().with_bucket(bucket))), prefix),
+                // There are no buckets here, so `oss://a/b` simply means `a/b`.
+                (Some(bucket), None) => (client, sync::join_key(&format!("{}/", bucket), &prefix)),
+                (None, _) => (client, prefix),
+            };
+            open_browser(client, &args, &prefix).await
+        })
+    })
+}
+
+/// Checks the arguments of `rot browse` and runs the browser.
+#[cfg(feature = "browse")]
+async fn open_browser(client: StoreClient, args: &Arguments, prefix: &str) -> Result<(), String> {
+    if client.dry_run {
+        return Err(messages::browse_dry_run());
+    }
+    let local_dir = match args.positional.first() {
+        Some(dir) => ensure_absolute_path(dir),
+        None => env::current_dir().map_err(messages::current_dir_missing)?,
+    };
+    if !local_dir.is_dir() {
+        return Err(messages::not_a_dir(local_dir.display()));
+    }
+    let prefix = match normalize_key(prefix).trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("{}/", prefix),
+    };
+    crate::browse::run(client, local_dir, prefix).await
+}
+
+/// Without the `browse` feature, `rot browse` only says how to get it.
+#[cfg(not(feature = "browse"))]
+async fn open_browser(_client: StoreClient, _args: &Arguments, _prefix: &str) -> Result<(), String> {
+    Err(messages::browse_not_built())
+}
+
+/// Prints a presigned URL for `key` to stdout and nothing else, so it can be
+/// piped. `--expires` defaults to one hour, `--method` to GET.
+/// `--process` adds OSS data processing, such as resizing or watermarking
+/// images, to GET links.
+pub fn share(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let key = normalize_key(args.positional.first().ok_or_else(messages::share_usage)?);
+            let expires_in = match args.option(&["e", "expires"]) {
+                Some(value) => parse_duration(value)?,
+                None => Duration::from_secs(60 * 60),
+            };
+            let method = match args.option(&["m", "method"]) {
+                Some(value) => value.parse()?,
+                None => ShareMethod::Get,
+            };
+            let process = args.option(&["process"]).map(|value| value.trim());
+            if process.is_some() && method != ShareMethod::Get {
+                return Err(messages::process_needs_get());
+            }
+            if process.is_some_and(|process| !process.contains('/') || process.contains(char::is_whitespace)) {
+                return Err(messages::process_invalid());
+            }
+            let client = client_clone.lock().unwrap().clone();
+            if method == ShareMethod::Get && client.head_obj(&key).await.is_err() {
+                return Err(messages::object_missing(&key));
+            }
+            let url = match process {
+                Some(process) => client.presign_processed(&key, process, expires_in).await?,
+                None => client.presign(&key, method, expires_in).await?,
+            };
+            let expires_at = DateTime::from_secs(DateTime::from(SystemTime::now()).secs() + expires_in.as_secs() as i64)
+                .fmt(DateTimeFormat::DateTime).unwrap_or_default();
+            client.reporter().record(&ShareRecord { key, url: url.clone(), expires_at }, url);
+            Ok(())
+        })
+    })
+}
+
+/// `rot encrypt <输入> <输出>` and `rot decrypt <输入> <输出>` work on local
+/// files. A file is encrypted as `encrypt_file` does and a directory file by
+/// file, or with `--archive` into one archive as `encrypt_dir_archive` does.
+pub fn crypt(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let reporter = client.lock().unwrap().reporter();
+        Box::pin(async move {
+            let encrypting = args.main_command.as_deref() == Some("encrypt");
+            let [input, output] = args.positional.as_slice() else {
+                return Err(messages::crypt_usage());
+            };
+            let (input, output) = (ensure_absolute_path(input), ensure_absolute_path(output));
+            let is_dir = tokio::fs::metadata(&input).await
+                .map_err(|e| messages::cannot_read(input.display(), e))?
+                .is_dir();
+            let archive = args.has_flag(&["archive"]);
+            if encrypting && archive && !is_dir {
+                return Err(messages::archive_needs_dir(input.display()));
+            }
+
+            let password = match args.optional.get("p") {
+                Some(password) => password.clone(),
+                None if encrypting => prompt_password(&messages::encryption_password_prompt())?,
+                None => prompt_password(&messages::decryption_password_prompt())?,
+            };
+            if encrypting {
+                check_new_password(&password, args.has_flag(&["allow-weak"]))?;
+            }
+
+            let count = match (encrypting, is_dir, archive) {
+                (true, true, true) => crypt::encrypt_dir_archive(&input, &output, password).await?,
+                (true, true, false) => crypt::encrypt_dir(&input, &output, password).await?,
+                (true, false, _) => crypt::encrypt_file(&input, &output, password).await.map(|_| 1)?,
+                (false, true, _) => crypt::decrypt_dir(&input, &output, password).await?,
+                (false, false, true) => crypt::decrypt_dir_archive(&input, &output, password).await?,
+                (false, false, false) => crypt::decrypt_file(&input, &output, password).await.map(|_| 1)?,
+            };
+            reporter.message(match encrypting {
+                true => messages::files_encrypted(count, output.display()),
+                false => messages::files_decrypted(count, output.display()),
+            });
+            Ok(())
+        })
+    })
+}
+
+/// `rot tmpshare <文件> [--expires 1d]` uploads a file to a fresh directory
+/// below `tmpshare/` (`-u` picks another prefix), tags it to expire with the
+/// link, so that `rot expire` cleans it up, and prints a download
+/// link. Encryption options work as for `upload`; the link then serves the
+/// encrypted file.
+pub fn tmpshare(client: Arc<Mutex<AliyunClient>>) -> CommandHandler {
+    Box::new(move |args: Arguments| -> Pin<Box<dyn Future<Output=Result<(), String>>>> {
+        let client_clone = Arc::clone(&client);
+        Box::pin(async move {
+            let file = args.positional.first().ok_or_else(messages::tmpshare_usage)?;
+            let path = ensure_absolute_path(file);
+            if !path.is_file() {
+                return Err(messages::not_a_file(path.display()));
+            }
+            let expires_in = match args.option(&["e", "expires"]) {
+                Some(value) => parse_duration(value)?,
+                None => TMPSHARE_EXPIRY,
+            };
+            if expires_in > MAX_PRESIGN_EXPIRY {
+                return Err(messages::link_expiry_too_long());
+            }
+            let prefix = args.option(&["u", "prefix-path"])
+                .map(|value| sanitize_path_prefix(&normalize_key(value)).to_string())
+                .unwrap_or_else(|| TMPSHARE_PREFIX.into());
+            let mut id = [0u8; 8];
+            SystemRandom::new().fill(&mut id).map_err(|_| messages::random_failed())?;
+            let dir = format!("{}{}/", prefix, to_hex(&id));
+
+            let client = apply_dry_run(client_clone.lock().unwrap().clone(), &args);
+            if client.is_dry_run() {
+                client.reporter().message(messages::planned_tmpshare(path.display(), &dir, expires_in.as_secs()));
+                return Ok(());
+            }
+            client.ensure_writable(&messages::action_upload())?;
+            let cipher = cipher_from_args(&args, true).await?;
+            let encrypted = cipher.is_some();
+            let resp = client.upload_file_with_key(dir, path.clone(), cipher, Some(expires_in.as_secs() as i64)).await?;
+            let key = resp.key.unwrap_or_default();
+            let size = tokio::fs::metadata(&path).await.ok().map(|metadata| metadata.len());
+            history::record(vec![HistoryEntry::new(HistoryAction::Upload, key.clone(), size, resp.version_id)]).await;
+
+            let url = client.presign(&key, ShareMethod::Get, expires_in).await?;
+            let expires_at = DateTime::from_secs(DateTime::from(SystemTime::now()).secs() + expires_in.as_secs() as i64)
+                .fmt(DateTimeFormat::DateTime).unwrap_or_default();
+            client.reporter().warn(messages::tmpshare_uploaded(&key, &expires_at));
+            if encrypted {
+                client.reporter().warn(messages::tmpshare_encrypted());
+            }
+            client.reporter().record(&ShareRecord { key, url: url.clone(), expires_at }, url);
+            Ok(())
+        })
+    })
+}
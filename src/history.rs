@@ -0,0 +1,157 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use aws_sdk_s3::primitives::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::messages;
+use crate::utils::{config_dir, create_dir};
+
+/// Oldest entries are dropped once the log grows past this many.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HistoryAction {
+    Upload,
+    Delete,
+    Copy,
+}
+
+impl Display for HistoryAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryAction::Upload => write!(f, "upload"),
+            HistoryAction::Delete => write!(f, "delete"),
+            HistoryAction::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+/// One mutating operation. `version_id` is the version the operation
+/// created (the new object, or the delete marker) and is only known when
+/// the bucket has versioning enabled; without it the entry can't be undone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HistoryEntry {
+    pub id: u64,
+    pub action: HistoryAction,
+    pub key: String,
+    pub at: i64,
+    pub size: Option<u64>,
+    pub version_id: Option<String>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(action: HistoryAction, key: impl Into<String>, size: Option<u64>, version_id: Option<String>) -> Self {
+        Self {
+            id: 0,
+            action,
+            key: key.into(),
+            at: DateTime::from(SystemTime::now()).secs(),
+            size,
+            version_id,
+            undone: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct History {
+    next_id: u64,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("history.json"))
+    }
+
+    /// Loads the log from `path`; a missing file is an empty log.
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| messages::history_invalid(path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(messages::cannot_read(path.display(), e)),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, text).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+
+    /// Appends `entry` with the next id, dropping the oldest entries past
+    /// `MAX_ENTRIES`.
+    pub fn push(&mut self, mut entry: HistoryEntry) -> u64 {
+        self.next_id += 1;
+        entry.id = self.next_id;
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+        self.next_id
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut HistoryEntry> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+}
+
+/// Appends `entries` to the default history log. Failing to record history
+/// never fails the operation itself, so errors are only reported.
+pub(crate) async fn record(entries: Vec<HistoryEntry>) {
+    if entries.is_empty() {
+        return;
+    }
+    let Some(path) = History::default_path() else { return };
+    let result = async {
+        let mut history = History::load(&path).await?;
+        for entry in entries {
+            history.push(entry);
+        }
+        history.save(&path).await
+    }.await;
+    if let Err(e) = result {
+        eprintln!("{}", messages::history_not_recorded(e));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use crate::history::{History, HistoryAction, HistoryEntry, MAX_ENTRIES};
+
+    #[tokio::test]
+    async fn test_history_round_trip() {
+        let path = Path::new("target/test_history/history.json");
+        let _ = tokio::fs::remove_file(path).await;
+        assert!(History::load(path).await.unwrap().entries().is_empty());
+
+        let mut history = History::default();
+        history.push(HistoryEntry::new(HistoryAction::Upload, "a.txt", Some(3), None));
+        let id = history.push(HistoryEntry::new(HistoryAction::Delete, "a.txt", None, Some("v2".into())));
+        history.get_mut(id).unwrap().undone = true;
+        history.save(path).await.unwrap();
+
+        let mut loaded = History::load(path).await.unwrap();
+        assert_eq!(loaded.entries().len(), 2);
+        assert!(loaded.entries()[1].undone);
+        assert_eq!(loaded.entries()[1].version_id.as_deref(), Some("v2"));
+
+        for _ in 0..MAX_ENTRIES {
+            loaded.push(HistoryEntry::new(HistoryAction::Copy, "b.txt", None, None));
+        }
+        assert_eq!(loaded.entries().len(), MAX_ENTRIES);
+        assert_eq!(loaded.entries()[0].id, 3);
+    }
+}
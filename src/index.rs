@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::Object;
+use serde::{Deserialize, Serialize};
+use crate::messages;
+use crate::output::ObjectRecord;
+use crate::utils::{cache_dir, create_dir};
+
+/// A bucket's full listing saved by `rot index refresh`, which `rot ls
+/// --cached` reads instead of listing the bucket again. Nothing keeps it
+/// up to date: changes made since `refreshed_at`, by rot or anyone else,
+/// only show after the next refresh.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ListingIndex {
+    pub bucket: String,
+    /// When the listing was taken, in seconds since the Unix epoch.
+    pub refreshed_at: i64,
+    /// Sorted by key.
+    pub objects: Vec<IndexedObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexedObject {
+    pub key: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+impl From<&Object> for IndexedObject {
+    fn from(object: &Object) -> Self {
+        Self {
+            key: object.key.clone().unwrap_or_default(),
+            size: object.size.unwrap_or(0).max(0) as u64,
+            last_modified: object.last_modified.map(|time| time.secs()),
+            storage_class: object.storage_class.as_ref().map(|class| class.as_str().to_string()),
+            etag: object.e_tag.clone(),
+        }
+    }
+}
+
+impl From<&IndexedObject> for ObjectRecord {
+    fn from(object: &IndexedObject) -> Self {
+        Self {
+            key: object.key.clone(),
+            size: object.size,
+            last_modified: object.last_modified.and_then(|secs| DateTime::from_secs(secs).fmt(DateTimeFormat::DateTime).ok()),
+            storage_class: object.storage_class.clone(),
+            etag: object.etag.clone(),
+            symlink_target: None,
+        }
+    }
+}
+
+/// What `ListingIndex::list` finds, in the shape of one listing page.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct IndexListing<'a> {
+    pub folders: Vec<String>,
+    pub objects: Vec<&'a IndexedObject>,
+    /// Whether entries were left out because of the limit.
+    pub truncated: bool,
+}
+
+impl ListingIndex {
+    pub fn new(bucket: impl Into<String>, objects: &[Object], refreshed_at: i64) -> Self {
+        let mut objects: Vec<IndexedObject> = objects.iter().map(IndexedObject::from).collect();
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Self { bucket: bucket.into(), refreshed_at, objects }
+    }
+
+    /// `~/.cache/rot/index/<endpoint>/<bucket>.json`. The endpoint is part
+    /// of the path so that buckets of the same name elsewhere, such as in
+    /// the offline store, get their own index.
+    pub fn default_path(endpoint: Option<&str>, bucket: &str) -> Option<PathBuf> {
+        let endpoint = endpoint.map(|url| url.split_once("://").map_or(url, |(_, host)| host).trim_end_matches('/'))
+            .filter(|host| !host.is_empty())
+            .map(|host| host.replace(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'), "_"))
+            .unwrap_or_else(|| "default".into());
+        cache_dir().map(|dir| dir.join("index").join(endpoint).join(format!("{}.json", bucket)))
+    }
+
+    /// Loads the index at `path`, `None` if there is none yet.
+    pub async fn load(path: &Path) -> Result<Option<Self>, String> {
+        match tokio::fs::read(path).await {
+            Ok(data) => serde_json::from_slice(&data).map(Some).map_err(|e| messages::index_invalid(path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(messages::cannot_read(path.display(), e)),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let data = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, data).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+
+    /// Lists `prefix` the way `ListObjectsV2` would: without `recursive`
+    /// keys below the next `/` are rolled up into folders. At most `limit`
+    /// entries are returned, folders and objects counted alike.
+    pub fn list(&self, prefix: &str, recursive: bool, limit: Option<usize>) -> IndexListing<'_> {
+        let start = self.objects.partition_point(|object| object.key.as_str() < prefix);
+        let mut listing = IndexListing::default();
+        let mut count = 0;
+        for object in self.objects[start..].iter().take_while(|object| object.key.starts_with(prefix)) {
+            let folder = match object.key[prefix.len()..].find('/') {
+                Some(end) if !recursive => Some(&object.key[..prefix.len() + end + 1]),
+                _ => None,
+            };
+            // Keys are sorted, so the keys of a folder are next to each other.
+            if folder.is_some_and(|folder| listing.folders.last().is_some_and(|last| last == folder)) {
+                continue;
+            }
+            if limit.is_some_and(|limit| count >= limit) {
+                listing.truncated = true;
+                break;
+            }
+            count += 1;
+            match folder {
+                Some(folder) => listing.folders.push(folder.to_string()),
+                None => listing.objects.push(object),
+            }
+        }
+        listing
+    }
+}
+
+/// How long ago `then` was, such as `3 小时前`.
+pub(crate) fn describe_age(then: i64, now: i64) -> String {
+    match (now - then).max(0) {
+        age if age < 60 => messages::just_now(),
+        age if age < 60 * 60 => messages::minutes_ago(age / 60),
+        age if age < 24 * 60 * 60 => messages::hours_ago(age / (60 * 60)),
+        age => messages::days_ago(age / (24 * 60 * 60)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aws_sdk_s3::types::Object;
+    use crate::index::{describe_age, ListingIndex};
+
+    #[test]
+    fn test_listing_index() {
+        let objects: Vec<Object> = ["logs/2024/b.log", "a.txt", "logs/2024/a.log", "logs/x.log", "logs-old/c.log", "logs/2025/a.log"]
+            .iter()
+            .map(|key| Object::builder().key(*key).size(1).build())
+            .collect();
+        let index = ListingIndex::new("photos", &objects, 0);
+        assert_eq!(index.objects[0].key, "a.txt");
+
+        let keys = |listing: &crate::index::IndexListing| listing.objects.iter().map(|object| object.key.clone()).collect::<Vec<_>>();
+        let top = index.list("", false, None);
+        assert_eq!(top.folders, ["logs-old/", "logs/"]);
+        assert_eq!(keys(&top), ["a.txt"]);
+        let logs = index.list("logs/", false, None);
+        assert_eq!(logs.folders, ["logs/2024/", "logs/2025/"]);
+        assert_eq!(keys(&logs), ["logs/x.log"]);
+        assert_eq!(keys(&index.list("logs/", true, None)).len(), 4);
+        let limited = index.list("logs/", true, Some(2));
+        assert_eq!(keys(&limited), ["logs/2024/a.log", "logs/2024/b.log"]);
+        assert!(limited.truncated);
+        assert!(!index.list("logs/", true, Some(4)).truncated);
+        assert_eq!(index.list("nothing/", false, None), Default::default());
+
+        let path = ListingIndex::default_path(Some("http://127.0.0.1:9000/"), "photos").unwrap();
+        assert!(path.ends_with("index/127.0.0.1_9000/photos.json"));
+        assert!(ListingIndex::default_path(None, "photos").unwrap().ends_with("index/default/photos.json"));
+    }
+
+    #[test]
+    fn test_describe_age() {
+        assert_eq!(describe_age(100, 130), "刚刚");
+        assert_eq!(describe_age(0, 150), "2 分钟前");
+        assert_eq!(describe_age(0, 3 * 3600 + 5), "3 小时前");
+        assert_eq!(describe_age(0, 2 * 86_400), "2 天前");
+    }
+}
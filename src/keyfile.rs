@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use crate::constant::{IDENTITY_FILE_NAME, KEY_FILE_LEN, KEY_FILE_NAME, MAX_KEY_NAME_LEN, NAMED_KEYS_DIR};
+use crate::crypt::recipient_of;
+use crate::error::RotError;
+use crate::manifest::to_hex;
+use crate::messages;
+use crate::parser::{Arguments, CommandParser};
+use crate::utils::{config_dir, confirm, ensure_absolute_path};
+
+/// Reads a key file as written by `rot keygen`: exactly 32 random bytes,
+/// used as the encryption key without any stretching.
+pub(crate) async fn load_key_file(path: &Path) -> Result<[u8; KEY_FILE_LEN], String> {
+    let data = tokio::fs::read(path).await.map_err(|e| messages::key_file_unreadable(path.display(), e))?;
+    data.try_into().map_err(|data: Vec<u8>| {
+        messages::key_file_len(path.display(), KEY_FILE_LEN, data.len())
+    })
+}
+
+/// Where `rot keygen` writes when no path is given (`~/.config/rot/key.bin`).
+pub(crate) fn default_key_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(KEY_FILE_NAME))
+}
+
+/// Where `rot keygen --identity` writes when no path is given
+/// (`~/.config/rot/identity.bin`).
+pub(crate) fn default_identity_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(IDENTITY_FILE_NAME))
+}
+
+/// Where `rot key add` keeps named keys (`~/.config/rot/keys`).
+fn named_keys_dir() -> Result<PathBuf, String> {
+    config_dir().map(|dir| dir.join(NAMED_KEYS_DIR)).ok_or_else(messages::config_dir_missing)
+}
+
+/// Reads the key stored as `name` by `rot key add`, for `--key <名称>`.
+pub(crate) async fn load_named_key(name: &str) -> Result<[u8; KEY_FILE_LEN], String> {
+    load_named_key_in(&named_keys_dir()?, name).await
+}
+
+async fn load_named_key_in(dir: &Path, name: &str) -> Result<[u8; KEY_FILE_LEN], String> {
+    let path = named_key_path(dir, name)?;
+    if !path.exists() {
+        return Err(messages::named_key_missing(name));
+    }
+    load_key_file(&path).await
+}
+
+/// The file of the key called `name` in `dir`. Names are used as file
+/// names, so only letters, digits, `-`, `_` and `.` are allowed.
+fn named_key_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty() && name.len() <= MAX_KEY_NAME_LEN && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(messages::key_name_invalid(name, MAX_KEY_NAME_LEN));
+    }
+    Ok(dir.join(format!("{}.bin", name)))
+}
+
+/// A short fingerprint telling keys apart without showing them.
+fn fingerprint(key: &[u8]) -> String {
+    to_hex(&digest(&SHA256, key).as_ref()[..8])
+}
+
+/// Runs `rot key add <名称> [--from 密钥文件] [--force]`, `rot key ls` and
+/// `rot key rm <名称> [--yes]`, which manage the keys `--key <名称>` refers
+/// to. They live in the configuration directory, one key file each.
+pub async fn execute_named(args: impl IntoIterator<Item=impl Into<String>>) -> Result<(), RotError> {
+    let dir = named_keys_dir().map_err(RotError::Command)?;
+    run_named(CommandParser::from_strings(args), &dir).await.map_err(RotError::Command)
+}
+
+async fn run_named(args: Arguments, dir: &Path) -> Result<(), String> {
+    match (args.positional.first().map(String::as_str), args.positional.get(1)) {
+        (Some("add"), Some(name)) => {
+            let path = named_key_path(dir, name)?;
+            if path.exists() && !args.has_flag(&["f", "force"]) {
+                return Err(messages::named_key_exists(name));
+            }
+            let key = match args.optional.get("from") {
+                Some(from) => load_key_file(&ensure_absolute_path(from)).await?,
+                None => {
+                    let mut key = [0u8; KEY_FILE_LEN];
+                    SystemRandom::new().fill(&mut key).map_err(|_| messages::random_failed())?;
+                    key
+                }
+            };
+            write_key_file(&path, &key).await.map_err(|e| messages::key_file_unwritable(path.display(), e))?;
+            println!("{}", messages::named_key_saved(name, fingerprint(&key), path.display()));
+            Ok(())
+        }
+        (Some("ls"), None) => {
+            let mut names = Vec::new();
+            if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+                while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+                    if let Some(name) = entry.file_name().to_str().and_then(|name| name.strip_suffix(".bin")) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+            names.sort();
+            if names.is_empty() {
+                println!("{}", messages::no_named_keys());
+            }
+            for name in names {
+                match load_named_key_in(dir, &name).await {
+                    Ok(key) => println!("{}\t{}", name, fingerprint(&key)),
+                    Err(e) => println!("{}\t{}", name, e),
+                }
+            }
+            Ok(())
+        }
+        (Some("rm"), Some(name)) => {
+            let path = named_key_path(dir, name)?;
+            if !path.exists() {
+                return Err(messages::named_key_not_found(name));
+            }
+            if !args.has_flag(&["y", "yes"]) && !confirm(&messages::confirm_key_removal(name))? {
+                return Ok(());
+            }
+            tokio::fs::remove_file(&path).await.map_err(|e| messages::cannot_delete(path.display(), e))?;
+            println!("{}", messages::named_key_removed(name));
+            Ok(())
+        }
+        _ => Err(messages::key_usage()),
+    }
+}
+
+/// Runs `rot keygen [<路径>] [--force]`. With `--identity` it creates a
+/// private key for receiving files and prints the public key to give to
+/// senders; `--public` prints the public key of an existing one.
+pub async fn execute(args: impl IntoIterator<Item=impl Into<String>>) -> Result<(), RotError> {
+    run(CommandParser::from_strings(args)).await.map_err(RotError::Command)
+}
+
+async fn run(args: Arguments) -> Result<(), String> {
+    // `--identity <路径>` and `--identity` followed by nothing both work.
+    let identity = args.has_flag(&["identity"]) || args.optional.contains_key("identity");
+    let public = args.has_flag(&["public"]) || args.optional.contains_key("public");
+    let path = match args.optional.get("identity").or(args.optional.get("public")).or(args.positional.first()) {
+        Some(path) => ensure_absolute_path(path),
+        None if identity || public => default_identity_path().ok_or_else(messages::identity_path_missing)?,
+        None => default_key_path().ok_or_else(messages::key_path_missing)?,
+    };
+    if public {
+        println!("{}", recipient_of(&load_key_file(&path).await?));
+        return Ok(());
+    }
+    if path.exists() && !args.has_flag(&["f", "force"]) {
+        return Err(messages::key_file_exists(path.display()));
+    }
+    let mut key = [0u8; KEY_FILE_LEN];
+    SystemRandom::new().fill(&mut key).map_err(|_| messages::random_failed())?;
+    write_key_file(&path, &key).await.map_err(|e| messages::key_file_unwritable(path.display(), e))?;
+    if identity {
+        println!("{}", messages::identity_created(path.display()));
+        println!("{}", messages::recipient_to_share(recipient_of(&key)));
+    } else {
+        println!("{}", messages::key_file_created(path.display()));
+    }
+    Ok(())
+}
+
+/// Writes `key` to `path`, readable only by the current user where the
+/// platform supports it.
+async fn write_key_file(path: &Path, key: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(key).await?;
+    file.flush().await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::keyfile::{load_key_file, load_named_key_in, run, run_named};
+    use crate::parser::CommandParser;
+
+    #[tokio::test]
+    async fn test_keygen() {
+        let path = std::env::current_dir().unwrap().join("target/test/keygen/key.bin");
+        let _ = tokio::fs::remove_file(&path).await;
+        let keygen = || CommandParser::from_strings(["rot", "keygen", path.to_str().unwrap()]);
+
+        run(keygen()).await.unwrap();
+        let key = load_key_file(&path).await.unwrap();
+        assert!(run(keygen()).await.unwrap_err().contains("--force"));
+        assert_eq!(load_key_file(&path).await.unwrap(), key);
+
+        run(CommandParser::from_strings(["rot", "keygen", path.to_str().unwrap(), "--force"])).await.unwrap();
+        assert_ne!(load_key_file(&path).await.unwrap(), key);
+
+        tokio::fs::write(&path, b"too short").await.unwrap();
+        assert!(load_key_file(&path).await.unwrap_err().contains("9 字节"));
+    }
+
+    #[tokio::test]
+    async fn test_named_keys() {
+        let dir = std::env::current_dir().unwrap().join("target/test/named_keys");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let key = |args: &[&str]| CommandParser::from_strings(["rot", "key"].iter().chain(args).copied());
+
+        run_named(key(&["add", "backup"]), &dir).await.unwrap();
+        let first = load_named_key_in(&dir, "backup").await.unwrap();
+        assert!(run_named(key(&["add", "backup"]), &dir).await.unwrap_err().contains("--force"));
+        assert!(run_named(key(&["add", "../escape"]), &dir).await.is_err());
+        run_named(key(&["ls"]), &dir).await.unwrap();
+
+        let imported = dir.join("backup.bin");
+        run_named(key(&["add", "copy", "--from", imported.to_str().unwrap()]), &dir).await.unwrap();
+        assert_eq!(load_named_key_in(&dir, "copy").await.unwrap(), first);
+
+        run_named(key(&["rm", "backup", "--yes"]), &dir).await.unwrap();
+        assert!(load_named_key_in(&dir, "backup").await.unwrap_err().contains("rot key ls"));
+        assert!(run_named(key(&["rm", "backup", "--yes"]), &dir).await.is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use keyring::{Entry, Error};
+use crate::messages;
+
+/// The `secret_access_key` value that means "look it up in the keyring".
+pub(crate) const KEYRING_SENTINEL: &str = "keyring";
+/// Service name the secrets are filed under.
+const SERVICE: &str = "rot";
+
+/// The secret stored for `account` in the system keychain: the login
+/// keychain on macOS, the Credential Manager on Windows and the Secret
+/// Service (GNOME Keyring, KWallet) elsewhere.
+pub(crate) async fn get_secret(account: &str) -> Result<String, String> {
+    let owned = account.to_string();
+    match with_entry(move || Entry::new(SERVICE, &owned)?.get_password()).await? {
+        Ok(secret) if !secret.is_empty() => Ok(secret),
+        Ok(_) | Err(Error::NoEntry) => Err(messages::keyring_missing(account)),
+        Err(e) => Err(unavailable(e)),
+    }
+}
+
+/// Stores `secret` for `account`, replacing any earlier one.
+pub(crate) async fn set_secret(account: &str, secret: &str) -> Result<(), String> {
+    let (owned, secret) = (account.to_string(), secret.to_string());
+    with_entry(move || Entry::new(SERVICE, &owned)?.set_password(&secret)).await?
+        .map_err(|e| messages::keyring_not_saved(account, e))
+}
+
+/// Runs a keyring call on the blocking pool, as every backend blocks while
+/// it talks to the system service.
+async fn with_entry<T: Send + 'static>(call: impl FnOnce() -> Result<T, Error> + Send + 'static) -> Result<Result<T, Error>, String> {
+    tokio::task::spawn_blocking(call).await.map_err(|e| e.to_string())
+}
+
+fn unavailable(e: Error) -> String {
+    messages::keyring_unavailable(e)
+}
@@ -1,7 +1,42 @@
 pub mod client;
+pub mod config;
+pub mod error;
 mod utils;
 mod parser;
 mod command;
-mod crypt;
+pub mod crypt;
 mod handler;
-mod constant;
\ No newline at end of file
+mod constant;
+mod transfer;
+mod bench;
+mod queue;
+mod manifest;
+mod signing;
+mod password;
+mod local;
+mod history;
+mod sync;
+mod resume;
+mod glob;
+mod find;
+mod lifecycle;
+mod backup;
+mod index;
+#[cfg(feature = "browse")]
+mod browse;
+#[cfg(all(feature = "mount", target_os = "linux"))]
+mod mount;
+mod checksum;
+mod symlink;
+mod credentials;
+mod keyring;
+pub mod sts;
+pub mod keyfile;
+pub mod store;
+mod sftp;
+mod webdav;
+mod proxy;
+pub mod output;
+pub mod event;
+pub mod locale;
+pub mod messages;
\ No newline at end of file
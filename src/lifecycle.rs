@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+use aws_sdk_s3::types::{ExpirationStatus, LifecycleExpiration, LifecycleRule, LifecycleRuleFilter, Transition, TransitionStorageClass};
+use serde::{Deserialize, Serialize};
+use crate::constant::ARCHIVE_STORAGE_CLASS;
+use crate::messages;
+
+/// A lifecycle rule as written in the rules file of `rot lifecycle set` and
+/// printed by `rot lifecycle get`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RuleSpec {
+    pub id: String,
+    /// Objects whose key starts with this; empty for the whole bucket.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Days after upload when objects are deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_days: Option<i32>,
+    /// Days after upload when objects move to the Archive storage class.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_days: Option<i32>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// The whole rules file, also what `rot lifecycle get --output json` prints.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RulesFile {
+    pub rules: Vec<RuleSpec>,
+}
+
+/// Reads a rules file. YAML is a superset of JSON, so both are accepted:
+///
+/// ```yaml
+/// rules:
+///   - id: logs
+///     prefix: logs/
+///     archive_days: 30
+///     expire_days: 365
+/// ```
+pub(crate) fn parse_rules(text: &str) -> Result<Vec<RuleSpec>, String> {
+    let file: RulesFile = serde_yaml::from_str(text).map_err(messages::lifecycle_rules_invalid)?;
+    let mut ids = BTreeSet::new();
+    for rule in &file.rules {
+        if rule.id.is_empty() || rule.id.len() > 255 {
+            return Err(messages::lifecycle_id_invalid());
+        }
+        if !ids.insert(rule.id.as_str()) {
+            return Err(messages::lifecycle_id_repeated(&rule.id));
+        }
+        if rule.expire_days.is_none() && rule.archive_days.is_none() {
+            return Err(messages::lifecycle_rule_empty(&rule.id));
+        }
+        if rule.expire_days.into_iter().chain(rule.archive_days).any(|days| days <= 0) {
+            return Err(messages::lifecycle_days_invalid(&rule.id));
+        }
+        if let (Some(expire), Some(archive)) = (rule.expire_days, rule.archive_days) {
+            if archive >= expire {
+                return Err(messages::lifecycle_archive_after_expiry(&rule.id));
+            }
+        }
+    }
+    Ok(file.rules)
+}
+
+/// Prints rules in the format `parse_rules` reads, so that the output of
+/// `rot lifecycle get` can be edited and set again.
+pub(crate) fn format_rules(file: &RulesFile) -> String {
+    serde_yaml::to_string(file).unwrap_or_default()
+}
+
+impl RuleSpec {
+    pub fn to_rule(&self) -> Result<LifecycleRule, String> {
+        let status = if self.enabled { ExpirationStatus::Enabled } else { ExpirationStatus::Disabled };
+        LifecycleRule::builder()
+            .id(&self.id)
+            .filter(LifecycleRuleFilter::builder().prefix(&self.prefix).build())
+            .status(status)
+            .set_expiration(self.expire_days.map(|days| LifecycleExpiration::builder().days(days).build()))
+            .set_transitions(self.archive_days.map(|days| vec![Transition::builder()
+                .days(days)
+                .storage_class(TransitionStorageClass::from(ARCHIVE_STORAGE_CLASS))
+                .build()]))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Only expiration and transitions to Archive are kept; other actions
+    /// set elsewhere, such as the OSS console, are left out.
+    pub fn from_rule(rule: &LifecycleRule) -> Self {
+        Self {
+            id: rule.id().unwrap_or_default().to_string(),
+            prefix: rule.filter().and_then(|filter| filter.prefix()).unwrap_or_default().to_string(),
+            enabled: rule.status() == &ExpirationStatus::Enabled,
+            expire_days: rule.expiration().and_then(|expiration| expiration.days()),
+            archive_days: rule.transitions().iter()
+                .find(|transition| transition.storage_class().map(|class| class.as_str()) == Some(ARCHIVE_STORAGE_CLASS))
+                .and_then(|transition| transition.days()),
+        }
+    }
+
+    /// A one-line description such as `logs（logs/*）：30 天后转为归档，365 天后删除`.
+    pub fn describe(&self) -> String {
+        let actions: Vec<String> = self.archive_days.map(messages::lifecycle_archive_after).into_iter()
+            .chain(self.expire_days.map(messages::lifecycle_expire_after))
+            .collect();
+        let actions = actions.join(&messages::list_separator());
+        match self.enabled {
+            true => messages::lifecycle_rule(&self.id, &self.prefix, actions),
+            false => messages::lifecycle_rule_disabled(&self.id, &self.prefix, actions),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lifecycle::{format_rules, parse_rules, RuleSpec, RulesFile};
+
+    #[test]
+    fn test_parse_rules() {
+        let yaml = "rules:\n  - id: logs\n    prefix: logs/\n    archive_days: 30\n    expire_days: 365\n  - id: tmp\n    prefix: tmp/\n    enabled: false\n    expire_days: 1\n";
+        let rules = parse_rules(yaml).unwrap();
+        assert_eq!(rules[0], RuleSpec { id: "logs".into(), prefix: "logs/".into(), enabled: true, expire_days: Some(365), archive_days: Some(30) });
+        assert!(!rules[1].enabled);
+        assert_eq!(parse_rules(&format_rules(&RulesFile { rules: rules.clone() })).unwrap(), rules);
+        assert_eq!(rules[0].describe(), "logs（logs/*）：30 天后转为归档，365 天后删除");
+
+        let json = r#"{"rules": [{"id": "all", "expire_days": 7}]}"#;
+        assert_eq!(parse_rules(json).unwrap()[0].prefix, "");
+
+        assert!(parse_rules("rules:\n  - id: a\n    prefix: a/\n").is_err());
+        assert!(parse_rules("rules:\n  - id: a\n    expire_days: 1\n  - id: a\n    expire_days: 2\n").is_err());
+        assert!(parse_rules("rules:\n  - id: a\n    expire_days: 30\n    archive_days: 30\n").is_err());
+        assert!(parse_rules("rules:\n  - id: a\n    expire_days: 0\n").is_err());
+        assert!(parse_rules("rules:\n  - id: a\n    expire: 1\n").is_err());
+
+        for rule in &rules {
+            assert_eq!(&RuleSpec::from_rule(&rule.to_rule().unwrap()), rule);
+        }
+    }
+}
@@ -0,0 +1,1000 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_smithy_runtime_api::client::http::{HttpConnector, HttpConnectorFuture};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::http::StatusCode;
+use aws_smithy_types::body::SdkBody;
+use ring::digest::{self, digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use crate::checksum::{crc64, crc64_file_prefix, Crc64};
+use crate::constant::{ALL_USERS_URI, CRC64_HEADER, NEXT_APPEND_POSITION_HEADER, READ_BUFFER_SIZE, SYMLINK_TARGET_HEADER};
+use crate::manifest::to_hex;
+use crate::messages;
+use crate::utils::{create_dir, percent_encode_key, walk_dir};
+
+/// Directory below the store root that holds metadata and unfinished
+/// multipart uploads; it never shows up as a bucket.
+const STATE_DIR: &str = ".rot";
+
+/// Serves the subset of the S3 API that rot uses from a local directory, so
+/// every command can run without credentials or network. It plugs in as the
+/// SDK's HTTP connector: requests never leave the process and each bucket is
+/// a plain directory below `root`, with object keys as relative paths.
+///
+/// Request and response bodies are held in memory, which is fine for trying
+/// rot out and for tests, but not meant for very large objects. Stored
+/// objects are only read as far as a request needs: HEAD reads none of it and
+/// a ranged GET only its range.
+#[derive(Debug, Clone)]
+pub(crate) struct LocalStore {
+    root: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ObjectMeta {
+    etag: String,
+    last_modified: i64,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    expires: Option<String>,
+    /// When a restored copy expires. Restores finish at once here.
+    #[serde(default)]
+    restored_until: Option<i64>,
+    /// Canned ACL, when one was set.
+    #[serde(default)]
+    acl: Option<String>,
+    /// Created by AppendObject, so that further appends are allowed.
+    #[serde(default)]
+    appendable: bool,
+    /// Set on symlinks, whose own file is empty: reads return this key.
+    #[serde(default)]
+    symlink_target: Option<String>,
+    /// CRC-64 of the content, so that HEAD needn't read it. Missing for
+    /// files put below the root by other means.
+    #[serde(default)]
+    crc64: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingUpload {
+    bucket: String,
+    key: String,
+    meta: ObjectMeta,
+}
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn new(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    fn xml(body: String) -> Self {
+        Self::new(200).header("content-type", "application/xml").body(body.into_bytes())
+    }
+
+    fn error(status: u16, code: &str, message: &str) -> Self {
+        let body = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message></Error>",
+                           code, xml_escape(message));
+        Self::new(status).header("content-type", "application/xml").body(body.into_bytes())
+    }
+
+    fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    fn into_response(self) -> HttpResponse {
+        let status = StatusCode::try_from(self.status).expect("valid status code");
+        let mut response = HttpResponse::new(status, SdkBody::from(self.body));
+        for (name, value) in self.headers {
+            response.headers_mut().insert(name, value);
+        }
+        response
+    }
+}
+
+impl HttpConnector for LocalStore {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let store = self.clone();
+        HttpConnectorFuture::new(async move {
+            let reply = match store.handle(request).await {
+                Ok(reply) => reply,
+                Err(e) => Reply::error(500, "InternalError", &e),
+            };
+            Ok(reply.into_response())
+        })
+    }
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    async fn handle(&self, request: HttpRequest) -> Result<Reply, String> {
+        let (path, query) = split_uri(request.uri());
+        let mut segments = path.trim_start_matches('/').splitn(2, '/');
+        let bucket = percent_decode(segments.next().unwrap_or_default());
+        let key = percent_decode(segments.next().unwrap_or_default());
+        if bucket.is_empty() && request.method() == "GET" {
+            return self.list_buckets().await;
+        }
+        if bucket.is_empty() || bucket.starts_with('.') {
+            return Ok(Reply::error(400, "InvalidBucketName", "invalid bucket name"));
+        }
+        if !key.is_empty() && !is_safe_key(&key) {
+            return Ok(Reply::error(400, "InvalidKey", "object keys may not contain empty, '.' or '..' components"));
+        }
+        // Versioning is never enabled here: each object has one version, `null`.
+        if query.get("versionId").is_some_and(|id| id != "null") {
+            return Ok(Reply::error(404, "NoSuchVersion", "The specified version does not exist."));
+        }
+
+        let method = request.method().to_string();
+        let header = |name: &str| request.headers().get(name).map(str::to_string);
+        let body = decode_body(&request);
+
+        match (method.as_str(), key.is_empty()) {
+            ("HEAD", true) => Ok(Reply::new(200)),
+            ("GET", true) if query.contains_key("lifecycle") => self.get_lifecycle(&bucket).await,
+            ("PUT", true) if query.contains_key("lifecycle") => self.put_lifecycle(&bucket, &body).await,
+            ("DELETE", true) if query.contains_key("lifecycle") => {
+                let _ = tokio::fs::remove_file(self.lifecycle_path(&bucket)).await;
+                Ok(Reply::new(204))
+            }
+            ("PUT", true) => self.create_bucket(&bucket).await,
+            ("DELETE", true) => self.delete_bucket(&bucket).await,
+            ("GET", true) if query.contains_key("versions") => self.list_versions(&bucket, query.get("prefix").map(String::as_str).unwrap_or("")).await,
+            ("GET", true) if query.contains_key("uploads") => self.list_uploads(&bucket, query.get("prefix").map(String::as_str).unwrap_or("")).await,
+            ("GET", true) => self.list_objects(&bucket, &query).await,
+            ("POST", true) if query.contains_key("delete") => self.delete_objects(&bucket, &body).await,
+            ("GET", false) if query.contains_key("tagging") => self.get_tagging(&bucket, &key).await,
+            ("PUT", false) if query.contains_key("tagging") => self.put_tagging(&bucket, &key, &body).await,
+            ("DELETE", false) if query.contains_key("tagging") => self.put_tagging(&bucket, &key, b"").await,
+            ("GET", false) if query.contains_key("acl") => self.get_acl(&bucket, &key).await,
+            ("PUT", false) if query.contains_key("acl") => self.put_acl(&bucket, &key, header("x-amz-acl")).await,
+            ("GET", false) if query.contains_key("uploadId") => self.list_parts(&query["uploadId"]).await,
+            ("GET", false) if query.contains_key("symlink") => self.get_symlink(&bucket, &key).await,
+            ("PUT", false) if query.contains_key("symlink") => {
+                let target = header(SYMLINK_TARGET_HEADER).map(|target| percent_decode(&target)).ok_or("missing symlink target")?;
+                self.put_symlink(&bucket, &key, &target).await
+            }
+            ("POST", false) if query.contains_key("append") => {
+                let position = query.get("position").and_then(|n| n.parse::<u64>().ok()).ok_or("missing append position")?;
+                self.append_object(&bucket, &key, position, &body, request_meta(&request)).await
+            }
+            ("POST", false) if query.contains_key("restore") => self.restore_object(&bucket, &key, &body).await,
+            ("GET", false) => self.get_object(&bucket, &key, header("range"), false).await,
+            ("HEAD", false) => self.get_object(&bucket, &key, None, true).await,
+            ("PUT", false) if query.contains_key("uploadId") => {
+                let number = query.get("partNumber").and_then(|n| n.parse::<u32>().ok()).ok_or("missing part number")?;
+                let upload_id = &query["uploadId"];
+                match header("x-amz-copy-source") {
+                    Some(source) => self.upload_part_copy(upload_id, number, &source, header("x-amz-copy-source-range")).await,
+                    None => self.upload_part(upload_id, number, body).await,
+                }
+            }
+            ("PUT", false) => match header("x-amz-copy-source") {
+                Some(source) => self.copy_object(&bucket, &key, &source).await,
+                None => self.put_object(&bucket, &key, body, request_meta(&request)).await,
+            },
+            ("POST", false) if query.contains_key("uploads") => self.create_upload(&bucket, &key, request_meta(&request)).await,
+            ("POST", false) if query.contains_key("uploadId") => self.complete_upload(&query["uploadId"], &body).await,
+            ("DELETE", false) if query.contains_key("uploadId") => {
+                let _ = tokio::fs::remove_dir_all(self.upload_dir(&query["uploadId"])).await;
+                Ok(Reply::new(204))
+            }
+            ("DELETE", false) => {
+                self.remove_object(&bucket, &key).await;
+                Ok(Reply::new(204))
+            }
+            _ => Ok(Reply::error(501, "NotImplemented", &format!("{} is not supported by the offline store", method))),
+        }
+    }
+
+    fn object_path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.root.join(bucket).join(key)
+    }
+
+    fn meta_path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.root.join(STATE_DIR).join("meta").join(bucket).join(format!("{}.json", key))
+    }
+
+    /// The lifecycle configuration of `bucket`, kept as the XML it was set
+    /// with. Rules are stored but never applied.
+    fn lifecycle_path(&self, bucket: &str) -> PathBuf {
+        self.root.join(STATE_DIR).join("lifecycle").join(format!("{}.xml", bucket))
+    }
+
+    fn upload_dir(&self, upload_id: &str) -> PathBuf {
+        self.root.join(STATE_DIR).join("uploads").join(upload_id.replace(['/', '\\', '.'], ""))
+    }
+
+    async fn read_meta(&self, bucket: &str, key: &str) -> ObjectMeta {
+        match tokio::fs::read_to_string(self.meta_path(bucket, key)).await {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_default(),
+            Err(_) => ObjectMeta::default(),
+        }
+    }
+
+    async fn write_meta(&self, bucket: &str, key: &str, meta: &ObjectMeta) -> Result<(), String> {
+        let path = self.meta_path(bucket, key);
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let text = serde_json::to_string(meta).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, text).await.map_err(|e| e.to_string())
+    }
+
+    async fn store_object(&self, bucket: &str, key: &str, data: &[u8], mut meta: ObjectMeta) -> Result<String, String> {
+        let path = self.object_path(bucket, key);
+        if key.ends_with('/') {
+            create_dir(&path).await;
+        } else {
+            if let Some(parent) = path.parent() {
+                create_dir(parent).await;
+            }
+            tokio::fs::write(&path, data).await.map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if meta.etag.is_empty() {
+            meta.etag = etag_of(data);
+        }
+        meta.crc64 = Some(crc64(data));
+        meta.last_modified = DateTime::from(SystemTime::now()).secs();
+        self.write_meta(bucket, key, &meta).await?;
+        Ok(meta.etag)
+    }
+
+    /// Moves the file at `source` into place as `key`, for content too large
+    /// to pass around in memory. `meta` already carries its etag and CRC-64.
+    async fn store_file(&self, bucket: &str, key: &str, source: &Path, mut meta: ObjectMeta) -> Result<String, String> {
+        let path = self.object_path(bucket, key);
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        tokio::fs::rename(source, &path).await.map_err(|e| format!("{}: {}", path.display(), e))?;
+        meta.last_modified = DateTime::from(SystemTime::now()).secs();
+        self.write_meta(bucket, key, &meta).await?;
+        Ok(meta.etag)
+    }
+
+    async fn remove_object(&self, bucket: &str, key: &str) {
+        let path = self.object_path(bucket, key);
+        if key.ends_with('/') {
+            let _ = tokio::fs::remove_dir(&path).await;
+        } else {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        let _ = tokio::fs::remove_file(self.meta_path(bucket, key)).await;
+    }
+
+    async fn list_buckets(&self) -> Result<Reply, String> {
+        let mut names = Vec::new();
+        if let Ok(mut entries) = tokio::fs::read_dir(&self.root).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let created = entry.metadata().await.ok().and_then(|metadata| metadata.modified().ok());
+                if !name.starts_with('.') && entry.path().is_dir() {
+                    names.push((name, created.map(|time| DateTime::from(time).secs()).unwrap_or(0)));
+                }
+            }
+        }
+        names.sort();
+        let buckets: String = names.iter()
+            .map(|(name, created)| format!("<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>", xml_escape(name), xml_date(*created)))
+            .collect();
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListAllMyBucketsResult><Owner><ID>offline</ID></Owner><Buckets>{}</Buckets></ListAllMyBucketsResult>", buckets)))
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<Reply, String> {
+        let path = self.root.join(bucket);
+        if path.is_dir() {
+            return Ok(Reply::error(409, "BucketAlreadyOwnedByYou", "The bucket already exists."));
+        }
+        create_dir(&path).await;
+        Ok(Reply::new(200))
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<Reply, String> {
+        let path = self.root.join(bucket);
+        if !path.is_dir() {
+            return Ok(Reply::error(404, "NoSuchBucket", "The specified bucket does not exist."));
+        }
+        if !self.list_keys(bucket).await.is_empty() {
+            return Ok(Reply::error(409, "BucketNotEmpty", "The bucket you tried to delete is not empty."));
+        }
+        tokio::fs::remove_dir_all(&path).await.map_err(|e| e.to_string())?;
+        let _ = tokio::fs::remove_dir_all(self.root.join(STATE_DIR).join("meta").join(bucket)).await;
+        let _ = tokio::fs::remove_file(self.lifecycle_path(bucket)).await;
+        Ok(Reply::new(204))
+    }
+
+    async fn list_keys(&self, bucket: &str) -> Vec<String> {
+        let bucket_root = self.root.join(bucket);
+        let mut keys: Vec<String> = walk_dir(&bucket_root).await
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|path| path.strip_prefix(&bucket_root).ok())
+            .filter_map(|relative| relative.to_str())
+            .map(|relative| relative.replace('\\', "/"))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    async fn list_objects(&self, bucket: &str, query: &HashMap<String, String>) -> Result<Reply, String> {
+        let prefix = query.get("prefix").cloned().unwrap_or_default();
+        let delimiter = query.get("delimiter").filter(|d| !d.is_empty()).cloned();
+        let start_after = query.get("continuation-token").or(query.get("start-after")).cloned().unwrap_or_default();
+        let max_keys = query.get("max-keys").and_then(|n| n.parse::<usize>().ok()).unwrap_or(1000);
+
+        let mut contents = String::new();
+        let mut common_prefixes: Vec<String> = Vec::new();
+        let mut count = 0;
+        let mut last_returned = String::new();
+        let mut truncated = false;
+        for key in self.list_keys(bucket).await {
+            if !key.starts_with(&prefix) || key <= start_after {
+                continue;
+            }
+            let common = delimiter.as_ref().and_then(|delimiter| {
+                key[prefix.len()..].find(delimiter.as_str())
+                    .map(|index| key[..prefix.len() + index + delimiter.len()].to_string())
+            });
+            if common.is_some() && common_prefixes.last() == common.as_ref() {
+                continue;
+            }
+            if count == max_keys {
+                truncated = true;
+                break;
+            }
+            count += 1;
+            match common {
+                Some(common) => {
+                    // Keys sort after their prefix, so resuming from the last
+                    // key under it skips the rest of the group.
+                    last_returned = format!("{}\u{10ffff}", common);
+                    common_prefixes.push(common);
+                }
+                None => {
+                    let meta = self.read_meta(bucket, &key).await;
+                    let size = tokio::fs::metadata(self.object_path(bucket, &key)).await.map(|m| m.len()).unwrap_or(0);
+                    let kind = if meta.symlink_target.is_some() { "Symlink" } else if meta.appendable { "Appendable" } else { "Normal" };
+                    contents.push_str(&format!("<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Type>{}</Type><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                                               xml_escape(&key), xml_date(meta.last_modified), xml_escape(&meta.etag), kind, size));
+                    last_returned = key;
+                }
+            }
+        }
+
+        // The token is the last key returned; listing resumes after it.
+        let token = truncated.then_some(last_returned);
+        let prefixes: String = common_prefixes.iter()
+            .map(|p| format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", xml_escape(p)))
+            .collect();
+        Ok(Reply::xml(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{}</MaxKeys><IsTruncated>{}</IsTruncated>{}{}{}</ListBucketResult>",
+            xml_escape(bucket), xml_escape(&prefix), count, max_keys, token.is_some(),
+            token.map(|t| format!("<NextContinuationToken>{}</NextContinuationToken>", xml_escape(&t))).unwrap_or_default(),
+            contents, prefixes)))
+    }
+
+    /// Lists each object as its only version, as S3 does for a bucket that
+    /// never had versioning enabled.
+    async fn list_versions(&self, bucket: &str, prefix: &str) -> Result<Reply, String> {
+        let mut versions = String::new();
+        for key in self.list_keys(bucket).await.into_iter().filter(|key| key.starts_with(prefix)) {
+            let meta = self.read_meta(bucket, &key).await;
+            let size = tokio::fs::metadata(self.object_path(bucket, &key)).await.map(|m| m.len()).unwrap_or(0);
+            versions.push_str(&format!("<Version><Key>{}</Key><VersionId>null</VersionId><IsLatest>true</IsLatest><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Version>",
+                                       xml_escape(&key), xml_date(meta.last_modified), xml_escape(&meta.etag), size));
+        }
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListVersionsResult><Name>{}</Name><Prefix>{}</Prefix><IsTruncated>false</IsTruncated>{}</ListVersionsResult>",
+                              xml_escape(bucket), xml_escape(prefix), versions)))
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str, range: Option<String>, head_only: bool) -> Result<Reply, String> {
+        // A symlink is read as its target, which may not exist.
+        let symlink = self.read_meta(bucket, key).await.symlink_target;
+        let key = symlink.as_deref().unwrap_or(key);
+        let path = self.object_path(bucket, key);
+        let total = match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ if head_only => return Ok(Reply::new(404)),
+            _ => return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist.")),
+        };
+        let meta = self.read_meta(bucket, key).await;
+        let crc = match meta.crc64 {
+            Some(crc) => crc,
+            None => crc64_file_prefix(&path, total).await?.value(),
+        };
+
+        let mut reply = Reply::new(200)
+            .header("etag", meta.etag.clone())
+            .header(CRC64_HEADER, crc.to_string())
+            .header("last-modified", http_date(meta.last_modified))
+            .header("accept-ranges", "bytes");
+        for (name, value) in &meta.metadata {
+            reply = reply.header(&format!("x-amz-meta-{}", name), value.clone());
+        }
+        if let Some(expires) = &meta.expires {
+            reply = reply.header("expires", expires.clone());
+        }
+        if !meta.tags.is_empty() {
+            reply = reply.header("x-amz-tagging-count", meta.tags.len().to_string());
+        }
+        if symlink.is_some() {
+            reply = reply.header("x-oss-object-type", "Symlink");
+        } else if meta.appendable {
+            reply = reply.header("x-oss-object-type", "Appendable");
+        }
+        if let Some(until) = meta.restored_until {
+            reply = reply.header("x-amz-restore", format!("ongoing-request=\"false\", expiry-date=\"{}\"", http_date(until)));
+        }
+
+        let (start, end) = match range.as_deref().and_then(|r| parse_range(r, total)) {
+            Some((start, end)) => {
+                reply.status = 206;
+                reply = reply.header("content-range", format!("bytes {}-{}/{}", start, end, total));
+                (start, end + 1)
+            }
+            None => (0, total),
+        };
+        reply = reply.header("content-length", (end - start).to_string());
+        if head_only {
+            return Ok(reply);
+        }
+        Ok(reply.body(read_range(&path, start, end - start).await?))
+    }
+
+    async fn put_object(&self, bucket: &str, key: &str, body: Vec<u8>, meta: ObjectMeta) -> Result<Reply, String> {
+        let etag = self.store_object(bucket, key, &body, meta).await?;
+        Ok(Reply::new(200).header("etag", etag).header(CRC64_HEADER, crc64(&body).to_string()))
+    }
+
+    /// OSS's AppendObject: `body` is added at `position`, which must be the
+    /// current length of an object created the same way.
+    async fn append_object(&self, bucket: &str, key: &str, position: u64, body: &[u8], new_meta: ObjectMeta) -> Result<Reply, String> {
+        let path = self.object_path(bucket, key);
+        let (mut data, meta) = match tokio::fs::read(&path).await {
+            Ok(data) => (data, self.read_meta(bucket, key).await),
+            Err(_) => (Vec::new(), new_meta),
+        };
+        if !data.is_empty() && !meta.appendable {
+            return Ok(Reply::error(409, "ObjectNotAppendable", "The object is not appendable."));
+        }
+        if position != data.len() as u64 {
+            return Ok(Reply::error(409, "PositionNotEqualToLength", "Position is not equal to file length.")
+                .header(NEXT_APPEND_POSITION_HEADER, data.len().to_string()));
+        }
+        data.extend_from_slice(body);
+        let etag = self.store_object(bucket, key, &data, ObjectMeta { etag: String::new(), appendable: true, ..meta }).await?;
+        Ok(Reply::new(200)
+            .header("etag", etag)
+            .header(CRC64_HEADER, crc64(&data).to_string())
+            .header(NEXT_APPEND_POSITION_HEADER, data.len().to_string()))
+    }
+
+    async fn put_symlink(&self, bucket: &str, key: &str, target: &str) -> Result<Reply, String> {
+        if !is_safe_key(target) {
+            return Ok(Reply::error(400, "InvalidTargetObject", "The symlink target is not a valid object name."));
+        }
+        let meta = ObjectMeta { symlink_target: Some(target.to_string()), ..ObjectMeta::default() };
+        let etag = self.store_object(bucket, key, b"", meta).await?;
+        Ok(Reply::new(200).header("etag", etag))
+    }
+
+    async fn get_symlink(&self, bucket: &str, key: &str) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        match self.read_meta(bucket, key).await.symlink_target {
+            Some(target) => Ok(Reply::new(200).header(SYMLINK_TARGET_HEADER, percent_encode_key(&target))),
+            None => Ok(Reply::error(400, "NotSymlink", "The specified object is not a symlink.")),
+        }
+    }
+
+    async fn copy_object(&self, bucket: &str, key: &str, source: &str) -> Result<Reply, String> {
+        let (source_bucket, source_key) = parse_copy_source(source).ok_or("invalid copy source")?;
+        let data = match tokio::fs::read(self.object_path(&source_bucket, &source_key)).await {
+            Ok(data) => data,
+            Err(_) => return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist.")),
+        };
+        let meta = self.read_meta(&source_bucket, &source_key).await;
+        let etag = self.store_object(bucket, key, &data, meta).await?;
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><CopyObjectResult><ETag>{}</ETag><LastModified>{}</LastModified></CopyObjectResult>",
+                              xml_escape(&etag), xml_date(DateTime::from(SystemTime::now()).secs()))))
+    }
+
+    async fn delete_objects(&self, bucket: &str, body: &[u8]) -> Result<Reply, String> {
+        let body = String::from_utf8_lossy(body);
+        let mut deleted = String::new();
+        for key in xml_values(&body, "Key") {
+            self.remove_object(bucket, &key).await;
+            deleted.push_str(&format!("<Deleted><Key>{}</Key></Deleted>", xml_escape(&key)));
+        }
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><DeleteResult>{}</DeleteResult>", deleted)))
+    }
+
+    async fn get_lifecycle(&self, bucket: &str) -> Result<Reply, String> {
+        match tokio::fs::read(self.lifecycle_path(bucket)).await {
+            Ok(body) => Ok(Reply::new(200).header("content-type", "application/xml").body(body)),
+            Err(_) => Ok(Reply::error(404, "NoSuchLifecycleConfiguration", "The lifecycle configuration does not exist.")),
+        }
+    }
+
+    async fn put_lifecycle(&self, bucket: &str, body: &[u8]) -> Result<Reply, String> {
+        let path = self.lifecycle_path(bucket);
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        tokio::fs::write(path, body).await.map_err(|e| e.to_string())?;
+        Ok(Reply::new(200))
+    }
+
+    async fn get_tagging(&self, bucket: &str, key: &str) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        let tags: String = self.read_meta(bucket, key).await.tags.iter()
+            .map(|(k, v)| format!("<Tag><Key>{}</Key><Value>{}</Value></Tag>", xml_escape(k), xml_escape(v)))
+            .collect();
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Tagging><TagSet>{}</TagSet></Tagging>", tags)))
+    }
+
+    async fn put_tagging(&self, bucket: &str, key: &str, body: &[u8]) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        let body = String::from_utf8_lossy(body);
+        let mut meta = self.read_meta(bucket, key).await;
+        meta.tags = xml_values(&body, "Key").into_iter().zip(xml_values(&body, "Value")).collect();
+        self.write_meta(bucket, key, &meta).await?;
+        Ok(Reply::new(200))
+    }
+
+    async fn get_acl(&self, bucket: &str, key: &str) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        let owner = "<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"CanonicalUser\"><ID>offline</ID></Grantee><Permission>FULL_CONTROL</Permission></Grant>";
+        let everyone = |permission: &str| format!("<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"Group\"><URI>{}</URI></Grantee><Permission>{}</Permission></Grant>",
+                                                  ALL_USERS_URI, permission);
+        let public = match self.read_meta(bucket, key).await.acl.as_deref() {
+            Some("public-read") => everyone("READ"),
+            Some("public-read-write") => everyone("READ") + &everyone("WRITE"),
+            _ => String::new(),
+        };
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><AccessControlPolicy><Owner><ID>offline</ID></Owner><AccessControlList>{}{}</AccessControlList></AccessControlPolicy>",
+                              owner, public)))
+    }
+
+    async fn put_acl(&self, bucket: &str, key: &str, acl: Option<String>) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        let mut meta = self.read_meta(bucket, key).await;
+        meta.acl = acl;
+        self.write_meta(bucket, key, &meta).await?;
+        Ok(Reply::new(200))
+    }
+
+    async fn restore_object(&self, bucket: &str, key: &str, body: &[u8]) -> Result<Reply, String> {
+        if !self.object_path(bucket, key).exists() {
+            return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist."));
+        }
+        let body = String::from_utf8_lossy(body);
+        let days: i64 = xml_values(&body, "Days").first().and_then(|days| days.parse().ok()).unwrap_or(1);
+        let mut meta = self.read_meta(bucket, key).await;
+        let status = if meta.restored_until.is_some() { 200 } else { 202 };
+        meta.restored_until = Some(DateTime::from(SystemTime::now()).secs() + days * 86400);
+        self.write_meta(bucket, key, &meta).await?;
+        Ok(Reply::new(status))
+    }
+
+    async fn create_upload(&self, bucket: &str, key: &str, meta: ObjectMeta) -> Result<Reply, String> {
+        let mut id = [0u8; 16];
+        SystemRandom::new().fill(&mut id).map_err(|_| messages::random_failed())?;
+        let upload_id = to_hex(&id);
+        let dir = self.upload_dir(&upload_id);
+        create_dir(&dir).await;
+        let pending = PendingUpload { bucket: bucket.into(), key: key.into(), meta };
+        let text = serde_json::to_string(&pending).map_err(|e| e.to_string())?;
+        tokio::fs::write(dir.join("upload.json"), text).await.map_err(|e| e.to_string())?;
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+                              xml_escape(bucket), xml_escape(key), upload_id)))
+    }
+
+    async fn read_upload(&self, upload_id: &str) -> Option<PendingUpload> {
+        let text = tokio::fs::read_to_string(self.upload_dir(upload_id).join("upload.json")).await.ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    async fn upload_part(&self, upload_id: &str, number: u32, body: Vec<u8>) -> Result<Reply, String> {
+        if self.read_upload(upload_id).await.is_none() {
+            return Ok(Reply::error(404, "NoSuchUpload", "The specified upload does not exist."));
+        }
+        tokio::fs::write(self.upload_dir(upload_id).join(number.to_string()), &body).await.map_err(|e| e.to_string())?;
+        Ok(Reply::new(200).header("etag", etag_of(&body)).header(CRC64_HEADER, crc64(&body).to_string()))
+    }
+
+    async fn upload_part_copy(&self, upload_id: &str, number: u32, source: &str, range: Option<String>) -> Result<Reply, String> {
+        let (source_bucket, source_key) = parse_copy_source(source).ok_or("invalid copy source")?;
+        let path = self.object_path(&source_bucket, &source_key);
+        let total = match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => return Ok(Reply::error(404, "NoSuchKey", "The specified key does not exist.")),
+        };
+        let (start, end) = range.as_deref().and_then(|r| parse_range(r, total)).map_or((0, total), |(start, end)| (start, end + 1));
+        let part = read_range(&path, start, end - start).await?;
+        let reply = self.upload_part(upload_id, number, part).await?;
+        let etag = reply.headers.iter().find(|(name, _)| name == "etag").map(|(_, v)| v.clone()).unwrap_or_default();
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><CopyPartResult><ETag>{}</ETag><LastModified>{}</LastModified></CopyPartResult>",
+                              xml_escape(&etag), xml_date(DateTime::from(SystemTime::now()).secs()))))
+    }
+
+    async fn complete_upload(&self, upload_id: &str, body: &[u8]) -> Result<Reply, String> {
+        let Some(pending) = self.read_upload(upload_id).await else {
+            return Ok(Reply::error(404, "NoSuchUpload", "The specified upload does not exist."));
+        };
+        let body = String::from_utf8_lossy(body);
+        let numbers = xml_values(&body, "PartNumber");
+        let dir = self.upload_dir(upload_id);
+        for number in &numbers {
+            if !dir.join(number).is_file() {
+                return Ok(Reply::error(400, "InvalidPart", &format!("part {} was not uploaded", number)));
+            }
+        }
+
+        // The parts are joined in a file next to them, then moved into place.
+        let joined = dir.join("object");
+        let mut output = File::create(&joined).await.map_err(|e| e.to_string())?;
+        let mut sha256 = digest::Context::new(&SHA256);
+        let mut crc = Crc64::new();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        for number in &numbers {
+            let mut part = File::open(dir.join(number)).await.map_err(|e| e.to_string())?;
+            loop {
+                let n = part.read(&mut buffer).await.map_err(|e| e.to_string())?;
+                if n == 0 {
+                    break;
+                }
+                sha256.update(&buffer[..n]);
+                crc.update(&buffer[..n]);
+                output.write_all(&buffer[..n]).await.map_err(|e| e.to_string())?;
+            }
+        }
+        output.flush().await.map_err(|e| e.to_string())?;
+        drop(output);
+
+        let mut meta = pending.meta;
+        meta.etag = format!("\"{}-{}\"", &to_hex(sha256.finish().as_ref())[..32], numbers.len());
+        meta.crc64 = Some(crc.value());
+        let etag = self.store_file(&pending.bucket, &pending.key, &joined, meta).await?;
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><CompleteMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>",
+                              xml_escape(&pending.bucket), xml_escape(&pending.key), xml_escape(&etag)))
+            .header(CRC64_HEADER, crc.value().to_string()))
+    }
+
+    async fn list_parts(&self, upload_id: &str) -> Result<Reply, String> {
+        let Some(pending) = self.read_upload(upload_id).await else {
+            return Ok(Reply::error(404, "NoSuchUpload", "The specified upload does not exist."));
+        };
+        let mut parts = Vec::new();
+        let mut entries = tokio::fs::read_dir(self.upload_dir(upload_id)).await.map_err(|e| e.to_string())?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Some(number) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else { continue };
+            let data = tokio::fs::read(entry.path()).await.map_err(|e| e.to_string())?;
+            parts.push((number, etag_of(&data), data.len()));
+        }
+        parts.sort();
+        let parts: String = parts.iter()
+            .map(|(number, etag, size)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag><Size>{}</Size></Part>", number, xml_escape(etag), size))
+            .collect();
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListPartsResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId><IsTruncated>false</IsTruncated>{}</ListPartsResult>",
+                              xml_escape(&pending.bucket), xml_escape(&pending.key), upload_id, parts)))
+    }
+
+    async fn list_uploads(&self, bucket: &str, prefix: &str) -> Result<Reply, String> {
+        let mut uploads = String::new();
+        let mut entries = match tokio::fs::read_dir(self.root.join(STATE_DIR).join("uploads")).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListMultipartUploadsResult><Bucket>{}</Bucket><IsTruncated>false</IsTruncated></ListMultipartUploadsResult>", xml_escape(bucket)))),
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let upload_id = entry.file_name().to_string_lossy().to_string();
+            if let Some(pending) = self.read_upload(&upload_id).await.filter(|p| p.bucket == bucket && p.key.starts_with(prefix)) {
+                let modified = entry.metadata().await.ok()
+                    .and_then(|m| m.modified().ok())
+                    .map(|t| DateTime::from(t).secs())
+                    .unwrap_or_default();
+                uploads.push_str(&format!("<Upload><Key>{}</Key><UploadId>{}</UploadId><Initiated>{}</Initiated></Upload>",
+                                          xml_escape(&pending.key), upload_id, xml_date(modified)));
+            }
+        }
+        Ok(Reply::xml(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListMultipartUploadsResult><Bucket>{}</Bucket><IsTruncated>false</IsTruncated>{}</ListMultipartUploadsResult>",
+                              xml_escape(bucket), uploads)))
+    }
+}
+
+/// Splits a request URI into its percent-encoded path and decoded query.
+fn split_uri(uri: &str) -> (String, HashMap<String, String>) {
+    let without_scheme = uri.split_once("://").map(|(_, rest)| rest).unwrap_or(uri);
+    let path_and_query = without_scheme.find('/').map(|i| &without_scheme[i..]).unwrap_or("/");
+    let (path, query) = path_and_query.split_once('?').unwrap_or((path_and_query, ""));
+    let query = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+    (path.to_string(), query)
+}
+
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                output.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).to_string()
+}
+
+fn is_safe_key(key: &str) -> bool {
+    let key = key.strip_suffix('/').unwrap_or(key);
+    key.split(['/', '\\']).all(|part| !(part.is_empty() || part == "." || part == ".."))
+}
+
+/// Returns the request body, undoing `aws-chunked` framing if the SDK used it.
+fn decode_body(request: &HttpRequest) -> Vec<u8> {
+    let body = request.body().bytes().unwrap_or_default();
+    let chunked = request.headers().get("content-encoding").is_some_and(|e| e.contains("aws-chunked"));
+    if !chunked {
+        return body.to_vec();
+    }
+
+    let mut output = Vec::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(line_end) = rest.windows(2).position(|w| w == b"\r\n") {
+        let size_text = String::from_utf8_lossy(&rest[..line_end]);
+        let size = usize::from_str_radix(size_text.split(';').next().unwrap_or("0").trim(), 16).unwrap_or(0);
+        rest = &rest[line_end + 2..];
+        if size == 0 || rest.len() < size {
+            break;
+        }
+        output.extend_from_slice(&rest[..size]);
+        rest = rest.get(size + 2..).unwrap_or_default();
+    }
+    output
+}
+
+/// Reads `len` bytes of the file at `path`, starting at `start`.
+async fn read_range(path: &Path, start: u64, len: u64) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).await.map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(start)).await.map_err(|e| e.to_string())?;
+    let mut data = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut data).await.map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// Collects `x-amz-meta-*`, tagging and expiry headers of a write request.
+fn request_meta(request: &HttpRequest) -> ObjectMeta {
+    let mut meta = ObjectMeta::default();
+    for (name, value) in request.headers() {
+        if let Some(name) = name.strip_prefix("x-amz-meta-") {
+            meta.metadata.insert(name.to_string(), value.to_string());
+        }
+    }
+    if let Some(tagging) = request.headers().get("x-amz-tagging") {
+        meta.tags = tagging.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (percent_decode(k), percent_decode(v)))
+            .collect();
+    }
+    meta.expires = request.headers().get("expires").map(str::to_string);
+    meta.acl = request.headers().get("x-amz-acl").map(str::to_string);
+    meta
+}
+
+fn parse_copy_source(source: &str) -> Option<(String, String)> {
+    let source = percent_decode(source.split('?').next().unwrap_or(source));
+    let (bucket, key) = source.trim_start_matches('/').split_once('/')?;
+    is_safe_key(key).then(|| (bucket.to_string(), key.to_string()))
+}
+
+/// Parses `bytes=start-end`, `bytes=start-` or `bytes=-suffix` into an
+/// inclusive range within `total` bytes.
+fn parse_range(range: &str, total: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    if total == 0 {
+        return None;
+    }
+    let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), Some(end)) => (start, end.min(total - 1)),
+        (Some(start), None) => (start, total - 1),
+        (None, Some(suffix)) => (total.saturating_sub(suffix), total - 1),
+        (None, None) => return None,
+    };
+    (start <= end).then_some((start, end))
+}
+
+fn etag_of(data: &[u8]) -> String {
+    format!("\"{}\"", &to_hex(digest(&SHA256, data).as_ref())[..32])
+}
+
+fn xml_date(secs: i64) -> String {
+    DateTime::from_secs(secs).fmt(DateTimeFormat::DateTime).unwrap_or_default()
+}
+
+fn http_date(secs: i64) -> String {
+    DateTime::from_secs(secs).fmt(DateTimeFormat::HttpDate).unwrap_or_default()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub(crate) fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&")
+}
+
+/// Values of every `<tag>` element, in document order.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split_once(close.as_str()).map(|(value, _)| xml_unescape(value)))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use crate::client::ShareMethod;
+    use crate::client::test::offline_client;
+    use crate::local::{parse_range, percent_decode};
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some((0, 9)));
+        assert_eq!(parse_range("bytes=90-200", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=50-", 100), Some((50, 99)));
+        assert_eq!(parse_range("bytes=-10", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=10-5", 100), None);
+        assert_eq!(percent_decode("a%20b%2Fc%E6%96%87"), "a b/c文");
+    }
+
+    #[tokio::test]
+    async fn test_offline_encrypted_round_trip() {
+        let (client, root) = offline_client("local_round_trip").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(root.join("report.bin"), &data).await.unwrap();
+
+        let client = client.with_part_size(8192);
+        client.upload_file("docs/", root.join("report.bin"), Some("correct horse"), Some(3600)).await.unwrap();
+        assert!(root.join("store/offline/docs/report.bin").exists());
+        assert!(client.expiry_of("docs/report.bin").await.unwrap().is_some());
+
+        let output = root.join("downloaded.bin");
+        client.download_file("docs/report.bin", &output, Some("correct horse")).await.unwrap();
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), data);
+        assert!(client.download_file("docs/report.bin", &output, Some("wrong")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offline_list_copy_delete() {
+        let (client, root) = offline_client("local_list_copy_delete").await;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            tokio::fs::write(root.join(name), name).await.unwrap();
+            client.upload_file("", root.join(name), None::<String>, None).await.unwrap();
+        }
+
+        let page = client.list_obj(Some(2), None, None).await.unwrap();
+        assert_eq!(page.is_truncated, Some(true));
+        let keys: Vec<String> = client.list_all(None).await.unwrap().into_iter().filter_map(|o| o.key).collect();
+        assert_eq!(keys, vec!["a.txt", "b.txt", "c.txt"]);
+
+        client.copy_obj("a.txt", "copies/a.txt").await.unwrap();
+        assert_eq!(client.head_obj("copies/a.txt").await.unwrap().content_length, Some(5));
+        client.delete_obj("a.txt").await.unwrap();
+        assert!(client.head_obj("a.txt").await.is_err());
+        let keys: Vec<String> = client.list_all(Some("copies/".into())).await.unwrap().into_iter().filter_map(|o| o.key).collect();
+        assert_eq!(keys, vec!["copies/a.txt"]);
+
+        let archive = client.clone().with_bucket("archive");
+        archive.copy_from("offline", "b.txt", "b.txt").await.unwrap();
+        assert_eq!(archive.head_obj("b.txt").await.unwrap().content_length, Some(5));
+        assert!(client.head_obj("b.txt").await.is_ok());
+
+        client.copy_obj("b.txt", "copies/b.txt").await.unwrap();
+        assert_eq!(client.delete_prefix("copies/").await.unwrap(), 2);
+        assert!(client.list_all(Some("copies/".into())).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_offline_ranges() {
+        let (client, root) = offline_client("local_ranges").await;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(root.join("data.bin"), &data).await.unwrap();
+        let client = client.with_part_size(8192);
+        client.upload_file("", root.join("data.bin"), None::<String>, None).await.unwrap();
+        assert_eq!(client.get_range("data.bin", 8000, 500).await.unwrap(), &data[8000..8500]);
+        assert_eq!(client.get_range("data.bin", 19_990, 100).await.unwrap(), &data[19_990..]);
+        assert!(!root.join("store/.rot/uploads").read_dir().unwrap().any(|_| true));
+
+        // A file put below the root by hand has no stored checksum.
+        tokio::fs::write(root.join("store/offline/plain.txt"), "plain").await.unwrap();
+        let head = client.head_obj("plain.txt").await.unwrap();
+        assert_eq!(head.content_length, Some(5));
+        assert_eq!(client.get_range("plain.txt", 1, 3).await.unwrap(), b"lai");
+    }
+
+    #[tokio::test]
+    async fn test_offline_share() {
+        let (client, root) = offline_client("local_share").await;
+        tokio::fs::write(root.join("a.txt"), "shared").await.unwrap();
+        client.upload_file("docs/", root.join("a.txt"), None::<String>, None).await.unwrap();
+
+        let url = client.presign("docs/a.txt", ShareMethod::Get, Duration::from_secs(60)).await.unwrap();
+        let path = url.strip_prefix("file://").unwrap();
+        assert_eq!(tokio::fs::read(path).await.unwrap(), b"shared");
+        assert!(client.presign("docs/b.txt", ShareMethod::Get, Duration::from_secs(60)).await.is_err());
+        assert!(client.presign("docs/b.txt", ShareMethod::Put, Duration::from_secs(60)).await.is_err());
+        assert!(client.presign_processed("docs/a.txt", "image/resize,w_80", Duration::from_secs(60)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offline_multipart_copy() {
+        let (client, root) = offline_client("local_multipart_copy").await;
+        tokio::fs::write(root.join("a.txt"), "multipart").await.unwrap();
+        let tags = BTreeMap::from([("team".to_string(), "raven".to_string())]);
+        let uploader = client.clone().with_upload_tags(tags.clone());
+        uploader.upload_if_changed("", root.join("a.txt"), None, Some(3600)).await.unwrap();
+        let source = client.head_obj("a.txt").await.unwrap();
+        assert!(source.metadata().is_some_and(|metadata| !metadata.is_empty()));
+
+        let client = client.with_multipart_copy_threshold(0);
+        client.copy_obj("a.txt", "b.txt").await.unwrap();
+        let copy = client.head_obj("b.txt").await.unwrap();
+        assert_eq!(copy.content_length, Some(9));
+        assert_eq!(copy.metadata, source.metadata);
+        let source_tags = client.get_tags("a.txt").await.unwrap();
+        assert_eq!(source_tags.get("team"), tags.get("team"));
+        assert_eq!(client.get_tags("b.txt").await.unwrap(), source_tags);
+        client.download_file("b.txt", &root.join("b.txt"), None::<String>).await.unwrap();
+        assert_eq!(tokio::fs::read(root.join("b.txt")).await.unwrap(), b"multipart");
+    }
+}
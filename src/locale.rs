@@ -0,0 +1,113 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use crate::config::ConfigFile;
+use crate::error::RotError;
+use crate::messages;
+
+/// The language rot writes its messages in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    ZhCn,
+    EnUs,
+}
+
+impl Locale {
+    /// Takes `zh-CN` and `en-US`, but also just `en` or a `LANG` value such
+    /// as `en_US.UTF-8`.
+    fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.', '@']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "zh" => Some(Locale::ZhCn),
+            "en" => Some(Locale::EnUs),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The message is looked up only after parsing, as `locale()` itself
+        // may still be parsing `ROT_LANG`.
+        Locale::from_tag(s).ok_or_else(|| messages::unknown_language(s))
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::EnUs => "en-US",
+        })
+    }
+}
+
+/// `CURRENT` before a locale has been chosen.
+const UNSET: u8 = u8::MAX;
+
+static CURRENT: AtomicU8 = AtomicU8::new(UNSET);
+
+/// The locale messages are written in: the one given to `set_locale`, else
+/// `ROT_LANG`, else Chinese. Tests always start out in Chinese, whatever
+/// `ROT_LANG` says, as they check the Chinese texts.
+pub fn locale() -> Locale {
+    match CURRENT.load(Ordering::Relaxed) {
+        UNSET => {
+            let locale = env_language().and_then(|language| Locale::from_tag(&language)).unwrap_or_default();
+            CURRENT.store(locale as u8, Ordering::Relaxed);
+            locale
+        }
+        value if value == Locale::EnUs as u8 => Locale::EnUs,
+        _ => Locale::ZhCn,
+    }
+}
+
+pub fn set_locale(locale: Locale) {
+    CURRENT.store(locale as u8, Ordering::Relaxed);
+}
+
+fn env_language() -> Option<String> {
+    if cfg!(test) {
+        return None;
+    }
+    std::env::var("ROT_LANG").ok().filter(|value| !value.is_empty())
+}
+
+/// Chooses the locale from `ROT_LANG` or, when that is unset, the `language`
+/// of `rot.json`. An unknown language is an error and leaves Chinese in
+/// place; a `rot.json` that can't be read is left for the command that
+/// needs it to report.
+pub async fn init() -> Result<(), RotError> {
+    let language = match env_language() {
+        Some(language) => language,
+        None => match ConfigFile::load().await {
+            Ok(Some(file)) => match file.language() {
+                Some(language) => language.to_string(),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        },
+    };
+    language.parse().map(set_locale).map_err(RotError::Config)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::locale::Locale;
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!("zh-CN".parse(), Ok(Locale::ZhCn));
+        assert_eq!("zh_CN.UTF-8".parse(), Ok(Locale::ZhCn));
+        assert_eq!("en".parse(), Ok(Locale::EnUs));
+        assert_eq!("EN-us".parse(), Ok(Locale::EnUs));
+        assert_eq!("en_GB.UTF-8@euro".parse(), Ok(Locale::EnUs));
+        assert!("fr-FR".parse::<Locale>().is_err());
+        assert!("".parse::<Locale>().is_err());
+        assert_eq!(Locale::EnUs.to_string().parse(), Ok(Locale::EnUs));
+    }
+}
@@ -0,0 +1,294 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::str::FromStr;
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use crate::constant::READ_BUFFER_SIZE;
+use crate::messages;
+use crate::utils::long_path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ManifestFormat {
+    Sha256Sums,
+    Json,
+}
+
+impl ManifestFormat {
+    pub fn filename(&self) -> &'static str {
+        match self {
+            ManifestFormat::Sha256Sums => "SHA256SUMS",
+            ManifestFormat::Json => "manifest.json",
+        }
+    }
+}
+
+impl FromStr for ManifestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256sums" | "sha256" => Ok(ManifestFormat::Sha256Sums),
+            "json" => Ok(ManifestFormat::Json),
+            _ => Err(messages::unknown_manifest_format(s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct ManifestEntry {
+    /// Path relative to the manifest, always `/`-separated.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Checksums of the local files in a batch upload. Digests are taken over
+/// the plaintext, so for encrypted uploads they match what `rot download -p`
+/// writes rather than the stored ciphertext.
+#[derive(Debug, Serialize)]
+pub(crate) struct Manifest {
+    pub algorithm: &'static str,
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Hashes every file in `files`, naming each relative to `root`.
+    pub async fn build(root: &Path, files: &[PathBuf]) -> Result<Self, String> {
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let (sha256, size) = sha256_file(file).await?;
+            let relative = file.strip_prefix(root).unwrap_or(file);
+            let path = relative.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            entries.push(ManifestEntry { path, size, sha256 });
+        }
+        Ok(Self { algorithm: "sha256", files: entries })
+    }
+
+    pub fn render(&self, format: ManifestFormat) -> String {
+        match format {
+            ManifestFormat::Sha256Sums => self.files.iter()
+                .map(|entry| format!("{}  {}\n", entry.sha256, entry.path))
+                .collect(),
+            ManifestFormat::Json => serde_json::to_string_pretty(self).expect("manifest is serializable"),
+        }
+    }
+}
+
+/// Returns the lowercase hex SHA-256 digest of the file at `path` and its size.
+pub(crate) async fn sha256_file(path: &Path) -> Result<(String, u64), String> {
+    let (digest, size) = sha256_digest(path).await?;
+    Ok((to_hex(&digest), size))
+}
+
+pub(crate) async fn sha256_digest(path: &Path) -> Result<(Vec<u8>, u64), String> {
+    let mut file = File::open(long_path(path)).await.map_err(|e| messages::cannot_open(path.display(), e))?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buffer).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buffer[..n]);
+        size += n as u64;
+    }
+    Ok((context.finish().as_ref().to_vec(), size))
+}
+
+/// SHA-256 of a whole stream plus one digest per `block_size` range, so two
+/// streams can be compared range by range without holding either in memory.
+pub(crate) struct BlockHasher {
+    block_size: usize,
+    total: Context,
+    block: Context,
+    block_len: usize,
+    blocks: Vec<Vec<u8>>,
+    len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BlockDigests {
+    pub sha256: String,
+    pub len: u64,
+    pub block_size: usize,
+    pub blocks: Vec<Vec<u8>>,
+}
+
+impl BlockHasher {
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            block_size: block_size.max(1),
+            total: Context::new(&SHA256),
+            block: Context::new(&SHA256),
+            block_len: 0,
+            blocks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total.update(data);
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (self.block_size - self.block_len).min(data.len());
+            self.block.update(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == self.block_size {
+                let block = std::mem::replace(&mut self.block, Context::new(&SHA256));
+                self.blocks.push(block.finish().as_ref().to_vec());
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> BlockDigests {
+        if self.block_len > 0 {
+            self.blocks.push(self.block.finish().as_ref().to_vec());
+        }
+        BlockDigests {
+            sha256: to_hex(self.total.finish().as_ref()),
+            len: self.len,
+            block_size: self.block_size,
+            blocks: self.blocks,
+        }
+    }
+}
+
+impl BlockDigests {
+    /// Byte ranges (start inclusive, end exclusive) where `self` and `other`
+    /// differ, merging adjacent blocks. Both must use the same block size.
+    pub fn differing_ranges(&self, other: &BlockDigests) -> Vec<(u64, u64)> {
+        let block_size = self.block_size as u64;
+        let longest = self.len.max(other.len);
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for index in 0..self.blocks.len().max(other.blocks.len()) {
+            if self.blocks.get(index) == other.blocks.get(index) {
+                continue;
+            }
+            let start = index as u64 * block_size;
+            let end = (start + block_size).min(longest);
+            match ranges.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+        ranges
+    }
+}
+
+/// Hashes everything read through it, for sources that can only be read
+/// once. The digest stays reachable through [`DigestHandle`] after the
+/// reader itself has been moved into another task. A disabled reader only
+/// passes data through.
+pub(crate) struct HashingReader<R> {
+    inner: R,
+    context: Option<Arc<Mutex<Context>>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct DigestHandle(Arc<Mutex<Context>>);
+
+impl DigestHandle {
+    pub fn finish(self) -> Vec<u8> {
+        let context = self.0.lock().unwrap().clone();
+        context.finish().as_ref().to_vec()
+    }
+}
+
+impl<R> HashingReader<R> {
+    pub fn new(inner: R, enabled: bool) -> Self {
+        let context = enabled.then(|| Arc::new(Mutex::new(Context::new(&SHA256))));
+        Self { inner, context }
+    }
+
+    pub fn digest_handle(&self) -> Option<DigestHandle> {
+        self.context.as_ref().map(|context| DigestHandle(Arc::clone(context)))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(context)) = (&result, &self.context) {
+            context.lock().unwrap().update(&buf.filled()[before..]);
+        }
+        result
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use tokio::fs::DirBuilder;
+    use tokio::io::AsyncReadExt;
+    use crate::manifest::{BlockHasher, HashingReader, Manifest, ManifestFormat, to_hex};
+
+    #[tokio::test]
+    async fn test_manifest_sha256sums() {
+        let root = Path::new("target/test_manifest/site");
+        DirBuilder::new().recursive(true).create(root.join("css")).await.unwrap();
+        tokio::fs::write(root.join("index.html"), b"").await.unwrap();
+        tokio::fs::write(root.join("css/app.css"), b"abc").await.unwrap();
+
+        let files = vec![root.join("css/app.css"), root.join("index.html")];
+        let manifest = Manifest::build(root, &files).await.unwrap();
+        assert_eq!(manifest.render(ManifestFormat::Sha256Sums),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  css/app.css\n\
+                    e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  index.html\n");
+        assert_eq!(manifest.files[0].size, 3);
+    }
+
+    #[tokio::test]
+    async fn test_hashing_reader() {
+        let mut reader = HashingReader::new(&b"abc"[..], true);
+        let digest = reader.digest_handle().unwrap();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).await.unwrap();
+        assert_eq!(output, b"abc");
+        assert_eq!(to_hex(&digest.finish()), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_block_differences() {
+        let local: Vec<u8> = (0..100u8).collect();
+        let mut remote = local.clone();
+        remote[15] = 0;
+        remote[25] = 0;
+        remote[70] = 0;
+        remote.extend_from_slice(&[1, 2, 3]);
+
+        let digest = |data: &[u8]| {
+            let mut hasher = BlockHasher::new(10);
+            for piece in data.chunks(7) {
+                hasher.update(piece);
+            }
+            hasher.finish()
+        };
+        let (local, remote) = (digest(&local), digest(&remote));
+        assert_eq!(local.blocks.len(), 10);
+        assert_eq!(local.differing_ranges(&local), vec![]);
+        assert_eq!(local.differing_ranges(&remote), vec![(10, 30), (70, 80), (100, 103)]);
+    }
+
+    #[test]
+    fn test_parse_manifest_format() {
+        assert_eq!("SHA256SUMS".parse::<ManifestFormat>().unwrap(), ManifestFormat::Sha256Sums);
+        assert_eq!("json".parse::<ManifestFormat>().unwrap(), ManifestFormat::Json);
+        assert!("md5".parse::<ManifestFormat>().is_err());
+    }
+}
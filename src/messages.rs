@@ -0,0 +1,1653 @@
+//! The texts rot shows its users, in every locale it speaks. Each message is
+//! a function that formats it for the current `locale()`.
+
+use std::fmt::Display;
+use crate::locale::{locale, Locale};
+
+/// Declares `name(args) => "中文", "English";` as a function taking the
+/// arguments in order. Both texts name the arguments they use, as in `{key}`.
+macro_rules! messages {
+    ($($name:ident($($arg:ident),*) => $zh:literal, $en:literal;)*) => {
+        $(message!($name($($arg),*) => $zh, $en);)*
+    };
+}
+
+macro_rules! message {
+    ($name:ident() => $zh:literal, $en:literal) => {
+        pub fn $name() -> String {
+            match locale() {
+                Locale::ZhCn => $zh.into(),
+                Locale::EnUs => $en.into(),
+            }
+        }
+    };
+    ($name:ident($($arg:ident),+) => $zh:literal, $en:literal) => {
+        pub fn $name($($arg: impl Display),+) -> String {
+            match locale() {
+                Locale::ZhCn => format!($zh, $($arg = $arg),+),
+                Locale::EnUs => format!($en, $($arg = $arg),+),
+            }
+        }
+    };
+}
+
+// What `ensure_writable` refuses, as in "已拒绝上传操作" and "Refused to
+// upload".
+messages! {
+    action_upload() => "上传", "upload";
+    action_reencrypt() => "重新加密", "re-encrypt";
+    action_abort_upload() => "取消上传", "abort uploads";
+    action_presign_upload() => "生成上传链接", "create upload links";
+    action_set_tags() => "设置标签", "set tags";
+    action_delete_tags() => "删除标签", "delete tags";
+    action_set_acl() => "设置访问权限", "set ACLs";
+    action_append() => "追加", "append";
+    action_symlink() => "创建符号链接", "create symlinks";
+    action_create_bucket() => "创建存储桶", "create buckets";
+    action_delete_bucket() => "删除存储桶", "delete buckets";
+    action_set_lifecycle() => "设置生命周期规则", "set lifecycle rules";
+    action_delete_lifecycle() => "删除生命周期规则", "delete lifecycle rules";
+    action_copy() => "复制", "copy";
+    action_delete() => "删除", "delete";
+    action_restore() => "解冻", "restore archived objects";
+    action_restore_version() => "恢复", "restore versions";
+    action_move() => "移动", "move";
+    action_rename() => "重命名", "rename";
+    action_watch() => "监视上传", "watch and upload";
+    action_backup() => "备份", "back up";
+    action_sync() => "同步", "sync";
+}
+
+// Settings and options.
+messages! {
+    unknown_language(value) =>
+        "未知的语言 '{value}'，可选 zh-CN 或 en-US。",
+        "Unknown language '{value}'; use zh-CN or en-US.";
+    missing_output_format() =>
+        "--output 需要指定格式：text 或 json。",
+        "--output needs a format: text or json.";
+    missing_profile() =>
+        "--profile 需要指定配置名称。",
+        "--profile needs a profile name.";
+    missing_duration(option, example) =>
+        "{option} 需要指定时长，例如 {example}。",
+        "{option} needs a duration, such as {example}.";
+    missing_attempts() =>
+        "--max-attempts 需要指定次数。",
+        "--max-attempts needs a number.";
+    missing_bwlimit() =>
+        "--bwlimit 需要指定带宽，例如 5MB/s。",
+        "--bwlimit needs a bandwidth, such as 5MB/s.";
+    offline_cache_missing() =>
+        "无法定位离线模式的缓存目录。",
+        "Cannot find a cache directory for offline mode.";
+    credentials_missing() =>
+        "尚未配置凭证。请使用 rot config set <字段> <值> 填写 access_key_id、secret_access_key、region、endpoint_url 和 bucket，\
+         或设置 ROT_* 环境变量，或使用 --offline 在本地体验。",
+        "No credentials are configured yet. Fill in access_key_id, secret_access_key, region, endpoint_url and bucket \
+         with rot config set <field> <value>, set the ROT_* environment variables, or try rot locally with --offline.";
+    cancelling() =>
+        "正在取消，再次按 Ctrl+C 立即退出。",
+        "Cancelling; press Ctrl+C again to quit at once.";
+    missing_command() =>
+        "缺少主指令！",
+        "Missing command!";
+    unknown_command(name) =>
+        "未找到命令： {name}",
+        "Unknown command: {name}";
+    unsupported_share_method(method) =>
+        "不支持的方法：'{method}'，可选值为 GET 或 PUT。",
+        "Unsupported method '{method}'; use GET or PUT.";
+    invalid_concurrency(value) =>
+        "无法将 '{value}' 解析为并发数，请提供正整数或 auto。",
+        "Cannot read '{value}' as a concurrency; give a positive integer or auto.";
+    invalid_tag(tag) =>
+        "无效的标签 '{tag}'，格式应为 键=值。",
+        "Invalid tag '{tag}'; the format is key=value.";
+    invalid_tag_length(tag, max_key, max_value) =>
+        "无效的标签 '{tag}'：键不能为空且最长 {max_key} 个字符，值最长 {max_value} 个字符。",
+        "Invalid tag '{tag}': the key takes 1 to {max_key} characters and the value at most {max_value}.";
+    too_many_tags(max) =>
+        "每个对象最多 {max} 个标签。",
+        "An object can have at most {max} tags.";
+    unknown_acl(value) =>
+        "未知的访问权限 '{value}'，可选 private 或 public-read。",
+        "Unknown ACL '{value}'; use private or public-read.";
+    invalid_expiry(error) =>
+        "无效的有效期：{error}",
+        "Invalid expiry: {error}";
+    offline_upload_link() =>
+        "离线模式下无法生成上传链接，请直接上传文件。",
+        "Upload links can't be created offline; upload the file instead.";
+    offline_process() =>
+        "离线模式下不支持 --process，离线存储无法处理图片。",
+        "--process isn't supported offline, as the offline store can't process images.";
+    local_needs_root() =>
+        "local 类型的配置需要设置 root 目录。",
+        "A local profile needs a root directory.";
+    provider_without_s3(provider) =>
+        "{provider} 类型的配置没有 S3 接口，只能通过 ObjectStore 访问。",
+        "{provider} profiles have no S3 API and can only be used through ObjectStore.";
+    read_only_bucket(action) =>
+        "当前为只读模式，已拒绝{action}操作。如需修改存储桶，请去掉 --read-only 或配置中的 read_only。",
+        "Refused to {action} in read-only mode. To change the bucket, drop --read-only or read_only from the configuration.";
+    read_only_store(action) =>
+        "当前为只读模式，已拒绝{action}操作。如需修改存储，请去掉 --read-only 或配置中的 read_only。",
+        "Refused to {action} in read-only mode. To change the storage, drop --read-only or read_only from the configuration.";
+    dry_run_refused(action) =>
+        "演练模式（--dry-run）下不会执行{action}操作。",
+        "Won't {action} in a dry run (--dry-run).";
+    unknown_size_unit(text) =>
+        "无法识别的大小单位：'{text}'。",
+        "Unknown size unit: '{text}'.";
+    invalid_size(text) =>
+        "无法将 '{text}' 解析为大小。",
+        "Can't read '{text}' as a size.";
+    zero_bwlimit(text) =>
+        "带宽限制必须大于 0：'{text}'。",
+        "The bandwidth limit must be above 0: '{text}'.";
+    unknown_time_unit(text) =>
+        "无法识别的时间单位：'{text}'，可用单位为 s、m、h、d、w。",
+        "Unknown time unit: '{text}'; use s, m, h, d or w.";
+    invalid_duration(text) =>
+        "无法将 '{text}' 解析为时长。",
+        "Can't read '{text}' as a duration.";
+    time_invalid(text) =>
+        "无法解析时间 '{text}'，请使用 30d 这样的时长或 2024-01-01 这样的日期。",
+        "Can't read the time '{text}'; use a duration such as 30d or a date such as 2024-01-01.";
+    invalid_bucket_name(name) =>
+        "无效的存储桶名称 '{name}'：须为 3 到 63 个小写字母、数字或短横线，且不能以短横线开头或结尾。",
+        "Invalid bucket name '{name}': it must be 3 to 63 lowercase letters, digits or hyphens, and can't start or end with a hyphen.";
+    confirm_needs_terminal() =>
+        "当前不是交互式终端，请使用 --yes 确认操作。",
+        "Not running in a terminal; use --yes to confirm.";
+    proxy_invalid(url, error) =>
+        "无效的代理地址 '{url}'：{error}",
+        "Invalid proxy address '{url}': {error}";
+    socks_proxy(url) =>
+        "暂不支持 SOCKS 代理 '{url}'，请使用 HTTP 或 HTTPS 代理。",
+        "SOCKS proxies such as '{url}' aren't supported yet; use an HTTP or HTTPS proxy.";
+    proxy_malformed(url) =>
+        "无效的代理地址 '{url}'，应形如 http://[用户名:密码@]主机:端口。",
+        "Invalid proxy address '{url}'; it should look like http://[user:password@]host:port.";
+    unknown_manifest_format(value) =>
+        "无效的清单格式：'{value}'，可选值为 sha256sums 或 json。",
+        "Unknown manifest format '{value}'; use sha256sums or json.";
+    unknown_output_format(value) =>
+        "未知的输出格式 '{value}'，可选 text 或 json。",
+        "Unknown output format '{value}'; use text or json.";
+    regex_invalid(error) =>
+        "正则表达式无效：{error}",
+        "Invalid regular expression: {error}";
+    list_separator() => "，", ", ";
+    name_separator() => "、", ", ";
+    none() => "无", "none";
+    unknown_provider(value) =>
+        "未知的服务类型 '{value}'，可选：aliyun、s3、b2、local、sftp、webdav。",
+        "Unknown provider '{value}'; use aliyun, s3, b2, local, sftp or webdav.";
+    invalid_attempts(value) =>
+        "无法将 '{value}' 解析为尝试次数，请提供正整数。",
+        "Can't read '{value}' as a number of attempts; give a positive whole number.";
+    invalid_bool(field, value) =>
+        "{field} 只能为 true 或 false，而不是 '{value}'。",
+        "{field} can only be true or false, not '{value}'.";
+    unknown_config_field(field, fields) =>
+        "未知的配置项 '{field}'，可选：default_profile、language、{fields}。",
+        "Unknown setting '{field}'; use default_profile, language, {fields}.";
+    unknown_profile(name, names) =>
+        "rot.json 中没有名为 '{name}' 的配置，可用的配置：{names}。",
+        "rot.json has no profile called '{name}'; the profiles are: {names}.";
+    no_default_profile() =>
+        "rot.json 顶层没有填写凭证，请使用 --profile 选择配置，或设置 default_profile。",
+        "rot.json has no top-level credentials; choose a profile with --profile, or set default_profile.";
+    config_file_invalid(path, error) =>
+        "无法解析 {path}：{error}。可以修正该文件，或删除后使用 rot config set 重新配置。",
+        "Can't read {path}: {error}. Fix the file, or delete it and set things up again with rot config set.";
+    config_dir_missing() =>
+        "无法定位配置目录。",
+        "Can't find the configuration folder.";
+    config_usage() =>
+        "用法：rot config set <字段> <值> | rot config set secret_access_key [<值>] --keyring | rot config get <字段> | rot config show [--redact]",
+        "Usage: rot config set <field> <value> | rot config set secret_access_key [<value>] --keyring | rot config get <field> | rot config show [--redact]";
+    keyring_needs_key_id() =>
+        "钥匙串中的密钥按 access_key_id 保存，请先设置 access_key_id。",
+        "Secrets are stored in the keychain under the access_key_id, so set access_key_id first.";
+    secret_prompt() =>
+        "secret_access_key：",
+        "secret_access_key: ";
+    secret_saved_to_keyring() =>
+        "已将 secret_access_key 保存到系统钥匙串。",
+        "Stored the secret_access_key in the system keychain.";
+    config_set(field, value) =>
+        "已将 {field} 设置为 '{value}'。",
+        "Set {field} to '{value}'.";
+    path_invalid() =>
+        "请输入正确的文件路径！",
+        "Please give a valid file path.";
+    current_dir_missing(error) =>
+        "无法获取当前目录：{error}",
+        "Couldn't get the current folder: {error}";
+    version_id_single_object() =>
+        "--version-id 只能用于下载单个对象。",
+        "--version-id only works when downloading a single object.";
+    key_options_conflict() =>
+        "-p、--key、--key-file、--recipient 与 --identity 只能使用其中一种！",
+        "Use only one of -p, --key, --key-file, --recipient and --identity.";
+    invalid_seconds(option, value) =>
+        "无法将 `{option}` 参数的值 '{value}' 解析为秒数，请确保你提供的是一个正整数。",
+        "Cannot read '{value}' given to `{option}` as seconds; give a positive integer.";
+    invalid_integer(option, value) =>
+        "无法将 `{option}` 参数的值 '{value}' 解析为整数，请确保你提供的是一个有效的整数值。",
+        "Cannot read '{value}' given to `{option}` as an integer; give a valid integer.";
+    hide_name_needs_encryption() =>
+        "--hide-name 只能用于加密上传，请同时使用 -p、--encrypt、--key、--key-file 或 --recipient。",
+        "--hide-name only works for encrypted uploads; add -p, --encrypt, --key, --key-file or --recipient.";
+    hide_name_conflict() =>
+        "--hide-name 不能与 --sign-key 或 --manifest 同时使用，否则签名和清单会暴露文件名。",
+        "--hide-name can't be combined with --sign-key or --manifest, as signatures and manifests would give the file names away.";
+    compress_needs_encryption() =>
+        "--compress 只能用于加密上传，请同时使用 -p、--encrypt、--key、--key-file 或 --recipient。",
+        "--compress only works for encrypted uploads; add -p, --encrypt, --key, --key-file or --recipient.";
+    compression_level_invalid(value) =>
+        "压缩级别无效：{value}，应为 1 到 22 之间的整数。",
+        "Invalid compression level {value}; give an integer from 1 to 22.";
+    invalid_depth(value) =>
+        "无法将 `--depth` 参数的值 '{value}' 解析为非负整数。",
+        "Cannot read '{value}' given to `--depth` as a non-negative integer.";
+    new_key_options_conflict() =>
+        "--new-password、--new-key、--new-key-file 与 --new-recipient 只能使用其中一种！",
+        "Use only one of --new-password, --new-key, --new-key-file and --new-recipient.";
+    invalid_days(value) =>
+        "无法将 `--days` 参数的值 '{value}' 解析为天数，请提供正整数。",
+        "Cannot read '{value}' given to `--days` as a number of days; give a positive integer.";
+    unknown_tier(value) =>
+        "未知的解冻优先级 '{value}'，可选 Expedited、Standard 或 Bulk。",
+        "Unknown restore tier '{value}'; use Expedited, Standard or Bulk.";
+    invalid_count(option, value) =>
+        "无法将 `{option}` 参数的值 '{value}' 解析为数量。",
+        "Cannot read '{value}' given to `{option}` as a count.";
+    part_size_too_small(size, min) =>
+        "分块大小 {size} 过小，OSS 要求除最后一块外每块至少 {min}。",
+        "The part size {size} is too small; OSS needs every part but the last to be at least {min}.";
+    unsupported_exec(command) =>
+        "--exec 目前只支持 rm，不支持 {command}。",
+        "--exec only supports rm for now, not {command}.";
+}
+
+// Buckets and objects.
+messages! {
+    request_failed(operation, error) =>
+        "请求 {operation} 失败：{error}",
+        "Request {operation} failed: {error}";
+    missing_upload_id(operation) =>
+        "{operation} 的响应中缺少 upload id。",
+        "The {operation} response has no upload id.";
+    object_exists(key) =>
+        "对象 {key} 已存在，如需覆盖请使用 --force。",
+        "Object {key} already exists; use --force to overwrite it.";
+    local_file_exists(path) =>
+        "本地文件 {path} 已存在，如需覆盖请使用 --force。",
+        "Local file {path} already exists; use --force to overwrite it.";
+    file_name_not_utf8(path) =>
+        "文件名不是有效的 UTF-8，无法作为对象名：{path}",
+        "The file name isn't valid UTF-8 and can't be an object key: {path}";
+    no_file_name(path) =>
+        "无法获取文件名：{path}",
+        "Couldn't get the file name of {path}";
+    object_not_found(key) =>
+        "对象 {key} 不存在！",
+        "Object {key} doesn't exist!";
+    version_not_found(key, version) =>
+        "对象 {key} 的版本 {version} 不存在！",
+        "Version {version} of object {key} doesn't exist!";
+    source_not_found(bucket, key) =>
+        "源对象 {bucket}/{key} 不存在或无权访问！",
+        "Source object {bucket}/{key} doesn't exist or can't be read!";
+    not_encrypted(key) =>
+        "{key} 未加密，无法重新加密。",
+        "{key} isn't encrypted, so it can't be re-encrypted.";
+    append_contended(key) =>
+        "对象 {key} 正被其他程序频繁追加，请稍后再试。",
+        "Object {key} is busy being appended to by others; try again later.";
+    not_appendable(key) =>
+        "对象 {key} 不是可追加对象，只能追加到由 rot append 创建的对象。",
+        "Object {key} isn't appendable; only objects created by rot append can be appended to.";
+    appended_object(key) =>
+        "追加后的 {key}",
+        "{key} after appending";
+    not_a_symlink(key) =>
+        "对象 {key} 不是符号链接！",
+        "Object {key} isn't a symlink!";
+    not_archived(key) =>
+        "对象 {key} 不是归档类型，无需解冻。",
+        "Object {key} isn't archived and needs no restoring.";
+    delete_failed(count, key, reason) =>
+        "{count} 个对象删除失败，例如 {key}：{reason}",
+        "Failed to delete {count} objects, such as {key}: {reason}";
+    bucket_name_taken(name) =>
+        "存储桶名称 {name} 已被占用！",
+        "The bucket name {name} is already taken!";
+    bucket_exists(name) =>
+        "存储桶 {name} 已存在！",
+        "Bucket {name} already exists!";
+    bucket_not_empty(name) =>
+        "存储桶 {name} 不为空，请先删除其中的对象和未完成的分片上传。",
+        "Bucket {name} isn't empty; delete its objects and unfinished multipart uploads first.";
+    bucket_not_found(name) =>
+        "存储桶 {name} 不存在！",
+        "Bucket {name} doesn't exist!";
+    lifecycle_rules_invalid(error) =>
+        "无法解析生命周期规则：{error}",
+        "Can't read the lifecycle rules: {error}";
+    lifecycle_id_invalid() =>
+        "规则 id 不能为空，且最长 255 个字符。",
+        "A rule id can't be empty and is at most 255 characters long.";
+    lifecycle_id_repeated(id) =>
+        "规则 id {id} 重复。",
+        "The rule id {id} is used twice.";
+    lifecycle_rule_empty(id) =>
+        "规则 {id} 需要设置 expire_days 或 archive_days。",
+        "Rule {id} needs expire_days or archive_days.";
+    lifecycle_days_invalid(id) =>
+        "规则 {id} 的天数必须为正整数。",
+        "The days of rule {id} must be positive whole numbers.";
+    lifecycle_archive_after_expiry(id) =>
+        "规则 {id} 的 archive_days 必须小于 expire_days，否则对象在转为归档前就已删除。",
+        "The archive_days of rule {id} must be less than its expire_days, or objects are deleted before they are archived.";
+    lifecycle_archive_after(days) =>
+        "{days} 天后转为归档",
+        "archive after {days} days";
+    lifecycle_expire_after(days) =>
+        "{days} 天后删除",
+        "delete after {days} days";
+    lifecycle_rule(id, prefix, actions) =>
+        "{id}（{prefix}*）：{actions}",
+        "{id} ({prefix}*): {actions}";
+    lifecycle_rule_disabled(id, prefix, actions) =>
+        "{id}（{prefix}*）：{actions}（已停用）",
+        "{id} ({prefix}*): {actions} (disabled)";
+    no_objects() =>
+        "该路径下不存在文件！",
+        "There are no objects under this path.";
+    key_without_file_name(key) =>
+        "无法从对象名 {key} 得到文件名！",
+        "Couldn't get a file name from the object key {key}.";
+    listing_truncated(count) =>
+        "仅显示了前 {count} 个对象，使用 --all 列出全部。",
+        "Showing only the first {count} objects; use --all to list them all.";
+    index_missing(bucket) =>
+        "存储桶 {bucket} 还没有索引，请先运行 rot index refresh。",
+        "The bucket {bucket} has no index yet; run rot index refresh first.";
+    listed_from_index(refreshed, age) =>
+        "以上结果来自 {refreshed} 刷新的索引（{age}）。",
+        "Listed from the index refreshed at {refreshed} ({age}).";
+    listed_from_stale_index(refreshed, age) =>
+        "以上结果来自 {refreshed} 刷新的索引（{age}），可能已过时，可运行 rot index refresh 更新。",
+        "Listed from the index refreshed at {refreshed} ({age}), which may be out of date; run rot index refresh to update it.";
+    index_usage() =>
+        "用法：rot index refresh | rot index status",
+        "Usage: rot index refresh | rot index status";
+    index_refreshed(bucket, count) =>
+        "已为存储桶 {bucket} 建立索引：{count} 个对象。",
+        "Indexed the bucket {bucket}: {count} objects.";
+    index_status(bucket, count, size, refreshed, age) =>
+        "存储桶 {bucket} 的索引：{count} 个对象，共 {size}，刷新于 {refreshed}（{age}）。",
+        "Index of the bucket {bucket}: {count} objects, {size} in all, refreshed at {refreshed} ({age}).";
+    no_index(bucket) =>
+        "存储桶 {bucket} 还没有索引。",
+        "The bucket {bucket} has no index yet.";
+    usage_line(size, count, prefix) =>
+        "{size}  {count} 个对象  {prefix}",
+        "{size}  {count} objects  {prefix}";
+    whole_bucket() =>
+        "（整个存储桶）",
+        "(whole bucket)";
+    stat_usage() =>
+        "用法：rot stat <对象>",
+        "Usage: rot stat <key>";
+    stat_key(key) => "对象：{key}", "Key: {key}";
+    stat_size(bytes, size) => "大小：{bytes} ({size})", "Size: {bytes} ({size})";
+    stat_type(content_type) => "类型：{content_type}", "Type: {content_type}";
+    stat_etag(e_tag) => "ETag：{e_tag}", "ETag: {e_tag}";
+    stat_modified(time) => "修改时间：{time}", "Modified: {time}";
+    stat_storage_class(class) => "存储类型：{class}", "Storage class: {class}";
+    stat_metadata() => "元数据：", "Metadata:";
+    cat_usage() =>
+        "用法：rot cat <对象> [-p 密码 | --decrypt | --key 名称 | --key-file 密钥文件 | --identity 私钥文件]",
+        "Usage: rot cat <key> [-p password | --decrypt | --key name | --key-file key-file | --identity identity-file]";
+    cannot_write_stdout(error) =>
+        "无法写入标准输出：{error}",
+        "Can't write to standard output: {error}";
+    cannot_read_stdin(error) =>
+        "无法读取标准输入：{error}",
+        "Can't read standard input: {error}";
+    append_usage() =>
+        "用法：rot append <对象> <文件|->",
+        "Usage: rot append <key> <file|->";
+    planned_append(key, size) =>
+        "将向 {key} 追加 {size}（未执行任何操作）。",
+        "Would append {size} to {key} (nothing was done).";
+    appended(key, size, length) =>
+        "已向 {key} 追加 {size}，当前大小 {length}。",
+        "Appended {size} to {key}, which is now {length}.";
+    tag_usage() =>
+        "用法：rot tag get <对象> | rot tag set <对象> 键=值 [...] | rot tag del <对象> [键 ...]",
+        "Usage: rot tag get <key> | rot tag set <key> name=value [...] | rot tag del <key> [name ...]";
+    no_tags(key) =>
+        "对象 {key} 没有标签。",
+        "The object {key} has no tags.";
+    too_many_tags_after(max, count) =>
+        "每个对象最多 {max} 个标签，设置后将有 {count} 个。",
+        "An object can have at most {max} tags; this would leave {count}.";
+    unknown_tag_action(action) =>
+        "未知的标签操作：{action}，可用操作为 get、set、del。",
+        "Unknown tag action {action}; use get, set or del.";
+    planned_tags(key, tags) =>
+        "将 {key} 的标签设为：{tags}（未执行任何操作）",
+        "Would set the tags of {key} to: {tags} (nothing was done)";
+    tags_deleted(key) =>
+        "已删除 {key} 的全部标签。",
+        "Deleted all tags of {key}.";
+    tags_updated(key, tags) =>
+        "已更新 {key} 的标签：{tags}",
+        "Updated the tags of {key}: {tags}";
+    acl_usage() =>
+        "用法：rot acl get <对象> | rot acl set <对象> private|public-read",
+        "Usage: rot acl get <key> | rot acl set <key> private|public-read";
+    planned_acl(key, acl) =>
+        "将 {key} 的访问权限设为 {acl}（未执行任何操作）。",
+        "Would set the ACL of {key} to {acl} (nothing was done).";
+    acl_set(key, acl) =>
+        "已将 {key} 的访问权限设为 {acl}。",
+        "Set the ACL of {key} to {acl}.";
+    restore_usage() =>
+        "用法：rot restore <对象> [--days 1] [--tier Expedited|Standard|Bulk] [--wait] [--interval 1m]",
+        "Usage: rot restore <key> [--days 1] [--tier Expedited|Standard|Bulk] [--wait] [--interval 1m]";
+    restored(key) =>
+        "{key} 已解冻。",
+        "{key} is restored.";
+    restored_until(key, date) =>
+        "{key} 已解冻，可读取至 {date}。",
+        "{key} is restored and readable until {date}.";
+    restoring(key) =>
+        "{key} 正在解冻中。",
+        "{key} is being restored.";
+    planned_restore(key, days) =>
+        "将解冻 {key}，保留 {days} 天（未执行任何操作）。",
+        "Would restore {key} for {days} days (nothing was done).";
+    restore_requested(key, days) =>
+        "已提交 {key} 的解冻请求，解冻后保留 {days} 天。",
+        "Asked to restore {key}; it will stay readable for {days} days.";
+    restore_takes_time(key) =>
+        "解冻需要数分钟到数小时，可使用 rot restore {key} --wait 等待完成。",
+        "Restoring takes minutes to hours; run rot restore {key} --wait to wait for it.";
+    restore_lapsed(key) =>
+        "{key} 的解冻请求未生效，请重新提交。",
+        "The restore request for {key} lapsed; please ask again.";
+    bucket_usage() =>
+        "用法：rot bucket ls | rot bucket create <名称> [--region 地域] | rot bucket rm <名称> [--yes]",
+        "Usage: rot bucket ls | rot bucket create <name> [--region region] | rot bucket rm <name> [--yes]";
+    no_buckets() =>
+        "没有可用的存储桶。",
+        "There are no buckets.";
+    planned_bucket_creation(name) =>
+        "将创建存储桶 {name}（未执行任何操作）。",
+        "Would create the bucket {name} (nothing was done).";
+    bucket_created(name) =>
+        "已创建存储桶 {name}。",
+        "Created the bucket {name}.";
+    planned_bucket_deletion(name) =>
+        "将删除存储桶 {name}（未执行任何操作）。",
+        "Would delete the bucket {name} (nothing was done).";
+    confirm_bucket_deletion(name) =>
+        "确定要删除存储桶 {name} 吗？",
+        "Delete the bucket {name}?";
+    bucket_deleted(name) =>
+        "已删除存储桶 {name}。",
+        "Deleted the bucket {name}.";
+    lifecycle_usage() =>
+        "用法：rot lifecycle get | rot lifecycle set <规则文件> | rot lifecycle rm [--yes]",
+        "Usage: rot lifecycle get | rot lifecycle set <rules-file> | rot lifecycle rm [--yes]";
+    no_lifecycle_rules(bucket) =>
+        "存储桶 {bucket} 没有生命周期规则。",
+        "The bucket {bucket} has no lifecycle rules.";
+    rules_file_unreadable(path, error) =>
+        "无法读取规则文件 {path}：{error}",
+        "Can't read the rules file {path}: {error}";
+    rules_file_empty() =>
+        "规则文件中没有规则，如需删除全部规则请使用 rot lifecycle rm。",
+        "The rules file has no rules; to delete all rules use rot lifecycle rm.";
+    planned_lifecycle(bucket, count) =>
+        "将为存储桶 {bucket} 设置 {count} 条生命周期规则（未执行任何操作）：",
+        "Would set {count} lifecycle rules on the bucket {bucket} (nothing was done):";
+    lifecycle_set(bucket, count) =>
+        "已为存储桶 {bucket} 设置 {count} 条生命周期规则：",
+        "Set {count} lifecycle rules on the bucket {bucket}:";
+    planned_lifecycle_deletion(bucket) =>
+        "将删除存储桶 {bucket} 的全部生命周期规则（未执行任何操作）。",
+        "Would delete all lifecycle rules of the bucket {bucket} (nothing was done).";
+    confirm_lifecycle_deletion(bucket) =>
+        "确定要删除存储桶 {bucket} 的全部生命周期规则吗？",
+        "Delete all lifecycle rules of the bucket {bucket}?";
+    lifecycle_deleted(bucket) =>
+        "已删除存储桶 {bucket} 的全部生命周期规则。",
+        "Deleted all lifecycle rules of the bucket {bucket}.";
+    cannot_read_tags(key, error) =>
+        "{key} 读取标签失败：{error}",
+        "Couldn't read the tags of {key}: {error}";
+    expired(key, time) =>
+        "{key}（已于 {time} 过期）",
+        "{key} (expired at {time})";
+    expired_deleted(key, time) =>
+        "已删除 {key}（已于 {time} 过期）",
+        "Deleted {key} (expired at {time})";
+    delete_failed_for(key, error) =>
+        "{key} 删除失败：{error}",
+        "Deleting {key} failed: {error}";
+    expired_found(checked, count, size) =>
+        "共检查 {checked} 个对象，发现 {count} 个过期对象（共 {size}），未执行删除。",
+        "Checked {checked} objects and found {count} expired ones ({size} in all); nothing was deleted.";
+    expired_summary(checked, count, size) =>
+        "共检查 {checked} 个对象，已删除 {count} 个过期对象（共 {size}）。",
+        "Checked {checked} objects and deleted {count} expired ones ({size} in all).";
+    objects_failed(count) =>
+        "{count} 个对象处理失败！",
+        "{count} objects failed.";
+    mpu_usage() =>
+        "用法：rot mpu clean [前缀] [--older-than 7d] [--dry-run]",
+        "Usage: rot mpu clean [prefix] [--older-than 7d] [--dry-run]";
+    multipart_upload(key, time) =>
+        "{key}（开始于 {time}）",
+        "{key} (started at {time})";
+    multipart_upload_aborted(key, time) =>
+        "已取消 {key}（开始于 {time}）",
+        "Aborted {key} (started at {time})";
+    multipart_abort_failed(key, error) =>
+        "{key} 取消失败：{error}",
+        "Aborting {key} failed: {error}";
+    multipart_uploads_found(count) =>
+        "共发现 {count} 个未完成的分片上传。",
+        "Found {count} unfinished multipart uploads.";
+    multipart_uploads_aborted(count) =>
+        "共取消 {count} 个未完成的分片上传。",
+        "Aborted {count} unfinished multipart uploads.";
+    multipart_aborts_failed(count) =>
+        "{count} 个分片上传取消失败！",
+        "{count} multipart uploads couldn't be aborted.";
+    planned_undelete(key) =>
+        "将恢复被删除的 {key}（未执行任何操作）。",
+        "Would bring back the deleted {key} (nothing was done).";
+    undeleted(key) =>
+        "已恢复被删除的 {key}。",
+        "Brought back the deleted {key}.";
+    no_previous_version(key) =>
+        "{key} 在此次上传前不存在，没有可恢复的旧版本。",
+        "{key} didn't exist before this upload, so there is no older version to go back to.";
+    planned_version_restore(key, version) =>
+        "将把 {key} 恢复为版本 {version}（未执行任何操作）。",
+        "Would restore {key} to version {version} (nothing was done).";
+    version_restored(key, version) =>
+        "已将 {key} 恢复为版本 {version}。",
+        "Restored {key} to version {version}.";
+    versions_usage() =>
+        "用法：rot versions <对象>",
+        "Usage: rot versions <key>";
+    no_versions(key) =>
+        "对象 {key} 没有任何版本。",
+        "The object {key} has no versions.";
+    delete_marker() => "删除标记", "delete marker";
+    latest_version() => "  (当前)", "  (latest)";
+    undelete_usage() =>
+        "用法：rot undelete <对象> [--dry-run]",
+        "Usage: rot undelete <key> [--dry-run]";
+    no_version_to_restore(key) =>
+        "对象 {key} 没有可恢复的版本。",
+        "The object {key} has no version to bring back.";
+    not_deleted(key, version) =>
+        "对象 {key} 未被删除，当前版本为 {version}。",
+        "The object {key} isn't deleted; its current version is {version}.";
+    planned_marker_removal(key, markers, version) =>
+        "将删除 {key} 的删除标记 {markers}，恢复版本 {version}（未执行任何操作）。",
+        "Would remove the delete markers {markers} of {key} and bring back version {version} (nothing was done).";
+    restored_version(key, version) =>
+        "已恢复 {key}，当前版本为 {version}。",
+        "Brought back {key}; its current version is {version}.";
+    rm_usage() =>
+        "用法：rot rm [-r] <对象名或前缀> [--yes]",
+        "Usage: rot rm [-r] <key or prefix> [--yes]";
+    object_missing(key) =>
+        "对象 {key} 不存在！",
+        "The object {key} doesn't exist.";
+    planned_deletion(key) =>
+        "删除 {key}（未执行任何操作）",
+        "Delete {key} (nothing was done)";
+    confirm_deletion(key) =>
+        "确定要删除 {key} 吗？",
+        "Delete {key}?";
+    deleted(key) =>
+        "已删除 {key}。",
+        "Deleted {key}.";
+    no_objects_under(prefix) =>
+        "前缀 {prefix} 下没有对象。",
+        "There are no objects under the prefix {prefix}.";
+    confirm_prefix_deletion(prefix, count, size) =>
+        "确定要删除 {prefix} 下的 {count} 个对象（共 {size}）吗？",
+        "Delete the {count} objects under {prefix} ({size} in all)?";
+    confirm_matches_deletion(count, size) =>
+        "确定要删除符合条件的 {count} 个对象（共 {size}）吗？",
+        "Delete the {count} matching objects ({size} in all)?";
+    planned_object_deletion(key) =>
+        "删除 {key}",
+        "Delete {key}";
+    planned_deletions(count, size) =>
+        "预计删除 {count} 个对象，共 {size}（未执行任何操作）。",
+        "Would delete {count} objects, {size} in all (nothing was done).";
+    deletions_summary(deleted, failed) =>
+        "共删除 {deleted} 个对象，失败 {failed} 个。",
+        "Deleted {deleted} objects, {failed} failed.";
+    deletions_failed(count) =>
+        "{count} 个对象删除失败！",
+        "{count} objects couldn't be deleted.";
+    no_matches() =>
+        "没有符合条件的对象。",
+        "No objects match.";
+    copy_usage(command) =>
+        "用法：rot {command} <源对象> <目标对象>，其他存储桶可写作 oss://bucket/key",
+        "Usage: rot {command} <source> <destination>; write objects in other buckets as oss://bucket/key";
+    source_missing(object) =>
+        "源对象 {object} 不存在！",
+        "The source object {object} doesn't exist.";
+    planned_copy(from, to) =>
+        "复制 {from} -> {to}（未执行任何操作）",
+        "Copy {from} -> {to} (nothing was done)";
+    copied(from, to) =>
+        "已复制 {from} -> {to}。",
+        "Copied {from} -> {to}.";
+    same_source_and_destination() =>
+        "源对象与目标对象相同！",
+        "The source and destination are the same object.";
+    destination_exists(object) =>
+        "目标对象 {object} 已存在，如需覆盖请使用 --force。",
+        "The destination {object} already exists; use --force to overwrite it.";
+    planned_move(from, to) =>
+        "移动 {from} -> {to}（未执行任何操作）",
+        "Move {from} -> {to} (nothing was done)";
+    move_source_not_deleted(to, error) =>
+        "已复制到 {to}，但删除源对象失败：{error}",
+        "Copied to {to}, but deleting the source failed: {error}";
+    moved(from, to) =>
+        "已移动 {from} -> {to}。",
+        "Moved {from} -> {to}.";
+    ln_usage() =>
+        "用法：rot ln <目标对象> <链接>",
+        "Usage: rot ln <target> <link>";
+    empty_key() =>
+        "对象名不能为空！",
+        "Object keys can't be empty.";
+    link_to_itself() =>
+        "链接不能指向自身！",
+        "A link can't point to itself.";
+    link_target_exists(link) =>
+        "{link} 已存在且不是符号链接，如需覆盖请使用 --force。",
+        "{link} already exists and isn't a symlink; use --force to overwrite it.";
+    planned_symlink(link, target) =>
+        "创建符号链接 {link} -> {target}（未执行任何操作）",
+        "Create the symlink {link} -> {target} (nothing was done)";
+    symlink_created(link, target) =>
+        "已创建符号链接 {link} -> {target}。",
+        "Created the symlink {link} -> {target}.";
+    share_usage() =>
+        "用法：rot share <对象名> [--expires 1h] [--method GET|PUT] [--process image/resize,w_800]",
+        "Usage: rot share <key> [--expires 1h] [--method GET|PUT] [--process image/resize,w_800]";
+    process_needs_get() =>
+        "--process 只能用于下载链接。",
+        "--process only works for download links.";
+    process_invalid() =>
+        "--process 的格式应为 image/resize,w_800 或 style/<样式名>。",
+        "--process should look like image/resize,w_800 or style/<style-name>.";
+    tmpshare_usage() =>
+        "用法：rot tmpshare <文件> [--expires 1d] [-u 前缀]",
+        "Usage: rot tmpshare <file> [--expires 1d] [-u prefix]";
+    link_expiry_too_long() =>
+        "下载链接最长有效 7 天，请缩短 --expires。",
+        "Download links last at most 7 days; shorten --expires.";
+    planned_tmpshare(path, dir, seconds) =>
+        "将上传 {path} 到 {dir} 并生成有效期 {seconds} 秒的下载链接（未执行任何操作）。",
+        "Would upload {path} to {dir} and make a download link valid for {seconds} seconds (nothing was done).";
+    tmpshare_uploaded(key, time) =>
+        "已上传为 {key}，链接和文件将于 {time} 过期。",
+        "Uploaded as {key}; the link and the file expire at {time}.";
+    tmpshare_encrypted() =>
+        "链接下载的是加密后的文件，接收方需要用对应的密码或密钥解密。",
+        "The link serves the encrypted file; the recipient needs the matching password or key to decrypt it.";
+    not_an_object_key(key) =>
+        "{key} 不是对象名！",
+        "{key} isn't an object key.";
+    store_copy_usage() =>
+        "用法：rot cp <源对象> <目标对象>",
+        "Usage: rot cp <source> <destination>";
+}
+
+// Transfers.
+messages! {
+    transfer_cancelled() =>
+        "传输已取消。",
+        "Transfer cancelled.";
+    resuming_upload(key, part) =>
+        "继续未完成的上传 {key}，从第 {part} 个分块开始。",
+        "Resuming the unfinished upload of {key} from part {part}.";
+    upload_interrupted(key) =>
+        "{key} 上传中断，已保留未完成的分片上传，重新运行即可继续；不再需要时可用 rot mpu clean 清理。",
+        "Upload of {key} interrupted. Its multipart upload was kept: run again to resume, \
+         or remove it with rot mpu clean if it is no longer needed.";
+    resuming_download(key, size) =>
+        "继续下载 {key}：已完成 {size}。",
+        "Resuming the download of {key}: {size} already done.";
+    download_interrupted(key, path) =>
+        "{key} 下载中断，已保留 {path}，重新运行即可继续下载。",
+        "Download of {key} interrupted. {path} was kept; run again to resume.";
+    download_incomplete(expected, received) =>
+        "下载不完整！预期 {expected} 字节，实际收到 {received} 字节。",
+        "Incomplete download! Expected {expected} bytes but received {received}.";
+    cannot_open(path, error) =>
+        "无法打开 {path}：{error}",
+        "Cannot open {path}: {error}";
+    cannot_read(path, error) =>
+        "无法读取 {path}：{error}",
+        "Cannot read {path}: {error}";
+    cannot_rename(from, to, error) =>
+        "无法将 {from} 重命名为 {to}：{error}",
+        "Cannot rename {from} to {to}: {error}";
+    sha256_mismatch(expected, actual) =>
+        "SHA-256 校验失败！预期 {expected}，实际 {actual}，已取消上传。",
+        "SHA-256 mismatch! Expected {expected} but got {actual}; the upload was cancelled.";
+    crc64_mismatch(what, reported, local) =>
+        "{what} CRC64 校验失败！服务器为 {reported}，本地计算为 {local}。",
+        "CRC64 mismatch for {what}! The server has {reported}, computed locally {local}.";
+    part(number) =>
+        "分块 {number}",
+        "part {number}";
+    cannot_seek(path, error) =>
+        "无法定位 {path}：{error}",
+        "Cannot seek in {path}: {error}";
+    cannot_read_part(number, error) =>
+        "无法读取分块 {number}：{error}",
+        "Cannot read part {number}: {error}";
+    part_failed(number, error) =>
+        "分块 {number} 失败：{error}",
+        "Part {number} failed: {error}";
+    pipeline_stopped(number) =>
+        "上传流程在分块 {number} 交出前中止。",
+        "The upload pipeline stopped before part {number} was handed on.";
+    pipeline_failed(error) =>
+        "上传流程异常：{error}",
+        "The upload pipeline failed: {error}";
+    cannot_write(path, error) =>
+        "无法写入 {path}：{error}",
+        "Can't write {path}: {error}";
+    cannot_fetch(url, error) =>
+        "无法获取 {url}：{error}",
+        "Can't fetch {url}: {error}";
+    download_broken_off(key, error) =>
+        "下载 {key} 中断：{error}",
+        "Downloading {key} broke off: {error}";
+    download_failed(finished, total, key, error) =>
+        "[{finished}/{total}] {key} 下载失败：{error}",
+        "[{finished}/{total}] Downloading {key} failed: {error}";
+    download_task_failed(finished, total, error) =>
+        "[{finished}/{total}] 下载任务异常：{error}",
+        "[{finished}/{total}] A download task failed: {error}";
+    upload_unchanged(finished, total, path) =>
+        "[{finished}/{total}] {path}（未变化，已跳过）",
+        "[{finished}/{total}] {path} (unchanged, skipped)";
+    upload_failed(finished, total, path, error) =>
+        "[{finished}/{total}] {path} 上传失败：{error}",
+        "[{finished}/{total}] Uploading {path} failed: {error}";
+    upload_task_failed(finished, total, error) =>
+        "[{finished}/{total}] 上传任务异常：{error}",
+        "[{finished}/{total}] An upload task failed: {error}";
+    skipped_not_utf8(path) =>
+        "跳过 {path}：路径不是有效的 UTF-8，无法作为对象名。",
+        "Skipping {path}: the path isn't valid UTF-8, so it can't become an object key.";
+    cannot_watch(path, error) =>
+        "无法监视目录 {path}：{error}",
+        "Can't watch the folder {path}: {error}";
+    cannot_create_dir(path, error) =>
+        "无法创建目录 {path}：{error}",
+        "Can't create the folder {path}: {error}";
+    cannot_delete(path, error) =>
+        "无法删除 {path}：{error}",
+        "Can't delete {path}: {error}";
+    cannot_list(prefix, error) =>
+        "无法列出 /{prefix}：{error}",
+        "Can't list /{prefix}: {error}";
+    upload_failed_for(key, error) =>
+        "上传 {key} 失败：{error}",
+        "Uploading {key} failed: {error}";
+    download_failed_for(key, error) =>
+        "下载 {key} 失败：{error}",
+        "Downloading {key} failed: {error}";
+    cancelled() =>
+        "已取消。",
+        "Cancelled.";
+    skipped(item, reason) =>
+        "跳过 {item}：{reason}",
+        "Skipping {item}: {reason}";
+    no_matching_files(path) =>
+        "没有与 {path} 匹配的文件！",
+        "No files match {path}.";
+    no_files_in_dir() =>
+        "该目录下不存在文件！",
+        "There are no files in this folder.";
+    downloaded(path) =>
+        "文件下载成功！所在路径：{path}。",
+        "Downloaded to {path}.";
+    downloads_summary(done, failed) =>
+        "共下载 {done} 个文件，失败 {failed} 个。",
+        "Downloaded {done} files, {failed} failed.";
+    downloads_failed(count) =>
+        "{count} 个文件下载失败！",
+        "{count} files failed to download.";
+    planned_download(key, path) =>
+        "下载 {key} -> {path}",
+        "Download {key} -> {path}";
+    planned_downloads(planned, existing) =>
+        "预计下载 {planned} 个文件，已存在 {existing} 个（未执行任何操作）。",
+        "Would download {planned} files, {existing} already exist (nothing was done).";
+    uploaded(e_tag) =>
+        "文件上传成功！ETag: {e_tag}。",
+        "Uploaded. ETag: {e_tag}.";
+    upload_failed_plain() =>
+        "文件上传失败！",
+        "Upload failed.";
+    upload_skipped_unchanged(key) =>
+        "{key} 未变化，已跳过上传。",
+        "{key} is unchanged, skipped.";
+    uploads_summary(done, unchanged, failed) =>
+        "共上传 {done} 个文件，未变化 {unchanged} 个，失败 {failed} 个。",
+        "Uploaded {done} files, {unchanged} unchanged, {failed} failed.";
+    uploads_failed(count) =>
+        "{count} 个文件上传失败！",
+        "{count} files failed to upload.";
+    planned_upload(path, key) =>
+        "上传 {path} -> {key}",
+        "Upload {path} -> {key}";
+    planned_unchanged(key) =>
+        "跳过 {key}（未变化）",
+        "Skip {key} (unchanged)";
+    planned_signature(key) =>
+        "上传签名 {key}",
+        "Upload the signature {key}";
+    planned_manifest(key) =>
+        "上传校验清单 {key}",
+        "Upload the manifest {key}";
+    planned_uploads(planned, unchanged, failed) =>
+        "预计上传 {planned} 个，未变化 {unchanged} 个，无法上传 {failed} 个（未执行任何操作）。",
+        "Would upload {planned}, {unchanged} unchanged, {failed} can't be uploaded (nothing was done).";
+    manifest_uploaded(key) =>
+        "已上传校验清单 {key}。",
+        "Uploaded the manifest {key}.";
+    watch_error(error) =>
+        "监视目录时出错：{error}",
+        "Error while watching the folder: {error}";
+    status_done() => "成功", "done";
+    status_planned() => "将上传", "planned";
+    status_unchanged() => "未变化", "unchanged";
+    status_failed() => "失败", "failed";
+    url_invalid() =>
+        "请输入以 http:// 或 https:// 开头的地址！",
+        "Please give an address starting with http:// or https://.";
+    url_without_file_name() =>
+        "无法从地址中推断文件名，请使用 --key 指定对象名！",
+        "Can't tell a file name from the address; name the object with --key.";
+    uploading_url(url, key) =>
+        "正在上传 {url} -> {key}",
+        "Uploading {url} -> {key}";
+    uploading_url_sized(url, key, size) =>
+        "正在上传 {url} -> {key}（{size}）",
+        "Uploading {url} -> {key} ({size})";
+    diff_usage() =>
+        "用法：rot diff <本地文件> <对象名> [-p 密码 | --decrypt | --key 名称 | --key-file 密钥文件 | --identity 私钥文件] [--block 大小]",
+        "Usage: rot diff <local-file> <key> [-p password | --decrypt | --key name | --key-file key-file | --identity identity-file] [--block size]";
+    diff_same(path, key, size, sha256) =>
+        "相同：{path} 与 {key}（{size}，SHA-256 {sha256}）。",
+        "Identical: {path} and {key} ({size}, SHA-256 {sha256}).";
+    diff_local(size, sha256) =>
+        "本地：{size}，SHA-256 {sha256}",
+        "Local:  {size}, SHA-256 {sha256}";
+    diff_remote(size, sha256) =>
+        "远程：{size}，SHA-256 {sha256}",
+        "Remote: {size}, SHA-256 {sha256}";
+    diff_range(start, end, size) =>
+        "字节 {start}..{end} 不同（{size}）",
+        "Bytes {start}..{end} differ ({size})";
+    diff_differs(path, key) =>
+        "{path} 与 {key} 不同！",
+        "{path} and {key} differ.";
+    bench_written(path) =>
+        "测试结果已写入：{path}。",
+        "Wrote the results to {path}.";
+    sync_usage() =>
+        "用法：rot sync <本地目录> <远程前缀> 或 rot sync oss://bucket/前缀 <本地目录> [--delete] [--dry-run] [--checksum] [-p 密码 | --encrypt | --key-file 密钥文件]",
+        "Usage: rot sync <local-folder> <remote-prefix> or rot sync oss://bucket/prefix <local-folder> [--delete] [--dry-run] [--checksum] [-p password | --encrypt | --key-file key-file]";
+    not_a_dir(path) =>
+        "{path} 不是目录！",
+        "{path} isn't a folder.";
+    watch_usage() =>
+        "用法：rot watch <本地目录> <远程前缀> [--interval 2s] [--debounce 2s] [--include 模式] [--exclude 模式]",
+        "Usage: rot watch <local-folder> <remote-prefix> [--interval 2s] [--debounce 2s] [--include pattern] [--exclude pattern]";
+    zero_interval() =>
+        "--interval 必须大于 0。",
+        "--interval must be greater than 0.";
+    watching(path) =>
+        "正在监视 {path}，按 Ctrl+C 停止。",
+        "Watching {path}; press Ctrl+C to stop.";
+    watch_stopped() =>
+        "已停止监视。",
+        "Stopped watching.";
+    planned_sync_upload(key) =>
+        "上传 {key}",
+        "Upload {key}";
+    planned_sync_check(key) =>
+        "校验 {key}（大小相同，上传前比较校验和）",
+        "Check {key} (same size, checksums are compared before uploading)";
+    planned_sync_download(key) =>
+        "下载 {key}",
+        "Download {key}";
+    planned_local_deletion(path) =>
+        "删除本地文件 {path}",
+        "Delete the local file {path}";
+    planned_sync_up(uploads, checks, deletions, unchanged) =>
+        "预计上传 {uploads} 个，待校验 {checks} 个，删除 {deletions} 个，未变化 {unchanged} 个（未执行任何操作）。",
+        "Would upload {uploads}, check {checks}, delete {deletions}, {unchanged} unchanged (nothing was done).";
+    planned_sync_down(downloads, deletions, unchanged) =>
+        "预计下载 {downloads} 个，删除 {deletions} 个，未变化 {unchanged} 个（未执行任何操作）。",
+        "Would download {downloads}, delete {deletions}, {unchanged} unchanged (nothing was done).";
+    synced_up(uploaded, deleted, unchanged, failed) =>
+        "同步完成：上传 {uploaded} 个，删除 {deleted} 个，未变化 {unchanged} 个，失败 {failed} 个。",
+        "Synced: {uploaded} uploaded, {deleted} deleted, {unchanged} unchanged, {failed} failed.";
+    synced_down(downloaded, deleted, unchanged, failed) =>
+        "同步完成：下载 {downloaded} 个，删除 {deleted} 个，未变化 {unchanged} 个，失败 {failed} 个。",
+        "Synced: {downloaded} downloaded, {deleted} deleted, {unchanged} unchanged, {failed} failed.";
+    sync_failed(count) =>
+        "{count} 个文件同步失败！",
+        "{count} files failed to sync.";
+    skipped_unsafe_key(key) =>
+        "跳过 {key}：对象名无法安全地映射为本地路径。",
+        "Skipping {key}: the key can't be safely mapped to a local path.";
+    not_a_file(path) =>
+        "{path} 不是文件！",
+        "{path} isn't a file.";
+    planned_store_upload(path, key) =>
+        "上传 {path} -> {key}（未执行任何操作）",
+        "Upload {path} -> {key} (nothing was done)";
+    uploaded_key(key) =>
+        "已上传 {key}。",
+        "Uploaded {key}.";
+    planned_store_download(key, path) =>
+        "下载 {key} -> {path}（未执行任何操作）",
+        "Download {key} -> {path} (nothing was done)";
+    downloaded_key(key, path, size) =>
+        "已下载 {key} -> {path}（{size}）。",
+        "Downloaded {key} -> {path} ({size}).";
+}
+
+// Encryption.
+messages! {
+    crypt_usage() =>
+        "用法：rot encrypt <输入> <输出> [--archive] [-p 密码] [--allow-weak] | rot decrypt <输入> <输出> [--archive] [-p 密码]",
+        "Usage: rot encrypt <input> <output> [--archive] [-p password] [--allow-weak] | rot decrypt <input> <output> [--archive] [-p password]";
+    archive_needs_dir(path) =>
+        "--archive 打包的是目录，{path} 不是目录。",
+        "--archive packs a directory, and {path} isn't one.";
+    encryption_password_prompt() =>
+        "请输入加密密码：",
+        "Encryption password: ";
+    decryption_password_prompt() =>
+        "请输入解密密码：",
+        "Decryption password: ";
+    files_encrypted(count, output) =>
+        "已加密 {count} 个文件到 {output}。",
+        "Encrypted {count} files into {output}.";
+    files_decrypted(count, output) =>
+        "已解密 {count} 个文件到 {output}。",
+        "Decrypted {count} files into {output}.";
+    unknown_kdf(name) =>
+        "不支持的密钥派生算法：{name}，可选 pbkdf2 或 argon2id。",
+        "Unsupported key derivation function {name}; use pbkdf2 or argon2id.";
+    header_unknown_kdf(id) =>
+        "加密数据头无效：未知的密钥派生算法 {id}。",
+        "Invalid encryption header: unknown key derivation function {id}.";
+    header_kdf_out_of_range(params) =>
+        "加密数据头无效：密钥派生参数 {params} 超出范围。",
+        "Invalid encryption header: key derivation parameters {params} out of range.";
+    header_truncated(len, expected) =>
+        "加密数据头被截断：只有 {len} 字节，应为 {expected} 字节。",
+        "Truncated encryption header: {len} bytes instead of {expected}.";
+    header_chunk_size(size) =>
+        "加密数据头无效：分块大小 {size}。",
+        "Invalid encryption header: chunk size {size}.";
+    header_name_len(len) =>
+        "加密数据头无效：文件名长度 {len}。",
+        "Invalid encryption header: file name length {len}.";
+    unsupported_format_version(version) =>
+        "不支持的加密格式版本：{version}，请升级 rot。",
+        "Unsupported encryption format version {version}; please upgrade rot.";
+    unsupported_format_flags(flags) =>
+        "不支持的加密格式选项：{flags}，请升级 rot。",
+        "Unsupported encryption format options {flags}; please upgrade rot.";
+    decryption_stream_closed() =>
+        "写入已关闭的解密流。",
+        "Write to a closed decryption stream.";
+    recipient_count(max) =>
+        "接收者数量应为 1 到 {max} 个。",
+        "There must be 1 to {max} recipients.";
+    not_a_recipient() =>
+        "该文件不是为此私钥加密的：文件的接收者中没有与 --identity 匹配的公钥。",
+        "The file wasn't encrypted for this private key: none of its recipients matches --identity.";
+    only_recipients_decrypt() =>
+        "只有接收者的私钥才能解密该文件，请使用 --identity 提供私钥。",
+        "Only a recipient's private key can decrypt the file; give it with --identity.";
+    needs_key_file() =>
+        "该文件使用密钥文件加密，请使用 --key-file 提供密钥文件。",
+        "The file was encrypted with a key file; give it with --key-file.";
+    needs_identity() =>
+        "该文件为指定接收者加密，请使用 --identity 提供私钥。",
+        "The file was encrypted for its recipients; give your private key with --identity.";
+    needs_password() =>
+        "该文件使用密码加密，请使用 -p 提供密码。",
+        "The file was encrypted with a password; give it with -p.";
+    identity_cannot_encrypt() =>
+        "私钥只能用于解密，加密请使用 --recipient 指定接收者的公钥。",
+        "A private key only decrypts; to encrypt, name the recipients' public keys with --recipient.";
+    wrong_key_file() =>
+        "解密失败：密钥文件与加密时使用的不一致。",
+        "Decryption failed: this isn't the key file the data was encrypted with.";
+    wrong_password() =>
+        "解密失败：密码不正确。",
+        "Decryption failed: wrong password.";
+    random_failed() =>
+        "无法生成随机数。",
+        "Cannot generate random numbers.";
+    invalid_recipient_hex(key, len) =>
+        "接收者公钥无效：{key}，应为 {len} 个十六进制字符。",
+        "Invalid recipient public key {key}; it should be {len} hex characters.";
+    invalid_recipient(key) =>
+        "接收者公钥无效：{key}。",
+        "Invalid recipient public key {key}.";
+    cannot_hide_name(name) =>
+        "无法隐藏文件名 {name}：文件名无效或过长。",
+        "Cannot hide the file name {name}: it is invalid or too long.";
+    invalid_compression_level(level, error) =>
+        "无法使用压缩级别 {level}：{error}",
+        "Cannot use compression level {level}: {error}";
+    invalid_chunk_len(len) =>
+        "加密数据无效：分块长度 {len}。",
+        "Invalid encrypted data: chunk length {len}.";
+    decompress_failed(error) =>
+        "解压失败：{error}",
+        "Decompression failed: {error}";
+    invalid_compressed_chunk() =>
+        "解压失败：分块格式无效。",
+        "Decompression failed: invalid chunk format.";
+    missing_last_chunk(count) =>
+        "加密数据被截断：缺少最后一个分块（已读取 {count} 个分块）。",
+        "Truncated encrypted data: the last chunk is missing ({count} chunks read).";
+    last_chunk_without_tag(len) =>
+        "加密数据被截断：最后一个分块只有 {len} 字节，不足以包含校验标签。",
+        "Truncated encrypted data: the last chunk has only {len} bytes, too few for its tag.";
+    last_chunk_truncated(len) =>
+        "加密数据被截断：最后一个分块只有 {len} 字节。",
+        "Truncated encrypted data: the last chunk has only {len} bytes.";
+    first_chunk_failed() =>
+        "解密失败：第 1 个分块校验未通过。请确认密码是否正确；若密码无误，则文件开头已损坏或并非 rot 加密的文件。",
+        "Decryption failed: chunk 1 didn't verify. Check the password; if it is right, \
+         the start of the file is damaged or it wasn't encrypted by rot.";
+    resumed_chunk_failed(chunk) =>
+        "解密失败：续传的第一个分块（第 {chunk} 个）校验未通过。请确认密码与之前下载时一致。",
+        "Decryption failed: the first chunk of the resumed download (chunk {chunk}) didn't verify. \
+         Check that the password is the one used before.";
+    last_chunk_failed(chunk, offset) =>
+        "解密失败：最后一个分块（第 {chunk} 个，明文偏移 {offset}）校验未通过，数据可能被截断或损坏。",
+        "Decryption failed: the last chunk (chunk {chunk}, plaintext offset {offset}) didn't verify; \
+         the data may be truncated or damaged.";
+    chunk_failed(chunk, offset) =>
+        "解密失败：第 {chunk} 个分块（明文偏移 {offset}）校验未通过。密码正确，但数据已损坏或被篡改。",
+        "Decryption failed: chunk {chunk} (plaintext offset {offset}) didn't verify. \
+         The password is right, but the data is damaged or was tampered with.";
+    invalid_key_len(expected, actual) =>
+        "密钥长度应为 {expected} 字节，实际为 {actual} 字节。",
+        "The key should be {expected} bytes long, not {actual}.";
+    encryption_failed() =>
+        "加密失败。",
+        "Encryption failed.";
+    wrong_key() =>
+        "解密失败：校验未通过，请确认密钥是否正确。",
+        "Decryption failed: verification failed; check that the key is right.";
+    file_error(path, error) =>
+        "{path}：{error}",
+        "{path}: {error}";
+    signing_key_invalid(path) =>
+        "无法解析签名私钥 {path}，请提供 Ed25519 PKCS#8 私钥。",
+        "Can't read the signing key {path}; give an Ed25519 PKCS#8 private key.";
+    verifying_key_invalid(path) =>
+        "无法解析验签公钥 {path}，请提供 Ed25519 公钥。",
+        "Can't read the verifying key {path}; give an Ed25519 public key.";
+    signature_invalid() =>
+        "签名格式无效！",
+        "The signature is malformed!";
+    signature_mismatch(path) =>
+        "{path} 的签名校验失败！文件可能已被篡改或公钥不匹配。",
+        "The signature of {path} doesn't match! The file may have been tampered with, or the public key is the wrong one.";
+    pem_invalid(path) =>
+        "无法解析 PEM 文件 {path}。",
+        "Can't read the PEM file {path}.";
+    signature_missing(key, signature) =>
+        "{key} 缺少签名文件 {signature}！",
+        "{key} has no signature {signature}.";
+    signature_verified() =>
+        "签名校验通过。",
+        "The signature checks out.";
+    signatures_verified(count) =>
+        "全部 {count} 个文件签名校验通过。",
+        "All {count} signatures check out.";
+    signatures_failed(count) =>
+        "{count} 个文件签名校验失败，已删除！",
+        "{count} files failed the signature check and were deleted.";
+    signing_with(key) =>
+        "使用签名公钥 {key}。",
+        "Signing with the public key {key}.";
+    signature_uploaded(key) =>
+        "已上传签名 {key}。",
+        "Uploaded the signature {key}.";
+    rekey_usage() =>
+        "用法：rot rekey <对象>... [-p 原密码 | --decrypt | --key 名称 | --key-file 密钥文件 | --identity 私钥文件] \
+         [--new-password 新密码 | --new-key 名称 | --new-key-file 密钥文件 | --new-recipient 公钥] [--kdf pbkdf2|argon2id] [--compress [级别]]",
+        "Usage: rot rekey <key>... [-p old-password | --decrypt | --key name | --key-file key-file | --identity identity-file] \
+         [--new-password new-password | --new-key name | --new-key-file key-file | --new-recipient public-key] [--kdf pbkdf2|argon2id] [--compress [level]]";
+    planned_rekey(key) =>
+        "将重新加密 {key}",
+        "Would re-encrypt {key}";
+    rekeyed(key) =>
+        "已重新加密 {key}",
+        "Re-encrypted {key}";
+    rekeyed_as(key, new_key) =>
+        "已重新加密 {key} -> {new_key}",
+        "Re-encrypted {key} -> {new_key}";
+}
+
+// Passwords.
+messages! {
+    weak_password(bits, min_length) =>
+        "密码过弱（约 {bits:.0} 位熵），加密的数据容易被破解。请使用至少 {min_length} 个字符并混合大小写、数字和符号，或添加 --allow-weak 强制使用。",
+        "The password is weak (about {bits:.0} bits of entropy), so the encrypted data would be easy to crack. \
+         Use at least {min_length} characters mixing cases, digits and symbols, or add --allow-weak to use it anyway.";
+    weak_password_warning() =>
+        "警告：正在使用弱密码加密。",
+        "Warning: encrypting with a weak password.";
+    confirm_password_prompt() =>
+        "请再次输入加密密码以确认：",
+        "Enter the encryption password again to confirm: ";
+    passwords_differ() =>
+        "两次输入的密码不一致！",
+        "The passwords don't match!";
+    password_needs_terminal() =>
+        "无法读取密码：标准输入不是终端。请在终端中运行，或改用 --key-file。",
+        "Can't read the password: standard input isn't a terminal. Run in a terminal, or use --key-file instead.";
+    empty_password() =>
+        "密码不能为空！",
+        "The password can't be empty!";
+    new_password_prompt() =>
+        "请输入新的加密密码：",
+        "New encryption password: ";
+    old_password_prompt() =>
+        "请输入原密码：",
+        "Current password: ";
+}
+
+// Services.
+messages! {
+    webdav_needs_endpoint() =>
+        "webdav 类型的配置需要将 endpoint_url 设置为 WebDAV 地址。",
+        "A webdav profile needs endpoint_url set to the WebDAV address.";
+    webdav_unreachable(error) =>
+        "无法连接 WebDAV 服务器：{error}",
+        "Can't reach the WebDAV server: {error}";
+    webdav_request_failed(url, status) =>
+        "WebDAV 请求 {url} 失败：HTTP {status}",
+        "WebDAV request for {url} failed: HTTP {status}";
+    webdav_mkdir_failed(url, status) =>
+        "无法创建目录 {url}：HTTP {status}",
+        "Can't create the folder {url}: HTTP {status}";
+    url_not_found(url) =>
+        "{url} 不存在。",
+        "{url} doesn't exist.";
+    sftp_needs_host() =>
+        "sftp 类型的配置需要设置 host。",
+        "An sftp profile needs a host.";
+    sftp_unavailable(error) =>
+        "无法运行 sftp，请确认已安装 OpenSSH 客户端：{error}",
+        "Can't run sftp; check that the OpenSSH client is installed: {error}";
+    sftp_error(host, error) =>
+        "sftp {host}：{error}",
+        "sftp {host}: {error}";
+    sftp_failed(host, status) =>
+        "sftp {host} 失败：{status}",
+        "sftp {host} failed: {status}";
+    sftp_listing_invalid(host) =>
+        "无法解析 sftp {host} 的目录列表。",
+        "Can't read the folder listing of sftp {host}.";
+    sts_unreachable(error) =>
+        "无法连接 STS：{error}",
+        "Can't reach STS: {error}";
+    assume_role_rejected(message, code) =>
+        "AssumeRole 失败：{message}（{code}）",
+        "AssumeRole failed: {message} ({code})";
+    assume_role_failed(status) =>
+        "AssumeRole 失败：HTTP {status}",
+        "AssumeRole failed: HTTP {status}";
+    sts_response_invalid(error) =>
+        "无法解析 STS 响应：{error}",
+        "Can't read the STS response: {error}";
+    assume_role_usage() =>
+        "用法：rot assume-role <role-arn> [--session-name <名称>] [--duration <时长>] [--save <配置名>]",
+        "Usage: rot assume-role <role-arn> [--session-name <name>] [--duration <time>] [--save <profile>]";
+    assume_role_needs_credentials() =>
+        "尚未配置凭证，无法申请临时凭证。请先使用 rot config set 配置。",
+        "No credentials are configured to request temporary ones with. Set them with rot config set first.";
+    temporary_credentials_saved(name, expiration) =>
+        "已将临时凭证保存到配置 '{name}'，有效期至 {expiration}。使用 rot --profile {name} 访问。",
+        "Saved the temporary credentials as profile '{name}', valid until {expiration}. Use them with rot --profile {name}.";
+    temporary_credentials_expire(expiration) =>
+        "临时凭证有效期至 {expiration}。",
+        "The temporary credentials are valid until {expiration}.";
+    keyring_missing(account) =>
+        "钥匙串中没有 {account} 的密钥，请使用 rot config set secret_access_key --keyring 保存。",
+        "The keychain has no secret for {account}; store it with rot config set secret_access_key --keyring.";
+    keyring_not_saved(account, error) =>
+        "无法将 {account} 的密钥保存到钥匙串：{error}",
+        "Can't store the secret for {account} in the keychain: {error}";
+    keyring_unavailable(error) =>
+        "无法访问系统钥匙串：{error}",
+        "Can't reach the system keychain: {error}";
+    sftp_path_invalid(path) =>
+        "路径 \"{path}\" 含有控制字符，无法通过 sftp 传输。",
+        "The path \"{path}\" contains control characters, which sftp can't carry.";
+}
+
+// Local state.
+messages! {
+    index_invalid(path, error) =>
+        "无法解析索引 {path}：{error}",
+        "Can't read the index {path}: {error}";
+    history_invalid(path, error) =>
+        "无法解析操作历史 {path}：{error}",
+        "Can't read the history {path}: {error}";
+    history_not_recorded(error) =>
+        "无法记录操作历史：{error}",
+        "Can't record the history: {error}";
+    queue_invalid(path, error) =>
+        "无法解析上传队列 {path}：{error}",
+        "Can't read the upload queue {path}: {error}";
+    just_now() => "刚刚", "just now";
+    minutes_ago(count) => "{count} 分钟前", "{count} minutes ago";
+    hours_ago(count) => "{count} 小时前", "{count} hours ago";
+    days_ago(count) => "{count} 天前", "{count} days ago";
+    upload_queued(id) =>
+        "无法连接到服务器，上传任务已加入队列（编号 {id}）。恢复网络后请执行 `rot queue flush`。",
+        "Can't reach the server, so the upload was queued (job {id}). Run `rot queue flush` once you're back online.";
+    queue_empty() =>
+        "上传队列为空。",
+        "The upload queue is empty.";
+    queued_encrypted() => " [加密]", " [encrypted]";
+    still_offline() =>
+        "仍无法连接到服务器，请稍后再试。",
+        "Still can't reach the server; try again later.";
+    queued_job_needs_key(id) =>
+        "跳过任务 {id}：该任务需要加密，请使用 -p、--encrypt、--key、--key-file 或 --recipient 指定加密方式。",
+        "Skipping job {id}: it needs encryption; give -p, --encrypt, --key, --key-file or --recipient.";
+    queued_job_failed(id, error) =>
+        "任务 {id} 上传失败：{error}",
+        "Job {id} failed: {error}";
+    queued_jobs_failed(count) =>
+        "{count} 个队列任务上传失败！",
+        "{count} queued jobs failed.";
+    queue_drop_usage() =>
+        "请指定要删除的任务编号或 all！",
+        "Give the number of the job to drop, or all.";
+    queued_job_invalid(value) =>
+        "无效的任务编号：'{value}'。",
+        "Invalid job number: '{value}'.";
+    queued_job_not_found(id) =>
+        "未找到任务 {id}！",
+        "There is no job {id}.";
+    queued_jobs_dropped() =>
+        "已从队列中删除。",
+        "Dropped from the queue.";
+    unknown_queue_action(action) =>
+        "未知的队列操作：{action}，可用操作为 ls、flush、drop。",
+        "Unknown queue action {action}; use ls, flush or drop.";
+    cache_dir_missing() =>
+        "无法定位缓存目录。",
+        "Cannot find the cache directory.";
+    history_empty() =>
+        "暂无操作记录。",
+        "Nothing has been recorded yet.";
+    history_size(size) => "（{size}）", " ({size})";
+    history_version(id) => " 版本 {id}", " version {id}";
+    history_undone() => " [已撤销]", " [undone]";
+    history_id_invalid(value) =>
+        "无效的记录编号：'{value}'。",
+        "Invalid record number: '{value}'.";
+    nothing_to_undo() =>
+        "没有可撤销的操作。",
+        "There is nothing to undo.";
+    history_entry_not_found(id) =>
+        "未找到记录 {id}！",
+        "There is no record {id}.";
+    already_undone(id) =>
+        "记录 {id} 已撤销过。",
+        "Record {id} has already been undone.";
+    undo_needs_versioning(id) =>
+        "记录 {id} 没有版本信息，无法撤销。请为存储桶开启版本控制。",
+        "Record {id} has no version, so it can't be undone. Turn on versioning for the bucket.";
+    copy_not_undoable() =>
+        "复制操作无法撤销。",
+        "Copies can't be undone.";
+}
+
+// Backups.
+messages! {
+    backup_without_sources(name) =>
+        "备份任务 {name} 没有设置 sources。",
+        "Backup job {name} has no sources.";
+    backup_without_destination(name) =>
+        "备份任务 {name} 没有设置 destination。",
+        "Backup job {name} has no destination.";
+    backup_keys_conflict(name) =>
+        "备份任务 {name} 的 key、key_file、recipients 与 password 只能设置其中一种。",
+        "Backup job {name} can only set one of key, key_file, recipients and password.";
+    backup_keep_zero(name) =>
+        "备份任务 {name} 的 keep 至少为 1。",
+        "The keep of backup job {name} must be at least 1.";
+    backup_source_invalid(name, source) =>
+        "备份任务 {name} 的源路径 {source} 无效。",
+        "The source {source} of backup job {name} is invalid.";
+    backup_source_reserved(name, manifest) =>
+        "备份任务 {name} 的源路径不能名为 {manifest}，快照清单使用了这个名字。",
+        "No source of backup job {name} can be called {manifest}, the name of the snapshot manifest.";
+    backup_sources_collide(name, source) =>
+        "备份任务 {name} 中有多个源路径名为 {source}，它们在快照中会相互覆盖。",
+        "Several sources of backup job {name} are called {source}, so they would overwrite each other in the snapshot.";
+    backup_schedule_invalid(value) =>
+        "无法解析备份周期 '{value}'，可使用 hourly、daily、weekly 或 6h 这样的时长。",
+        "Can't read the backup schedule '{value}'; use hourly, daily, weekly or a duration such as 6h.";
+    snapshot_manifest_invalid(error) =>
+        "无法解析快照清单：{error}",
+        "Can't read the snapshot manifest: {error}";
+    backup_usage() =>
+        "用法：rot backup run [--job 名称] [--force] [--dry-run] | rot backup ls [--job 名称]",
+        "Usage: rot backup run [--job name] [--force] [--dry-run] | rot backup ls [--job name]";
+    backup_job_not_found(name) =>
+        "rot.json 中没有名为 {name} 的备份任务。",
+        "rot.json has no backup job called {name}.";
+    no_backup_jobs() =>
+        "rot.json 中没有配置备份任务，请在 backups 中添加。",
+        "rot.json has no backup jobs; add them under backups.";
+    backup_job_failed(name, error) =>
+        "备份任务 {name} 失败：{error}",
+        "Backup job {name} failed: {error}";
+    backup_jobs_failed(count) =>
+        "{count} 个备份任务失败！",
+        "{count} backup jobs failed.";
+    backup_snapshots(name, count) =>
+        "{name}（{count} 个快照）：",
+        "{name} ({count} snapshots):";
+    snapshot_contents(count, size) =>
+        "{count} 个文件，{size}",
+        "{count} files, {size}";
+    snapshot_manifest_unreadable(snapshot, error) =>
+        "无法读取快照 {snapshot} 的清单：{error}",
+        "Can't read the manifest of the snapshot {snapshot}: {error}";
+    backup_not_due(name) =>
+        "备份任务 {name} 尚未到期，已跳过。",
+        "Backup job {name} isn't due yet, skipped.";
+    backup_source_missing(path) =>
+        "源路径 {path} 不存在！",
+        "The source {path} doesn't exist.";
+    planned_backup(name, count, snapshot, unchanged, expired) =>
+        "备份任务 {name} 将上传 {count} 个文件到 {snapshot}（{unchanged} 个未变化），并删除 {expired} 个旧快照（未执行任何操作）。",
+        "Backup job {name} would upload {count} files to {snapshot} ({unchanged} unchanged) and delete {expired} old snapshots (nothing was done).";
+    snapshot_abandoned(snapshot, failed) =>
+        "快照 {snapshot} 中有 {failed} 个文件上传失败，已放弃这个快照。",
+        "{failed} files of the snapshot {snapshot} failed to upload, so the snapshot was given up.";
+    snapshot_not_deleted(snapshot, key, error) =>
+        "删除旧快照 {snapshot} 时无法删除 {key}：{error}",
+        "Couldn't delete {key} while deleting the old snapshot {snapshot}: {error}";
+    snapshot_deleted(snapshot, count) =>
+        "已删除旧快照 {snapshot}（{count} 个对象）。",
+        "Deleted the old snapshot {snapshot} ({count} objects).";
+    backup_done(name, snapshot, count, uploaded, unchanged) =>
+        "备份任务 {name} 完成：快照 {snapshot}，共 {count} 个文件，上传 {uploaded} 个，{unchanged} 个未变化。",
+        "Backup job {name} done: snapshot {snapshot}, {count} files, {uploaded} uploaded, {unchanged} unchanged.";
+    restore_backup_usage() =>
+        "用法：rot restore-backup <快照> <本地目录> [--job 名称] [--force]",
+        "Usage: rot restore-backup <snapshot> <local-folder> [--job name] [--force]";
+    not_a_snapshot(snapshot) =>
+        "{snapshot} 不是备份快照，快照名形如 20240601T020000Z。",
+        "{snapshot} isn't a backup snapshot; snapshots are named like 20240601T020000Z.";
+    job_keys_conflict() =>
+        "--job 已包含备份任务的密钥，不能再使用 -p、--key 等选项。",
+        "--job already brings the secret of the backup job; don't add -p, --key or the like.";
+    snapshot_may_need_key(error) =>
+        "{error}（加密的快照需要用 --job 或 -p、--key 等选项提供密钥）",
+        "{error} (encrypted snapshots need their secret from --job, or -p, --key and the like)";
+    planned_snapshot_restore(count, size, dir) =>
+        "将恢复 {count} 个文件（{size}）到 {dir}（未执行任何操作）。",
+        "Would restore {count} files ({size}) to {dir} (nothing was done).";
+    restored_file_corrupt(path) =>
+        "{path} 的 SHA-256 与快照清单不符，文件可能已损坏！",
+        "The SHA-256 of {path} doesn't match the snapshot manifest; the file may be damaged.";
+    cannot_set_modified(path, error) =>
+        "无法设置 {path} 的修改时间：{error}",
+        "Can't set the modification time of {path}: {error}";
+    files_not_restored(count) =>
+        "{count} 个文件未能恢复！",
+        "{count} files couldn't be restored.";
+    snapshot_restored(snapshot, count, dir) =>
+        "已将快照 {snapshot} 的 {count} 个文件恢复到 {dir}。",
+        "Restored the {count} files of the snapshot {snapshot} to {dir}.";
+}
+
+// Mounting.
+messages! {
+    cannot_mount(path, error) =>
+        "无法挂载到 {path}：{error}",
+        "Can't mount on {path}: {error}";
+    cannot_unmount(path) =>
+        "无法卸载 {path}，请手动运行 umount。",
+        "Can't unmount {path}; run umount yourself.";
+    mount_failed(path, error) =>
+        "{path} 上的挂载出错：{error}",
+        "The mount on {path} failed: {error}";
+    object_shorter_than_listed() =>
+        "对象比列出的短",
+        "the object is shorter than listed";
+    mount_usage() =>
+        "用法：rot mount <挂载点> [前缀|oss://bucket/prefix] [-p 密码 | --key 名称 | --key-file 文件 | --identity 文件]",
+        "Usage: rot mount <mountpoint> [prefix|oss://bucket/prefix] [-p password | --key name | --key-file file | --identity file]";
+    mounted(remote, path) =>
+        "已将 {remote} 挂载到 {path}，按 Ctrl+C 卸载。",
+        "Mounted {remote} on {path}; press Ctrl+C to unmount.";
+    mount_not_built() =>
+        "这个 rot 没有编译挂载功能，请在 Linux 上用 cargo build --features mount 重新编译。",
+        "This rot was built without mounting; rebuild it on Linux with cargo build --features mount.";
+}
+
+// Keys.
+messages! {
+    key_usage() =>
+        "用法：rot key add <名称> [--from 密钥文件] [--force] | rot key ls | rot key rm <名称> [--yes]",
+        "Usage: rot key add <name> [--from key-file] [--force] | rot key ls | rot key rm <name> [--yes]";
+    key_file_unreadable(path, error) =>
+        "无法读取密钥文件 {path}：{error}",
+        "Can't read the key file {path}: {error}";
+    key_file_unwritable(path, error) =>
+        "无法写入密钥文件 {path}：{error}",
+        "Can't write the key file {path}: {error}";
+    key_file_len(path, expected, actual) =>
+        "密钥文件 {path} 应为 {expected} 字节，实际为 {actual} 字节。请使用 rot keygen 生成密钥文件。",
+        "The key file {path} should be {expected} bytes long, but is {actual}. Create key files with rot keygen.";
+    key_file_exists(path) =>
+        "密钥文件 {path} 已存在。覆盖后，用原密钥加密的文件将无法解密；如确需覆盖请使用 --force。",
+        "The key file {path} already exists. Files encrypted with the old key can't be decrypted once it is overwritten; use --force if you really mean to.";
+    key_file_created(path) =>
+        "已生成密钥文件 {path}。请妥善备份，丢失后用它加密的文件将无法解密。",
+        "Created the key file {path}. Back it up: files encrypted with it can't be decrypted without it.";
+    identity_created(path) =>
+        "已生成私钥文件 {path}。请妥善保管，丢失后为它加密的文件将无法解密。",
+        "Created the private key file {path}. Keep it safe: files encrypted for it can't be decrypted without it.";
+    recipient_to_share(recipient) =>
+        "公钥（交给发送方，用于 --recipient）：{recipient}",
+        "Public key (give it to senders for --recipient): {recipient}";
+    key_path_missing() =>
+        "无法定位配置目录，请指定密钥文件路径：rot keygen <路径>",
+        "Can't find the configuration folder; give the key file's path: rot keygen <path>";
+    identity_path_missing() =>
+        "无法定位配置目录，请指定私钥文件路径：rot keygen --identity <路径>",
+        "Can't find the configuration folder; give the private key file's path: rot keygen --identity <path>";
+    named_key_missing(name) =>
+        "没有名为 {name} 的密钥，可使用 rot key ls 查看已保存的密钥。",
+        "There's no key called {name}; rot key ls lists the saved keys.";
+    named_key_not_found(name) =>
+        "没有名为 {name} 的密钥。",
+        "There's no key called {name}.";
+    key_name_invalid(name, max) =>
+        "密钥名称无效：{name}。名称最长 {max} 个字符，只能包含字母、数字、-、_ 和 .，且不能以 . 开头。",
+        "Invalid key name: {name}. Names are at most {max} characters of letters, digits, -, _ and ., and can't start with a dot.";
+    named_key_exists(name) =>
+        "密钥 {name} 已存在。覆盖后，用原密钥加密的文件将无法解密；如确需覆盖请使用 --force。",
+        "The key {name} already exists. Files encrypted with the old key can't be decrypted once it is overwritten; use --force if you really mean to.";
+    named_key_saved(name, fingerprint, path) =>
+        "已保存密钥 {name}（指纹 {fingerprint}）。请妥善备份 {path}，丢失后用它加密的文件将无法解密。",
+        "Saved the key {name} (fingerprint {fingerprint}). Back up {path}: files encrypted with it can't be decrypted without it.";
+    no_named_keys() =>
+        "尚未保存任何密钥，可使用 rot key add <名称> 创建。",
+        "No keys are saved yet; create one with rot key add <name>.";
+    confirm_key_removal(name) =>
+        "删除后，用密钥 {name} 加密的文件将无法解密。确定要删除吗？",
+        "Files encrypted with the key {name} can't be decrypted once it is deleted. Delete it?";
+    named_key_removed(name) =>
+        "已删除密钥 {name}。",
+        "Deleted the key {name}.";
+}
+
+// Browser.
+messages! {
+    browse_help() =>
+        "Tab 切换  Enter 打开  Backspace 上级  u 上传  d 下载  r 重命名  x 删除  g 刷新  q 退出",
+        "Tab switch  Enter open  Backspace up  u upload  d download  r rename  x delete  g refresh  q quit";
+    browse_needs_terminal() =>
+        "rot browse 需要在终端中运行。",
+        "rot browse has to run in a terminal.";
+    cannot_draw(error) =>
+        "无法绘制界面：{error}",
+        "Can't draw the screen: {error}";
+    browse_busy_quit() =>
+        "操作仍在进行，中途退出可能留下不完整的文件；再按一次 q 强制退出。",
+        "An operation is still running, and quitting now may leave files half-written; press q again to quit anyway.";
+    browse_busy() =>
+        "请等待当前操作完成。",
+        "Wait for the current operation to finish.";
+    browse_working() =>
+        "正在处理……",
+        "Working…";
+    browse_nothing_to_rename() =>
+        "请先选择要重命名的文件或文件夹。",
+        "Select the file or folder to rename first.";
+    browse_nothing_to_upload() =>
+        "请先在左侧选择要上传的文件或文件夹。",
+        "Select the file or folder to upload on the left first.";
+    browse_nothing_to_download() =>
+        "请先在右侧选择要下载的文件或文件夹。",
+        "Select the file or folder to download on the right first.";
+    browse_overwrite(name) =>
+        "{name} 已存在，覆盖？(y/n)",
+        "{name} already exists. Overwrite it? (y/n)";
+    browse_delete_local_dir(path) =>
+        "删除本地目录 {path} 及其中所有文件？(y/n)",
+        "Delete the local folder {path} and everything in it? (y/n)";
+    browse_delete_local_file(path) =>
+        "删除本地文件 {path}？(y/n)",
+        "Delete the local file {path}? (y/n)";
+    browse_delete_remote_dir(prefix) =>
+        "删除 /{prefix} 下的所有对象？(y/n)",
+        "Delete every object below /{prefix}? (y/n)";
+    browse_delete_remote_object(key) =>
+        "删除对象 /{key}？(y/n)",
+        "Delete the object /{key}? (y/n)";
+    browse_name_invalid(name) =>
+        "'{name}' 不是有效的名字。",
+        "'{name}' isn't a valid name.";
+    browse_name_taken(name) =>
+        "{name} 已存在。",
+        "{name} already exists.";
+    browse_local_title(path) =>
+        " 本地：{path} ",
+        " Local: {path} ";
+    browse_remote_title(prefix) =>
+        " 远程：/{prefix} ",
+        " Remote: /{prefix} ";
+    browse_rename_prompt(name) =>
+        "新名字：{name}▏  Enter 确认  Esc 取消",
+        "New name: {name}▏  Enter confirm  Esc cancel";
+    browse_progress(action, done, total, item) =>
+        "正在{action} [{done}/{total}] {item}",
+        "{action} [{done}/{total}] {item}";
+    browse_uploaded(key) =>
+        "已上传 /{key}。",
+        "Uploaded /{key}.";
+    browse_downloaded(path, size) =>
+        "已下载 {path}（{size}）。",
+        "Downloaded {path} ({size}).";
+    browse_deleted(path) =>
+        "已删除 {path}。",
+        "Deleted {path}.";
+    browse_renamed(path) =>
+        "已重命名为 {path}。",
+        "Renamed to {path}.";
+    browse_job_done(action, count) =>
+        "已{action} {count} 个文件。",
+        "{action} {count} files.";
+    browse_job_failed(action, done, failed, error) =>
+        "{action}完成 {done} 个，失败 {failed} 个，首个错误：{error}",
+        "{action} {done} files and {failed} failed, the first with: {error}";
+    browse_uploading() => "上传", "Uploading";
+    browse_downloading() => "下载", "Downloading";
+    browse_deleting() => "删除", "Deleting";
+    browse_renaming() => "重命名", "Renaming";
+    browse_upload_done() => "上传", "Uploaded";
+    browse_download_done() => "下载", "Downloaded";
+    browse_delete_done() => "删除", "Deleted";
+    browse_rename_done() => "重命名", "Renamed";
+    browse_dry_run() =>
+        "rot browse 不支持 --dry-run。",
+        "rot browse doesn't support --dry-run.";
+    browse_not_built() =>
+        "这个 rot 没有编译文件浏览器，请用 cargo build --features browse 重新编译。",
+        "This rot was built without the file browser; rebuild it with cargo build --features browse.";
+}
+
+#[cfg(test)]
+mod test {
+    use crate::messages::{download_incomplete, unknown_command};
+
+    // The wording is left to the tests of the code using the messages.
+    #[test]
+    fn test_message_arguments() {
+        let message = download_incomplete(10, 4);
+        assert!(message.contains("10") && message.contains('4'));
+        assert!(message.find("10") < message.find('4'));
+        assert!(unknown_command("\"lss\"").contains("\"lss\""));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use aws_sdk_s3::primitives::DateTime;
+use fuser::consts::{FOPEN_DIRECT_IO, FOPEN_KEEP_CACHE};
+use fuser::{FileAttr, FileType, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, ReplyOpen, Request, Session};
+use tokio::runtime::Handle;
+use crate::client::AliyunClient;
+use crate::constant::{ENCRYPTED_EXT, MAX_HEADER_LEN};
+use crate::crypt::{Cipher, plain_len, StreamDecryptor};
+use crate::messages;
+
+const ROOT_INODE: u64 = fuser::FUSE_ROOT_ID;
+/// How long the kernel may cache names and attributes. The mount doesn't
+/// notice changes to the bucket before that.
+const ATTR_TTL: Duration = Duration::from_secs(60);
+/// Bytes of an encrypted object fetched per request while decrypting.
+const READ_PIECE: u64 = 256 * 1024;
+
+/// A read-only view of `prefix` mounted at `mountpoint` until Ctrl+C or an
+/// `umount` from outside. Folders are listed when first looked into and
+/// reads fetch only the ranges asked for. With a cipher, objects ending in
+/// `.enc` show without the suffix and are decrypted on the fly, from the
+/// chunk holding the first byte read; compressed ones have to be decrypted
+/// from the start on every read and show their stored size.
+pub(crate) async fn serve(client: AliyunClient,
+                          prefix: String,
+                          cipher: Option<Arc<Cipher>>,
+                          mountpoint: &Path) -> Result<(), String> {
+    let filesystem = Fuse {
+        filesystem: Arc::new(Filesystem::new(client.clone(), prefix, cipher)),
+        runtime: Handle::current(),
+    };
+    // fuser mounts with mount(2) when allowed to, as root, and otherwise
+    // through the setuid `fusermount3` of libfuse.
+    let options = [
+        MountOption::RO,
+        MountOption::NoSuid,
+        MountOption::NoDev,
+        MountOption::DefaultPermissions,
+        MountOption::FSName("rot".into()),
+        MountOption::Subtype("rot".into()),
+    ];
+    let mut session = Session::new(filesystem, mountpoint, &options)
+        .map_err(|e| messages::cannot_mount(mountpoint.display(), e))?;
+    let mut unmounter = session.unmount_callable();
+    // The session ends once the kernel closes the connection, which it does
+    // when the mount is gone.
+    let mut running = tokio::task::spawn_blocking(move || session.run());
+    let finished = tokio::select! {
+        finished = &mut running => finished,
+        _ = client.cancelled() => {
+            // Detaches the filesystem; open files keep it alive until closed.
+            if unmounter.unmount().is_err() {
+                eprintln!("{}", messages::cannot_unmount(mountpoint.display()));
+            }
+            running.await
+        }
+    };
+    finished.map_err(|e| e.to_string())?
+        .map_err(|e| messages::mount_failed(mountpoint.display(), e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Attr {
+    ino: u64,
+    size: u64,
+    mtime: i64,
+    dir: bool,
+}
+
+impl Attr {
+    /// The attributes as fuser reports them, owned by whoever mounted the
+    /// filesystem.
+    fn file_attr(&self) -> FileAttr {
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(self.mtime.max(0) as u64);
+        FileAttr {
+            ino: self.ino,
+            size: self.size,
+            blocks: self.size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind: if self.dir { FileType::Directory } else { FileType::RegularFile },
+            perm: if self.dir { 0o555 } else { 0o444 },
+            nlink: if self.dir { 2 } else { 1 },
+            // SAFETY: getuid and getgid cannot fail.
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+}
+
+/// A name in a listed folder, before it gets an inode.
+#[derive(Debug, Clone, PartialEq)]
+enum Entry {
+    Folder { prefix: String },
+    Object { key: String, size: u64, mtime: i64, encrypted: bool },
+}
+
+/// The entries of the folder `prefix` from its listing. With `decrypt`,
+/// `.enc` objects lose the suffix. A folder wins over an object of the same
+/// name, and keys that make no file name, such as folder markers, are left
+/// out.
+fn folder_entries(prefix: &str, folders: &[String], objects: &[(String, u64, i64)], decrypt: bool) -> BTreeMap<String, Entry> {
+    let valid = |name: &str| !(name.is_empty() || name == "." || name == ".." || name.contains('/'));
+    let mut entries = BTreeMap::new();
+    for folder in folders {
+        let Some(name) = folder.strip_prefix(prefix).map(|name| name.trim_end_matches('/')) else { continue };
+        if valid(name) {
+            entries.insert(name.to_string(), Entry::Folder { prefix: folder.clone() });
+        }
+    }
+    for (key, size, mtime) in objects {
+        let Some(name) = key.strip_prefix(prefix) else { continue };
+        let stripped = name.strip_suffix(ENCRYPTED_EXT).filter(|_| decrypt);
+        let name = stripped.unwrap_or(name);
+        if valid(name) {
+            entries.entry(name.to_string()).or_insert_with(|| Entry::Object {
+                key: key.clone(),
+                size: *size,
+                mtime: *mtime,
+                encrypted: stripped.is_some(),
+            });
+        }
+    }
+    entries
+}
+
+struct FileNode {
+    key: String,
+    stored_size: u64,
+    mtime: i64,
+    encrypted: bool,
+    /// The start of an encrypted object and its plaintext size, once read.
+    /// The size is `None` for compressed files.
+    head: Option<(Arc<Vec<u8>>, Option<u64>)>,
+}
+
+enum Node {
+    Dir { parent: u64, prefix: String, children: Option<BTreeMap<String, u64>> },
+    File(FileNode),
+}
+
+/// Inodes are handed out as folders are listed and never reused, which
+/// keeps the kernel's `FORGET` messages from mattering.
+struct Filesystem {
+    client: AliyunClient,
+    cipher: Option<Arc<Cipher>>,
+    /// Inode `n` is at index `n - 1`; the kernel never asks for inode 0.
+    nodes: Mutex<Vec<Node>>,
+    mounted_at: i64,
+}
+
+impl Filesystem {
+    fn new(client: AliyunClient, prefix: String, cipher: Option<Arc<Cipher>>) -> Self {
+        Self {
+            client,
+            cipher,
+            nodes: Mutex::new(vec![Node::Dir { parent: ROOT_INODE, prefix, children: None }]),
+            mounted_at: DateTime::from(SystemTime::now()).secs(),
+        }
+    }
+
+    /// The children of folder `ino`, listing it the first time.
+    async fn children(&self, ino: u64) -> Result<BTreeMap<String, u64>, i32> {
+        let prefix = match self.nodes.lock().unwrap().get(ino.wrapping_sub(1) as usize) {
+            Some(Node::Dir { children: Some(children), .. }) => return Ok(children.clone()),
+            Some(Node::Dir { prefix, .. }) => prefix.clone(),
+            Some(Node::File(_)) => return Err(libc::ENOTDIR),
+            None => return Err(libc::ENOENT),
+        };
+
+        let mut folders = Vec::new();
+        let mut objects = Vec::new();
+        let mut token = None;
+        loop {
+            let page = self.client.list_level(None, Some(prefix.clone()).filter(|prefix| !prefix.is_empty()), token).await
+                .map_err(|e| io_error(&prefix, e.to_string()))?;
+            folders.extend(page.common_prefixes().iter().filter_map(|common| common.prefix()).map(String::from));
+            objects.extend(page.contents().iter().map(|object| (
+                object.key().unwrap_or_default().to_string(),
+                object.size().unwrap_or(0).max(0) as u64,
+                object.last_modified().map_or(self.mounted_at, |time| time.secs()),
+            )));
+            match page.next_continuation_token {
+                Some(next) if page.is_truncated == Some(true) => token = Some(next),
+                _ => break,
+            }
+        }
+        let entries = folder_entries(&prefix, &folders, &objects, self.cipher.is_some());
+
+        let mut nodes = self.nodes.lock().unwrap();
+        // Another request may have listed the folder meanwhile.
+        if let Some(Node::Dir { children: Some(children), .. }) = nodes.get(ino.wrapping_sub(1) as usize) {
+            return Ok(children.clone());
+        }
+        let mut children = BTreeMap::new();
+        for (name, entry) in entries {
+            nodes.push(match entry {
+                Entry::Folder { prefix } => Node::Dir { parent: ino, prefix, children: None },
+                Entry::Object { key, size, mtime, encrypted } => Node::File(FileNode { key, stored_size: size, mtime, encrypted, head: None }),
+            });
+            children.insert(name, nodes.len() as u64);
+        }
+        if let Some(Node::Dir { children: slot, .. }) = nodes.get_mut(ino.wrapping_sub(1) as usize) {
+            *slot = Some(children.clone());
+        }
+        Ok(children)
+    }
+
+    async fn lookup(&self, parent: u64, name: &str) -> Result<Attr, i32> {
+        let ino = *self.children(parent).await?.get(name).ok_or(libc::ENOENT)?;
+        self.attr(ino).await
+    }
+
+    /// The attributes of `ino`. An encrypted file's plaintext size needs
+    /// its header, which is fetched the first time.
+    async fn attr(&self, ino: u64) -> Result<Attr, i32> {
+        let (key, stored_size) = match self.nodes.lock().unwrap().get(ino.wrapping_sub(1) as usize) {
+            Some(Node::Dir { .. }) => return Ok(Attr { ino, size: 0, mtime: self.mounted_at, dir: true }),
+            Some(Node::File(file)) => match &file.head {
+                _ if !file.encrypted => return Ok(Attr { ino, size: file.stored_size, mtime: file.mtime, dir: false }),
+                Some((_, size)) => return Ok(Attr { ino, size: size.unwrap_or(file.stored_size), mtime: file.mtime, dir: false }),
+                None => (file.key.clone(), file.stored_size),
+            },
+            None => return Err(libc::ENOENT),
+        };
+        let head = self.get_range(&key, 0, stored_size.min(MAX_HEADER_LEN as u64)).await?;
+        let size = plain_len(&head, stored_size).map_err(|e| io_error(&key, e))?;
+        let mut nodes = self.nodes.lock().unwrap();
+        let Some(Node::File(file)) = nodes.get_mut(ino.wrapping_sub(1) as usize) else { return Err(libc::ENOENT) };
+        file.head = Some((Arc::new(head), size));
+        Ok(Attr { ino, size: size.unwrap_or(stored_size), mtime: file.mtime, dir: false })
+    }
+
+    /// The flags `ino` is opened with.
+    async fn open(&self, ino: u64, flags: i32) -> Result<u32, i32> {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return Err(libc::EROFS);
+        }
+        self.attr(ino).await?;
+        let exact = match self.nodes.lock().unwrap().get(ino.wrapping_sub(1) as usize) {
+            Some(Node::File(file)) => file.head.as_ref().is_none_or(|(_, size)| size.is_some()),
+            Some(Node::Dir { .. }) => return Err(libc::EISDIR),
+            None => return Err(libc::ENOENT),
+        };
+        // Without a known size the kernel must not stop reading at it.
+        Ok(if exact { FOPEN_KEEP_CACHE } else { FOPEN_DIRECT_IO })
+    }
+
+    async fn read(&self, ino: u64, offset: u64, size: u32) -> Result<Vec<u8>, i32> {
+        let (key, stored_size, head) = match self.nodes.lock().unwrap().get(ino.wrapping_sub(1) as usize) {
+            Some(Node::File(file)) => (file.key.clone(), file.stored_size, file.head.as_ref().map(|(head, _)| Arc::clone(head))),
+            Some(Node::Dir { .. }) => return Err(libc::EISDIR),
+            None => return Err(libc::ENOENT),
+        };
+        match (head, &self.cipher) {
+            (Some(head), Some(cipher)) => self.read_decrypted(&key, stored_size, &head, Arc::clone(cipher), offset, size as u64).await,
+            _ if offset >= stored_size => Ok(Vec::new()),
+            _ => self.get_range(&key, offset, (size as u64).min(stored_size - offset)).await,
+        }
+    }
+
+    /// Decrypts `size` bytes at plaintext `offset`, starting from the chunk
+    /// holding it.
+    async fn read_decrypted(&self,
+                            key: &str,
+                            stored_size: u64,
+                            head: &[u8],
+                            cipher: Arc<Cipher>,
+                            offset: u64,
+                            size: u64) -> Result<Vec<u8>, i32> {
+        let (mut decryptor, mut position, start) = StreamDecryptor::resume(cipher, head, offset + 1)
+            .map_err(|e| io_error(key, e))?;
+        let mut plain = Vec::new();
+        while start + (plain.len() as u64) < offset + size && position < stored_size {
+            let data = self.get_range(key, position, READ_PIECE.min(stored_size - position)).await?;
+            if data.is_empty() {
+                return Err(io_error(key, messages::object_shorter_than_listed()));
+            }
+            position += data.len() as u64;
+            plain.extend(decryptor.update(&data).map_err(|e| io_error(key, e))?);
+        }
+        if position >= stored_size {
+            plain.extend(decryptor.finish().map_err(|e| io_error(key, e))?);
+        }
+        let skip = (offset - start).min(plain.len() as u64) as usize;
+        let end = (skip as u64 + size).min(plain.len() as u64) as usize;
+        Ok(plain[skip..end].to_vec())
+    }
+
+    /// The entries of folder `ino`, with `.` and `..` first, as `(inode,
+    /// name, is a folder)`.
+    async fn read_dir(&self, ino: u64) -> Result<Vec<(u64, String, bool)>, i32> {
+        let children = self.children(ino).await?;
+        let nodes = self.nodes.lock().unwrap();
+        let parent = match nodes.get(ino.wrapping_sub(1) as usize) {
+            Some(Node::Dir { parent, .. }) => *parent,
+            _ => return Err(libc::ENOTDIR),
+        };
+        let mut entries = vec![(ino, ".".to_string(), true), (parent, "..".to_string(), true)];
+        entries.extend(children.into_iter()
+            .map(|(name, child)| (child, name, matches!(nodes.get(child.wrapping_sub(1) as usize), Some(Node::Dir { .. })))));
+        Ok(entries)
+    }
+
+    async fn get_range(&self, key: &str, start: u64, len: u64) -> Result<Vec<u8>, i32> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        self.client.get_range(key, start, len).await.map_err(|e| io_error(key, e))
+    }
+}
+
+/// Reports a failed request on stderr, the only place it can be seen, and
+/// turns it into EIO for the caller.
+fn io_error(key: &str, error: String) -> i32 {
+    eprintln!("{}", messages::cannot_read(key, error));
+    libc::EIO
+}
+
+/// Answers fuser's callbacks, which come in one at a time on its thread,
+/// from tasks on the runtime, so that slow requests don't hold up others.
+struct Fuse {
+    filesystem: Arc<Filesystem>,
+    runtime: Handle,
+}
+
+impl Fuse {
+    fn spawn<F: Future<Output=()> + Send + 'static>(&self, request: impl FnOnce(Arc<Filesystem>) -> F) {
+        self.runtime.spawn(request(Arc::clone(&self.filesystem)));
+    }
+}
+
+impl fuser::Filesystem for Fuse {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(name) = name.to_str().map(String::from) else { return reply.error(libc::ENOENT) };
+        self.spawn(move |filesystem| async move {
+            match filesystem.lookup(parent, &name).await {
+                Ok(attr) => reply.entry(&ATTR_TTL, &attr.file_attr(), 0),
+                Err(errno) => reply.error(errno),
+            }
+        });
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        self.spawn(move |filesystem| async move {
+            match filesystem.attr(ino).await {
+                Ok(attr) => reply.attr(&ATTR_TTL, &attr.file_attr()),
+                Err(errno) => reply.error(errno),
+            }
+        });
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.spawn(move |filesystem| async move {
+            match filesystem.open(ino, flags).await {
+                Ok(open_flags) => reply.opened(0, open_flags),
+                Err(errno) => reply.error(errno),
+            }
+        });
+    }
+
+    fn read(&mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData) {
+        self.spawn(move |filesystem| async move {
+            match filesystem.read(ino, offset.max(0) as u64, size).await {
+                Ok(data) => reply.data(&data),
+                Err(errno) => reply.error(errno),
+            }
+        });
+    }
+
+    fn opendir(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        self.spawn(move |filesystem| async move {
+            match filesystem.children(ino).await {
+                Ok(_) => reply.opened(0, 0),
+                Err(errno) => reply.error(errno),
+            }
+        });
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        self.spawn(move |filesystem| async move {
+            let entries = match filesystem.read_dir(ino).await {
+                Ok(entries) => entries,
+                Err(errno) => return reply.error(errno),
+            };
+            // Each entry's offset is that of the next one.
+            for (index, (child, name, dir)) in entries.into_iter().enumerate().skip(offset.max(0) as usize) {
+                let kind = if dir { FileType::Directory } else { FileType::RegularFile };
+                if reply.add(child, index as i64 + 1, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mount::{Entry, folder_entries};
+
+    #[test]
+    fn test_folder_entries() {
+        let folders = vec!["photos/2024/".to_string(), "photos/a.jpg/".to_string()];
+        let objects = vec![
+            ("photos/".to_string(), 0, 0),
+            ("photos/a.jpg".to_string(), 3, 10),
+            ("photos/notes.txt.enc".to_string(), 100, 20),
+            ("photos/notes.txt".to_string(), 5, 30),
+        ];
+        let entries = folder_entries("photos/", &folders, &objects, true);
+        assert_eq!(entries.keys().collect::<Vec<_>>(), ["2024", "a.jpg", "notes.txt"]);
+        assert_eq!(entries["a.jpg"], Entry::Folder { prefix: "photos/a.jpg/".into() });
+        assert_eq!(entries["notes.txt"], Entry::Object { key: "photos/notes.txt.enc".into(), size: 100, mtime: 20, encrypted: true });
+
+        let entries = folder_entries("photos/", &folders, &objects, false);
+        assert_eq!(entries.keys().collect::<Vec<_>>(), ["2024", "a.jpg", "notes.txt", "notes.txt.enc"]);
+        assert_eq!(entries["notes.txt"], Entry::Object { key: "photos/notes.txt".into(), size: 5, mtime: 30, encrypted: false });
+    }
+}
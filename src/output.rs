@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{Bucket, DeleteMarkerEntry, Object, ObjectVersion};
+use serde::Serialize;
+use crate::messages;
+use crate::store::ObjectInfo;
+
+/// How command results are printed, chosen with `--output`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON document per line on stdout; everything meant for a person
+    /// goes to stderr so that stdout stays parseable.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(messages::unknown_output_format(value)),
+        }
+    }
+}
+
+/// Prints what commands report in the chosen format.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Reporter {
+    format: OutputFormat,
+}
+
+impl Reporter {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+
+    /// Progress or summary text for a person.
+    pub fn message(&self, text: impl Display) {
+        match self.format {
+            OutputFormat::Text => println!("{}", text),
+            OutputFormat::Json => eprintln!("{}", text),
+        }
+    }
+
+    /// A warning, or a note beside results meant for scripts. It goes to
+    /// stderr in either format, away from the results.
+    pub fn warn(&self, text: impl Display) {
+        eprintln!("{}", text);
+    }
+
+    /// One result of the command: `text` as is, or `record` as a JSON line.
+    pub fn record(&self, record: &impl Serialize, text: impl Display) {
+        match self.format {
+            OutputFormat::Text => println!("{}", text),
+            OutputFormat::Json => println!("{}", to_json(record)),
+        }
+    }
+
+    /// A failed item. The text goes to stderr, while in JSON mode the record
+    /// joins the other results so that scripts see every item.
+    pub fn failure(&self, record: &impl Serialize, text: impl Display) {
+        match self.format {
+            OutputFormat::Text => eprintln!("{}", text),
+            OutputFormat::Json => println!("{}", to_json(record)),
+        }
+    }
+}
+
+fn to_json(record: &impl Serialize) -> String {
+    serde_json::to_string(record).unwrap_or_else(|e| format!("{{\"error\":{:?}}}", e.to_string()))
+}
+
+/// An object as listed.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ObjectRecord {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<String>,
+    pub storage_class: Option<String>,
+    pub etag: Option<String>,
+    /// The key a symlink points to. Only looked up by `ls -l`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
+}
+
+impl From<&Object> for ObjectRecord {
+    fn from(object: &Object) -> Self {
+        Self {
+            key: object.key.clone().unwrap_or_default(),
+            size: object.size.unwrap_or(0).max(0) as u64,
+            last_modified: object.last_modified.and_then(|time| time.fmt(DateTimeFormat::DateTime).ok()),
+            storage_class: object.storage_class.as_ref().map(|class| class.as_str().to_string()),
+            etag: object.e_tag.clone(),
+            symlink_target: None,
+        }
+    }
+}
+
+impl From<&ObjectInfo> for ObjectRecord {
+    fn from(object: &ObjectInfo) -> Self {
+        Self {
+            key: object.key.clone(),
+            size: object.size,
+            last_modified: object.last_modified.and_then(|secs| DateTime::from_secs(secs).fmt(DateTimeFormat::DateTime).ok()),
+            storage_class: None,
+            etag: object.etag.clone(),
+            symlink_target: None,
+        }
+    }
+}
+
+/// One version of an object, or a delete marker, as `rot versions` lists it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct VersionRecord {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub delete_marker: bool,
+    /// Absent for delete markers.
+    pub size: Option<u64>,
+    pub last_modified: Option<String>,
+}
+
+impl From<&ObjectVersion> for VersionRecord {
+    fn from(version: &ObjectVersion) -> Self {
+        Self {
+            key: version.key.clone().unwrap_or_default(),
+            version_id: version.version_id.clone().unwrap_or_default(),
+            is_latest: version.is_latest.unwrap_or(false),
+            delete_marker: false,
+            size: version.size.map(|size| size.max(0) as u64),
+            last_modified: version.last_modified.and_then(|time| time.fmt(DateTimeFormat::DateTime).ok()),
+        }
+    }
+}
+
+impl From<&DeleteMarkerEntry> for VersionRecord {
+    fn from(marker: &DeleteMarkerEntry) -> Self {
+        Self {
+            key: marker.key.clone().unwrap_or_default(),
+            version_id: marker.version_id.clone().unwrap_or_default(),
+            is_latest: marker.is_latest.unwrap_or(false),
+            delete_marker: true,
+            size: None,
+            last_modified: marker.last_modified.and_then(|time| time.fmt(DateTimeFormat::DateTime).ok()),
+        }
+    }
+}
+
+/// A single object's headers, as `stat` shows them.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct StatRecord {
+    pub key: String,
+    pub content_length: u64,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// HEAD responses omit the default class, which is reported as
+    /// `STANDARD`.
+    pub storage_class: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl StatRecord {
+    pub fn new(key: impl Into<String>, head: &HeadObjectOutput) -> Self {
+        Self {
+            key: key.into(),
+            content_length: head.content_length.unwrap_or(0).max(0) as u64,
+            content_type: head.content_type.clone(),
+            etag: head.e_tag.clone(),
+            last_modified: head.last_modified.and_then(|time| time.fmt(DateTimeFormat::DateTime).ok()),
+            storage_class: head.storage_class.as_ref().map_or("STANDARD", |class| class.as_str()).to_string(),
+            metadata: head.metadata.clone().unwrap_or_default().into_iter().collect(),
+        }
+    }
+}
+
+/// A bucket as `rot bucket ls` lists it.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct BucketRecord {
+    pub name: String,
+    pub creation_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+impl From<&Bucket> for BucketRecord {
+    fn from(bucket: &Bucket) -> Self {
+        Self {
+            name: bucket.name.clone().unwrap_or_default(),
+            creation_date: bucket.creation_date.and_then(|time| time.fmt(DateTimeFormat::DateTime).ok()),
+            region: bucket.bucket_region.clone(),
+        }
+    }
+}
+
+/// A common prefix in a listing, which `rot ls` shows as a folder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct FolderRecord {
+    pub prefix: String,
+}
+
+/// Objects and bytes stored under one prefix, as `rot du` adds them up.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct UsageRecord {
+    pub prefix: String,
+    pub objects: u64,
+    pub size: u64,
+}
+
+/// The canned ACL of one object, as `rot acl get` shows it.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct AclRecord {
+    pub key: String,
+    pub acl: String,
+}
+
+/// The tags of one object, as `rot tag get` shows them.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TagRecord {
+    pub key: String,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TransferStatus {
+    Done,
+    /// Would be transferred, reported by `--dry-run`.
+    Planned,
+    Unchanged,
+    Failed,
+}
+
+/// A file uploaded by `rot tmpshare` and the link to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct ShareRecord {
+    pub key: String,
+    pub url: String,
+    pub expires_at: String,
+}
+
+/// The outcome of uploading or downloading one file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TransferRecord {
+    pub action: &'static str,
+    pub status: TransferStatus,
+    pub key: Option<String>,
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TransferRecord {
+    pub fn new(action: &'static str, status: TransferStatus, key: Option<String>, path: PathBuf) -> Self {
+        Self { action, status, key, path, bytes: None, etag: None, version_id: None, error: None }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use crate::output::{OutputFormat, TransferRecord, TransferStatus};
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!("json".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+        assert_eq!("TEXT".parse::<OutputFormat>(), Ok(OutputFormat::Text));
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_transfer_record_json() {
+        let mut record = TransferRecord::new("upload", TransferStatus::Done, Some("a.txt".into()), PathBuf::from("/tmp/a.txt"));
+        record.bytes = Some(3);
+        assert_eq!(serde_json::to_string(&record).unwrap(),
+                   "{\"action\":\"upload\",\"status\":\"done\",\"key\":\"a.txt\",\"path\":\"/tmp/a.txt\",\"bytes\":3}");
+    }
+}
@@ -6,6 +6,9 @@ pub struct Arguments {
     pub positional: Vec<String>,
     pub main_command: Option<String>,
     pub optional: HashMap<String, String>,
+    /// Every option in command-line order, including repeats that
+    /// `optional` only keeps the last of.
+    pub ordered: Vec<(String, String)>,
 }
 
 impl PartialEq for Arguments {
@@ -30,6 +33,25 @@ impl PartialEq for Arguments {
     }
 }
 
+impl Arguments {
+    pub fn has_flag(&self, names: &[&str]) -> bool {
+        self.flags.iter().any(|flag| names.contains(&flag.as_str()))
+    }
+
+    pub fn option(&self, names: &[&str]) -> Option<&String> {
+        names.iter().find_map(|name| self.optional.get(*name))
+    }
+
+    /// Every value given for any of `names`, in command-line order, for
+    /// options that may be repeated.
+    pub fn options(&self, names: &[&str]) -> Vec<(&str, &str)> {
+        self.ordered.iter()
+            .filter(|(name, _)| names.contains(&name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
 pub struct CommandParser;
 
 impl CommandParser {
@@ -37,6 +59,7 @@ impl CommandParser {
         let mut flags: Vec<String> = Vec::new();
         let mut positional: Vec<String> = Vec::new();
         let mut optional: HashMap<String, String> = HashMap::new();
+        let mut ordered: Vec<(String, String)> = Vec::new();
         let mut main_command: Option<String> = None;
 
         let mut iter = args.into_iter().skip(1);
@@ -44,7 +67,8 @@ impl CommandParser {
         let mut buffer: Option<String> = None;
 
         while let Some(arg) = buffer.take().or_else(|| iter.next().map(|arg| arg.into())) {
-            if arg.starts_with('-') {
+            // A lone `-` stands for stdin or stdout, like a file name.
+            if arg.starts_with('-') && arg != "-" {
                 let skip_chr = arg.get_skip_chr();
                 if skip_chr == -1 {
                     continue;
@@ -57,13 +81,17 @@ impl CommandParser {
                         buffer = Some(next_arg);
                         flags.push(arg[skip_chr as usize..].into());
                     } else {
+                        ordered.push((arg[skip_chr as usize..].into(), next_arg.clone()));
                         optional.insert(arg[skip_chr as usize..].into(), next_arg);
                     }
+                } else {
+                    flags.push(arg[skip_chr as usize..].into());
                 }
             } else if arg.contains('=') && !(arg.starts_with('=') || arg.ends_with('=')) {
                 let mut parts = arg.splitn(2, '=');
-                optional.insert(parts.next().unwrap().into(),
-                                parts.next().unwrap().into());
+                let (name, value): (String, String) = (parts.next().unwrap().into(), parts.next().unwrap().into());
+                ordered.push((name.clone(), value.clone()));
+                optional.insert(name, value);
             } else if main_command.is_none() {
                 main_command = Some(arg);
             } else {
@@ -74,6 +102,7 @@ impl CommandParser {
         Arguments {
             flags,
             optional,
+            ordered,
             main_command,
             positional,
         }
@@ -132,22 +161,19 @@ mod test {
     fn test_parse_command() {
         let args = Vec::from(["a.exe", "put", "text=Hello World!", "--release", "-c", "-s", "mode=1", "-e", "environment=java", "box-1", "box-2"]);
 
-        let mut flags: Vec<String> = Vec::new();
-        flags.push("c".into());
-        flags.push("release".into());
+        let flags: Vec<String> = vec!["c".into(), "release".into()];
 
         let mut optional: HashMap<String, String> = HashMap::new();
         optional.insert("s".into(), "mode=1".into());
         optional.insert("e".into(), "environment=java".into());
         optional.insert("text".into(), "Hello World!".into());
 
-        let mut positional: Vec<String> = Vec::new();
-        positional.push("box-1".into());
-        positional.push("box-2".into());
+        let positional: Vec<String> = vec!["box-1".into(), "box-2".into()];
 
         let command = Arguments {
             flags,
             optional,
+            ordered: Vec::new(),
             main_command: Some("put".into()),
             positional,
         };
@@ -155,4 +181,30 @@ mod test {
 
         assert_eq!(command, command_by_from);
     }
+
+    #[test]
+    fn test_trailing_flag() {
+        let arguments = CommandParser::from_strings(["rot", "download", "photos/", "-o", "out", "-r"]);
+
+        assert!(arguments.has_flag(&["r", "recursive"]));
+        assert_eq!(arguments.option(&["o", "output"]), Some(&"out".to_string()));
+        assert_eq!(arguments.positional, vec!["photos/".to_string()]);
+    }
+
+    #[test]
+    fn test_dash_positional() {
+        let arguments = CommandParser::from_strings(["rot", "append", "logs/app.log", "-", "--dry-run"]);
+
+        assert_eq!(arguments.positional, vec!["logs/app.log".to_string(), "-".to_string()]);
+        assert!(arguments.has_flag(&["dry-run"]));
+    }
+
+    #[test]
+    fn test_repeated_options() {
+        let arguments = CommandParser::from_strings(["rot", "sync", "--include", "*.jpg", "--exclude", "*.tmp", "--include", "keep.tmp", "src", "dst"]);
+
+        assert_eq!(arguments.options(&["include", "exclude"]), vec![("include", "*.jpg"), ("exclude", "*.tmp"), ("include", "keep.tmp")]);
+        assert_eq!(arguments.option(&["include"]), Some(&"keep.tmp".to_string()));
+        assert_eq!(arguments.positional, vec!["src".to_string(), "dst".to_string()]);
+    }
 }
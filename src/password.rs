@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use crate::messages;
+
+/// Passwords shorter than this are always treated as weak.
+const MIN_LENGTH: usize = 8;
+/// Estimated bits below which a password is treated as weak.
+const MIN_BITS: f64 = 50.0;
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "12345678", "123456789", "1234567890", "password", "password1", "qwerty", "qwerty123",
+    "abc123", "111111", "000000", "iloveyou", "admin", "letmein", "welcome", "1q2w3e4r", "woaini1314",
+];
+
+/// Rough entropy estimate in bits: length times the log of the character
+/// pool, counting repeated characters once and rejecting well-known
+/// passwords outright. Good enough to catch typos and trivial choices,
+/// not a substitute for a real cracking model.
+pub(crate) fn estimate_bits(password: &str) -> f64 {
+    if COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+        return 0.0;
+    }
+
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) { pool += 26; }
+    if password.chars().any(|c| c.is_ascii_uppercase()) { pool += 26; }
+    if password.chars().any(|c| c.is_ascii_digit()) { pool += 10; }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') { pool += 33; }
+    if !password.is_ascii() { pool += 100; }
+
+    let distinct = password.chars().collect::<HashSet<_>>().len();
+    let length = (distinct + password.chars().count()) as f64 / 2.0;
+    length * (pool.max(1) as f64).log2()
+}
+
+pub(crate) fn is_weak(password: &str) -> bool {
+    password.chars().count() < MIN_LENGTH || estimate_bits(password) < MIN_BITS
+}
+
+/// Checks a password that is about to encrypt data: rejects weak ones unless
+/// `allow_weak`, and when running interactively asks for it a second time,
+/// since a typo would make the data unrecoverable. Used by every command
+/// that takes a new password on the command line or the terminal.
+pub(crate) fn check_new_password(password: &str, allow_weak: bool) -> Result<(), String> {
+    check_strength(password, allow_weak)?;
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        let confirmation = rpassword::prompt_password(messages::confirm_password_prompt()).map_err(|e| e.to_string())?;
+        if confirmation != password {
+            return Err(messages::passwords_differ());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects a weak password unless `allow_weak`, warning when one is used.
+/// On its own for passwords read from a file, which there is no point in
+/// typing again.
+pub(crate) fn check_strength(password: &str, allow_weak: bool) -> Result<(), String> {
+    if is_weak(password) {
+        if !allow_weak {
+            return Err(messages::weak_password(estimate_bits(password), MIN_LENGTH));
+        }
+        eprintln!("{}", messages::weak_password_warning());
+    }
+    Ok(())
+}
+
+/// Reads a password typed on the terminal without echoing it, for
+/// `--encrypt` and `--decrypt` without `-p`.
+pub(crate) fn prompt_password(prompt: &str) -> Result<String, String> {
+    if !std::io::stdin().is_terminal() {
+        return Err(messages::password_needs_terminal());
+    }
+    let password = rpassword::prompt_password(prompt).map_err(|e| e.to_string())?;
+    if password.is_empty() {
+        return Err(messages::empty_password());
+    }
+    Ok(password)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::password::{estimate_bits, is_weak};
+
+    #[test]
+    fn test_password_strength() {
+        assert!(is_weak("short1!"));
+        assert!(is_weak("password1"));
+        assert!(is_weak("aaaaaaaaaaaaaaaa"));
+        assert!(is_weak("abcdefgh"));
+        assert!(!is_weak("correct horse battery staple"));
+        assert!(!is_weak("Tr0ub4dor&3x!"));
+        assert!(estimate_bits("abcdefgH1!") > estimate_bits("abcdefgh"));
+        assert_eq!(estimate_bits("Qwerty123"), 0.0);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::glob::Filter;
+use crate::manifest::ManifestFormat;
+use crate::messages;
+use crate::utils::{config_dir, create_dir};
+
+/// An upload recorded while the endpoint was unreachable. Passwords are never
+/// written to disk; encrypted jobs need `-p` again when the queue is flushed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct QueuedUpload {
+    pub id: u64,
+    pub path: PathBuf,
+    pub upload_dir: String,
+    pub recursive: bool,
+    pub encrypted: bool,
+    pub expiry_seconds: Option<i64>,
+    #[serde(default)]
+    pub manifest: Option<ManifestFormat>,
+    /// Path of the signing key, re-read when the queue is flushed.
+    #[serde(default)]
+    pub sign_key: Option<PathBuf>,
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    #[serde(default)]
+    pub filter: Filter,
+    /// Replace existing objects, as `--force` does.
+    #[serde(default)]
+    pub force: bool,
+    /// Tags from `--tagging`.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// ACL from `--acl`.
+    #[serde(default)]
+    pub acl: Option<String>,
+    /// Store the objects under hashes of their names, as `--hide-name` does.
+    #[serde(default)]
+    pub hide_name: bool,
+    /// zstd level from `--compress`.
+    #[serde(default)]
+    pub compress: Option<i32>,
+    pub queued_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct UploadQueue {
+    next_id: u64,
+    jobs: Vec<QueuedUpload>,
+}
+
+impl UploadQueue {
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("queue.json"))
+    }
+
+    /// Loads the queue from `path`; a missing file is an empty queue.
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| messages::queue_invalid(path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(messages::cannot_read(path.display(), e)),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, text).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+
+    /// Appends `job`, assigning it the next id and the current time.
+    pub fn push(&mut self, mut job: QueuedUpload) -> u64 {
+        self.next_id += 1;
+        job.id = self.next_id;
+        job.queued_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.jobs.push(job);
+        self.next_id
+    }
+
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.jobs.len();
+        self.jobs.retain(|job| job.id != id);
+        self.jobs.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.jobs.clear();
+    }
+
+    pub fn jobs(&self) -> &[QueuedUpload] {
+        &self.jobs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+    use crate::glob::Filter;
+    use crate::queue::{QueuedUpload, UploadQueue};
+
+    fn job(path: &str) -> QueuedUpload {
+        QueuedUpload {
+            id: 0,
+            path: PathBuf::from(path),
+            upload_dir: "backup".into(),
+            recursive: false,
+            encrypted: true,
+            expiry_seconds: Some(3600),
+            manifest: None,
+            sign_key: None,
+            skip_unchanged: false,
+            filter: Filter::default(),
+            force: false,
+            tags: BTreeMap::new(),
+            acl: None,
+            hide_name: false,
+            compress: None,
+            queued_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_round_trip() {
+        let path = Path::new("target/test_queue/queue.json");
+        let _ = tokio::fs::remove_file(path).await;
+        assert!(UploadQueue::load(path).await.unwrap().jobs().is_empty());
+
+        let mut queue = UploadQueue::default();
+        assert_eq!(queue.push(job("/tmp/a.txt")), 1);
+        assert_eq!(queue.push(job("/tmp/b.txt")), 2);
+        assert!(queue.remove(1));
+        assert!(!queue.remove(1));
+        queue.save(path).await.unwrap();
+
+        let mut loaded = UploadQueue::load(path).await.unwrap();
+        assert_eq!(loaded.jobs().len(), 1);
+        assert_eq!(loaded.jobs()[0].path, PathBuf::from("/tmp/b.txt"));
+        assert_eq!(loaded.push(job("/tmp/c.txt")), 3);
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use crate::manifest::to_hex;
+use crate::messages;
+use crate::utils::{config_dir, create_dir};
+
+/// A multipart upload of a local file that may be continued by a later run.
+/// The parts already stored are not recorded here; they are asked from the
+/// server with ListParts, which is the only reliable source for them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct UploadState {
+    pub bucket: String,
+    pub key: String,
+    pub path: PathBuf,
+    pub upload_id: String,
+    pub part_size: usize,
+    pub file_len: u64,
+    /// Modification time of the file, in seconds since the Unix epoch.
+    pub modified: i64,
+    /// `FileCipher::key_check` of the key the parts were encrypted with.
+    pub key_check: Option<String>,
+    /// Hex of the encryption header of the stored parts, so that the rest is
+    /// sealed under the same salts and nonce.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+impl UploadState {
+    /// State file for uploading `path` to `bucket/key`, under
+    /// `~/.config/rot/transfers/`.
+    pub fn path_for(bucket: &str, key: &str, path: &Path) -> Option<PathBuf> {
+        let id = format!("{}\0{}\0{}", bucket, key, path.display());
+        let name = to_hex(&digest(&SHA256, id.as_bytes()).as_ref()[..16]);
+        config_dir().map(|dir| dir.join("transfers").join(format!("{}.json", name)))
+    }
+
+    /// Loads the state at `path`. A missing or unreadable file just means
+    /// there is nothing to resume.
+    pub async fn load(path: &Path) -> Option<Self> {
+        let text = tokio::fs::read_to_string(path).await.ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, text).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+
+    pub async fn remove(path: &Path) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+
+    /// Whether this state was written for the same file contents and target
+    /// as `other`, so that its stored parts can be reused. Each upload draws
+    /// new salts, so the password is checked against `header` by the caller.
+    pub fn resumes(&self, other: &UploadState) -> bool {
+        self.bucket == other.bucket
+            && self.key == other.key
+            && self.path == other.path
+            && self.file_len == other.file_len
+            && self.modified == other.modified
+    }
+}
+
+/// A download of an object into `<path>.part` that may be continued by a
+/// later run. The partial file itself records how far the download got; the
+/// ETag makes sure it is continued only while the object is unchanged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DownloadState {
+    pub bucket: String,
+    pub key: String,
+    pub path: PathBuf,
+    pub etag: String,
+}
+
+impl DownloadState {
+    /// State file for downloading `bucket/key` to `path`, next to the upload
+    /// states.
+    pub fn path_for(bucket: &str, key: &str, path: &Path) -> Option<PathBuf> {
+        let id = format!("download\0{}\0{}\0{}", bucket, key, path.display());
+        let name = to_hex(&digest(&SHA256, id.as_bytes()).as_ref()[..16]);
+        config_dir().map(|dir| dir.join("transfers").join(format!("{}.json", name)))
+    }
+
+    pub async fn load(path: &Path) -> Option<Self> {
+        let text = tokio::fs::read_to_string(path).await.ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        tokio::fs::write(path, text).await.map_err(|e| messages::cannot_write(path.display(), e))
+    }
+
+    pub async fn remove(path: &Path) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
+
+/// The partial file a download of `path` is written to before it is renamed
+/// into place.
+pub(crate) fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Number of the first part missing from `stored`, the part numbers already
+/// on the server. Parts after a gap are uploaded again.
+pub(crate) fn first_missing_part(stored: &[i32]) -> i32 {
+    let mut next = 1;
+    let mut sorted = stored.to_vec();
+    sorted.sort_unstable();
+    for number in sorted {
+        if number == next {
+            next += 1;
+        } else if number > next {
+            break;
+        }
+    }
+    next
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use crate::resume::{first_missing_part, part_path, UploadState};
+
+    fn state(file_len: u64) -> UploadState {
+        UploadState {
+            bucket: "backup".into(),
+            key: "video.mp4".into(),
+            path: PathBuf::from("/data/video.mp4"),
+            upload_id: "abc".into(),
+            part_size: 8192,
+            file_len,
+            modified: 1_700_000_000,
+            key_check: None,
+            header: None,
+        }
+    }
+
+    #[test]
+    fn test_first_missing_part() {
+        assert_eq!(first_missing_part(&[]), 1);
+        assert_eq!(first_missing_part(&[1, 2, 3]), 4);
+        assert_eq!(first_missing_part(&[3, 1, 2, 5]), 4);
+        assert_eq!(first_missing_part(&[2, 3]), 1);
+    }
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(part_path(Path::new("/data/video.mp4")), PathBuf::from("/data/video.mp4.part"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_state_round_trip() {
+        let path = Path::new("target/test_resume/state.json");
+        UploadState::remove(path).await;
+        assert!(UploadState::load(path).await.is_none());
+
+        state(100).save(path).await.unwrap();
+        let loaded = UploadState::load(path).await.unwrap();
+        assert!(loaded.resumes(&state(100)));
+        assert!(!loaded.resumes(&state(101)));
+        UploadState::remove(path).await;
+        assert!(UploadState::load(path).await.is_none());
+    }
+}
@@ -0,0 +1,400 @@
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::SystemTime;
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::config::Config;
+use crate::error::RotError;
+use crate::messages;
+use crate::store::{ObjectInfo, ObjectStore};
+use crate::utils::create_dir;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// A directory on an SFTP server, reached through the system `sftp` client in
+/// batch mode so that keys, agents and `~/.ssh/config` work as they do in a
+/// terminal. Object keys are paths below `root`, or below the login directory
+/// when `root` is empty.
+#[derive(Debug, Clone)]
+pub struct SftpStore {
+    host: String,
+    port: Option<u16>,
+    user: Option<String>,
+    key_path: Option<PathBuf>,
+    root: String,
+}
+
+/// One line of `ls -ln` output.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<i64>,
+}
+
+impl SftpStore {
+    /// `host` may carry a port, as in `backup.example.com:2222`.
+    pub fn new(host: &str, root: impl Into<String>) -> Self {
+        let (host, port) = match host.rsplit_once(':') {
+            Some((name, port)) if port.parse::<u16>().is_ok() => (name.to_string(), port.parse().ok()),
+            _ => (host.to_string(), None),
+        };
+        Self { host, port, user: None, key_path: None, root: root.into() }
+    }
+
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    pub fn with_key_path(mut self, key_path: impl Into<PathBuf>) -> Self {
+        self.key_path = Some(key_path.into());
+        self
+    }
+
+    pub(crate) fn from_config(config: &Config) -> Result<Self, RotError> {
+        let host = config.host.as_deref().filter(|host| !host.is_empty())
+            .ok_or_else(|| RotError::Config(messages::sftp_needs_host()))?;
+        let mut store = Self::new(host, config.root.clone().unwrap_or_default());
+        if let Some(user) = &config.user {
+            store = store.with_user(user);
+        }
+        if let Some(key_path) = &config.key_path {
+            // There is no shell to expand `~` on the way to `sftp -i`.
+            store = store.with_key_path(match (key_path.strip_prefix("~/"), home::home_dir()) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(key_path),
+            });
+        }
+        Ok(store)
+    }
+
+    fn remote_path(&self, key: &str) -> String {
+        match (self.root.trim_end_matches('/'), key) {
+            ("", _) if self.root.starts_with('/') => format!("/{}", key),
+            ("", key) => key.to_string(),
+            (root, "") => root.to_string(),
+            (root, key) => format!("{}/{}", root, key),
+        }
+    }
+
+    /// Runs `commands` in one session and returns what it printed. A failing
+    /// command ends the session unless it starts with `-`.
+    async fn run(&self, commands: &[String]) -> Result<String, String> {
+        let output = self.session(commands).await?;
+        if !output.status.success() {
+            return Err(self.failure(&output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Runs `commands` in one session, whether or not it succeeds.
+    async fn session(&self, commands: &[String]) -> Result<Output, String> {
+        let mut command = Command::new("sftp");
+        command.args(["-q", "-b", "-", "-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-P").arg(port.to_string());
+        }
+        if let Some(key_path) = &self.key_path {
+            command.arg("-i").arg(key_path);
+        }
+        let destination = match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        };
+        // Listings then show times in UTC, which `parse_date` relies on.
+        let mut child = command.arg(destination)
+            .env("TZ", "UTC")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(messages::sftp_unavailable)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(format!("{}\n", commands.join("\n")).as_bytes()).await.map_err(|e| e.to_string())?;
+        drop(stdin);
+        child.wait_with_output().await.map_err(|e| e.to_string())
+    }
+
+    /// Why a session failed, from the last line it wrote to stderr.
+    fn failure(&self, output: &Output) -> String {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => messages::sftp_error(&self.host, line.trim()),
+            None => messages::sftp_failed(&self.host, output.status),
+        }
+    }
+
+    /// `mkdir` commands for the directories above `key`, ignoring those that
+    /// already exist.
+    fn mkdirs(&self, key: &str) -> Result<Vec<String>, String> {
+        let mut commands = Vec::new();
+        if !self.root.is_empty() {
+            commands.push(format!("-mkdir {}", quote(&self.remote_path(""))?));
+        }
+        let mut dir = String::new();
+        for component in key.split('/').rev().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            if !dir.is_empty() {
+                dir.push('/');
+            }
+            dir.push_str(component);
+            commands.push(format!("-mkdir {}", quote(&self.remote_path(&dir))?));
+        }
+        Ok(commands)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for SftpStore {
+    async fn put(&self, key: &str, path: &Path) -> Result<(), RotError> {
+        let mut commands = self.mkdirs(key).map_err(RotError::Transfer)?;
+        commands.push(format!("put {} {}", quote(&path.to_string_lossy()).map_err(RotError::Transfer)?,
+                              quote(&self.remote_path(key)).map_err(RotError::Transfer)?));
+        self.run(&commands).await.map(|_| ()).map_err(RotError::Transfer)
+    }
+
+    async fn get(&self, key: &str, path: &Path) -> Result<u64, RotError> {
+        let command = format!("get {} {}", quote(&self.remote_path(key)).map_err(RotError::Transfer)?,
+                              quote(&path.to_string_lossy()).map_err(RotError::Transfer)?);
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        self.run(&[command]).await.map_err(RotError::Transfer)?;
+        Ok(tokio::fs::metadata(path).await?.len())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, RotError> {
+        let start = prefix.rfind('/').map_or("", |i| &prefix[..i]);
+        let mut pending = vec![start.to_string()];
+        let mut objects = Vec::new();
+        // One session per directory level rather than per directory.
+        while !pending.is_empty() {
+            let commands = pending.iter()
+                .map(|dir| Ok(format!("-ls -lan {}", quote(&self.remote_path(dir))?)))
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(RotError::Sdk)?;
+            let output = self.run(&commands).await.map_err(RotError::Sdk)?;
+            let sections = split_sections(&output);
+            if sections.len() != pending.len() {
+                return Err(RotError::Sdk(messages::sftp_listing_invalid(&self.host)));
+            }
+            let now = DateTime::from(SystemTime::now()).secs();
+            let mut next = Vec::new();
+            for (dir, lines) in pending.iter().zip(sections) {
+                for entry in lines.iter().filter_map(|line| parse_entry(line, now)) {
+                    let key = if dir.is_empty() { entry.name } else { format!("{}/{}", dir, entry.name) };
+                    if entry.is_dir {
+                        let below = format!("{}/", key);
+                        if below.starts_with(prefix) || prefix.starts_with(&below) {
+                            next.push(key);
+                        }
+                    } else if key.starts_with(prefix) {
+                        objects.push(ObjectInfo { key, size: entry.size, last_modified: entry.modified, ..Default::default() });
+                    }
+                }
+            }
+            pending = next;
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), RotError> {
+        // Directories only exist to hold objects, so emptied ones go too.
+        let mut commands = vec![format!("rm {}", quote(&self.remote_path(key)).map_err(RotError::Sdk)?)];
+        let mut dir = key;
+        while let Some(i) = dir.rfind('/') {
+            dir = &dir[..i];
+            commands.push(format!("-rmdir {}", quote(&self.remote_path(dir)).map_err(RotError::Sdk)?));
+        }
+        self.run(&commands).await.map(|_| ()).map_err(RotError::Sdk)
+    }
+
+    async fn copy(&self, src_key: &str, dst_key: &str) -> Result<(), RotError> {
+        // SFTP has no server-side copy that every server supports.
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+        let temp = std::env::temp_dir().join(format!("rot-sftp-{}-{}", std::process::id(), nanos));
+        let result = match self.get(src_key, &temp).await {
+            Ok(_) => self.put(dst_key, &temp).await,
+            Err(e) => Err(e),
+        };
+        let _ = tokio::fs::remove_file(&temp).await;
+        result
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, RotError> {
+        let name = key.rsplit('/').next().unwrap_or(key);
+        let output = self.session(&[format!("ls -lan {}", quote(&self.remote_path(key)).map_err(RotError::Sdk)?)]).await
+            .map_err(RotError::Sdk)?;
+        if !output.status.success() {
+            // Only a missing path means a missing object; an unreachable
+            // server or a refused login must not pass for one.
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("not found") || stderr.contains("No such file") {
+                return Err(RotError::Sdk(messages::object_not_found(key)));
+            }
+            return Err(RotError::Sdk(self.failure(&output)));
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        let now = DateTime::from(SystemTime::now()).secs();
+        split_sections(&output).into_iter().flatten()
+            .filter_map(|line| parse_entry(line, now))
+            .find(|entry| !entry.is_dir && entry.name == name)
+            .map(|entry| ObjectInfo { key: key.to_string(), size: entry.size, last_modified: entry.modified, ..Default::default() })
+            .ok_or_else(|| RotError::Sdk(messages::object_not_found(key)))
+    }
+}
+
+/// `path` as one argument of a batch command. `sftp` expands globs in paths,
+/// so their characters are escaped too. Control characters can't be escaped
+/// at all: a line break would end the command and start another one, such
+/// as a `!` shell escape, so paths holding them are refused.
+fn quote(path: &str) -> Result<String, String> {
+    if path.contains(char::is_control) {
+        return Err(messages::sftp_path_invalid(path.escape_debug()));
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        if matches!(c, '"' | '\\' | '*' | '?' | '[' | ']') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Ok(quoted)
+}
+
+/// The output of a batch session split by the `sftp> ` echo of each
+/// command.
+fn split_sections(output: &str) -> Vec<Vec<&str>> {
+    let mut sections: Vec<Vec<&str>> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("sftp> ") {
+            sections.push(Vec::new());
+        } else if let Some(section) = sections.last_mut() {
+            section.push(line);
+        }
+    }
+    sections
+}
+
+/// Parses a line of `ls -ln`, such as
+/// `-rw-r--r--    1 1000     1000         5 Oct 16 12:00 docs/a.txt`, keeping
+/// the last component of the name. `.`, `..` and anything but plain files
+/// and directories are skipped.
+fn parse_entry(line: &str, now: i64) -> Option<Entry> {
+    let mut fields = Vec::new();
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        let end = rest.find(' ')?;
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    // The date is always 12 characters, between single spaces.
+    let date = rest.get(1..13)?;
+    let name = rest.get(14..)?.rsplit('/').next()?;
+    let is_dir = match fields[0].chars().next()? {
+        'd' => true,
+        '-' => false,
+        _ => return None,
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(Entry {
+        name: name.to_string(),
+        is_dir,
+        size: fields[4].parse().ok()?,
+        modified: parse_date(date, now),
+    })
+}
+
+/// Reads an `ls` date in UTC: `Oct 16 12:00` within the last half year,
+/// `Oct 16  2025` otherwise.
+fn parse_date(text: &str, now: i64) -> Option<i64> {
+    let mut parts = text.split_whitespace();
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month_name)? + 1;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let (year, time) = match parts.next()? {
+        time if time.contains(':') => {
+            let this_year: i32 = DateTime::from_secs(now).fmt(DateTimeFormat::DateTime).ok()?.get(..4)?.parse().ok()?;
+            (this_year, time)
+        }
+        year => (year.parse().ok()?, "00:00"),
+    };
+    let at = |year: i32| DateTime::from_str(&format!("{:04}-{:02}-{:02}T{}:00Z", year, month, day, time), DateTimeFormat::DateTime)
+        .ok()
+        .map(|time| time.secs());
+    match at(year)? {
+        // Recent dates carry no year, and may be from last year.
+        secs if secs > now + 24 * 60 * 60 && time != "00:00" => at(year - 1),
+        secs => Some(secs),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+    use std::path::Path;
+    use crate::sftp::{Entry, parse_date, parse_entry, quote, split_sections, SftpStore};
+    use crate::store::ObjectStore;
+
+    fn secs(text: &str) -> i64 {
+        DateTime::from_str(text, DateTimeFormat::DateTime).unwrap().secs()
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let now = secs("2026-10-16T12:00:00Z");
+        let output = "sftp> -ls -lan \"/srv/backup\"\n\
+            drwxr-xr-x    4 1000     1000         4096 Oct 16 11:58 /srv/backup/.\n\
+            drwxr-xr-x    2 1000     1000         4096 Oct  2 09:30 /srv/backup/docs\n\
+            -rw-r--r--    1 1000     1000            5 Jan  3  2025 /srv/backup/old notes.txt\n\
+            sftp> -ls -lan \"/srv/backup/empty\"\n";
+        let sections = split_sections(output);
+        assert_eq!(sections.len(), 2);
+        assert!(sections[1].is_empty());
+        let entries: Vec<Entry> = sections[0].iter().filter_map(|line| parse_entry(line, now)).collect();
+        assert_eq!(entries, vec![
+            Entry { name: "docs".into(), is_dir: true, size: 4096, modified: Some(secs("2026-10-02T09:30:00Z")) },
+            Entry { name: "old notes.txt".into(), is_dir: false, size: 5, modified: Some(secs("2025-01-03T00:00:00Z")) },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let now = secs("2026-01-10T12:00:00Z");
+        assert_eq!(parse_date("Jan 10 11:59", now), Some(secs("2026-01-10T11:59:00Z")));
+        assert_eq!(parse_date("Dec 30 08:00", now), Some(secs("2025-12-30T08:00:00Z")));
+        assert_eq!(parse_date("Smarch 1 08:00", now), None);
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(quote("a \"b\"/c*.txt").unwrap(), "\"a \\\"b\\\"/c\\*.txt\"");
+        let store = SftpStore::new("backup.example.com:2222", "/srv/backup/");
+        assert_eq!((store.host.as_str(), store.port), ("backup.example.com", Some(2222)));
+        assert_eq!(store.remote_path("docs/a.txt"), "/srv/backup/docs/a.txt");
+        assert_eq!(store.mkdirs("docs/2026/a.txt").unwrap(), vec!["-mkdir \"/srv/backup\"", "-mkdir \"/srv/backup/docs\"", "-mkdir \"/srv/backup/docs/2026\""]);
+        assert_eq!(SftpStore::new("host", "").remote_path("a.txt"), "a.txt");
+    }
+
+    #[tokio::test]
+    async fn test_refuse_control_characters() {
+        assert!(quote("a\rb").is_err());
+        assert!(quote("a\0b").is_err());
+        // Each of these would otherwise run `!touch x` on this machine.
+        let store = SftpStore::new("host", "/srv/backup");
+        let key = "a\n!touch x";
+        assert!(store.put(key, Path::new("/tmp/a.txt")).await.is_err());
+        assert!(store.put("a.txt", Path::new("/tmp/a\n!touch x")).await.is_err());
+        assert!(store.get(key, Path::new("/tmp/a.txt")).await.is_err());
+        assert!(store.head(key).await.is_err());
+        assert!(store.delete(key).await.is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+use ring::signature::{ED25519, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use crate::manifest::{sha256_digest, to_hex};
+use crate::messages;
+
+/// Length of an Ed25519 public key or private seed.
+const KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// Loads an Ed25519 private key from a PKCS#8 file (DER or PEM, as written by
+/// `openssl genpkey -algorithm ed25519`) or a raw 32-byte seed.
+pub(crate) async fn load_signing_key(path: &Path) -> Result<Ed25519KeyPair, String> {
+    let der = read_key_file(path).await?;
+    let key_pair = if der.len() == KEY_LEN {
+        Ed25519KeyPair::from_seed_unchecked(&der)
+    } else {
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+    };
+    key_pair.map_err(|_| messages::signing_key_invalid(path.display()))
+}
+
+/// Loads an Ed25519 public key from a SubjectPublicKeyInfo file (DER or PEM)
+/// or a raw 32-byte key.
+pub(crate) async fn load_verifying_key(path: &Path) -> Result<Vec<u8>, String> {
+    let der = read_key_file(path).await?;
+    match der.len() {
+        // SubjectPublicKeyInfo wraps the raw key in a fixed 12-byte header.
+        KEY_LEN => Ok(der),
+        44 => Ok(der[12..].to_vec()),
+        _ => Err(messages::verifying_key_invalid(path.display())),
+    }
+}
+
+/// Signs the SHA-256 digest of the file at `path`, returning the detached
+/// signature as a line of hex.
+pub(crate) async fn sign_file(key: &Ed25519KeyPair, path: &Path) -> Result<Vec<u8>, String> {
+    let (digest, _) = sha256_digest(path).await?;
+    Ok(format!("{}\n", to_hex(key.sign(&digest).as_ref())).into_bytes())
+}
+
+/// Checks a signature written by [`sign_file`] against the file at `path`.
+pub(crate) async fn verify_file(public_key: &[u8], path: &Path, signature: &[u8]) -> Result<(), String> {
+    let signature = String::from_utf8_lossy(signature);
+    let signature = from_hex(signature.trim())
+        .filter(|bytes| bytes.len() == SIGNATURE_LEN)
+        .ok_or_else(messages::signature_invalid)?;
+    let (digest, _) = sha256_digest(path).await?;
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&digest, &signature)
+        .map_err(|_| messages::signature_mismatch(path.display()))
+}
+
+/// Hex-encoded public key of `key`, for telling users which key signed.
+pub(crate) fn public_key_hex(key: &Ed25519KeyPair) -> String {
+    to_hex(key.public_key().as_ref())
+}
+
+async fn read_key_file(path: &Path) -> Result<Vec<u8>, String> {
+    let data = tokio::fs::read(path).await.map_err(|e| messages::cannot_read(path.display(), e))?;
+    match std::str::from_utf8(&data) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN") => {
+            let body: String = text.lines()
+                .filter(|line| !line.starts_with("-----"))
+                .collect();
+            decode_base64(body.trim()).ok_or_else(|| messages::pem_invalid(path.display()))
+        }
+        _ => Ok(data),
+    }
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in text.bytes().filter(|b| !b.is_ascii_whitespace() && *b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use ring::signature::KeyPair;
+    use tokio::fs::DirBuilder;
+    use crate::signing::{decode_base64, load_signing_key, load_verifying_key, sign_file, verify_file};
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("cmF2ZW4=").unwrap(), b"raven");
+        assert_eq!(decode_base64("cmF2ZW4tYm9vaw==").unwrap(), b"raven-book");
+        assert!(decode_base64("not*base64").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_verify() {
+        let dir = Path::new("target/test_signing");
+        DirBuilder::new().recursive(true).create(dir).await.unwrap();
+        tokio::fs::write(dir.join("seed.key"), [7u8; 32]).await.unwrap();
+        tokio::fs::write(dir.join("artifact.bin"), b"release artifact").await.unwrap();
+
+        let key = load_signing_key(&dir.join("seed.key")).await.unwrap();
+        tokio::fs::write(dir.join("seed.pub"), key.public_key().as_ref()).await.unwrap();
+        let public_key = load_verifying_key(&dir.join("seed.pub")).await.unwrap();
+
+        let signature = sign_file(&key, &dir.join("artifact.bin")).await.unwrap();
+        verify_file(&public_key, &dir.join("artifact.bin"), &signature).await.unwrap();
+
+        tokio::fs::write(dir.join("artifact.bin"), b"tampered artifact").await.unwrap();
+        assert!(verify_file(&public_key, &dir.join("artifact.bin"), &signature).await.is_err());
+    }
+}
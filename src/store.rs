@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use async_trait::async_trait;
+use aws_sdk_s3::types::Object;
+use crate::client::{AliyunClient, DeleteReport};
+use crate::config::{Config, Provider};
+use crate::error::RotError;
+use crate::messages;
+use crate::output::{OutputFormat, Reporter};
+use crate::sftp::SftpStore;
+use crate::webdav::WebDavStore;
+
+/// One stored object, as every backend can describe it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch, when the backend reports it.
+    pub last_modified: Option<i64>,
+    pub etag: Option<String>,
+    /// User metadata, such as the checksum rot records at upload time. Only
+    /// filled in by `head`.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<&Object> for ObjectInfo {
+    fn from(object: &Object) -> Self {
+        Self {
+            key: object.key.clone().unwrap_or_default(),
+            size: object.size.unwrap_or(0).max(0) as u64,
+            last_modified: object.last_modified.map(|time| time.secs()),
+            etag: object.e_tag.clone(),
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
+/// The operations every storage backend provides. Commands that need no
+/// more than these work against any backend, and tests can stand in a mock.
+/// Keys are `/`-separated and never start with `/`.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Stores the local file `path` as `key`, replacing any existing object.
+    async fn put(&self, key: &str, path: &Path) -> Result<(), RotError>;
+
+    /// Writes `key` to the local file `path`, returning the bytes received.
+    async fn get(&self, key: &str, path: &Path) -> Result<u64, RotError>;
+
+    /// Every object whose key starts with `prefix`, in key order.
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, RotError>;
+
+    async fn delete(&self, key: &str) -> Result<(), RotError>;
+
+    async fn copy(&self, src_key: &str, dst_key: &str) -> Result<(), RotError>;
+
+    /// Describes `key`, failing when it doesn't exist.
+    async fn head(&self, key: &str) -> Result<ObjectInfo, RotError>;
+
+    /// The S3 client behind this store, for what only the S3 API offers,
+    /// such as encryption, versioning and batch deletes.
+    fn s3(&self) -> Option<&AliyunClient> {
+        None
+    }
+}
+
+/// The store of a profile: its own for providers without an S3 API, an
+/// `AliyunClient` for the others.
+pub(crate) fn open(config: &Config) -> Result<Arc<dyn ObjectStore>, RotError> {
+    Ok(match config.provider {
+        Provider::Sftp => Arc::new(SftpStore::from_config(config)?),
+        Provider::WebDav => Arc::new(WebDavStore::from_config(config)?),
+        _ => Arc::new(AliyunClient::from_config(config)?),
+    })
+}
+
+/// An `ObjectStore` with the switches that apply to every backend, for the
+/// commands that work on any of them.
+#[derive(Clone)]
+pub(crate) struct StoreClient {
+    pub store: Arc<dyn ObjectStore>,
+    pub read_only: bool,
+    pub dry_run: bool,
+    pub output: OutputFormat,
+}
+
+impl StoreClient {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store, read_only: false, dry_run: false, output: OutputFormat::default() }
+    }
+
+    /// The store of `client`, with its switches.
+    pub fn from_client(client: AliyunClient) -> Self {
+        Self {
+            read_only: client.is_read_only(),
+            dry_run: client.is_dry_run(),
+            output: client.output(),
+            store: Arc::new(client),
+        }
+    }
+
+    /// Works on `store` instead, keeping the switches.
+    pub fn with_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn s3(&self) -> Option<&AliyunClient> {
+        self.store.s3()
+    }
+
+    pub fn reporter(&self) -> Reporter {
+        Reporter::new(self.output)
+    }
+
+    pub fn ensure_writable(&self, action: &str) -> Result<(), RotError> {
+        if let Some(client) = self.s3() {
+            return client.ensure_writable(action);
+        }
+        if self.read_only {
+            return Err(RotError::Config(messages::read_only_store(action)));
+        }
+        if self.dry_run {
+            return Err(RotError::Config(messages::dry_run_refused(action)));
+        }
+        Ok(())
+    }
+
+    /// Deletes `keys`, in batches through the S3 API and one by one
+    /// elsewhere. Per-key failures are collected in the report.
+    pub async fn delete_many(&self, keys: Vec<String>) -> Result<DeleteReport, String> {
+        if let Some(client) = self.s3() {
+            return client.delete_objects(keys).await;
+        }
+        self.ensure_writable(&messages::action_delete())?;
+        let mut report = DeleteReport::default();
+        for key in keys {
+            match self.store.delete(&key).await {
+                Ok(()) => report.deleted.push((key, None)),
+                Err(e) => report.failed.push((key, e.to_string())),
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// As in the other stores, a failed request is `Sdk` and a failed transfer
+/// `Transfer`, while a missing object is `Sdk(object_not_found)` in every
+/// method: `get` and `copy` look it up first, since a transfer or copy
+/// would report it otherwise.
+#[async_trait]
+impl ObjectStore for AliyunClient {
+    async fn put(&self, key: &str, path: &Path) -> Result<(), RotError> {
+        self.clone().with_overwrite(true)
+            .upload_to_key(key, path.to_path_buf(), None, None, None).await
+            .map(|_| ())
+            .map_err(RotError::Transfer)
+    }
+
+    async fn get(&self, key: &str, path: &Path) -> Result<u64, RotError> {
+        self.head(key).await?;
+        self.clone().with_overwrite(true)
+            .download_file_with_key(key, path, None).await
+            .map_err(RotError::Transfer)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, RotError> {
+        let objects = self.list_all((!prefix.is_empty()).then(|| prefix.to_string())).await?;
+        Ok(objects.iter().map(ObjectInfo::from).collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), RotError> {
+        self.delete_obj(key).await.map(|_| ())
+    }
+
+    async fn copy(&self, src_key: &str, dst_key: &str) -> Result<(), RotError> {
+        self.head(src_key).await?;
+        self.copy_obj(src_key, dst_key).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, RotError> {
+        let head = self.head_obj(key).await?;
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: head.content_length.unwrap_or(0).max(0) as u64,
+            last_modified: head.last_modified.map(|time| time.secs()),
+            etag: head.e_tag,
+            metadata: head.metadata.map(BTreeMap::from_iter).unwrap_or_default(),
+        })
+    }
+
+    fn s3(&self) -> Option<&AliyunClient> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use crate::client::test::offline_client;
+    use crate::config::Config;
+    use crate::error::RotError;
+    use crate::messages;
+    use crate::store::{open, ObjectStore};
+
+    #[tokio::test]
+    async fn test_object_store() {
+        let (client, dir) = offline_client("object_store").await;
+        let store: Box<dyn ObjectStore> = Box::new(client);
+        tokio::fs::write(dir.join("a.txt"), b"hello").await.unwrap();
+
+        store.put("docs/a.txt", &dir.join("a.txt")).await.unwrap();
+        store.copy("docs/a.txt", "docs/b.txt").await.unwrap();
+        let listed: Vec<(String, u64)> = store.list("docs/").await.unwrap().into_iter().map(|info| (info.key, info.size)).collect();
+        assert_eq!(listed, vec![("docs/a.txt".to_string(), 5), ("docs/b.txt".to_string(), 5)]);
+        assert_eq!(store.head("docs/b.txt").await.unwrap().size, 5);
+
+        assert_eq!(store.get("docs/b.txt", &dir.join("b.txt")).await.unwrap(), 5);
+        assert_eq!(tokio::fs::read(dir.join("b.txt")).await.unwrap(), b"hello");
+
+        store.delete("docs/a.txt").await.unwrap();
+        assert!(store.head("docs/a.txt").await.is_err());
+        assert_eq!(store.list("").await.unwrap().len(), 1);
+
+        let not_found = messages::object_not_found("docs/a.txt");
+        assert!(matches!(store.head("docs/a.txt").await, Err(RotError::Sdk(e)) if e == not_found));
+        assert!(matches!(store.get("docs/a.txt", &dir.join("c.txt")).await, Err(RotError::Sdk(e)) if e == not_found));
+        assert!(matches!(store.copy("docs/a.txt", "docs/c.txt").await, Err(RotError::Sdk(e)) if e == not_found));
+    }
+
+    #[tokio::test]
+    async fn test_local_profile() {
+        let dir = Path::new("target").join("test_local_profile");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(dir.join("a.txt"), b"hello").await.unwrap();
+        let json = format!("{{\"provider\":\"local\",\"root\":\"{}\",\"bucket\":\"backup\"}}", dir.join("root").display());
+        let config: Config = serde_json::from_str(&json).unwrap();
+        let store = open(&config).unwrap();
+        assert!(store.s3().is_some());
+
+        store.put("2026/a.txt", &dir.join("a.txt")).await.unwrap();
+        assert_eq!(tokio::fs::read(dir.join("root/backup/2026/a.txt")).await.unwrap(), b"hello");
+        assert_eq!(store.list("2026/").await.unwrap().len(), 1);
+    }
+}
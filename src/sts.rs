@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Deserialize;
+use crate::config::{resolve, Config, ConfigFile};
+use crate::error::RotError;
+use crate::manifest::to_hex;
+use crate::messages;
+use crate::parser::{Arguments, CommandParser};
+use crate::proxy::http_client;
+use crate::utils::{encode_base64, parse_duration, percent_encode_component};
+
+const STS_ENDPOINT: &str = "https://sts.aliyuncs.com";
+const STS_VERSION: &str = "2015-04-01";
+const DEFAULT_SESSION_NAME: &str = "rot";
+/// STS accepts 15 minutes up to the role's maximum session duration.
+const DEFAULT_DURATION_SECONDS: u64 = 3600;
+
+/// Temporary credentials issued by STS.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct StsCredentials {
+    pub access_key_id: String,
+    pub access_key_secret: String,
+    pub security_token: String,
+    pub expiration: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AssumeRoleResponse {
+    credentials: StsCredentials,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StsError {
+    code: String,
+    message: String,
+}
+
+fn canonical_query(params: &BTreeMap<&str, String>) -> String {
+    params.iter()
+        .map(|(name, value)| format!("{}={}", percent_encode_component(name), percent_encode_component(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// The signature of an RPC-style request (signature version 1.0): HMAC-SHA1
+/// over the sorted, percent-encoded query string.
+fn sign(params: &BTreeMap<&str, String>, access_key_secret: &str) -> String {
+    let string_to_sign = format!("GET&%2F&{}", percent_encode_component(&canonical_query(params)));
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, format!("{}&", access_key_secret).as_bytes());
+    encode_base64(hmac::sign(&key, string_to_sign.as_bytes()).as_ref())
+}
+
+/// Calls STS `AssumeRole` with the key (long-lived or itself temporary) and
+/// proxy of `config`.
+pub(crate) async fn assume_role(config: &Config,
+                                role_arn: &str,
+                                session_name: &str,
+                                duration_seconds: u64,
+                                endpoint: &str) -> Result<StsCredentials, String> {
+    let mut nonce = [0u8; 16];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| messages::random_failed())?;
+    let timestamp = DateTime::from_secs(DateTime::from(SystemTime::now()).secs())
+        .fmt(DateTimeFormat::DateTime)
+        .map_err(|e| e.to_string())?;
+
+    let mut params = BTreeMap::from([
+        ("Action", "AssumeRole".to_string()),
+        ("Format", "JSON".to_string()),
+        ("Version", STS_VERSION.to_string()),
+        ("AccessKeyId", config.access_key_id.clone()),
+        ("SignatureMethod", "HMAC-SHA1".to_string()),
+        ("SignatureVersion", "1.0".to_string()),
+        ("SignatureNonce", to_hex(&nonce)),
+        ("Timestamp", timestamp),
+        ("RoleArn", role_arn.to_string()),
+        ("RoleSessionName", session_name.to_string()),
+        ("DurationSeconds", duration_seconds.to_string()),
+    ]);
+    if let Some(token) = &config.security_token {
+        params.insert("SecurityToken", token.clone());
+    }
+    let signature = sign(&params, &config.secret_access_key);
+    params.insert("Signature", signature);
+
+    let url = format!("{}/?{}", endpoint.trim_end_matches('/'), canonical_query(&params));
+    let resp = http_client(config.proxy.as_deref())?.get(&url).send().await.map_err(messages::sts_unreachable)?;
+    let status = resp.status();
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(match serde_json::from_str::<StsError>(&body) {
+            Ok(error) => messages::assume_role_rejected(error.message, error.code),
+            Err(_) => messages::assume_role_failed(status),
+        });
+    }
+    serde_json::from_str::<AssumeRoleResponse>(&body)
+        .map(|response| response.credentials)
+        .map_err(messages::sts_response_invalid)
+}
+
+/// `rot assume-role <role-arn> [--session-name <name>] [--duration <time>]
+/// [--sts-endpoint <url>] [--save <profile>]`: exchanges the configured key
+/// for temporary credentials, printed as `ROT_*` exports or saved as a
+/// profile that otherwise copies the current one.
+pub async fn execute(args: impl IntoIterator<Item=impl Into<String>>, profile: Option<&str>) -> Result<(), RotError> {
+    run(CommandParser::from_strings(args), profile).await.map_err(RotError::Command)
+}
+
+async fn run(args: Arguments, profile: Option<&str>) -> Result<(), String> {
+    let role_arn = args.positional.first().ok_or_else(messages::assume_role_usage)?;
+    let session_name = args.option(&["session-name"]).map_or(DEFAULT_SESSION_NAME, String::as_str);
+    let duration_seconds = match args.option(&["duration"]) {
+        Some(value) => parse_duration(value)?.as_secs(),
+        None => DEFAULT_DURATION_SECONDS,
+    };
+    let endpoint = args.option(&["sts-endpoint"]).map_or(STS_ENDPOINT, String::as_str);
+
+    let mut config = resolve(profile).await?
+        .ok_or_else(messages::assume_role_needs_credentials)?;
+    let credentials = assume_role(&config, role_arn, session_name, duration_seconds, endpoint).await?;
+
+    match args.option(&["save"]) {
+        Some(name) => {
+            config.access_key_id = credentials.access_key_id;
+            config.secret_access_key = credentials.access_key_secret;
+            config.security_token = Some(credentials.security_token);
+            let mut file = ConfigFile::load().await?.unwrap_or_default();
+            *file.profile_mut(Some(name)) = config;
+            file.save().await?;
+            println!("{}", messages::temporary_credentials_saved(name, credentials.expiration));
+        }
+        None => {
+            println!("export ROT_ACCESS_KEY_ID={}", credentials.access_key_id);
+            println!("export ROT_SECRET_ACCESS_KEY={}", credentials.access_key_secret);
+            println!("export ROT_SECURITY_TOKEN={}", credentials.security_token);
+            eprintln!("{}", messages::temporary_credentials_expire(credentials.expiration));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crate::sts::{AssumeRoleResponse, sign};
+
+    #[test]
+    fn test_sign() {
+        // The worked example from the RPC signature documentation.
+        let params = BTreeMap::from([
+            ("Timestamp", "2016-02-23T12:46:24Z".to_string()),
+            ("Format", "XML".to_string()),
+            ("AccessKeyId", "testid".to_string()),
+            ("Action", "DescribeRegions".to_string()),
+            ("SignatureMethod", "HMAC-SHA1".to_string()),
+            ("SignatureNonce", "3ee8c1b8-83d3-44af-a94f-4e0ad82fd6cf".to_string()),
+            ("Version", "2014-05-26".to_string()),
+            ("SignatureVersion", "1.0".to_string()),
+        ]);
+        assert_eq!(sign(&params, "testsecret"), "OLeaidS1JvxuMvnyHOwuJ+uX5qY=");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let body = r#"{"RequestId":"x","AssumedRoleUser":{"Arn":"acs:ram::1:role/r/rot","AssumedRoleId":"1:rot"},
+            "Credentials":{"SecurityToken":"token","AccessKeyId":"STS.id","AccessKeySecret":"secret","Expiration":"2026-10-16T12:00:00Z"}}"#;
+        let response: AssumeRoleResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(response.credentials.access_key_id, "STS.id");
+        assert_eq!(response.credentials.security_token, "token");
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::interceptors::context::AfterDeserializationInterceptorContextRef;
+use aws_smithy_runtime_api::client::interceptors::Intercept;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_types::config_bag::ConfigBag;
+use crate::constant::SYMLINK_TARGET_HEADER;
+use crate::local::{percent_decode, xml_unescape};
+
+/// Records what OSS says about symlinks in the response to the request it
+/// is attached to, which the SDK's output types leave out: the target
+/// header of GetSymlink, and the keys a listing marks as `Symlink`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SymlinkCapture {
+    target: Arc<Mutex<Option<String>>>,
+    links: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl SymlinkCapture {
+    pub fn target(&self) -> Option<String> {
+        self.target.lock().unwrap().clone()
+    }
+
+    pub fn links(&self) -> BTreeSet<String> {
+        self.links.lock().unwrap().clone()
+    }
+}
+
+impl Intercept for SymlinkCapture {
+    fn name(&self) -> &'static str {
+        "SymlinkCapture"
+    }
+
+    fn read_after_deserialization(&self,
+                                  context: &AfterDeserializationInterceptorContextRef<'_>,
+                                  _runtime_components: &RuntimeComponents,
+                                  _cfg: &mut ConfigBag) -> Result<(), BoxError> {
+        let response = context.response();
+        *self.target.lock().unwrap() = response.headers().get(SYMLINK_TARGET_HEADER).map(percent_decode);
+        // Bodies of operations that are not streamed are in memory by now.
+        *self.links.lock().unwrap() = response.body().bytes()
+            .map(|body| symlink_keys(&String::from_utf8_lossy(body)))
+            .unwrap_or_default();
+        Ok(())
+    }
+}
+
+/// The keys of a ListObjects result whose `<Type>` is `Symlink`.
+fn symlink_keys(xml: &str) -> BTreeSet<String> {
+    xml.split("<Contents>")
+        .skip(1)
+        .filter_map(|entry| entry.split_once("</Contents>").map(|(entry, _)| entry))
+        .filter(|entry| entry.contains("<Type>Symlink</Type>"))
+        .filter_map(|entry| entry.split_once("<Key>")?.1.split_once("</Key>").map(|(key, _)| xml_unescape(key)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::symlink::symlink_keys;
+
+    #[test]
+    fn test_symlink_keys() {
+        let xml = "<ListBucketResult><Contents><Key>backup/a.tar</Key><Type>Normal</Type></Contents>\
+                   <Contents><Key>backup/latest &amp; greatest</Key><Size>0</Size><Type>Symlink</Type></Contents>\
+                   <CommonPrefixes><Prefix>logs/</Prefix></CommonPrefixes></ListBucketResult>";
+        assert_eq!(symlink_keys(xml).into_iter().collect::<Vec<_>>(), ["backup/latest & greatest"]);
+        assert!(symlink_keys("<ListBucketResult></ListBucketResult>").is_empty());
+    }
+}
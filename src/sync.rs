@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use aws_sdk_s3::primitives::DateTime;
+use notify::{Config, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use crate::messages;
+use crate::store::ObjectInfo;
+use crate::utils::normalize_key;
+
+/// What is known about one file on either side of a sync, keyed by its path
+/// relative to the synced root.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FileState {
+    pub size: u64,
+    /// Seconds since the Unix epoch.
+    pub modified: i64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SyncPlan {
+    /// Missing or out of date on the target.
+    pub transfer: Vec<String>,
+    /// Same size on both sides; only a checksum can tell whether they differ.
+    pub verify: Vec<String>,
+    /// On the target but no longer on the source, filled only with `--delete`.
+    pub delete: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Compares `source` with `target`. A file needs transferring when it is
+/// missing from the target, the sizes differ or, unless `by_checksum`, the
+/// source is newer. With `by_checksum` same-sized files are left for a
+/// checksum comparison instead of trusting timestamps.
+pub(crate) fn plan(source: &BTreeMap<String, FileState>,
+                   target: &BTreeMap<String, FileState>,
+                   by_checksum: bool,
+                   delete: bool) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    for (path, state) in source {
+        match target.get(path) {
+            None => plan.transfer.push(path.clone()),
+            Some(existing) if existing.size != state.size => plan.transfer.push(path.clone()),
+            Some(_) if by_checksum => plan.verify.push(path.clone()),
+            Some(existing) if state.modified > existing.modified => plan.transfer.push(path.clone()),
+            Some(_) => plan.unchanged += 1,
+        }
+    }
+    if delete {
+        plan.delete = target.keys().filter(|path| !source.contains_key(*path)).cloned().collect();
+    }
+    plan
+}
+
+/// States of the files below `root`, keyed by their `/`-separated relative
+/// path. Paths that aren't valid UTF-8 can't become object keys and are
+/// reported and skipped.
+pub(crate) async fn local_states(root: &Path, files: Vec<PathBuf>) -> BTreeMap<String, FileState> {
+    let mut states = BTreeMap::new();
+    for file in files {
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        let Some(relative) = relative.to_str() else {
+            eprintln!("{}", messages::skipped_not_utf8(file.display()));
+            continue;
+        };
+        let Ok(metadata) = tokio::fs::metadata(&file).await else { continue };
+        let modified = metadata.modified().map(|time| DateTime::from(time).secs()).unwrap_or(0);
+        states.insert(normalize_key(&relative.replace('\\', "/")), FileState { size: metadata.len(), modified });
+    }
+    states
+}
+
+/// States of the listed objects, keyed by their key with `prefix` removed.
+/// Directory placeholder keys are ignored.
+pub(crate) fn remote_states(prefix: &str, objects: Vec<ObjectInfo>) -> BTreeMap<String, FileState> {
+    objects.into_iter()
+        .filter_map(|object| {
+            let relative = object.key.strip_prefix(prefix)?.to_string();
+            if relative.is_empty() || relative.ends_with('/') {
+                return None;
+            }
+            let state = FileState {
+                size: object.size,
+                modified: object.last_modified.unwrap_or(0),
+            };
+            Some((relative, state))
+        })
+        .collect()
+}
+
+/// Notifications about a watched directory, as `watch_dir` sends them.
+pub(crate) type WatchEvents = UnboundedReceiver<notify::Result<Event>>;
+
+/// Watches `root` and everything below it for changes, which arrive on the
+/// returned channel until the watcher is dropped. The platform's
+/// notifications are used unless `poll` is given, in which case the tree is
+/// rescanned that often instead; notifications miss changes made from
+/// other machines on a network filesystem.
+pub(crate) fn watch_dir(root: &Path, poll: Option<Duration>) -> Result<(Box<dyn Watcher + Send>, WatchEvents), String> {
+    let (sender, receiver) = unbounded_channel();
+    let handler = move |event| {
+        let _ = sender.send(event);
+    };
+    let watcher: notify::Result<Box<dyn Watcher + Send>> = match poll {
+        Some(interval) => PollWatcher::new(handler, Config::default().with_poll_interval(interval)).map(|watcher| Box::new(watcher) as _),
+        None => RecommendedWatcher::new(handler, Config::default()).map(|watcher| Box::new(watcher) as _),
+    };
+    let mut watcher = watcher.map_err(|e| messages::cannot_watch(root.display(), e))?;
+    watcher.watch(root, RecursiveMode::Recursive).map_err(|e| messages::cannot_watch(root.display(), e))?;
+    Ok((watcher, receiver))
+}
+
+/// Decides what `rot watch` uploads after each scan of the watched
+/// directory. A file is sent once it has kept the same size and
+/// modification time for the debounce period, so that files still being
+/// written don't go up half-finished, and only if it differs from what was
+/// last uploaded.
+#[derive(Debug, Default)]
+pub(crate) struct ChangeTracker {
+    /// The state each file was last uploaded, or first seen, in.
+    uploaded: BTreeMap<String, FileState>,
+    /// Changed files, with when they were first seen in their current state.
+    pending: BTreeMap<String, (FileState, i64)>,
+}
+
+impl ChangeTracker {
+    /// Starts from `current`, the files already up to date remotely.
+    pub fn new(current: BTreeMap<String, FileState>) -> Self {
+        Self { uploaded: current, pending: BTreeMap::new() }
+    }
+
+    /// Takes a scan made at `now`, in seconds, and returns the paths that
+    /// have settled and should be uploaded.
+    pub fn scan(&mut self, states: &BTreeMap<String, FileState>, now: i64, debounce: i64) -> Vec<String> {
+        self.pending.retain(|path, _| states.contains_key(path));
+        let mut ready = Vec::new();
+        for (path, state) in states {
+            if self.uploaded.get(path) == Some(state) {
+                self.pending.remove(path);
+                continue;
+            }
+            match self.pending.get(path) {
+                Some((pending, since)) if pending == state => {
+                    if now - since >= debounce {
+                        ready.push(path.clone());
+                    }
+                }
+                _ => {
+                    self.pending.insert(path.clone(), (*state, now));
+                }
+            }
+        }
+        ready
+    }
+
+    /// Whether some changed file still has to be uploaded, so another scan
+    /// is due once the debounce period has passed.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Records that `path` was uploaded in `state`. Failed uploads are left
+    /// pending and tried again after the next scan.
+    pub fn uploaded(&mut self, path: &str, state: FileState) {
+        self.pending.remove(path);
+        self.uploaded.insert(path.to_string(), state);
+    }
+}
+
+/// Joins a remote prefix and a relative path; a non-empty prefix always gets
+/// a separating `/`.
+pub(crate) fn join_key(prefix: &str, relative: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with('/') {
+        format!("{}{}", prefix, relative)
+    } else {
+        format!("{}/{}", prefix, relative)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crate::sync::{ChangeTracker, FileState, join_key, plan, SyncPlan};
+
+    fn states(entries: &[(&str, u64, i64)]) -> BTreeMap<String, FileState> {
+        entries.iter()
+            .map(|(path, size, modified)| (path.to_string(), FileState { size: *size, modified: *modified }))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let local = states(&[("a.txt", 1, 100), ("b.txt", 2, 100), ("c.txt", 3, 300), ("d.txt", 4, 100)]);
+        let remote = states(&[("b.txt", 5, 200), ("c.txt", 3, 200), ("d.txt", 4, 200), ("old.txt", 1, 50)]);
+
+        assert_eq!(plan(&local, &remote, false, false), SyncPlan {
+            transfer: vec!["a.txt".into(), "b.txt".into(), "c.txt".into()],
+            verify: vec![],
+            delete: vec![],
+            unchanged: 1,
+        });
+        assert_eq!(plan(&local, &remote, true, true), SyncPlan {
+            transfer: vec!["a.txt".into(), "b.txt".into()],
+            verify: vec!["c.txt".into(), "d.txt".into()],
+            delete: vec!["old.txt".into()],
+            unchanged: 0,
+        });
+    }
+
+    #[test]
+    fn test_change_tracker() {
+        let mut tracker = ChangeTracker::new(states(&[("a.txt", 1, 100)]));
+        assert!(tracker.scan(&states(&[("a.txt", 1, 100)]), 1000, 2).is_empty());
+
+        // A new file waits for the debounce period, restarting while it grows.
+        let growing = states(&[("a.txt", 1, 100), ("b.txt", 10, 1000)]);
+        assert!(tracker.scan(&growing, 1000, 2).is_empty());
+        let grown = states(&[("a.txt", 1, 100), ("b.txt", 20, 1001)]);
+        assert!(tracker.scan(&grown, 1001, 2).is_empty());
+        assert!(tracker.scan(&grown, 1002, 2).is_empty());
+        assert_eq!(tracker.scan(&grown, 1003, 2), ["b.txt"]);
+
+        // Until it is marked uploaded it stays ready, so failures are retried.
+        assert_eq!(tracker.scan(&grown, 1004, 2), ["b.txt"]);
+        assert!(tracker.has_pending());
+        tracker.uploaded("b.txt", grown["b.txt"]);
+        assert!(tracker.scan(&grown, 1005, 2).is_empty());
+        assert!(!tracker.has_pending());
+
+        // Even without debouncing a change must show up in two scans.
+        let modified = states(&[("a.txt", 1, 1006), ("b.txt", 20, 1001)]);
+        assert!(tracker.scan(&modified, 1006, 0).is_empty());
+        assert!(tracker.scan(&states(&[]), 1007, 0).is_empty());
+        assert!(tracker.scan(&modified, 1008, 0).is_empty());
+        assert_eq!(tracker.scan(&modified, 1008, 0), ["a.txt"]);
+    }
+
+    #[test]
+    fn test_join_key() {
+        assert_eq!(join_key("", "a/b.txt"), "a/b.txt");
+        assert_eq!(join_key("site", "a/b.txt"), "site/a/b.txt");
+        assert_eq!(join_key("site/", "a/b.txt"), "site/a/b.txt");
+    }
+}
@@ -0,0 +1,666 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use ring::aead::AES_256_GCM;
+use futures_util::TryStreamExt;
+use tokio::io;
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use crate::client::{AliyunClient, Concurrency, UploadOutcome};
+use crate::constant::{AUTO_CONCURRENCY_START, CHUNK_SIZE, FRAME_LEN_SIZE, MAX_CONCURRENCY, MAX_HEADER_LEN, MAX_PART_SIZE, MAX_PARTS, PIPELINE_DEPTH};
+use crate::crypt::{Cipher, encrypt_part, FileCipher, read_chunk};
+use crate::history::{HistoryAction, HistoryEntry};
+use crate::messages;
+use crate::output::{TransferRecord, TransferStatus};
+use crate::proxy;
+use crate::utils::{create_dir, format_size};
+
+#[derive(Debug)]
+pub(crate) struct Part {
+    pub number: i32,
+    pub data: Vec<u8>,
+    /// Whether the part runs to the end of the input.
+    pub is_last: bool,
+    /// Memory reserved for this part, released once the part is dropped.
+    pub permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Caps the bytes held in transfer buffers across every part, file and
+/// pipeline stage that shares it. Callers wait for room before allocating,
+/// which lowers the effective concurrency when memory is tight. Each
+/// reservation covers a buffer at its peak (see [`part_footprint`]), so the
+/// buffers stay within the budget; the one exception is a single buffer
+/// larger than the whole budget, which is let through alone.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryBudget {
+    semaphore: Arc<Semaphore>,
+    total_units: u32,
+}
+
+impl MemoryBudget {
+    const UNIT: usize = 1024;
+
+    pub fn new(max_bytes: u64) -> Self {
+        let total_units = (max_bytes / Self::UNIT as u64).clamp(1, u32::MAX as u64) as u32;
+        Self {
+            semaphore: Arc::new(Semaphore::new(total_units as usize)),
+            total_units,
+        }
+    }
+
+    /// Waits until `bytes` fit in the budget. A single request larger than
+    /// the whole budget is clamped to it, so it runs alone instead of never.
+    pub async fn reserve(&self, bytes: usize) -> OwnedSemaphorePermit {
+        let units = bytes.div_ceil(Self::UNIT).clamp(1, self.total_units as usize) as u32;
+        Arc::clone(&self.semaphore)
+            .acquire_many_owned(units)
+            .await
+            .expect("memory budget semaphore closed")
+    }
+}
+
+/// Caps throughput at a number of bytes per second across every part and
+/// file that shares it, so concurrent transfers split the limit instead of
+/// each getting all of it. Up to one second of unused allowance is kept
+/// for bursts; beyond that, callers wait off the debt they run up.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    bytes_per_second: f64,
+    bucket: Arc<Mutex<TokenBucket>>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    available: f64,
+    /// Tokio's clock rather than the standard one, so tests can pause it.
+    updated: tokio::time::Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1) as f64;
+        Self {
+            bytes_per_second,
+            bucket: Arc::new(Mutex::new(TokenBucket { available: bytes_per_second, updated: tokio::time::Instant::now() })),
+        }
+    }
+
+    /// Takes `bytes` from the allowance, sleeping for as long as the
+    /// allowance is overdrawn.
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = tokio::time::Instant::now();
+            let refill = now.duration_since(bucket.updated).as_secs_f64() * self.bytes_per_second;
+            bucket.available = (bucket.available + refill).min(self.bytes_per_second) - bytes as f64;
+            bucket.updated = now;
+            Duration::from_secs_f64((-bucket.available).max(0.0) / self.bytes_per_second)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Bytes one part of an upload holds at its peak. A part being sealed keeps
+/// its plaintext until the encrypted form, header included for the first
+/// part, is complete, so both count. As in `part_size_for`, the encrypted
+/// form is taken at its longest, that of chunks that don't compress.
+pub(crate) fn part_footprint(part_size: usize, sealed: bool) -> usize {
+    match sealed {
+        true => {
+            let overhead = AES_256_GCM.tag_len() + FRAME_LEN_SIZE + 1;
+            2 * part_size + MAX_HEADER_LEN + part_size.div_ceil(CHUNK_SIZE).max(1) * overhead
+        }
+        false => part_size,
+    }
+}
+
+/// First stage of the upload pipeline: reads `file` in `part_size` pieces,
+/// numbering them from `first_part`, and reserves each part's footprint,
+/// with room to seal it when `sealed`, from `budget`. Reading is paced by `limiter`, which
+/// in turn paces the upload. An empty file still yields one empty part,
+/// since a multipart upload needs at least one. A full part is only known to
+/// be the last one once a read past it finds the end, so one byte is read
+/// ahead into the next part.
+pub(crate) fn spawn_reader(mut file: impl AsyncRead + Unpin + Send + 'static,
+                           part_size: usize,
+                           first_part: i32,
+                           sealed: bool,
+                           budget: Option<MemoryBudget>,
+                           limiter: Option<RateLimiter>) -> (Receiver<Part>, JoinHandle<Result<(), String>>) {
+    let (tx, rx) = channel(PIPELINE_DEPTH);
+    let handle = tokio::spawn(async move {
+        let mut number = first_part;
+        let mut ahead = Vec::new();
+        loop {
+            let permit = match &budget {
+                Some(budget) => Some(budget.reserve(part_footprint(part_size, sealed)).await),
+                None => None,
+            };
+            let mut data = std::mem::take(&mut ahead);
+            match read_chunk(&mut file, part_size - data.len()).await {
+                Ok(Some(rest)) => data.extend_from_slice(&rest),
+                Ok(None) if data.is_empty() && number != 1 => break,
+                Ok(None) => {}
+                Err(e) => return Err(messages::cannot_read_part(number, e)),
+            }
+            let is_last = data.len() < part_size || match read_chunk(&mut file, 1).await {
+                Ok(Some(next)) => {
+                    ahead = next;
+                    false
+                }
+                Ok(None) => true,
+                Err(e) => return Err(messages::cannot_read_part(number + 1, e)),
+            };
+            if let Some(limiter) = &limiter {
+                limiter.acquire(data.len()).await;
+            }
+            if tx.send(Part { number, data, is_last, permit }).await.is_err() {
+                return Err(messages::pipeline_stopped(number));
+            }
+            if is_last {
+                break;
+            }
+            number += 1;
+        }
+        Ok(())
+    });
+    (rx, handle)
+}
+
+/// Second stage: encrypts parts on a blocking thread so that sealing one part
+/// overlaps with reading the next and uploading the previous one. Parts are
+/// `part_size` long, so each one's first chunk index follows from its
+/// number. The first part also carries the format header. Compressed parts
+/// may shrink below the minimum part size, so they are joined until they
+/// reach `part_size` again and numbered anew.
+pub(crate) fn spawn_encryptor(mut input: Receiver<Part>, file: FileCipher, part_size: usize) -> (Receiver<Part>, JoinHandle<Result<(), String>>) {
+    let (tx, rx) = channel(PIPELINE_DEPTH);
+    let handle = tokio::task::spawn_blocking(move || {
+        let mut joined: Option<Part> = None;
+        let mut next_number = None;
+        while let Some(mut part) = input.blocking_recv() {
+            let first_chunk = (part.number - 1) as u64 * (part_size / CHUNK_SIZE) as u64;
+            let encrypted = encrypt_part(&file, first_chunk, &part.data, part.is_last);
+            part.data = if part.number == 1 {
+                let mut data = file.header().to_vec();
+                data.extend_from_slice(&encrypted);
+                data
+            } else {
+                encrypted
+            };
+            let mut ready = Vec::new();
+            if file.is_compressed() {
+                if let Some(previous) = joined.take() {
+                    if previous.data.len() + part.data.len() > MAX_PART_SIZE {
+                        ready.push(previous);
+                    } else {
+                        part = join_parts(previous, part);
+                    }
+                }
+                if part.data.len() < part_size && !part.is_last {
+                    joined = Some(part);
+                } else {
+                    ready.push(part);
+                }
+            } else {
+                ready.push(part);
+            }
+            for mut part in ready {
+                let number = next_number.get_or_insert(part.number);
+                part.number = *number;
+                *number += 1;
+                if tx.blocking_send(part).is_err() {
+                    return Err(messages::pipeline_stopped(*number - 1));
+                }
+            }
+        }
+        Ok(())
+    });
+    (rx, handle)
+}
+
+/// Waits for a pipeline stage to finish. A stage that panicked closes its
+/// channel just like one that ran out of input, so only its handle tells
+/// the two apart.
+pub(crate) async fn join_stage(stage: JoinHandle<Result<(), String>>) -> Result<(), String> {
+    stage.await.map_err(messages::pipeline_failed)?
+}
+
+/// Appends `next` to `part`, keeping the memory reserved for both.
+fn join_parts(mut part: Part, next: Part) -> Part {
+    part.data.extend_from_slice(&next.data);
+    part.is_last = next.is_last;
+    match (&mut part.permit, next.permit) {
+        (Some(permit), Some(other)) => permit.merge(other),
+        (permit, other) => *permit = permit.take().or(other),
+    }
+    part
+}
+
+/// Opens an HTTP(S) source for streaming, through `proxy` if given,
+/// returning the body as a reader together with its length when the server
+/// reports one.
+pub(crate) async fn open_url(url: &str, proxy: Option<&str>) -> Result<(impl AsyncRead + Unpin + Send + 'static, Option<u64>), String> {
+    let resp = proxy::http_client(proxy)?.get(url).send().await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| messages::cannot_fetch(url, e))?;
+    let len = resp.content_length();
+    let body = resp.bytes_stream().map_err(io::Error::other);
+    Ok((StreamReader::new(body), len))
+}
+
+/// Picks the part size for a file of `file_len` bytes: `preferred`, grown
+/// just enough to stay within the part count limit and capped so that an
+/// encrypted part (tags and header included) still fits the part size limit.
+/// The result is always a multiple of `CHUNK_SIZE`.
+pub(crate) fn part_size_for(file_len: u64, preferred: usize) -> usize {
+    // A compressed chunk that doesn't shrink is the longest to store.
+    let frame = (CHUNK_SIZE + AES_256_GCM.tag_len() + FRAME_LEN_SIZE + 1) as u64;
+    let max = (MAX_PART_SIZE as u64 - MAX_HEADER_LEN as u64) / frame * CHUNK_SIZE as u64;
+    let needed = file_len.div_ceil(MAX_PARTS).div_ceil(CHUNK_SIZE as u64) * CHUNK_SIZE as u64;
+    needed.max(preferred as u64).min(max) as usize
+}
+
+/// Decides how many parts may be uploaded at once. In adaptive mode the
+/// limit grows by one while each window of parts is faster than the previous
+/// window, shrinks by one when it gets slower, and halves on every failure.
+pub(crate) struct ConcurrencyController {
+    limit: usize,
+    adaptive: bool,
+    window_bytes: usize,
+    window_parts: usize,
+    window_started: Instant,
+    last_throughput: f64,
+}
+
+impl ConcurrencyController {
+    pub fn new(concurrency: Concurrency) -> Self {
+        let (limit, adaptive) = match concurrency {
+            Concurrency::Fixed(n) => (n.max(1), false),
+            Concurrency::Auto => (AUTO_CONCURRENCY_START, true),
+        };
+        Self {
+            limit,
+            adaptive,
+            window_bytes: 0,
+            window_parts: 0,
+            window_started: Instant::now(),
+            last_throughput: 0.0,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn record_success(&mut self, bytes: usize) {
+        if !self.adaptive {
+            return;
+        }
+        self.window_bytes += bytes;
+        self.window_parts += 1;
+        if self.window_parts >= self.limit {
+            let elapsed = self.window_started.elapsed().as_secs_f64().max(f64::EPSILON);
+            self.finish_window(self.window_bytes as f64 / elapsed);
+        }
+    }
+
+    pub fn record_failure(&mut self) {
+        if !self.adaptive {
+            return;
+        }
+        self.limit = (self.limit / 2).max(1);
+        self.reset_window();
+    }
+
+    fn finish_window(&mut self, throughput: f64) {
+        if throughput > self.last_throughput * 1.1 {
+            self.limit = (self.limit + 1).min(MAX_CONCURRENCY);
+        } else if throughput < self.last_throughput * 0.9 {
+            self.limit = (self.limit - 1).max(1);
+        }
+        self.last_throughput = throughput;
+        self.reset_window();
+    }
+
+    fn reset_window(&mut self) {
+        self.window_bytes = 0;
+        self.window_parts = 0;
+        self.window_started = Instant::now();
+    }
+}
+
+/// Downloads each `(key, path)` job with at most `pool_size` objects in
+/// flight, printing a progress line as each one finishes. The key is derived
+/// once by the caller and shared by every download. Returns the number of
+/// failed downloads.
+pub(crate) async fn download_many(client: &AliyunClient,
+                                  jobs: Vec<(String, PathBuf)>,
+                                  cipher: Option<Arc<Cipher>>,
+                                  pool_size: usize) -> usize {
+    let total = jobs.len();
+    let reporter = client.reporter();
+    let mut jobs = jobs.into_iter();
+    let mut in_flight = JoinSet::new();
+    let mut finished = 0;
+    let mut failed = 0;
+
+    loop {
+        while in_flight.len() < pool_size {
+            let Some((key, path)) = jobs.next() else { break };
+            let client = client.clone();
+            let cipher = cipher.clone();
+            in_flight.spawn(async move {
+                if let Some(parent) = path.parent() {
+                    create_dir(parent).await;
+                }
+                let result = client.download_file_with_key(&key, &path, cipher).await;
+                (key, path, result)
+            });
+        }
+
+        let Some(joined) = in_flight.join_next().await else { break };
+        finished += 1;
+        match joined {
+            Ok((key, path, Ok(bytes))) => {
+                let text = format!("[{}/{}] {} ({})", finished, total, key, format_size(bytes));
+                let mut record = TransferRecord::new("download", TransferStatus::Done, Some(key), path);
+                record.bytes = Some(bytes);
+                reporter.record(&record, text);
+            }
+            Ok((key, path, Err(e))) => {
+                failed += 1;
+                let text = messages::download_failed(finished, total, &key, &e);
+                let mut record = TransferRecord::new("download", TransferStatus::Failed, Some(key), path);
+                record.error = Some(e);
+                reporter.failure(&record, text);
+            }
+            Err(e) => {
+                failed += 1;
+                reporter.warn(messages::download_task_failed(finished, total, e));
+            }
+        }
+    }
+
+    failed
+}
+
+/// Outcome counts of a batch upload.
+#[derive(Debug, Default)]
+pub(crate) struct BatchReport {
+    pub failed: usize,
+    pub unchanged: usize,
+    /// One history entry per object actually written.
+    pub uploaded: Vec<HistoryEntry>,
+    /// The outcome of every file, in the order they finished.
+    pub records: Vec<TransferRecord>,
+}
+
+/// Uploads each `(key prefix, path)` job with at most `pool_size` files in
+/// flight, sharing one derived key across the whole batch. With
+/// `skip_unchanged`, files whose checksum matches the stored object are
+/// not transferred again.
+pub(crate) async fn upload_many(client: &AliyunClient,
+                                jobs: Vec<(String, PathBuf)>,
+                                cipher: Option<Arc<Cipher>>,
+                                expiry_seconds: Option<i64>,
+                                skip_unchanged: bool,
+                                pool_size: usize) -> BatchReport {
+    let total = jobs.len();
+    let reporter = client.reporter();
+    let mut jobs = jobs.into_iter();
+    let mut in_flight = JoinSet::new();
+    let mut finished = 0;
+    let mut report = BatchReport::default();
+
+    loop {
+        while in_flight.len() < pool_size {
+            let Some((prefix, path)) = jobs.next() else { break };
+            let client = client.clone();
+            let cipher = cipher.clone();
+            in_flight.spawn(async move {
+                let result = if skip_unchanged {
+                    client.upload_if_changed(prefix, path.clone(), cipher, expiry_seconds).await
+                } else {
+                    client.upload_file_with_key(prefix, path.clone(), cipher, expiry_seconds).await
+                        .map(|resp| UploadOutcome::Uploaded(Box::new(resp)))
+                };
+                (path, result)
+            });
+        }
+
+        let Some(joined) = in_flight.join_next().await else { break };
+        finished += 1;
+        match joined {
+            Ok((path, Ok(UploadOutcome::Uploaded(resp)))) => {
+                let text = format!("[{}/{}] {}", finished, total, path.display());
+                let size = tokio::fs::metadata(&path).await.ok().map(|metadata| metadata.len());
+                let mut record = TransferRecord::new("upload", TransferStatus::Done, resp.key.clone(), path);
+                record.bytes = size;
+                record.etag = resp.e_tag.clone();
+                record.version_id = resp.version_id.clone();
+                reporter.record(&record, text);
+                report.records.push(record);
+                report.uploaded.push(HistoryEntry::new(HistoryAction::Upload, resp.key.unwrap_or_default(), size, resp.version_id));
+            }
+            Ok((path, Ok(UploadOutcome::Unchanged(key)))) => {
+                report.unchanged += 1;
+                let text = messages::upload_unchanged(finished, total, path.display());
+                let record = TransferRecord::new("upload", TransferStatus::Unchanged, Some(key), path);
+                reporter.record(&record, text);
+                report.records.push(record);
+            }
+            Ok((path, Err(e))) => {
+                report.failed += 1;
+                let text = messages::upload_failed(finished, total, path.display(), &e);
+                let mut record = TransferRecord::new("upload", TransferStatus::Failed, None, path);
+                record.error = Some(e);
+                reporter.failure(&record, text);
+                report.records.push(record);
+            }
+            Err(e) => {
+                report.failed += 1;
+                reporter.warn(messages::upload_task_failed(finished, total, e));
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use tokio::fs::{DirBuilder, File};
+    use tokio::io::AsyncWriteExt;
+    use crate::client::Concurrency;
+    use crate::constant::PIPELINE_DEPTH;
+    use crate::crypt::{Cipher, decrypt_file, encrypted_len, stored_len};
+    use crate::transfer::{ConcurrencyController, MemoryBudget, RateLimiter, join_stage, part_footprint, part_size_for, spawn_encryptor, spawn_reader};
+
+    #[tokio::test]
+    async fn test_memory_budget() {
+        let budget = MemoryBudget::new(4096);
+        let first = budget.reserve(3000).await;
+        let waiting = tokio::time::timeout(Duration::from_millis(50), budget.reserve(2048)).await;
+        assert!(waiting.is_err());
+
+        drop(first);
+        let second = budget.reserve(2048).await;
+        drop(second);
+        let oversized = tokio::time::timeout(Duration::from_millis(50), budget.reserve(1 << 20)).await;
+        assert!(oversized.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_sealed_parts_within_budget() {
+        let footprint = part_footprint(4096, true);
+        assert!(footprint >= 4096 + stored_len(4096) as usize);
+        assert_eq!(part_footprint(4096, false), 4096);
+
+        // Room for two sealed parts: a third waits until one is dropped.
+        let budget = MemoryBudget::new(footprint.div_ceil(1024) as u64 * 2 * 1024);
+        let (rx, reader) = spawn_reader(std::io::Cursor::new(vec![7u8; 4096 * 4]), 4096, 1, true, Some(budget), None);
+        let sealer = Cipher::new("RAVEN_BOOK").new_file(4096).unwrap();
+        let (mut rx, _) = spawn_encryptor(rx, sealer, 4096);
+        let first = rx.recv().await.unwrap();
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second.data.len(), encrypted_len(4096));
+        assert!(tokio::time::timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+
+        drop((first, second));
+        while rx.recv().await.is_some() {}
+        reader.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let start = tokio::time::Instant::now();
+        // The first second's worth goes through as a burst.
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() < Duration::from_millis(10));
+        // Clones share the allowance.
+        let other = limiter.clone();
+        tokio::join!(limiter.acquire(500), other.acquire(500));
+        assert!(start.elapsed() >= Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_adaptive_concurrency() {
+        let mut controller = ConcurrencyController::new(Concurrency::Auto);
+        assert_eq!(controller.limit(), 2);
+
+        controller.finish_window(100.0);
+        assert_eq!(controller.limit(), 3);
+        controller.finish_window(200.0);
+        assert_eq!(controller.limit(), 4);
+        controller.finish_window(205.0);
+        assert_eq!(controller.limit(), 4);
+        controller.finish_window(100.0);
+        assert_eq!(controller.limit(), 3);
+
+        controller.record_failure();
+        assert_eq!(controller.limit(), 1);
+        controller.record_failure();
+        assert_eq!(controller.limit(), 1);
+    }
+
+    #[test]
+    fn test_part_size_for() {
+        let five_mib = 5 * 1024 * 1024;
+        assert_eq!(part_size_for(1 << 30, five_mib), five_mib);
+        let large = 100u64 << 30;
+        let part_size = part_size_for(large, five_mib);
+        assert_eq!(part_size % 4096, 0);
+        assert!(large.div_ceil(part_size as u64) <= 10_000);
+        let capped = part_size_for(u64::MAX / 2, five_mib);
+        assert!(capped / 4096 * (4096 + 16) + 9 <= 5 << 30);
+    }
+
+    #[test]
+    fn test_fixed_concurrency() {
+        let mut controller = ConcurrencyController::new(Concurrency::Fixed(6));
+        controller.record_success(1 << 20);
+        controller.record_failure();
+        assert_eq!(controller.limit(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_decrypts() {
+        let input_path = "target/test/pipeline.txt";
+        let output_path = "target/test/pipeline.enc";
+        let decrypted_path = "target/test/pipeline.dec";
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        let mut file = File::create(input_path).await.unwrap();
+        file.write_all(&vec![7u8; 4096 * 3 + 100]).await.unwrap();
+        file.flush().await.unwrap();
+
+        let (rx, reader) = spawn_reader(File::open(input_path).await.unwrap(), 4096 * 2, 1, false, None, None);
+        let sealer = Cipher::new("RAVEN_BOOK").new_file(4096).unwrap();
+        let (mut rx, _) = spawn_encryptor(rx, sealer, 4096 * 2);
+        let mut numbers = Vec::new();
+        let mut actual = Vec::new();
+        while let Some(part) = rx.recv().await {
+            numbers.push(part.number);
+            actual.extend_from_slice(&part.data);
+        }
+        reader.await.unwrap().unwrap();
+        assert_eq!(numbers, vec![1, 2]);
+        assert_eq!(actual.len() as u64, stored_len(4096 * 3 + 100));
+
+        tokio::fs::write(output_path, &actual).await.unwrap();
+        decrypt_file(output_path, decrypted_path, "RAVEN_BOOK").await.unwrap();
+        assert_eq!(tokio::fs::read(decrypted_path).await.unwrap(), vec![7u8; 4096 * 3 + 100]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_joins_compressed_parts() {
+        let output_path = "target/test/pipeline_compressed.enc";
+        let decrypted_path = "target/test/pipeline_compressed.dec";
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        let plain: Vec<u8> = b"text compresses well. ".iter().cycle().take(4096 * 7 + 100).copied().collect();
+
+        let (rx, reader) = spawn_reader(std::io::Cursor::new(plain.clone()), 4096 * 2, 1, false, None, None);
+        let sealer = Cipher::new("RAVEN_BOOK").new_file(4096).unwrap().with_compression(3).unwrap();
+        let (mut rx, _) = spawn_encryptor(rx, sealer, 4096 * 2);
+        let mut parts = Vec::new();
+        let mut actual = Vec::new();
+        while let Some(part) = rx.recv().await {
+            parts.push((part.number, part.is_last));
+            actual.extend_from_slice(&part.data);
+        }
+        reader.await.unwrap().unwrap();
+        assert_eq!(parts, vec![(1, true)]);
+
+        tokio::fs::write(output_path, &actual).await.unwrap();
+        decrypt_file(output_path, decrypted_path, "RAVEN_BOOK").await.unwrap();
+        assert_eq!(tokio::fs::read(decrypted_path).await.unwrap(), plain);
+    }
+
+    #[tokio::test]
+    async fn test_reader_empty_file() {
+        let input_path = "target/test/pipeline_empty.txt";
+        DirBuilder::new().recursive(true).create("target/test").await.unwrap();
+        File::create(input_path).await.unwrap();
+
+        let (mut rx, _) = spawn_reader(File::open(input_path).await.unwrap(), 4096, 1, false, None, None);
+        let part = rx.recv().await.unwrap();
+        assert_eq!(part.number, 1);
+        assert!(part.data.is_empty() && part.is_last);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reader_last_part() {
+        let data: Vec<u8> = (0..4096 * 2).map(|i| i as u8).collect();
+        let (mut rx, reader) = spawn_reader(std::io::Cursor::new(data), 4096, 1, false, None, None);
+        let mut parts = Vec::new();
+        while let Some(part) = rx.recv().await {
+            parts.push((part.number, part.data.len(), part.is_last));
+        }
+        reader.await.unwrap().unwrap();
+        assert_eq!(parts, vec![(1, 4096, false), (2, 4096, true)]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_stage_failures() {
+        // A stage whose parts go nowhere fails instead of looking finished.
+        // The reader has more parts than the encryptor and the channel
+        // between them can take, so it is still sending when that stops.
+        let (rx, reader) = spawn_reader(std::io::Cursor::new(vec![7u8; 4096 * (PIPELINE_DEPTH + 2)]), 4096, 1, false, None, None);
+        let sealer = Cipher::new("RAVEN_BOOK").new_file(4096).unwrap();
+        let (rx, encryptor) = spawn_encryptor(rx, sealer, 4096);
+        drop(rx);
+        assert!(join_stage(encryptor).await.is_err());
+        assert!(join_stage(reader).await.is_err());
+
+        let panicked = tokio::task::spawn_blocking(|| -> Result<(), String> { panic!("sealing failed") });
+        assert!(join_stage(panicked).await.is_err());
+    }
+}
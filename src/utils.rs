@@ -1,8 +1,11 @@
-use std::env;
-use std::path::{Path, PathBuf};
+use std::{env, io};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 use async_trait::async_trait;
-use tokio::fs::{DirBuilder, File, OpenOptions, remove_dir_all};
-use tokio::process::Command;
+use unicode_normalization::UnicodeNormalization;
+use tokio::fs::{DirBuilder, remove_dir_all};
+use crate::messages;
 
 
 pub fn sanitize_path_prefix(path: &str) -> &str {
@@ -10,10 +13,9 @@ pub fn sanitize_path_prefix(path: &str) -> &str {
         return path;
     }
 
-    let mut chars = path.chars();
     let mut index = 0;
 
-    while let Some(chr) = chars.next() {
+    for chr in path.chars() {
         if chr == '/' || chr == '\\' {
             index += 1;
         } else {
@@ -24,85 +26,331 @@ pub fn sanitize_path_prefix(path: &str) -> &str {
     &path[index..]
 }
 
+/// Parses sizes such as `4096`, `512K`, `5M`, `1.5GiB` or `2GB` into bytes.
+/// All units are binary multiples.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(messages::unknown_size_unit(text)),
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok((value * multiplier as f64) as u64),
+        _ => Err(messages::invalid_size(text)),
+    }
+}
+
+/// Parses transfer rates such as `5MB/s`, `512K` or `1.5MiB/s` into bytes per
+/// second. The `/s` suffix is optional.
+pub fn parse_rate(text: &str) -> Result<u64, String> {
+    let trimmed = text.trim();
+    let size = trimmed.strip_suffix("/s").or_else(|| trimmed.strip_suffix("/S")).unwrap_or(trimmed);
+    match parse_size(size)? {
+        0 => Err(messages::zero_bwlimit(text)),
+        rate => Ok(rate),
+    }
+}
+
+/// Parses durations such as `90`, `30s`, `15m`, `1h` or `7d`. A bare number
+/// is in seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(messages::unknown_time_unit(text)),
+    };
+    match number.parse::<u64>() {
+        Ok(value) if value > 0 => Ok(Duration::from_secs(value * multiplier)),
+        _ => Err(messages::invalid_duration(text)),
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Brings a key into Unicode NFC. Keys written by rot are always NFC, so a
+/// name typed on one system or read from a decomposing filesystem (such as
+/// macOS, which stores NFD) maps to the same object everywhere.
+pub fn normalize_key(key: &str) -> String {
+    key.nfc().collect()
+}
+
+/// Maps an object key below `prefix` to a relative local path, dropping
+/// empty, `.` and `..` components so that keys can't escape the target dir.
+/// Splits `oss://bucket/key` into its bucket and key. `oss:///key` and
+/// anything without the scheme name a key in the configured bucket.
+pub fn parse_remote(spec: &str) -> (Option<String>, String) {
+    match spec.strip_prefix("oss://") {
+        Some(rest) => {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            ((!bucket.is_empty()).then(|| bucket.to_string()), normalize_key(key))
+        }
+        None => (None, normalize_key(sanitize_path_prefix(spec))),
+    }
+}
+
+/// Checks a name for a new bucket against the OSS rules: 3 to 63 lowercase
+/// letters, digits and hyphens, not starting or ending with a hyphen.
+pub fn validate_bucket_name(name: &str) -> Result<(), String> {
+    let valid = (3..=63).contains(&name.len())
+        && name.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        Err(messages::invalid_bucket_name(name))
+    }
+}
+
+pub fn key_to_relative_path(key: &str, prefix: &str) -> PathBuf {
+    let relative = key.strip_prefix(prefix).unwrap_or(key);
+    let relative = if relative.trim_matches('/').is_empty() { key } else { relative };
+    relative.split(['/', '\\'])
+        .filter(|part| !(part.is_empty() || *part == "." || *part == ".."))
+        .collect()
+}
+
+/// Lists every regular file below `root`, sorted by path. Symlinked
+/// directories are not followed so that link cycles can't loop forever.
+pub async fn walk_dir(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            } else if file_type.is_symlink() {
+                if let Ok(metadata) = tokio::fs::metadata(entry.path()).await {
+                    if metadata.is_file() {
+                        files.push(entry.path());
+                    }
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Builds the key prefix for `file` when uploading the directory `root`,
+/// keeping the root's own name like `cp -r` does. The result is empty or
+/// ends with `/`.
+pub fn relative_key_prefix(upload_dir: &str, root: &Path, file: &Path) -> String {
+    let root = PathBuf::from(strip_extended_prefix(&root.to_string_lossy()));
+    key_prefix_below(upload_dir, root.parent().unwrap_or(&root), file)
+}
+
+/// The first `depth` levels of `key` below `prefix`, ending with `/`, which
+/// `rot du --depth` adds up. Keys that aren't that deep, and every key with
+/// a depth of 0, only count towards `prefix` itself.
+pub fn usage_group<'a>(prefix: &str, key: &'a str, depth: usize) -> Option<&'a str> {
+    let relative = key.strip_prefix(prefix)?;
+    let (end, _) = relative.match_indices('/').nth(depth.checked_sub(1)?)?;
+    Some(&key[..prefix.len() + end + 1])
+}
+
+/// Builds the key prefix for `file` from its directories below `base`.
+/// The result is empty or ends with `/`.
+pub fn key_prefix_below(upload_dir: &str, base: &Path, file: &Path) -> String {
+    let base = PathBuf::from(strip_extended_prefix(&base.to_string_lossy()));
+    let file = PathBuf::from(strip_extended_prefix(&file.to_string_lossy()));
+    let relative = file.strip_prefix(&base).unwrap_or(&file);
+    let mut parts: Vec<String> = Vec::new();
+    if !upload_dir.trim_matches('/').is_empty() {
+        parts.push(upload_dir.trim_matches('/').to_string());
+    }
+    if let Some(parent) = relative.parent() {
+        parts.extend(parent.components().map(|c| c.as_os_str().to_string_lossy().to_string()));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        normalize_key(&format!("{}/", parts.join("/")))
+    }
+}
+
+/// Percent-encodes an object key for use in headers such as
+/// `x-oss-copy-source`, leaving unreserved characters and `/` intact.
+pub fn percent_encode_key(key: &str) -> String {
+    percent_encode(key, b"/")
+}
+
+/// The `file://` URL of an absolute local path.
+pub fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let path = if path.starts_with('/') { path } else { format!("/{}", path) };
+    format!("file://{}", percent_encode(&path, b"/:"))
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters, as signed
+/// query strings require.
+pub fn percent_encode_component(text: &str) -> String {
+    percent_encode(text, b"")
+}
+
+fn percent_encode(text: &str, keep: &[u8]) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ if keep.contains(&byte) => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
 pub fn ensure_absolute_path(input_path: &str) -> PathBuf {
     let path = Path::new(input_path);
-    if path.is_absolute() {
+    let absolute = if path.is_absolute() {
         path.to_path_buf()
     } else {
         let current_dir = env::current_dir().expect("failed to get current directory");
         current_dir.join(path)
+    };
+    long_path(&absolute)
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+/// Needed before adding the `\\?\` prefix, which turns off Windows' own
+/// normalization.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(normalized.components().next_back(), Some(Component::RootDir | Component::Prefix(_)) | None) {
+                    normalized.pop();
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
     }
+    normalized
 }
 
+/// On Windows, returns the normalized extended-length form of an absolute
+/// path so that paths longer than `MAX_PATH` (260 chars) work. Elsewhere a
+/// no-op, leaving `..` to follow symlinks as the OS would.
+pub fn long_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(extended_length_path(&normalize_path(path).to_string_lossy()))
+    } else {
+        path.to_path_buf()
+    }
+}
 
-pub async fn get_parent_path(path: impl Into<PathBuf>) -> Result<PathBuf, String> {
-    let file_path = path.into();
+/// `C:\dir` becomes `\\?\C:\dir` and `\\server\share` becomes
+/// `\\?\UNC\server\share`. Relative and already prefixed paths are returned
+/// unchanged apart from using `\` as separator.
+pub fn extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else if path.len() >= 3 && path.as_bytes()[1] == b':' && path.as_bytes()[2] == b'\\' {
+        format!(r"\\?\{}", path)
+    } else {
+        path
+    }
+}
 
-    let _file = File::open(&file_path).await.expect("无法获取文件路径！");
+/// Inverse of `extended_length_path`, for messages and object keys.
+pub fn strip_extended_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        path.to_string()
+    }
+}
 
-    if let Some(path) = file_path.parent() {
-        return Ok(path.into());
+/// Asks a yes/no question on the terminal; anything but `y`/`yes` is a no.
+/// Without a terminal nobody can answer, so `--yes` is required instead.
+pub fn confirm(prompt: &str) -> Result<bool, String> {
+    if !io::stdin().is_terminal() {
+        return Err(messages::confirm_needs_terminal());
     }
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush().map_err(|e| e.to_string())?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).map_err(|e| e.to_string())?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
 
-    Err("无法获取文件路径！".into())
+/// Directory holding rot's configuration and local state (`~/.config/rot`).
+pub fn config_dir() -> Option<PathBuf> {
+    home::home_dir().map(|home| home.join(".config").join("rot"))
 }
 
-pub async fn open_file(path: impl AsRef<Path>) -> File {
-    OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(path)
-        .await
-        .expect("couldn't open file")
+/// Directory for data rot can recreate, such as the offline store (`~/.cache/rot`).
+pub fn cache_dir() -> Option<PathBuf> {
+    home::home_dir().map(|home| home.join(".cache").join("rot"))
 }
+
 pub async fn create_dir(path: impl AsRef<Path>) {
-    let path_ref = path.as_ref();
+    let path_ref = long_path(path.as_ref());
     if !path_ref.exists(){
         DirBuilder::new()
             .recursive(true)
-            .create(path)
+            .create(&path_ref)
             .await
             .expect("couldn't create or open dir");
     }
 }
 
-#[async_trait]
-pub trait HidePath {
-    async fn hide_path(&self);
-}
-
-#[async_trait]
-impl HidePath for PathBuf {
-    async fn hide_path(&self) {
-        let path_text = self.to_str()
-            .expect("Couldn't found path");
-        let path_buf = PathBuf::from(&path_text);
-        if std::env::consts::OS == "windows" {
-            let _ = match Command::new("attrib")
-                .args(&["+H", &path_text])
-                .status()
-                .await {
-                Ok(_) => {}
-                Err(_) => {}
-            };
-        } else {
-            let filename = path_buf.file_name()
-                .expect( "not found file_name")
-                .to_string_lossy();
-            if !filename.starts_with(".") {
-                let mut new_path_buf = path_buf.clone();
-                new_path_buf.pop();
-                new_path_buf.push(format!("{}{}", ".", filename));
-
-                tokio::fs::rename(path_buf, new_path_buf)
-                    .await
-                    .expect("couldn't rename file");
-            }
-        }
-    }
-}
-
 #[async_trait]
 pub trait DeleteFolder {
     async fn delete(&self);
@@ -114,7 +362,7 @@ impl DeleteFolder for Option<PathBuf> {
         if let Some(value) = self {
             match remove_dir_all(value).await {
                 Ok(_) => {}
-                Err(e) => { eprintln!("{}", e.to_string()) }
+                Err(e) => { eprintln!("{}", e) }
             }
         }
     }
@@ -125,15 +373,16 @@ impl DeleteFolder for PathBuf {
     async fn delete(&self) {
         match remove_dir_all(self).await {
             Ok(_) => {}
-            Err(e) => { eprintln!("{}", e.to_string()) }
+            Err(e) => { eprintln!("{}", e) }
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
-    use crate::utils::{create_dir, HidePath, sanitize_path_prefix};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use crate::utils::{create_dir, encode_base64, extended_length_path, file_url, format_size, key_prefix_below, key_to_relative_path, normalize_key, normalize_path, parse_duration, parse_rate, parse_remote, parse_size, percent_encode_component, percent_encode_key, relative_key_prefix, sanitize_path_prefix, strip_extended_prefix, usage_group, validate_bucket_name, walk_dir};
 
     #[test]
     fn test_sanitize() {
@@ -143,12 +392,144 @@ mod test {
         assert_eq!(sanitize_path_prefix(raw_text), parsed_text)
     }
 
+    #[test]
+    fn test_validate_bucket_name() {
+        assert!(validate_bucket_name("raven-backup-01").is_ok());
+        assert!(validate_bucket_name("ab").is_err());
+        assert!(validate_bucket_name("Raven").is_err());
+        assert!(validate_bucket_name("-raven").is_err());
+        assert!(validate_bucket_name("raven_books").is_err());
+    }
+
+    #[test]
+    fn test_key_to_relative_path() {
+        assert_eq!(key_to_relative_path("photos/2024/a.jpg", "photos/"), PathBuf::from("2024/a.jpg"));
+        assert_eq!(key_to_relative_path("photos/../../etc/passwd", "photos/"), PathBuf::from("etc/passwd"));
+        assert_eq!(key_to_relative_path("photos/a.jpg", "photos/a.jpg"), PathBuf::from("photos/a.jpg"));
+    }
+
+    #[test]
+    fn test_relative_key_prefix() {
+        let root = Path::new("/home/raven/photos");
+        assert_eq!(relative_key_prefix("backup", root, Path::new("/home/raven/photos/2024/a.jpg")), "backup/photos/2024/");
+        assert_eq!(relative_key_prefix("", root, Path::new("/home/raven/photos/a.jpg")), "photos/");
+        assert_eq!(key_prefix_below("backup", root, Path::new("/home/raven/photos/2024/a.jpg")), "backup/2024/");
+        assert_eq!(key_prefix_below("", root, Path::new("/home/raven/photos/a.jpg")), "");
+    }
+
+    #[test]
+    fn test_usage_group() {
+        assert_eq!(usage_group("photos/", "photos/2024/05/a.jpg", 1), Some("photos/2024/"));
+        assert_eq!(usage_group("photos/", "photos/2024/05/a.jpg", 2), Some("photos/2024/05/"));
+        assert_eq!(usage_group("photos/", "photos/2024/05/a.jpg", 3), None);
+        assert_eq!(usage_group("photos/", "photos/a.jpg", 1), None);
+        assert_eq!(usage_group("", "docs/a.txt", 1), Some("docs/"));
+        assert_eq!(usage_group("", "docs/a.txt", 0), None);
+    }
+
     #[tokio::test]
-    async fn test_hide_path() {
-        let path_text = "./target/test";
-        create_dir(path_text).await;
-        let path_buf = PathBuf::from(path_text);
-        path_buf.hide_path().await;
+    async fn test_walk_dir() {
+        let root = PathBuf::from("target/test_walk");
+        create_dir(root.join("a/b")).await;
+        tokio::fs::write(root.join("top.txt"), b"1").await.unwrap();
+        tokio::fs::write(root.join("a/b/deep.txt"), b"2").await.unwrap();
+
+        let files = walk_dir(&root).await.unwrap();
+        assert_eq!(files, vec![root.join("a/b/deep.txt"), root.join("top.txt")]);
+    }
+
+    #[test]
+    fn test_normalize_key() {
+        let decomposed = "caf\u{65}\u{301}/\u{1100}\u{1161}.txt";
+        assert_eq!(normalize_key(decomposed), "caf\u{e9}/\u{ac00}.txt");
+        assert_eq!(normalize_key("照片 🦀.jpg"), "照片 🦀.jpg");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("1y").is_err());
+    }
+
+    #[test]
+    fn test_parse_remote() {
+        assert_eq!(parse_remote("oss://media/photos/a.jpg"), (Some("media".into()), "photos/a.jpg".into()));
+        assert_eq!(parse_remote("oss://media"), (Some("media".into()), "".into()));
+        assert_eq!(parse_remote("oss:///photos/"), (None, "photos/".into()));
+        assert_eq!(parse_remote("/photos/a.jpg"), (None, "photos/a.jpg".into()));
+    }
+
+    #[test]
+    fn test_unicode_key_round_trip() {
+        let root = Path::new("/home/raven/Cafe\u{301} 照片");
+        let file = root.join("2024 🦀").join("海边 1.jpg");
+        let prefix = relative_key_prefix("backup", root, &file);
+        assert_eq!(prefix, "backup/Caf\u{e9} 照片/2024 🦀/");
+
+        let key = format!("{}{}", prefix, "海边 1.jpg");
+        assert_eq!(key_to_relative_path(&key, "backup/"), PathBuf::from("Caf\u{e9} 照片/2024 🦀/海边 1.jpg"));
+        assert_eq!(percent_encode_key(&key), "backup/Caf%C3%A9%20%E7%85%A7%E7%89%87/2024%20%F0%9F%A6%80/%E6%B5%B7%E8%BE%B9%201.jpg");
+    }
+
+    #[test]
+    fn test_percent_encode_key() {
+        assert_eq!(percent_encode_key("backup/2024 照片.jpg"), "backup/2024%20%E7%85%A7%E7%89%87.jpg");
+        assert_eq!(percent_encode_component("a/b=c~d*"), "a%2Fb%3Dc~d%2A");
+        assert_eq!(file_url(Path::new("/srv/rot/2024 照片.jpg")), "file:///srv/rot/2024%20%E7%85%A7%E7%89%87.jpg");
+        assert_eq!(file_url(Path::new(r"C:\rot\a.txt")), "file:///C:/rot/a.txt");
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"raven"), "cmF2ZW4=");
+        assert_eq!(encode_base64(b"raven-book"), "cmF2ZW4tYm9vaw==");
+        assert_eq!(encode_base64(b"rot"), "cm90");
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(extended_length_path(r"C:\Users\raven/node_modules"), r"\\?\C:\Users\raven\node_modules");
+        assert_eq!(extended_length_path(r"\\nas\backup\a.txt"), r"\\?\UNC\nas\backup\a.txt");
+        assert_eq!(extended_length_path(r"\\?\C:\a"), r"\\?\C:\a");
+        assert_eq!(extended_length_path("relative/a"), r"relative\a");
+        assert_eq!(strip_extended_prefix(r"\\?\UNC\nas\backup"), r"\\nas\backup");
+        assert_eq!(strip_extended_prefix(r"\\?\C:\a"), r"C:\a");
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path(Path::new("/home/raven/./docs/../photos")), PathBuf::from("/home/raven/photos"));
+        assert_eq!(normalize_path(Path::new("/../a")), PathBuf::from("/a"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("5MiB"), Ok(5 * 1024 * 1024));
+        assert_eq!(parse_size("1.5g"), Ok(3 * 512 * 1024 * 1024));
+        assert!(parse_size("5X").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("5MB/s"), Ok(5 * 1024 * 1024));
+        assert_eq!(parse_rate("512K"), Ok(512 * 1024));
+        assert!(parse_rate("0/s").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,420 @@
+use std::path::Path;
+use std::time::SystemTime;
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use futures_util::StreamExt;
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use reqwest::{Body, Method, RequestBuilder, Response, StatusCode};
+use reqwest::header::CONTENT_LENGTH;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use crate::config::Config;
+use crate::error::RotError;
+use crate::messages;
+use crate::proxy::http_client;
+use crate::store::{ObjectInfo, ObjectStore};
+use crate::utils::{create_dir, percent_encode_key};
+
+/// Files above this size go to Nextcloud in chunks of this size, which its
+/// chunked upload requires to be at least 5 MB.
+const UPLOAD_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+const PROPFIND_BODY: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+    <d:propfind xmlns:d=\"DAV:\"><d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/><d:getetag/></d:prop></d:propfind>";
+
+/// A collection on a WebDAV server, such as Nextcloud or Jianguoyun. Object
+/// keys are paths below `url`, and the collections between are created as
+/// uploads need them.
+#[derive(Debug, Clone)]
+pub struct WebDavStore {
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    http: reqwest::Client,
+}
+
+/// One `<response>` of a `PROPFIND`.
+#[derive(Debug, PartialEq)]
+struct Resource {
+    /// The percent-decoded path, without the scheme and host.
+    path: String,
+    is_collection: bool,
+    size: u64,
+    modified: Option<i64>,
+    etag: Option<String>,
+}
+
+impl WebDavStore {
+    pub fn new(url: &str, http: reqwest::Client) -> Self {
+        Self { url: url.trim_end_matches('/').to_string(), user: None, password: None, http }
+    }
+
+    pub fn with_credentials(mut self, user: impl Into<String>, password: Option<String>) -> Self {
+        self.user = Some(user.into());
+        self.password = password;
+        self
+    }
+
+    pub(crate) fn from_config(config: &Config) -> Result<Self, RotError> {
+        if config.endpoint_url.is_empty() {
+            return Err(RotError::Config(messages::webdav_needs_endpoint()));
+        }
+        let mut store = Self::new(&config.endpoint_url, http_client(config.proxy.as_deref()).map_err(RotError::Config)?);
+        if let Some(user) = &config.user {
+            store = store.with_credentials(user, config.password.clone());
+        }
+        Ok(store)
+    }
+
+    fn key_url(&self, key: &str) -> String {
+        match key {
+            "" => format!("{}/", self.url),
+            key => format!("{}/{}", self.url, percent_encode_key(key)),
+        }
+    }
+
+    /// The decoded path of `url` below the host, which `PROPFIND` responses
+    /// name resources by.
+    fn base_path(&self) -> String {
+        path_of(&self.url).trim_end_matches('/').to_string()
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self.http.request(method, url);
+        match &self.user {
+            Some(user) => request.basic_auth(user, self.password.as_deref()),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, url: &str) -> Result<Response, RotError> {
+        let response = request.send().await.map_err(|e| RotError::Sdk(messages::webdav_unreachable(e)))?;
+        self.check(response, url)
+    }
+
+    fn check(&self, response: Response, url: &str) -> Result<Response, RotError> {
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(RotError::Sdk(messages::url_not_found(url))),
+            status => Err(RotError::Sdk(messages::webdav_request_failed(url, status))),
+        }
+    }
+
+    /// Creates the collections above `key`. Those that exist already answer
+    /// 405, which is fine.
+    async fn make_parents(&self, key: &str) -> Result<(), RotError> {
+        let mut dir = String::new();
+        for component in key.split('/').rev().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            dir.push_str(component);
+            dir.push('/');
+            let url = self.key_url(&dir);
+            let response = self.request(Method::from_bytes(b"MKCOL").expect("valid method"), &url).send().await
+                .map_err(|e| RotError::Sdk(messages::webdav_unreachable(e)))?;
+            if !(response.status().is_success() || response.status() == StatusCode::METHOD_NOT_ALLOWED) {
+                return Err(RotError::Sdk(messages::webdav_mkdir_failed(url, response.status())));
+            }
+        }
+        Ok(())
+    }
+
+    /// The resources a `PROPFIND` of `key` reports, `None` when there is
+    /// nothing at `key`.
+    async fn propfind(&self, key: &str, depth: &str) -> Result<Option<Vec<Resource>>, RotError> {
+        let url = self.key_url(key);
+        let request = self.request(Method::from_bytes(b"PROPFIND").expect("valid method"), &url)
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY);
+        let response = request.send().await.map_err(|e| RotError::Sdk(messages::webdav_unreachable(e)))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = self.check(response, &url)?.text().await.map_err(|e| RotError::Sdk(e.to_string()))?;
+        Ok(Some(parse_multistatus(&body)))
+    }
+
+    /// Nextcloud's chunked upload: the chunks go to a temporary upload
+    /// collection, which is then moved into place as one file.
+    async fn put_chunked(&self, uploads: &str, key: &str, path: &Path, size: u64) -> Result<(), RotError> {
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+        let upload_url = format!("{}/rot-{}-{}", uploads, std::process::id(), nanos);
+        let destination = self.key_url(key);
+        let mkcol = self.request(Method::from_bytes(b"MKCOL").expect("valid method"), &upload_url)
+            .header("Destination", &destination);
+        self.send(mkcol, &upload_url).await?;
+
+        let result = async {
+            let mut file = tokio::fs::File::open(path).await?;
+            let mut number = 1;
+            loop {
+                let mut chunk = Vec::with_capacity(UPLOAD_CHUNK_SIZE as usize);
+                (&mut file).take(UPLOAD_CHUNK_SIZE).read_to_end(&mut chunk).await?;
+                if chunk.is_empty() {
+                    break;
+                }
+                let chunk_url = format!("{}/{:05}", upload_url, number);
+                let put = self.request(Method::PUT, &chunk_url)
+                    .header("Destination", &destination)
+                    .header("OC-Total-Length", size.to_string())
+                    .body(chunk);
+                self.send(put, &chunk_url).await?;
+                number += 1;
+            }
+            let assembled = format!("{}/.file", upload_url);
+            let finish = self.request(Method::from_bytes(b"MOVE").expect("valid method"), &assembled)
+                .header("Destination", &destination)
+                .header("Overwrite", "T")
+                .header("OC-Total-Length", size.to_string());
+            self.send(finish, &assembled).await.map(|_| ())
+        }.await;
+        if result.is_err() {
+            let _ = self.request(Method::DELETE, &upload_url).send().await;
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl ObjectStore for WebDavStore {
+    async fn put(&self, key: &str, path: &Path) -> Result<(), RotError> {
+        self.make_parents(key).await?;
+        let size = tokio::fs::metadata(path).await?.len();
+        if size > UPLOAD_CHUNK_SIZE {
+            if let Some(uploads) = nextcloud_uploads_url(&self.url) {
+                return self.put_chunked(&uploads, key, path, size).await.map_err(|e| RotError::Transfer(e.to_string()));
+            }
+        }
+        // Streamed, so that large files aren't held in memory.
+        let file = tokio::fs::File::open(path).await?;
+        let url = self.key_url(key);
+        let request = self.request(Method::PUT, &url)
+            .header(CONTENT_LENGTH, size)
+            .body(Body::wrap_stream(ReaderStream::new(file)));
+        self.send(request, &url).await.map(|_| ()).map_err(|e| RotError::Transfer(e.to_string()))
+    }
+
+    async fn get(&self, key: &str, path: &Path) -> Result<u64, RotError> {
+        let url = self.key_url(key);
+        let response = self.send(self.request(Method::GET, &url), &url).await?;
+        if let Some(parent) = path.parent() {
+            create_dir(parent).await;
+        }
+        let mut file = tokio::fs::File::create(path).await?;
+        let mut stream = response.bytes_stream();
+        let mut received = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| RotError::Transfer(messages::download_broken_off(key, e)))?;
+            file.write_all(&chunk).await?;
+            received += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(received)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, RotError> {
+        let base = self.base_path();
+        let start = prefix.rfind('/').map_or(String::new(), |i| prefix[..=i].to_string());
+        let mut pending = vec![start];
+        let mut objects = Vec::new();
+        // Servers often refuse `Depth: infinity`, so walk one level at a time.
+        while let Some(dir) = pending.pop() {
+            // A collection that doesn't exist simply holds nothing.
+            for resource in self.propfind(&dir, "1").await?.unwrap_or_default() {
+                let Some(key) = resource_key(&base, &resource.path) else { continue };
+                if key.is_empty() || key == dir.trim_end_matches('/') {
+                    continue;
+                }
+                if resource.is_collection {
+                    let below = format!("{}/", key);
+                    if below.starts_with(prefix) || prefix.starts_with(&below) {
+                        pending.push(below);
+                    }
+                } else if key.starts_with(prefix) {
+                    objects.push(ObjectInfo { key, size: resource.size, last_modified: resource.modified, etag: resource.etag, ..Default::default() });
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), RotError> {
+        let url = self.key_url(key);
+        self.send(self.request(Method::DELETE, &url), &url).await.map(|_| ())
+    }
+
+    async fn copy(&self, src_key: &str, dst_key: &str) -> Result<(), RotError> {
+        self.make_parents(dst_key).await?;
+        let url = self.key_url(src_key);
+        let request = self.request(Method::from_bytes(b"COPY").expect("valid method"), &url)
+            .header("Destination", self.key_url(dst_key))
+            .header("Overwrite", "T");
+        self.send(request, &url).await.map(|_| ())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, RotError> {
+        let not_found = || RotError::Sdk(messages::object_not_found(key));
+        let resource = self.propfind(key, "0").await?
+            .unwrap_or_default()
+            .into_iter()
+            .next()
+            .filter(|resource| !resource.is_collection)
+            .ok_or_else(not_found)?;
+        Ok(ObjectInfo { key: key.to_string(), size: resource.size, last_modified: resource.modified, etag: resource.etag, ..Default::default() })
+    }
+}
+
+/// Where Nextcloud takes chunked uploads for a `.../remote.php/dav/files/<user>/...`
+/// URL, `None` for other servers.
+fn nextcloud_uploads_url(url: &str) -> Option<String> {
+    let (server, rest) = url.split_once("/remote.php/dav/files/")?;
+    let user = rest.split('/').next().filter(|user| !user.is_empty())?;
+    Some(format!("{}/remote.php/dav/uploads/{}", server, user))
+}
+
+/// The percent-decoded path of a URL or of an absolute path.
+fn path_of(href: &str) -> String {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => href,
+    };
+    percent_decode(path)
+}
+
+/// The key of the resource at `path`, when it is below `base`.
+fn resource_key(base: &str, path: &str) -> Option<String> {
+    let relative = path.strip_prefix(base)?;
+    if !(relative.is_empty() || relative.starts_with('/')) {
+        return None;
+    }
+    Some(relative.trim_matches('/').to_string())
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                output.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Parses a `207 Multi-Status` body. Elements are matched by namespace, as
+/// servers differ in the prefix they bind `DAV:` to, and text is unescaped
+/// in full, as hrefs and etags often come with character references.
+fn parse_multistatus(xml: &str) -> Vec<Resource> {
+    let mut reader = NsReader::from_str(xml);
+    let mut resources = Vec::new();
+    // The `DAV:` elements open around the current event, innermost last;
+    // those of other namespaces are kept as empty names.
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    let mut resource: Option<Resource> = None;
+    let mut text = String::new();
+    loop {
+        let (namespace, event) = match reader.read_resolved_event() {
+            Ok((_, Event::Eof)) | Err(_) => break,
+            Ok(resolved) => resolved,
+        };
+        let in_dav = namespace == ResolveResult::Bound(Namespace(b"DAV:"));
+        match event {
+            Event::Start(element) => {
+                let name = if in_dav { element.local_name().as_ref().to_vec() } else { Vec::new() };
+                if name == b"response" {
+                    resource = Some(Resource { path: String::new(), is_collection: false, size: 0, modified: None, etag: None });
+                }
+                open.push(name);
+                text.clear();
+            }
+            Event::Empty(element) if in_dav && element.local_name().as_ref() == b"collection" && open.iter().any(|name| name == b"resourcetype") => {
+                if let Some(resource) = &mut resource {
+                    resource.is_collection = true;
+                }
+            }
+            Event::Text(content) => match content.unescape() {
+                Ok(unescaped) => text.push_str(&unescaped),
+                Err(_) => text.push_str(&String::from_utf8_lossy(&content)),
+            },
+            Event::CData(content) => text.push_str(&String::from_utf8_lossy(&content)),
+            Event::End(_) => {
+                let name = open.pop().unwrap_or_default();
+                let value = text.trim();
+                match (name.as_slice(), &mut resource) {
+                    (b"response", _) => resources.extend(resource.take().filter(|resource| !resource.path.is_empty())),
+                    (b"href", Some(resource)) if open.last().is_some_and(|parent| parent == b"response") => resource.path = path_of(value),
+                    (b"collection", Some(resource)) => resource.is_collection = true,
+                    (b"getcontentlength", Some(resource)) => resource.size = value.parse().unwrap_or(0),
+                    (b"getlastmodified", Some(resource)) => resource.modified = DateTime::from_str(value, DateTimeFormat::HttpDate).ok().map(|time| time.secs()),
+                    (b"getetag", Some(resource)) => resource.etag = Some(value.to_string()).filter(|etag| !etag.is_empty()),
+                    _ => {}
+                }
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+    resources
+}
+
+#[cfg(test)]
+mod test {
+    use crate::webdav::{nextcloud_uploads_url, parse_multistatus, resource_key, Resource};
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response><d:href>/dav/backup/</d:href><d:propstat><d:prop>
+                <d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>
+              <d:response><d:href>https://dav.example.com/dav/backup/My%20Notes/a%26b.txt</d:href><d:propstat><d:prop>
+                <d:resourcetype/><d:getcontentlength>5</d:getcontentlength>
+                <d:getlastmodified>Fri, 16 Oct 2026 11:48:30 GMT</d:getlastmodified>
+                <d:getetag>&quot;abc&quot;</d:getetag></d:prop></d:propstat></d:response>
+            </d:multistatus>"#;
+        let resources = parse_multistatus(xml);
+        assert_eq!(resources, vec![
+            Resource { path: "/dav/backup/".into(), is_collection: true, size: 0, modified: None, etag: None },
+            Resource { path: "/dav/backup/My Notes/a&b.txt".into(), is_collection: false, size: 5, modified: Some(1792151310), etag: Some("\"abc\"".into()) },
+        ]);
+
+        let upper = "<D:multistatus xmlns:D=\"DAV:\"><D:response><D:href>/dav/x</D:href>\
+            <D:propstat><D:prop><D:resourcetype><D:collection /></D:resourcetype></D:prop></D:propstat></D:response></D:multistatus>";
+        assert!(parse_multistatus(upper)[0].is_collection);
+    }
+
+    #[test]
+    fn test_parse_escaped_multistatus() {
+        // A default namespace, character references in the href, a CDATA
+        // etag and an href of another namespace, which isn't the resource's.
+        let xml = r#"<multistatus xmlns="DAV:" xmlns:x="urn:example">
+              <response><href>/dav/backup/&#x4e2d;&#25991;/a&#38;b%20c.txt</href><propstat><prop>
+                <x:href>/elsewhere</x:href><resourcetype></resourcetype>
+                <getcontentlength>7</getcontentlength><getetag><![CDATA["e<1>"]]></getetag></prop></propstat></response>
+              <response><href>/dav/backup/docs/</href><propstat><prop>
+                <resourcetype><collection></collection></resourcetype></prop></propstat></response>
+            </multistatus>"#;
+        assert_eq!(parse_multistatus(xml), vec![
+            Resource { path: "/dav/backup/中文/a&b c.txt".into(), is_collection: false, size: 7, modified: None, etag: Some("\"e<1>\"".into()) },
+            Resource { path: "/dav/backup/docs/".into(), is_collection: true, size: 0, modified: None, etag: None },
+        ]);
+    }
+
+    #[test]
+    fn test_keys_and_urls() {
+        assert_eq!(resource_key("/dav/backup", "/dav/backup/My Notes/a.txt"), Some("My Notes/a.txt".into()));
+        assert_eq!(resource_key("/dav/backup", "/dav/backup/"), Some("".into()));
+        assert_eq!(resource_key("/dav/backup", "/dav/backups/a.txt"), None);
+        assert_eq!(nextcloud_uploads_url("https://cloud.example.com/remote.php/dav/files/alice/Backup"),
+                   Some("https://cloud.example.com/remote.php/dav/uploads/alice".into()));
+        assert_eq!(nextcloud_uploads_url("https://dav.jianguoyun.com/dav/backup"), None);
+    }
+}
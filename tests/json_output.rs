@@ -0,0 +1,42 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Runs `rot --offline --output json` in `dir`, with `dir/home` as the home
+/// directory so that the offline store starts out empty, and returns stdout.
+fn rot_json(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rot"))
+        .args(["--offline", "--output", "json"])
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", dir.join("home"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "rot {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_json_stdout_parses() {
+    let dir = std::env::current_dir().unwrap().join("target").join("test_json_output");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("home")).unwrap();
+    std::fs::write(dir.join("a.txt"), b"a").unwrap();
+
+    let mut stdout = String::new();
+    for args in [
+        &["upload", "a.txt"][..],
+        &["cp", "a.txt", "b.txt"],
+        &["tag", "set", "b.txt", "team=ops"],
+        &["tag", "get", "b.txt"],
+        &["acl", "set", "b.txt", "private"],
+        &["rm", "b.txt", "--yes"],
+        &["ls"],
+    ] {
+        stdout.push_str(&rot_json(&dir, args));
+    }
+    let records: Vec<serde_json::Value> = stdout.lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{:?} isn't JSON: {}", line, e)))
+        .collect();
+    let keys: Vec<_> = records.iter().filter_map(|record| record["key"].as_str()).collect();
+    assert_eq!(keys, ["a.txt", "b.txt", "a.txt"]);
+}